@@ -0,0 +1,149 @@
+use crate::analysis::tuning::ratio_to_cents;
+use crate::catalog::bowl::Mode;
+
+/// Partials further apart than this are not considered the same mode
+const MATCH_RANGE_CENTS: f32 = 200.0;
+/// Partial distance at which the pitch similarity has fallen to 1/e
+const PITCH_SCALE_CENTS: f32 = 50.0;
+/// Beat rate difference at which the beat similarity has fallen to 1/e
+const BEAT_SCALE_HZ: f32 = 0.5;
+/// Share of each measure in the score
+const PITCH_WEIGHT: f32 = 0.5;
+const COVERAGE_WEIGHT: f32 = 0.2;
+const DECAY_WEIGHT: f32 = 0.15;
+const BEAT_WEIGHT: f32 = 0.15;
+
+/// One row of the aligned partial tables. A partial without counterpart in the other bowl
+/// has only one side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PartialMatch {
+    /// Index into the first bowl's modes
+    pub a: Option<usize>,
+    pub b: Option<usize>,
+    /// Interval from the first to the second partial
+    pub cents: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Similarity {
+    /// Ascending in frequency
+    pub matches: Vec<PartialMatch>,
+    /// Level weighted mean distance between matched partials
+    pub partial_distance_cents: Option<f32>,
+    /// Share of partials that have a counterpart in the other bowl
+    pub coverage: f32,
+    /// T60 of the first fundamental over the second
+    pub t60_ratio: Option<f32>,
+    /// Difference of the fundamentals' beat rates
+    pub beat_difference_hz: Option<f32>,
+    /// 0 to 100, combining the measures above that are available
+    pub score: f32,
+}
+
+/// Compare the modes of two bowls, each ascending in frequency with the fundamental first.
+/// Partials are paired closest first so every partial is used at most once.
+pub fn compare(a: &[Mode], b: &[Mode]) -> Similarity {
+    let mut candidates: Vec<(usize, usize, f32)> = a
+        .iter()
+        .enumerate()
+        .flat_map(|(i, mode_a)| {
+            b.iter().enumerate().map(move |(j, mode_b)| {
+                let cents = ratio_to_cents((mode_b.frequency / mode_a.frequency) as f64) as f32;
+                (i, j, cents)
+            })
+        })
+        .filter(|(_, _, cents)| cents.abs() <= MATCH_RANGE_CENTS)
+        .collect();
+    candidates.sort_by(|x, y| x.2.abs().total_cmp(&y.2.abs()));
+
+    let mut used_a = vec![false; a.len()];
+    let mut used_b = vec![false; b.len()];
+    let mut matches = Vec::new();
+    for (i, j, cents) in candidates {
+        if !used_a[i] && !used_b[j] {
+            used_a[i] = true;
+            used_b[j] = true;
+            matches.push(PartialMatch {
+                a: Some(i),
+                b: Some(j),
+                cents: Some(cents),
+            });
+        }
+    }
+    let matched = matches.len();
+
+    let unmatched_a = (0..a.len()).filter(|&i| !used_a[i]).map(|i| PartialMatch {
+        a: Some(i),
+        b: None,
+        cents: None,
+    });
+    let unmatched_b = (0..b.len()).filter(|&j| !used_b[j]).map(|j| PartialMatch {
+        a: None,
+        b: Some(j),
+        cents: None,
+    });
+    matches.extend(unmatched_a.chain(unmatched_b));
+
+    let frequency = |m: &PartialMatch| match (m.a, m.b) {
+        (Some(i), _) => a[i].frequency,
+        (None, Some(j)) => b[j].frequency,
+        (None, None) => 0.0,
+    };
+    matches.sort_by(|x, y| frequency(x).total_cmp(&frequency(y)));
+
+    let (distance_sum, weight_sum) = matches
+        .iter()
+        .filter_map(|m| Some((m.a?, m.b?, m.cents?)))
+        .fold((0.0, 0.0), |(sum, weight), (i, j, cents)| {
+            let amplitude = |db: f32| 10.0_f32.powf(db / 20.0);
+            let w = (amplitude(a[i].level_db) + amplitude(b[j].level_db)) / 2.0;
+            (sum + w * cents.abs(), weight + w)
+        });
+    let partial_distance_cents = (weight_sum > 0.0).then(|| distance_sum / weight_sum);
+
+    let total = a.len() + b.len();
+    let coverage = if total > 0 {
+        2.0 * matched as f32 / total as f32
+    } else {
+        0.0
+    };
+
+    let fundamentals = a.first().zip(b.first());
+    let t60_ratio = fundamentals
+        .and_then(|(a, b)| Some(a.t60? / b.t60?))
+        .filter(|ratio| ratio.is_finite() && *ratio > 0.0);
+    let beat_difference_hz = fundamentals.and_then(|(a, b)| Some((a.beat_hz? - b.beat_hz?).abs()));
+
+    let measures = [
+        (
+            partial_distance_cents.map(|d| (-d / PITCH_SCALE_CENTS).exp()),
+            PITCH_WEIGHT,
+        ),
+        (Some(coverage), COVERAGE_WEIGHT),
+        (t60_ratio.map(|r| r.min(1.0 / r)), DECAY_WEIGHT),
+        (
+            beat_difference_hz.map(|d| (-d / BEAT_SCALE_HZ).exp()),
+            BEAT_WEIGHT,
+        ),
+    ];
+    let (score_sum, weight_sum) = measures
+        .iter()
+        .filter_map(|(value, weight)| Some((value.as_ref()? * weight, *weight)))
+        .fold((0.0, 0.0), |(s, w), (value, weight)| {
+            (s + value, w + weight)
+        });
+    let score = if partial_distance_cents.is_some() {
+        100.0 * score_sum / weight_sum
+    } else {
+        0.0
+    };
+
+    Similarity {
+        matches,
+        partial_distance_cents,
+        coverage,
+        t60_ratio,
+        beat_difference_hz,
+        score,
+    }
+}
@@ -0,0 +1,185 @@
+use crate::analysis::partials::PartialAnalysis;
+
+/// Frame length and hop of the level envelope
+const FRAME_SIZE: usize = 4096;
+const HOP_SIZE: usize = 1024;
+/// The decay is fitted until the level has fallen this far below its peak
+const FIT_RANGE_DB: f32 = 40.0;
+/// Fewer frames than this don't give a usable slope
+const MIN_FIT_FRAMES: usize = 4;
+/// Beats faster than this are heard as roughness rather than measured here
+const MAX_BEAT_HZ: f32 = 8.0;
+/// Swell of the level around the decay line needed to count as beating
+const MIN_BEAT_DEPTH_DB: f32 = 0.5;
+
+/// Level of one frequency over time
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecayCurve {
+    pub frequency: f32,
+    /// Level of each frame in dB, full scale sine being 0 dB
+    pub levels_db: Vec<f32>,
+    /// Time between frames
+    pub frame_seconds: f32,
+}
+
+/// Track the level of a single frequency with a Hann windowed DFT bin evaluated at exactly
+/// that frequency, so modes don't have to sit on FFT bins.
+pub fn mode_envelope(samples: &[f32], sample_rate: u32, frequency: f32) -> DecayCurve {
+    let window: Vec<f32> = (0..FRAME_SIZE)
+        .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / FRAME_SIZE as f32).cos())
+        .collect();
+    let window_sum: f32 = window.iter().sum();
+    let omega = 2.0 * std::f64::consts::PI * frequency as f64 / sample_rate as f64;
+    let (sin_step, cos_step) = omega.sin_cos();
+
+    let mut levels_db = Vec::new();
+    let mut start = 0;
+    while start + FRAME_SIZE <= samples.len() {
+        let (mut re, mut im) = (0.0_f64, 0.0_f64);
+        // rotate the reference phasor instead of evaluating sin/cos per sample
+        let (mut cos, mut sin) = (1.0_f64, 0.0_f64);
+        for (sample, w) in samples[start..start + FRAME_SIZE].iter().zip(window.iter()) {
+            let x = (sample * w) as f64;
+            re += x * cos;
+            im -= x * sin;
+            (cos, sin) = (
+                cos * cos_step - sin * sin_step,
+                sin * cos_step + cos * sin_step,
+            );
+        }
+        let amplitude = 2.0 * (re * re + im * im).sqrt() as f32 / window_sum;
+        levels_db.push(20.0 * amplitude.max(1e-10).log10());
+        start += HOP_SIZE;
+    }
+
+    DecayCurve {
+        frequency,
+        levels_db,
+        frame_seconds: HOP_SIZE as f32 / sample_rate as f32,
+    }
+}
+
+/// Straight line through the decay after the peak, in dB over seconds from `start`
+struct DecayFit {
+    start: usize,
+    len: usize,
+    slope: f32,
+    intercept: f32,
+}
+
+/// Fit the first 40 dB of decay after the peak, so the noise floor doesn't flatten the line
+fn fit_decay(curve: &DecayCurve) -> Option<DecayFit> {
+    let (start, &peak_db) = curve
+        .levels_db
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+    let len = curve.levels_db[start..]
+        .iter()
+        .take_while(|&&db| db > peak_db - FIT_RANGE_DB)
+        .count();
+    if len < MIN_FIT_FRAMES {
+        return None;
+    }
+    let decay = &curve.levels_db[start..start + len];
+
+    let n = len as f32;
+    let mean_t = (n - 1.0) / 2.0 * curve.frame_seconds;
+    let mean_db = decay.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (i, db) in decay.iter().enumerate() {
+        let t = i as f32 * curve.frame_seconds - mean_t;
+        covariance += t * (db - mean_db);
+        variance += t * t;
+    }
+    let slope = covariance / variance;
+
+    Some(DecayFit {
+        start,
+        len,
+        slope,
+        intercept: mean_db - slope * mean_t,
+    })
+}
+
+/// Reverberation time (60 dB of decay) from the slope of the decay. Returns `None` if the
+/// level isn't falling.
+pub fn estimate_t60(curve: &DecayCurve) -> Option<f32> {
+    let fit = fit_decay(curve)?;
+    // slower than 1 dB per minute is not a decay
+    if fit.slope > -1.0 / 60.0 {
+        return None;
+    }
+    Some(-60.0 / fit.slope)
+}
+
+/// Rate at which the level swells and fades around the decay line. Bowls beat when a mode is
+/// split into two close frequencies by asymmetries in the casting.
+pub fn estimate_beat_rate(curve: &DecayCurve) -> Option<f32> {
+    let fit = fit_decay(curve)?;
+    let duration = fit.len as f32 * curve.frame_seconds;
+    let residual: Vec<(f32, f32)> = curve.levels_db[fit.start..fit.start + fit.len]
+        .iter()
+        .enumerate()
+        .map(|(i, db)| {
+            let t = i as f32 * curve.frame_seconds;
+            (t, db - (fit.intercept + fit.slope * t))
+        })
+        .collect();
+
+    // at least two beats have to fit in the decay, and a few frames per beat
+    let min_rate = 2.0 / duration;
+    let max_rate = MAX_BEAT_HZ.min(0.25 / curve.frame_seconds);
+    let step = 0.25 / duration;
+
+    let mut best = (0.0, 0.0);
+    let mut rate = min_rate;
+    while rate <= max_rate {
+        let (mut re, mut im) = (0.0, 0.0);
+        for &(t, r) in residual.iter() {
+            let phase = 2.0 * std::f32::consts::PI * rate * t;
+            re += r * phase.cos();
+            im -= r * phase.sin();
+        }
+        let depth = 2.0 * (re * re + im * im).sqrt() / residual.len() as f32;
+        if depth > best.1 {
+            best = (rate, depth);
+        }
+        rate += step;
+    }
+
+    (best.1 >= MIN_BEAT_DEPTH_DB).then_some(best.0)
+}
+
+/// Decay measurements of one partial
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModeAnalysis {
+    pub frequency: f32,
+    pub level_db: f32,
+    pub curve: DecayCurve,
+    pub t60: Option<f32>,
+    pub beat_hz: Option<f32>,
+}
+
+/// Measure the decay of each partial over `samples`
+pub fn analyze_modes(
+    samples: &[f32],
+    sample_rate: u32,
+    partials: &PartialAnalysis,
+) -> Vec<ModeAnalysis> {
+    partials
+        .partials
+        .iter()
+        .map(|partial| {
+            let curve = mode_envelope(samples, sample_rate, partial.frequency);
+            ModeAnalysis {
+                frequency: partial.frequency,
+                level_db: partial.magnitude_db,
+                t60: estimate_t60(&curve),
+                beat_hz: estimate_beat_rate(&curve),
+                curve,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,44 @@
+use crate::analysis::partials::{analyze_partials, PartialAnalysis};
+use crate::analysis::spectrum::{
+    estimate_fundamental, find_peaks, Peak, Spectrum, SpectrumAnalyzer,
+};
+
+/// Analysis frame length. 16384 samples resolve about 2.7 Hz at 44.1 kHz.
+pub const FFT_SIZE: usize = 16384;
+/// Lowest frequency considered a partial
+const MIN_FREQUENCY: f32 = 30.0;
+/// Peaks are looked for this far below the strongest one
+const PEAK_RANGE_DB: f32 = 50.0;
+const MAX_PEAKS: usize = 16;
+/// The fundamental has to be at most this far below the strongest partial
+const FUNDAMENTAL_RANGE_DB: f32 = 30.0;
+/// Peaks below this level are treated as noise
+const SILENCE_DB: f32 = -70.0;
+
+/// Spectrum of one frame with its partials, shared by live input and recordings
+#[derive(Clone)]
+pub struct FrameAnalysis {
+    pub spectrum: Spectrum,
+    /// Strongest first
+    pub peaks: Vec<Peak>,
+    pub fundamental: Option<Peak>,
+    pub partials: Option<PartialAnalysis>,
+}
+
+impl FrameAnalysis {
+    /// Analyze the last frame of `samples`
+    pub fn new(analyzer: &mut SpectrumAnalyzer, samples: &[f32], sample_rate: u32) -> Self {
+        let spectrum = analyzer.analyze(samples, sample_rate);
+        let mut peaks = find_peaks(&spectrum, MIN_FREQUENCY, PEAK_RANGE_DB, MAX_PEAKS);
+        peaks.retain(|p| p.magnitude_db > SILENCE_DB);
+        let fundamental = estimate_fundamental(&peaks, FUNDAMENTAL_RANGE_DB);
+        let partials = fundamental.map(|fundamental| analyze_partials(&peaks, fundamental));
+
+        FrameAnalysis {
+            spectrum,
+            peaks,
+            fundamental,
+            partials,
+        }
+    }
+}
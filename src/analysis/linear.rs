@@ -0,0 +1,72 @@
+/// Solve a square linear system by Gaussian elimination with partial pivoting. None if the
+/// system is close to singular: a pivot no larger than `min_condition` times the largest row
+/// sum of the matrix.
+pub fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>, min_condition: f64) -> Option<Vec<f64>> {
+    let size = rhs.len();
+    let scale = matrix
+        .iter()
+        .map(|row| row.iter().map(|v| v.abs()).sum::<f64>())
+        .fold(0.0, f64::max);
+    for col in 0..size {
+        let pivot =
+            (col..size).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        if matrix[pivot][col].abs() <= min_condition * scale {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+        let pivot_row = matrix[col].clone();
+        for row in col + 1..size {
+            let factor = matrix[row][col] / pivot_row[col];
+            for (value, pivot) in matrix[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        let known: f64 = (row + 1..size).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - known) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solves_with_pivoting() {
+        // the zero in the first pivot position needs a row swap
+        let matrix = vec![
+            vec![0.0, 2.0, 1.0],
+            vec![1.0, -1.0, 2.0],
+            vec![3.0, 1.0, -1.0],
+        ];
+        let expected = [2.0, -1.0, 3.0];
+        let rhs = matrix
+            .iter()
+            .map(|row| row.iter().zip(expected).map(|(a, x)| a * x).sum())
+            .collect();
+        let solution = solve(matrix, rhs, 1e-12).unwrap();
+        for (x, expected) in solution.iter().zip(expected) {
+            assert!((x - expected).abs() < 1e-12, "{:?}", solution);
+        }
+    }
+
+    #[test]
+    fn singular_system_has_no_solution() {
+        let matrix = vec![vec![1.0, 2.0], vec![2.0, 4.0]];
+        assert_eq!(solve(matrix, vec![1.0, 2.0], 1e-12), None);
+        // nearly singular only fails below the given condition
+        let matrix = vec![vec![1.0, 2.0], vec![1.0, 2.0 + 1e-6]];
+        assert_eq!(solve(matrix.clone(), vec![1.0, 1.0], 1e-3), None);
+        assert!(solve(matrix, vec![1.0, 1.0], 1e-12).is_some());
+    }
+
+    #[test]
+    fn empty_system_is_solved() {
+        assert_eq!(solve(Vec::new(), Vec::new(), 1e-12), Some(Vec::new()));
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+
+use crate::analysis::frame::{FrameAnalysis, FFT_SIZE};
+use crate::analysis::onset::{Onset, OnsetDetector, OnsetFunction};
+use crate::analysis::partials::PartialAnalysis;
+use crate::analysis::pitch::{PitchAlgorithm, PitchEstimate, PitchEstimator};
+use crate::analysis::spectrum::{Peak, Spectrum, SpectrumAnalyzer};
+use crate::audio_engine::tap::AnalysisTap;
+
+/// Seconds of input kept for the analysis views
+const HISTORY_SECONDS: u32 = 4;
+/// Detected strikes kept for display
+const MAX_STRIKES: usize = 32;
+
+/// Spectrum and partials of the live input, updated on the UI thread from the analysis tap
+pub struct LiveAnalysis {
+    /// Most recent input, oldest first
+    history: Vec<f32>,
+    sample_rate: u32,
+    analyzer: SpectrumAnalyzer,
+    frame: Option<FrameAnalysis>,
+    pitch_algorithm: PitchAlgorithm,
+    pitch_estimator: Box<dyn PitchEstimator>,
+    pitch: Option<PitchEstimate>,
+    onset_function: OnsetFunction,
+    onset_sensitivity: f32,
+    /// Created once the sample rate is known
+    onset_detector: Option<OnsetDetector>,
+    /// Most recent strikes, newest last
+    strikes: VecDeque<Onset>,
+    /// Samples and strikes added by the last update
+    latest_len: usize,
+    latest_strikes: Vec<Onset>,
+}
+
+impl LiveAnalysis {
+    pub fn new() -> Self {
+        LiveAnalysis {
+            history: Vec::new(),
+            sample_rate: 0,
+            analyzer: SpectrumAnalyzer::new(FFT_SIZE),
+            frame: None,
+            pitch_algorithm: PitchAlgorithm::SpectralPeak,
+            pitch_estimator: PitchAlgorithm::SpectralPeak.create(),
+            pitch: None,
+            onset_function: OnsetFunction::ComplexDomain,
+            onset_sensitivity: 0.5,
+            onset_detector: None,
+            strikes: VecDeque::new(),
+            latest_len: 0,
+            latest_strikes: Vec::new(),
+        }
+    }
+
+    pub fn pitch_algorithm(&self) -> PitchAlgorithm {
+        self.pitch_algorithm
+    }
+
+    pub fn set_pitch_algorithm(&mut self, algorithm: PitchAlgorithm) {
+        if algorithm != self.pitch_algorithm {
+            self.pitch_algorithm = algorithm;
+            self.pitch_estimator = algorithm.create();
+            self.pitch = None;
+        }
+    }
+
+    pub fn set_onset_detection(&mut self, function: OnsetFunction, sensitivity: f32) {
+        self.onset_function = function;
+        self.onset_sensitivity = sensitivity;
+        if let Some(detector) = &mut self.onset_detector {
+            detector.set_function(function);
+            detector.set_sensitivity(sensitivity);
+        }
+    }
+
+    /// Pull new input from the tap and re-analyze. Called once per UI frame.
+    pub fn update(&mut self, tap: &AnalysisTap) {
+        let previous_len = self.history.len();
+        let sample_rate = tap.drain(&mut self.history);
+        self.latest_len = 0;
+        self.latest_strikes.clear();
+        if sample_rate != self.sample_rate {
+            // mixing rates would smear the spectrum
+            self.history.clear();
+            self.sample_rate = sample_rate;
+            self.frame = None;
+            self.pitch = None;
+            self.strikes.clear();
+            self.onset_detector = (sample_rate > 0).then(|| {
+                OnsetDetector::new(self.onset_function, self.onset_sensitivity, sample_rate)
+            });
+            return;
+        }
+        if self.history.len() == previous_len || sample_rate == 0 {
+            return;
+        }
+
+        self.latest_len = self.history.len() - previous_len;
+        if let Some(detector) = &mut self.onset_detector {
+            detector.process(&self.history[previous_len..], &mut self.latest_strikes);
+            self.strikes.extend(self.latest_strikes.iter().copied());
+            while self.strikes.len() > MAX_STRIKES {
+                self.strikes.pop_front();
+            }
+        }
+
+        // trim in chunks rather than shifting the history every frame, keeping all of the
+        // latest input for the recorder even after a long stall
+        let max_len = (HISTORY_SECONDS * sample_rate) as usize;
+        if self.history.len() > 2 * max_len {
+            let keep = max_len.max(self.latest_len);
+            self.history.drain(..self.history.len() - keep);
+        }
+
+        if self.history.len() < self.analyzer.size() / 2 {
+            return;
+        }
+
+        self.frame = Some(FrameAnalysis::new(
+            &mut self.analyzer,
+            &self.history,
+            sample_rate,
+        ));
+        self.pitch = self.pitch_estimator.estimate(&self.history, sample_rate);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Most recent input, oldest first
+    pub fn history(&self) -> &[f32] {
+        &self.history
+    }
+
+    /// Input added by the last update
+    pub fn latest_input(&self) -> &[f32] {
+        &self.history[self.history.len() - self.latest_len..]
+    }
+
+    /// Strikes found by the last update
+    pub fn latest_strikes(&self) -> &[Onset] {
+        &self.latest_strikes
+    }
+
+    /// Most recent strikes, newest last, with sample positions counted by `input_position`
+    pub fn strikes(&self) -> &VecDeque<Onset> {
+        &self.strikes
+    }
+
+    /// Samples fed to the strike detector since the input started
+    pub fn input_position(&self) -> u64 {
+        self.onset_detector
+            .as_ref()
+            .map_or(0, |detector| detector.position())
+    }
+
+    pub fn spectrum(&self) -> Option<&Spectrum> {
+        self.frame.as_ref().map(|frame| &frame.spectrum)
+    }
+
+    pub fn peaks(&self) -> &[Peak] {
+        self.frame.as_ref().map_or(&[], |frame| &frame.peaks)
+    }
+
+    /// Pitch from the selected estimator
+    pub fn pitch(&self) -> Option<PitchEstimate> {
+        self.pitch
+    }
+
+    pub fn partials(&self) -> Option<&PartialAnalysis> {
+        self.frame
+            .as_ref()
+            .and_then(|frame| frame.partials.as_ref())
+    }
+}
@@ -0,0 +1,17 @@
+pub mod comparison;
+pub mod decay;
+pub mod frame;
+pub mod linear;
+pub mod live;
+pub mod modal;
+pub mod nodal;
+pub mod onset;
+pub mod partials;
+pub mod pitch;
+pub mod recording;
+pub mod roughness;
+pub mod sms;
+pub mod spectrum;
+pub mod sweep;
+pub mod tuning;
+pub mod zoom;
@@ -0,0 +1,88 @@
+use std::f64::consts::PI;
+
+use crate::catalog::bowl::Mode;
+
+/// Decay time given to modes whose T60 couldn't be measured
+pub const DEFAULT_T60: f32 = 10.0;
+/// Modes further than this below the loudest are left out
+const LEVEL_RANGE_DB: f32 = 80.0;
+
+/// One damped sinusoid of a modal model
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resonator {
+    pub frequency: f32,
+    /// Time to decay by 60 dB in seconds
+    pub t60: f32,
+    /// Peak amplitude after a unit strike
+    pub amplitude: f32,
+}
+
+/// A bowl as a bank of decaying resonators, one per measured mode and two for a split mode.
+/// The amplitudes sum to one, so a strike doesn't clip.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModalModel {
+    pub resonators: Vec<Resonator>,
+}
+
+impl ModalModel {
+    /// A mode that beats is split into a pair of equal resonators the beat rate apart, so
+    /// their sum swells at that rate
+    pub fn from_modes(modes: &[Mode]) -> Self {
+        let loudest = modes
+            .iter()
+            .map(|mode| mode.level_db)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let mut resonators = Vec::new();
+        for mode in modes
+            .iter()
+            .filter(|mode| mode.level_db >= loudest - LEVEL_RANGE_DB)
+        {
+            let t60 = mode.t60.unwrap_or(DEFAULT_T60);
+            let amplitude = 10.0_f32.powf((mode.level_db - loudest) / 20.0);
+            match mode.beat_hz {
+                Some(beat_hz) if beat_hz > 0.0 => {
+                    for offset in [-beat_hz / 2.0, beat_hz / 2.0] {
+                        resonators.push(Resonator {
+                            frequency: mode.frequency + offset,
+                            t60,
+                            amplitude: amplitude / 2.0,
+                        });
+                    }
+                }
+                _ => resonators.push(Resonator {
+                    frequency: mode.frequency,
+                    t60,
+                    amplitude,
+                }),
+            }
+        }
+
+        let total: f32 = resonators.iter().map(|r| r.amplitude).sum();
+        if total > 0.0 {
+            for resonator in resonators.iter_mut() {
+                resonator.amplitude /= total;
+            }
+        }
+        ModalModel { resonators }
+    }
+
+    /// Response to a unit strike at the first sample: exponentially decaying cosines
+    pub fn render(&self, sample_rate: u32, len: usize) -> Vec<f32> {
+        let mut output = vec![0.0; len];
+        let nyquist = sample_rate as f32 / 2.0;
+        for resonator in self.resonators.iter() {
+            if resonator.frequency >= nyquist || resonator.t60 <= 0.0 {
+                continue;
+            }
+            let omega = 2.0 * PI * resonator.frequency as f64 / sample_rate as f64;
+            // 60 dB down after t60 seconds
+            let decay = 1000.0_f64.ln() / (resonator.t60 as f64 * sample_rate as f64);
+            for (n, sample) in output.iter_mut().enumerate() {
+                let n = n as f64;
+                *sample +=
+                    (resonator.amplitude as f64 * (-decay * n).exp() * (omega * n).cos()) as f32;
+            }
+        }
+        output
+    }
+}
@@ -0,0 +1,533 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use rustfft::num_complex::Complex;
+
+use crate::analysis::decay::{estimate_beat_rate, mode_envelope};
+use crate::analysis::linear::solve;
+use crate::analysis::onset::{detect_onsets, OnsetFunction};
+use crate::analysis::partials::Partial;
+use crate::analysis::recording::analyze_recording;
+
+/// The attack is skipped, as the strike itself is heard differently at each position
+const ATTACK_SECONDS: f32 = 0.05;
+/// Length of the window each partial's amplitude and phase are measured over
+const WINDOW_SECONDS: f32 = 1.0;
+/// Onset detection settings for finding the strike in each take
+const ONSET_FUNCTION: OnsetFunction = OnsetFunction::ComplexDomain;
+const ONSET_SENSITIVITY: f32 = 0.5;
+/// Lowest order the positions have to tell apart, that of the fundamental
+const MIN_ORDER: u32 = 2;
+/// Highest mode order fitted, if there are positions enough to tell it apart
+pub const MAX_ORDER: u32 = 8;
+/// A higher order is only taken if it leaves this fraction of the residual of a lower one, as
+/// a higher order can also follow measurement noise
+const ORDER_PREFERENCE: f64 = 0.5;
+/// Fits whose equations are closer to singular than this are skipped
+const MIN_CONDITION: f64 = 1e-6;
+
+#[derive(Debug)]
+pub enum NodalError {
+    /// Fewer positions than the number given
+    TooFewPositions(usize),
+    SampleRateMismatch,
+    NoStrike,
+    NoPartials,
+}
+
+impl fmt::Display for NodalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodalError::TooFewPositions(needed) => {
+                write!(f, "at least {} positions are needed", needed)
+            }
+            NodalError::SampleRateMismatch => write!(f, "the takes differ in sample rate"),
+            NodalError::NoStrike => write!(f, "no strike found"),
+            NodalError::NoPartials => write!(f, "no partials found"),
+        }
+    }
+}
+
+/// What was heard of one partial at one position around the bowl
+#[derive(Clone, Copy, Debug)]
+pub struct AngleMeasurement {
+    pub angle_deg: f32,
+    /// Relative to the loudest position
+    pub amplitude: f32,
+    /// Relative to the loudest position, in degrees. Only known when the positions were
+    /// captured together or against a reference mic.
+    pub phase_deg: Option<f32>,
+}
+
+/// Mode order and orientation fitted to a partial's pattern around the bowl. The power heard
+/// at angle t is modelled as P + Q cos 2nt + R sin 2nt, which covers a standing wave of order
+/// n at any orientation, a travelling wave and anything in between.
+#[derive(Clone, Copy, Debug)]
+pub struct ModeFit {
+    pub order: u32,
+    /// Angle of one antinode; the others follow every 180/n degrees
+    pub antinode_deg: f32,
+    /// Amplitude at the nodes over the amplitude at the antinodes: 0 for a pure standing
+    /// wave, 1 for a travelling wave or a pattern without nodes
+    pub node_depth: f32,
+    /// Part of the measured power the fit leaves unexplained
+    pub residual: f32,
+    power: [f64; 3],
+}
+
+impl ModeFit {
+    /// Fitted amplitude at an angle, on the scale of the measurements
+    pub fn amplitude(&self, angle_deg: f32) -> f32 {
+        let t = 2.0 * self.order as f64 * (angle_deg as f64).to_radians();
+        let [p, q, r] = self.power;
+        (p + q * t.cos() + r * t.sin()).max(0.0).sqrt() as f32
+    }
+
+    /// Angles of the nodal lines within a full turn
+    pub fn nodes_deg(&self) -> Vec<f32> {
+        let spacing = 180.0 / self.order as f32;
+        (0..2 * self.order)
+            .map(|k| (self.antinode_deg + (k as f32 + 0.5) * spacing).rem_euclid(360.0))
+            .collect()
+    }
+
+    fn from_power(order: u32, power: [f64; 3], residual: f64) -> Self {
+        let [p, q, r] = power;
+        let swing = q.hypot(r);
+        let depth = if p + swing > 0.0 {
+            ((p - swing).max(0.0) / (p + swing)).sqrt()
+        } else {
+            1.0
+        };
+        let antinode = r.atan2(q) / (2.0 * order as f64);
+
+        ModeFit {
+            order,
+            antinode_deg: (antinode.to_degrees() as f32).rem_euclid(180.0 / order as f32),
+            node_depth: depth as f32,
+            residual: residual as f32,
+            power,
+        }
+    }
+}
+
+/// One partial's measurements around the bowl and the mode fitted to them
+pub struct NodalPattern {
+    pub partial: Partial,
+    pub measurements: Vec<AngleMeasurement>,
+    /// None if there are too few positions for any order
+    pub fit: Option<ModeFit>,
+    /// Beating of the doublet the mode is split into, measured after the strike
+    pub beat_hz: Option<f32>,
+}
+
+/// The strike heard at one position, with the mic that stayed put if there was one
+pub struct Position<'a> {
+    pub angle_deg: f32,
+    pub samples: &'a [f32],
+    pub reference: Option<&'a [f32]>,
+}
+
+/// Highest order that the positions can tell apart from the lower ones. The amplitude
+/// pattern of order n repeats 2n times around the bowl, its complex pattern n times.
+pub fn max_order(positions: usize, with_phase: bool) -> u32 {
+    ((positions.saturating_sub(1) / positions_per_order(with_phase)) as u32).min(MAX_ORDER)
+}
+
+/// Positions needed to tell apart the order of the fundamental
+pub fn min_positions(with_phase: bool) -> usize {
+    MIN_ORDER as usize * positions_per_order(with_phase) + 1
+}
+
+fn positions_per_order(with_phase: bool) -> usize {
+    if with_phase {
+        2
+    } else {
+        4
+    }
+}
+
+/// Map the nodal patterns from mics recorded together, such as the channels of one
+/// multi-channel take. The strike and the partials are found in the mix of all of them.
+pub fn map_simultaneous(
+    channels: &[&[f32]],
+    angles_deg: &[f32],
+    sample_rate: u32,
+) -> Result<Vec<NodalPattern>, NodalError> {
+    if channels.len() < min_positions(true) {
+        return Err(NodalError::TooFewPositions(min_positions(true)));
+    }
+    let len = channels.iter().map(|c| c.len()).min().unwrap_or(0);
+    let mix: Vec<f32> = (0..len)
+        .map(|i| channels.iter().map(|c| c[i]).sum::<f32>() / channels.len() as f32)
+        .collect();
+    let strike = find_strike(&mix, sample_rate).ok_or(NodalError::NoStrike)?;
+    let partials = find_partials(&mix[strike..], sample_rate)?;
+
+    let window = measurement_window(strike, len, sample_rate);
+    Ok(partials
+        .into_iter()
+        .map(|partial| {
+            let values = channels
+                .iter()
+                .map(|samples| measure(samples, window.clone(), partial.frequency, sample_rate))
+                .collect::<Vec<_>>();
+            let beat_hz = estimate_beat_rate(&mode_envelope(
+                &mix[strike..],
+                sample_rate,
+                partial.frequency,
+            ));
+            pattern(partial, angles_deg, &values, true, beat_hz)
+        })
+        .collect())
+}
+
+/// Map the nodal patterns from takes at one position after the other, each aligned on its
+/// strike. With a reference mic in each take, amplitude and phase are taken relative to it;
+/// without, only amplitudes are known and the strikes should be equally strong.
+pub fn map_sequential(
+    positions: &[Position],
+    sample_rate: u32,
+) -> Result<Vec<NodalPattern>, NodalError> {
+    let with_phase = positions
+        .iter()
+        .all(|position| position.reference.is_some());
+    if positions.len() < min_positions(with_phase) {
+        return Err(NodalError::TooFewPositions(min_positions(with_phase)));
+    }
+    let strikes = positions
+        .iter()
+        .map(|position| find_strike(position.samples, sample_rate))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(NodalError::NoStrike)?;
+    let partials = find_partials(&positions[0].samples[strikes[0]..], sample_rate)?;
+
+    Ok(partials
+        .into_iter()
+        .map(|partial| {
+            let values = positions
+                .iter()
+                .zip(&strikes)
+                .map(|(position, &strike)| {
+                    let window = measurement_window(strike, position.samples.len(), sample_rate);
+                    let value = measure(
+                        position.samples,
+                        window.clone(),
+                        partial.frequency,
+                        sample_rate,
+                    );
+                    match position.reference {
+                        Some(reference) if with_phase => {
+                            let reference =
+                                measure(reference, window, partial.frequency, sample_rate);
+                            if reference.norm() > 0.0 {
+                                value / reference
+                            } else {
+                                Complex::new(0.0, 0.0)
+                            }
+                        }
+                        _ => value,
+                    }
+                })
+                .collect::<Vec<_>>();
+            let angles: Vec<f32> = positions.iter().map(|p| p.angle_deg).collect();
+            let beat_hz = estimate_beat_rate(&mode_envelope(
+                &positions[0].samples[strikes[0]..],
+                sample_rate,
+                partial.frequency,
+            ));
+            pattern(partial, &angles, &values, with_phase, beat_hz)
+        })
+        .collect())
+}
+
+/// Sample of the first strike, or of the loudest sample if no strike is detected
+fn find_strike(samples: &[f32], sample_rate: u32) -> Option<usize> {
+    if samples.is_empty() {
+        return None;
+    }
+    let onsets = detect_onsets(samples, sample_rate, ONSET_FUNCTION, ONSET_SENSITIVITY);
+    let strike = match onsets.first() {
+        Some(onset) => onset.sample as usize,
+        None => samples
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+            .map_or(0, |(i, _)| i),
+    };
+    Some(strike.min(samples.len() - 1))
+}
+
+fn find_partials(samples: &[f32], sample_rate: u32) -> Result<Vec<Partial>, NodalError> {
+    analyze_recording(samples, sample_rate)
+        .frame
+        .partials
+        .map(|analysis| analysis.partials)
+        .filter(|partials| !partials.is_empty())
+        .ok_or(NodalError::NoPartials)
+}
+
+fn measurement_window(strike: usize, len: usize, sample_rate: u32) -> std::ops::Range<usize> {
+    let start = (strike + (ATTACK_SECONDS * sample_rate as f32) as usize).min(len);
+    let end = (start + (WINDOW_SECONDS * sample_rate as f32) as usize).min(len);
+    start..end
+}
+
+/// Complex amplitude of one frequency over a Hann windowed stretch of the signal
+fn measure(
+    samples: &[f32],
+    window: std::ops::Range<usize>,
+    frequency: f32,
+    sample_rate: u32,
+) -> Complex<f64> {
+    let samples = &samples[window.start.min(samples.len())..window.end.min(samples.len())];
+    let n = samples.len();
+    if n < 2 {
+        return Complex::new(0.0, 0.0);
+    }
+    let step = 2.0 * PI * frequency as f64 / sample_rate as f64;
+    let mut sum = Complex::new(0.0, 0.0);
+    let mut weight = 0.0;
+    for (k, &sample) in samples.iter().enumerate() {
+        let w = 0.5 - 0.5 * (2.0 * PI * k as f64 / (n - 1) as f64).cos();
+        sum += Complex::from_polar(w * sample as f64, -step * k as f64);
+        weight += w;
+    }
+    sum * (2.0 / weight)
+}
+
+fn pattern(
+    partial: Partial,
+    angles_deg: &[f32],
+    values: &[Complex<f64>],
+    with_phase: bool,
+    beat_hz: Option<f32>,
+) -> NodalPattern {
+    let loudest = values
+        .iter()
+        .max_by(|a, b| a.norm().total_cmp(&b.norm()))
+        .copied()
+        .unwrap_or(Complex::new(1.0, 0.0));
+    let scale = loudest.norm();
+    // relative to the loudest position, so its phase is 0
+    let relative: Vec<Complex<f64>> = values
+        .iter()
+        .map(|value| {
+            if scale > 0.0 {
+                value * loudest.conj() / (scale * scale)
+            } else {
+                Complex::new(0.0, 0.0)
+            }
+        })
+        .collect();
+
+    let measurements = angles_deg
+        .iter()
+        .zip(&relative)
+        .map(|(&angle_deg, value)| AngleMeasurement {
+            angle_deg,
+            amplitude: value.norm() as f32,
+            phase_deg: with_phase.then(|| value.arg().to_degrees() as f32),
+        })
+        .collect();
+
+    NodalPattern {
+        partial,
+        measurements,
+        fit: fit_mode(angles_deg, &relative, with_phase),
+        beat_hz,
+    }
+}
+
+/// Fit each order the positions can resolve and keep the best, preferring lower orders
+fn fit_mode(angles_deg: &[f32], values: &[Complex<f64>], with_phase: bool) -> Option<ModeFit> {
+    let angles: Vec<f64> = angles_deg
+        .iter()
+        .map(|a| (*a as f64).to_radians())
+        .collect();
+    let mut best: Option<ModeFit> = None;
+    for order in 1..=max_order(values.len(), with_phase) {
+        let fit = if with_phase {
+            fit_complex(&angles, values, order)
+        } else {
+            fit_power(&angles, values, order)
+        };
+        let Some(fit) = fit else {
+            continue;
+        };
+        let better =
+            best.is_none_or(|best| (fit.residual as f64) < best.residual as f64 * ORDER_PREFERENCE);
+        if better {
+            best = Some(fit);
+        }
+    }
+    best
+}
+
+/// Least squares fit of a cos nt + b sin nt with complex a and b to the measured values
+fn fit_complex(angles: &[f64], values: &[Complex<f64>], order: u32) -> Option<ModeFit> {
+    let n = order as f64;
+    let (mut cc, mut cs, mut ss) = (0.0, 0.0, 0.0);
+    let mut vc = Complex::new(0.0, 0.0);
+    let mut vs = Complex::new(0.0, 0.0);
+    for (&t, &v) in angles.iter().zip(values) {
+        let (s, c) = (n * t).sin_cos();
+        cc += c * c;
+        cs += c * s;
+        ss += s * s;
+        vc += v * c;
+        vs += v * s;
+    }
+    let det = cc * ss - cs * cs;
+    if det <= MIN_CONDITION * (cc + ss).powi(2) {
+        return None;
+    }
+    let a = (vc * ss - vs * cs) / det;
+    let b = (vs * cc - vc * cs) / det;
+
+    let total: f64 = values.iter().map(|v| v.norm_sqr()).sum();
+    let error: f64 = angles
+        .iter()
+        .zip(values)
+        .map(|(&t, &v)| (v - a * (n * t).cos() - b * (n * t).sin()).norm_sqr())
+        .sum();
+    let power = [
+        (a.norm_sqr() + b.norm_sqr()) / 2.0,
+        (a.norm_sqr() - b.norm_sqr()) / 2.0,
+        (a * b.conj()).re,
+    ];
+    Some(ModeFit::from_power(
+        order,
+        power,
+        error / total.max(f64::MIN_POSITIVE),
+    ))
+}
+
+/// Least squares fit of P + Q cos 2nt + R sin 2nt to the measured power
+fn fit_power(angles: &[f64], values: &[Complex<f64>], order: u32) -> Option<ModeFit> {
+    let n = 2.0 * order as f64;
+    let mut matrix = vec![vec![0.0; 3]; 3];
+    let mut rhs = vec![0.0; 3];
+    for (&t, v) in angles.iter().zip(values) {
+        let row = [1.0, (n * t).cos(), (n * t).sin()];
+        for i in 0..3 {
+            for j in 0..3 {
+                matrix[i][j] += row[i] * row[j];
+            }
+            rhs[i] += row[i] * v.norm_sqr();
+        }
+    }
+    let power = solve(matrix, rhs, MIN_CONDITION)?;
+    let power = [power[0], power[1], power[2]];
+
+    let total: f64 = values.iter().map(|v| v.norm_sqr().powi(2)).sum();
+    let error: f64 = angles
+        .iter()
+        .zip(values)
+        .map(|(&t, v)| {
+            let fitted = power[0] + power[1] * (n * t).cos() + power[2] * (n * t).sin();
+            (v.norm_sqr() - fitted).powi(2)
+        })
+        .sum();
+    Some(ModeFit::from_power(
+        order,
+        power,
+        error / total.max(f64::MIN_POSITIVE),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evenly spaced positions around the bowl
+    fn angles(count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|i| i as f32 * 360.0 / count as f32)
+            .collect()
+    }
+
+    /// Standing wave of `order` with an antinode at `antinode_deg`
+    fn standing(angles: &[f32], order: u32, antinode_deg: f32) -> Vec<Complex<f64>> {
+        angles
+            .iter()
+            .map(|a| {
+                let t = order as f64 * (*a as f64 - antinode_deg as f64).to_radians();
+                Complex::new(t.cos(), 0.0)
+            })
+            .collect()
+    }
+
+    fn assert_angle(actual: f32, expected: f32, period: f32) {
+        let difference = (actual - expected + period / 2.0).rem_euclid(period) - period / 2.0;
+        assert!(difference.abs() < 0.1, "{} instead of {}", actual, expected);
+    }
+
+    #[test]
+    fn position_counts() {
+        assert_eq!(min_positions(false), 9);
+        assert_eq!(min_positions(true), 5);
+        assert_eq!(max_order(8, false), 1);
+        assert_eq!(max_order(9, false), 2);
+        assert_eq!(max_order(12, true), 5);
+        assert_eq!(max_order(100, true), MAX_ORDER);
+    }
+
+    #[test]
+    fn fits_a_standing_wave_from_amplitudes() {
+        let angles = angles(9);
+        let values: Vec<Complex<f64>> = standing(&angles, 2, 20.0)
+            .iter()
+            .map(|v| Complex::new(v.norm(), 0.0))
+            .collect();
+        let fit = fit_mode(&angles, &values, false).unwrap();
+        assert_eq!(fit.order, 2);
+        assert_angle(fit.antinode_deg, 20.0, 90.0);
+        assert!(fit.node_depth < 0.01, "{:?}", fit);
+        assert!(fit.residual < 1e-9, "{:?}", fit);
+        assert!((fit.amplitude(20.0) - 1.0).abs() < 1e-3);
+        assert!(fit.amplitude(65.0) < 1e-3);
+
+        let nodes = fit.nodes_deg();
+        assert_eq!(nodes.len(), 4);
+        for (node, expected) in nodes.iter().zip([65.0, 155.0, 245.0, 335.0]) {
+            assert_angle(*node, expected, 360.0);
+        }
+    }
+
+    #[test]
+    fn fits_a_standing_wave_with_phase() {
+        let angles = angles(12);
+        let fit = fit_mode(&angles, &standing(&angles, 3, 10.0), true).unwrap();
+        assert_eq!(fit.order, 3);
+        assert_angle(fit.antinode_deg, 10.0, 60.0);
+        assert!(fit.node_depth < 0.01, "{:?}", fit);
+    }
+
+    #[test]
+    fn travelling_wave_has_no_nodes() {
+        let angles = angles(12);
+        let values: Vec<Complex<f64>> = angles
+            .iter()
+            .map(|a| Complex::from_polar(1.0, 2.0 * (*a as f64).to_radians()))
+            .collect();
+        let fit = fit_mode(&angles, &values, true).unwrap();
+        assert_eq!(fit.order, 2);
+        assert!((fit.node_depth - 1.0).abs() < 1e-3, "{:?}", fit);
+    }
+
+    #[test]
+    fn measures_amplitude_and_phase() {
+        let sample_rate = 48000;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|n| {
+                let t = n as f64 / sample_rate as f64;
+                (0.5 * (2.0 * PI * 440.0 * t + 0.3).cos()) as f32
+            })
+            .collect();
+        let value = measure(&samples, 0..samples.len(), 440.0, sample_rate);
+        assert!((value.norm() - 0.5).abs() < 1e-3, "{}", value);
+        assert!((value.arg() - 0.3).abs() < 1e-3, "{}", value);
+        assert_eq!(measure(&samples, 10..10, 440.0, sample_rate).norm(), 0.0);
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+/// Frame length and hop of the onset function. 256 samples are about 6 ms at 44.1 kHz.
+const FRAME_SIZE: usize = 1024;
+const HOP_SIZE: usize = 256;
+/// Past onset function values the adaptive threshold is taken from
+const THRESHOLD_FRAMES: usize = 64;
+/// Spread of the threshold in mean absolute deviations above the median, at the lowest and
+/// highest sensitivity
+const LEAST_SENSITIVE_SPREAD: f32 = 12.0;
+const MOST_SENSITIVE_SPREAD: f32 = 2.0;
+/// An onset has to be at least this many times the median as well
+const MIN_MEDIAN_RATIO: f32 = 2.0;
+/// Lowest onset function value counted as a strike. The functions are relative to the level of
+/// the frame, so in a steady decay, where the adaptive threshold sinks towards zero, small
+/// fluctuations aren't taken for strikes.
+const MIN_ONSET_VALUE: f32 = 0.05;
+/// Frames quieter than this are never onsets
+const SILENCE_DB: f32 = -60.0;
+/// Strikes closer together than this are taken as one
+const MIN_GAP_SECONDS: f32 = 0.1;
+/// Block length of the energy comparison that places an onset within its frame
+const REFINE_BLOCK: usize = 16;
+/// Block length for finding where a strike has died away
+const SEGMENT_BLOCK: usize = 1024;
+
+/// Onset detection function, each reacting to a different aspect of an attack
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum OnsetFunction {
+    /// Rise in magnitude summed over all bins
+    SpectralFlux,
+    /// Rise in log frequency weighted energy, favouring the broadband click of a strike
+    HighFrequencyContent,
+    /// Deviation from the magnitude and phase predicted from the previous frames, which also
+    /// catches strikes that don't change the level much
+    ComplexDomain,
+}
+
+/// A detected strike
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Onset {
+    /// Sample index from the start of the input
+    pub sample: u64,
+    /// Onset function over the threshold at the detection
+    pub strength: f32,
+}
+
+impl Onset {
+    pub fn seconds(&self, sample_rate: u32) -> f32 {
+        self.sample as f32 / sample_rate as f32
+    }
+}
+
+/// Streaming onset detector, fed in blocks of any size. Detections lag the input by two hops
+/// plus a frame, and are placed on the sample where the energy rises most.
+pub struct OnsetDetector {
+    function: OnsetFunction,
+    sensitivity: f32,
+    min_gap: u64,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    previous: Vec<Complex<f32>>,
+    before_previous: Vec<Complex<f32>>,
+    previous_hfc: f32,
+    /// Input kept for the frames still to come and for placing onsets
+    input: Vec<f32>,
+    /// Sample index of `input[0]`
+    input_start: u64,
+    next_frame: u64,
+    /// Onset function and level in dB of the most recent frames, newest last
+    history: VecDeque<(f32, f32)>,
+    last_onset: Option<u64>,
+}
+
+impl OnsetDetector {
+    /// `sensitivity` ranges from 0, only clear strikes, to 1, also soft ones
+    pub fn new(function: OnsetFunction, sensitivity: f32, sample_rate: u32) -> Self {
+        let window = (0..FRAME_SIZE)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / FRAME_SIZE as f32).cos())
+            .collect();
+        let bins = FRAME_SIZE / 2 + 1;
+
+        OnsetDetector {
+            function,
+            sensitivity: sensitivity.clamp(0.0, 1.0),
+            min_gap: (MIN_GAP_SECONDS * sample_rate as f32) as u64,
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            window,
+            buffer: vec![Complex::new(0.0, 0.0); FRAME_SIZE],
+            previous: vec![Complex::new(0.0, 0.0); bins],
+            before_previous: vec![Complex::new(0.0, 0.0); bins],
+            previous_hfc: 0.0,
+            input: Vec::new(),
+            input_start: 0,
+            next_frame: 0,
+            history: VecDeque::new(),
+            last_onset: None,
+        }
+    }
+
+    /// Switching the function restarts the threshold, as the functions differ in scale
+    pub fn set_function(&mut self, function: OnsetFunction) {
+        if function != self.function {
+            self.function = function;
+            self.history.clear();
+        }
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity.clamp(0.0, 1.0);
+    }
+
+    /// Number of samples fed so far
+    pub fn position(&self) -> u64 {
+        self.input_start + self.input.len() as u64
+    }
+
+    /// Feed input, appending detected strikes to `onsets`
+    pub fn process(&mut self, samples: &[f32], onsets: &mut Vec<Onset>) {
+        self.input.extend_from_slice(samples);
+
+        loop {
+            let frame_start = self.next_frame * HOP_SIZE as u64;
+            if frame_start + FRAME_SIZE as u64 > self.position() {
+                break;
+            }
+            let offset = (frame_start - self.input_start) as usize;
+            let value = self.analyze_frame(offset);
+            self.history.push_back(value);
+            if self.history.len() > THRESHOLD_FRAMES + 2 {
+                self.history.pop_front();
+            }
+            self.next_frame += 1;
+
+            if let Some(onset) = self.pick_peak() {
+                onsets.push(onset);
+            }
+        }
+
+        // keep what the next frame and the placement of the frame before it need
+        let keep_from = (self.next_frame * HOP_SIZE as u64).saturating_sub(2 * FRAME_SIZE as u64);
+        if keep_from > self.input_start {
+            self.input.drain(..(keep_from - self.input_start) as usize);
+            self.input_start = keep_from;
+        }
+    }
+
+    /// Onset function and level of the frame starting at `offset` in the input
+    fn analyze_frame(&mut self, offset: usize) -> (f32, f32) {
+        let frame = &self.input[offset..offset + FRAME_SIZE];
+        let power = frame.iter().map(|s| s * s).sum::<f32>() / FRAME_SIZE as f32;
+        let level_db = 10.0 * power.max(1e-12).log10();
+
+        for ((value, sample), w) in self.buffer.iter_mut().zip(frame).zip(self.window.iter()) {
+            *value = Complex::new(sample * w, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+        let current = &self.buffer[..FRAME_SIZE / 2 + 1];
+
+        let magnitude_sum = current
+            .iter()
+            .map(|x| x.norm())
+            .sum::<f32>()
+            .max(f32::MIN_POSITIVE);
+        let value = match self.function {
+            OnsetFunction::SpectralFlux => {
+                current
+                    .iter()
+                    .zip(self.previous.iter())
+                    .map(|(x, p)| (x.norm() - p.norm()).max(0.0))
+                    .sum::<f32>()
+                    / magnitude_sum
+            }
+            OnsetFunction::HighFrequencyContent => {
+                let hfc: f32 = current
+                    .iter()
+                    .enumerate()
+                    .map(|(k, x)| k as f32 * x.norm_sqr())
+                    .sum::<f32>()
+                    .max(f32::MIN_POSITIVE)
+                    .ln();
+                let rise = (hfc - self.previous_hfc).max(0.0);
+                self.previous_hfc = hfc;
+                rise
+            }
+            OnsetFunction::ComplexDomain => {
+                current
+                    .iter()
+                    .zip(self.previous.iter().zip(self.before_previous.iter()))
+                    .filter(|(x, (p, _))| x.norm() >= p.norm())
+                    .map(|(x, (p, pp))| {
+                        let predicted = Complex::from_polar(p.norm(), 2.0 * p.arg() - pp.arg());
+                        (x - predicted).norm()
+                    })
+                    .sum::<f32>()
+                    / magnitude_sum
+            }
+        };
+
+        std::mem::swap(&mut self.before_previous, &mut self.previous);
+        self.previous.copy_from_slice(current);
+        (value, level_db)
+    }
+
+    /// Check the second newest frame, which now has a neighbour on each side
+    fn pick_peak(&mut self) -> Option<Onset> {
+        let len = self.history.len();
+        if len < 2 {
+            return None;
+        }
+        let (value, level_db) = self.history[len - 2];
+        let before = if len >= 3 {
+            self.history[len - 3].0
+        } else {
+            0.0
+        };
+        let after = self.history[len - 1].0;
+        if value <= before || value < after || level_db < SILENCE_DB {
+            return None;
+        }
+
+        let mut past: Vec<f32> = self.history.iter().take(len - 2).map(|h| h.0).collect();
+        past.sort_by(f32::total_cmp);
+        let median = past.get(past.len() / 2).copied().unwrap_or(0.0);
+        let deviation =
+            past.iter().map(|v| (v - median).abs()).sum::<f32>() / past.len().max(1) as f32;
+        let spread = LEAST_SENSITIVE_SPREAD
+            + (MOST_SENSITIVE_SPREAD - LEAST_SENSITIVE_SPREAD) * self.sensitivity;
+        let threshold = (median + spread * deviation)
+            .max(MIN_MEDIAN_RATIO * median)
+            .max(MIN_ONSET_VALUE);
+        if value <= threshold {
+            return None;
+        }
+
+        let frame = self.next_frame - 2;
+        let sample = self.place(frame);
+        if self
+            .last_onset
+            .is_some_and(|last| sample < last + self.min_gap)
+        {
+            return None;
+        }
+        self.last_onset = Some(sample);
+
+        Some(Onset {
+            sample,
+            strength: value / threshold,
+        })
+    }
+
+    /// Sample around `frame` after which the energy of the differentiated signal rises the
+    /// most relative to before, which favours the click of the strike over the ringing it adds
+    fn place(&self, frame: u64) -> u64 {
+        let start = (frame * HOP_SIZE as u64)
+            .saturating_sub(FRAME_SIZE as u64)
+            .max(self.input_start);
+        let end = (frame * HOP_SIZE as u64 + FRAME_SIZE as u64).min(self.position());
+        let region =
+            &self.input[(start - self.input_start) as usize..(end - self.input_start) as usize];
+        if region.len() < 2 * REFINE_BLOCK {
+            return frame * HOP_SIZE as u64;
+        }
+
+        let mut energy = vec![0.0_f64; region.len()];
+        for (i, pair) in region.windows(2).enumerate() {
+            let difference = (pair[1] - pair[0]) as f64;
+            energy[i + 1] = energy[i] + difference * difference;
+        }
+        let rise = |n: usize| {
+            let after = energy[n + REFINE_BLOCK - 1] - energy[n - 1];
+            let before = energy[n - 1] - energy[n - 1 - REFINE_BLOCK];
+            (after + 1e-12) / (before + 1e-12)
+        };
+        let best = (REFINE_BLOCK + 1..=region.len() - REFINE_BLOCK)
+            .max_by(|&a, &b| rise(a).total_cmp(&rise(b)))
+            .unwrap_or(REFINE_BLOCK);
+        start + best as u64
+    }
+}
+
+/// Find the strikes in a whole recording
+pub fn detect_onsets(
+    samples: &[f32],
+    sample_rate: u32,
+    function: OnsetFunction,
+    sensitivity: f32,
+) -> Vec<Onset> {
+    let mut detector = OnsetDetector::new(function, sensitivity, sample_rate);
+    let mut onsets = Vec::new();
+    detector.process(samples, &mut onsets);
+    onsets
+}
+
+/// Split a recording into one region per strike, each running from its onset until the next
+/// one or until the sound has died away into silence
+pub fn segment_strikes(samples: &[f32], onsets: &[Onset]) -> Vec<Range<usize>> {
+    onsets
+        .iter()
+        .enumerate()
+        .map(|(i, onset)| {
+            let start = (onset.sample as usize).min(samples.len());
+            let next = onsets.get(i + 1).map_or(samples.len(), |next| {
+                (next.sample as usize).min(samples.len())
+            });
+            let audible = samples[start..next]
+                .chunks(SEGMENT_BLOCK)
+                .rposition(|block| {
+                    let power = block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32;
+                    10.0 * power.max(1e-12).log10() > SILENCE_DB
+                });
+            let end = audible.map_or(next, |block| {
+                (start + (block + 1) * SEGMENT_BLOCK).min(next)
+            });
+            start..end
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Decaying partials starting at each of `strikes`, each with a short click
+    fn strikes(strikes: &[usize], len: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; len];
+        for &start in strikes {
+            for (n, sample) in samples[start..].iter_mut().enumerate() {
+                let t = n as f32 / SAMPLE_RATE as f32;
+                let ring = [(220.0, 0.3), (605.0, 0.15), (1180.0, 0.08)]
+                    .iter()
+                    .map(|(f, a)| a * (2.0 * std::f32::consts::PI * f * t).sin())
+                    .sum::<f32>()
+                    * (-3.0 * t).exp();
+                let click = if n < 32 {
+                    0.5 * (1.0 - n as f32 / 32.0)
+                } else {
+                    0.0
+                };
+                *sample += ring + click;
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn finds_each_strike() {
+        let starts = [12000, 60000, 110000];
+        let samples = strikes(&starts, 150000);
+        for function in [
+            OnsetFunction::SpectralFlux,
+            OnsetFunction::HighFrequencyContent,
+            OnsetFunction::ComplexDomain,
+        ] {
+            let onsets = detect_onsets(&samples, SAMPLE_RATE, function, 0.5);
+            let found: Vec<u64> = onsets.iter().map(|onset| onset.sample).collect();
+            assert_eq!(found.len(), starts.len(), "{:?}: {:?}", function, found);
+            for (sample, start) in found.iter().zip(starts) {
+                assert!(
+                    sample.abs_diff(start as u64) <= REFINE_BLOCK as u64,
+                    "{:?}: strike at {} found at {}",
+                    function,
+                    start,
+                    sample
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn block_size_does_not_change_detections() {
+        let samples = strikes(&[12000, 60000], 100000);
+        let whole = detect_onsets(&samples, SAMPLE_RATE, OnsetFunction::SpectralFlux, 0.5);
+
+        let mut detector = OnsetDetector::new(OnsetFunction::SpectralFlux, 0.5, SAMPLE_RATE);
+        let mut blocks = Vec::new();
+        for block in samples.chunks(333) {
+            detector.process(block, &mut blocks);
+        }
+        assert_eq!(detector.position(), samples.len() as u64);
+        assert_eq!(whole, blocks);
+    }
+
+    #[test]
+    fn silence_has_no_onsets() {
+        let silence = vec![0.0; 100000];
+        assert!(detect_onsets(&silence, SAMPLE_RATE, OnsetFunction::ComplexDomain, 1.0).is_empty());
+    }
+
+    #[test]
+    fn segments_end_where_the_sound_dies_away() {
+        let mut samples = vec![0.0; 20000];
+        samples[1000..5000].fill(0.5);
+        samples[10000..12000].fill(0.5);
+        let onsets = [1000, 10000].map(|sample| Onset {
+            sample,
+            strength: 1.0,
+        });
+        let segments = segment_strikes(&samples, &onsets);
+        // up to the end of the last audible block
+        assert_eq!(segments, vec![1000..5096, 10000..12048]);
+    }
+}
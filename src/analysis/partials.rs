@@ -0,0 +1,105 @@
+use crate::analysis::spectrum::Peak;
+use crate::analysis::tuning::ratio_to_cents;
+
+/// Highest thin-ring mode partials are matched against
+pub const MAX_RING_MODE: u32 = 12;
+/// Partials this far below the fundamental are ignored
+const MIN_RATIO: f32 = 0.99;
+
+/// One partial relative to the fundamental
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Partial {
+    pub frequency: f32,
+    pub magnitude_db: f32,
+    /// Frequency over the fundamental
+    pub ratio: f32,
+    /// Nearest integer multiple of the fundamental
+    pub harmonic: u32,
+    pub harmonic_deviation_cents: f32,
+    /// Nearest flexural mode of a free thin ring, the fundamental being mode 2
+    pub ring_mode: u32,
+    pub ring_ratio: f32,
+    pub ring_deviation_cents: f32,
+}
+
+/// Partials of a sound with summary measures of how far they are from a harmonic series
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialAnalysis {
+    pub fundamental: f32,
+    /// Ascending in frequency, the fundamental first
+    pub partials: Vec<Partial>,
+    /// Amplitude weighted RMS deviation of the overtones from the nearest harmonic in cents.
+    /// 0 for a perfectly harmonic sound.
+    pub inharmonicity_cents: f32,
+    /// Amplitude weighted RMS deviation of the overtones from the thin-ring model in cents
+    pub ring_model_deviation_cents: f32,
+}
+
+/// Relative frequency of flexural mode `n` (n >= 2) of a free circular ring (Rayleigh):
+/// f ~ n (n^2 - 1) / sqrt(n^2 + 1). Bowls approximate this with their rim.
+pub fn ring_mode_frequency(n: u32) -> f64 {
+    let n = n as f64;
+    n * (n * n - 1.0) / (n * n + 1.0).sqrt()
+}
+
+/// Ring mode frequency relative to the lowest mode (n = 2): 1, 2.83, 5.42, 8.77, ...
+pub fn ring_ratio(n: u32) -> f64 {
+    ring_mode_frequency(n) / ring_mode_frequency(2)
+}
+
+/// Relate the peaks to the fundamental. Peaks below the fundamental are left out.
+pub fn analyze_partials(peaks: &[Peak], fundamental: Peak) -> PartialAnalysis {
+    let f0 = fundamental.frequency;
+
+    let mut partials: Vec<Partial> = peaks
+        .iter()
+        .filter(|p| p.frequency >= f0 * MIN_RATIO)
+        .map(|p| {
+            let ratio = p.frequency / f0;
+            let cents_from = |target: f64| (ratio_to_cents(ratio as f64 / target)) as f32;
+
+            let harmonic = (ratio.round() as u32).max(1);
+            let ring_mode = (2..=MAX_RING_MODE)
+                .min_by(|&a, &b| {
+                    cents_from(ring_ratio(a))
+                        .abs()
+                        .total_cmp(&cents_from(ring_ratio(b)).abs())
+                })
+                .unwrap_or(2);
+
+            Partial {
+                frequency: p.frequency,
+                magnitude_db: p.magnitude_db,
+                ratio,
+                harmonic,
+                harmonic_deviation_cents: cents_from(harmonic as f64),
+                ring_mode,
+                ring_ratio: ring_ratio(ring_mode) as f32,
+                ring_deviation_cents: cents_from(ring_ratio(ring_mode)),
+            }
+        })
+        .collect();
+    partials.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+
+    let overtones = partials
+        .iter()
+        .filter(|p| p.ratio > 1.0 + (1.0 - MIN_RATIO));
+    let weighted_rms = |deviation: fn(&Partial) -> f32| {
+        let (sum, weight) = overtones.clone().fold((0.0, 0.0), |(sum, weight), p| {
+            let amplitude = 10.0_f32.powf(p.magnitude_db / 20.0);
+            (sum + amplitude * deviation(p).powi(2), weight + amplitude)
+        });
+        if weight > 0.0 {
+            (sum / weight).sqrt()
+        } else {
+            0.0
+        }
+    };
+
+    PartialAnalysis {
+        fundamental: f0,
+        inharmonicity_cents: weighted_rms(|p| p.harmonic_deviation_cents),
+        ring_model_deviation_cents: weighted_rms(|p| p.ring_deviation_cents),
+        partials,
+    }
+}
@@ -0,0 +1,66 @@
+use crate::analysis::spectrum::parabolic_peak;
+
+use super::{is_silent, max_lag, min_lag, Correlator, PitchEstimate, PitchEstimator};
+
+/// Shortest analysis window. It grows to twice the longest period at high sample rates.
+const MIN_WINDOW: usize = 4096;
+/// The first peak this close to the highest is taken, as every multiple of the period
+/// correlates about as well
+const PEAK_RATIO: f32 = 0.9;
+
+/// Normalised autocorrelation: the first strong peak after the correlation has dropped below
+/// zero. Confidence is the correlation at that lag, 1 for a perfectly periodic signal.
+pub struct AutocorrelationEstimator {
+    correlator: Correlator,
+    correlation: Vec<f32>,
+}
+
+impl AutocorrelationEstimator {
+    pub fn new() -> Self {
+        AutocorrelationEstimator {
+            correlator: Correlator::new(),
+            correlation: Vec::new(),
+        }
+    }
+}
+
+impl PitchEstimator for AutocorrelationEstimator {
+    fn estimate(&mut self, samples: &[f32], sample_rate: u32) -> Option<PitchEstimate> {
+        let lags = max_lag(sample_rate) + 2;
+        let window = MIN_WINDOW.max(2 * lags);
+        if samples.len() < window || is_silent(samples) {
+            return None;
+        }
+        let frame = &samples[samples.len() - window..];
+
+        self.correlator
+            .correlate(frame, frame, lags, &mut self.correlation);
+        // correct for the shrinking overlap at longer lags
+        let energy = self.correlation[0].max(f32::MIN_POSITIVE);
+        let normalized: Vec<f32> = self
+            .correlation
+            .iter()
+            .enumerate()
+            .map(|(lag, r)| r / energy * window as f32 / (window - lag) as f32)
+            .collect();
+
+        let first_negative = normalized.iter().position(|&r| r < 0.0)?;
+        let mut search = first_negative.max(min_lag(sample_rate))..lags - 1;
+        let highest = search
+            .clone()
+            .map(|lag| normalized[lag])
+            .fold(f32::MIN, f32::max);
+        let lag = search.find(|&lag| {
+            normalized[lag] >= PEAK_RATIO * highest
+                && normalized[lag] >= normalized[lag - 1]
+                && normalized[lag] >= normalized[lag + 1]
+        })?;
+        let (offset, value) =
+            parabolic_peak(normalized[lag - 1], normalized[lag], normalized[lag + 1]);
+
+        Some(PitchEstimate {
+            frequency: sample_rate as f32 / (lag as f32 + offset),
+            confidence: value.clamp(0.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::analysis::spectrum::parabolic_peak;
+
+use super::{fft_size, is_silent, max_lag, min_lag, PitchEstimate, PitchEstimator};
+
+/// FFT length at 48 kHz, scaled with the sample rate
+const FFT_SIZE: usize = 8192;
+/// Magnitudes further below the strongest bin are raised to this, so the log of a near silent
+/// noise floor doesn't swamp the harmonic ripple
+const FLOOR_DB: f32 = 80.0;
+/// Peak prominence, in standard deviations of the searched cepstrum, at which confidence
+/// starts to rise and where it reaches 1
+const MIN_PROMINENCE: f32 = 3.0;
+const FULL_PROMINENCE: f32 = 10.0;
+
+/// Real cepstrum (Noll, 1967): the inverse transform of the log magnitude spectrum has a peak at
+/// the period of evenly spaced harmonics. Confidence grows with the prominence of that peak.
+pub struct CepstrumEstimator {
+    planner: FftPlanner<f32>,
+    size: usize,
+    forward: Option<Arc<dyn Fft<f32>>>,
+    inverse: Option<Arc<dyn Fft<f32>>>,
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl CepstrumEstimator {
+    pub fn new() -> Self {
+        CepstrumEstimator {
+            planner: FftPlanner::new(),
+            size: 0,
+            forward: None,
+            inverse: None,
+            window: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn set_size(&mut self, size: usize) {
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        self.forward = Some(self.planner.plan_fft_forward(size));
+        self.inverse = Some(self.planner.plan_fft_inverse(size));
+        self.window = (0..size)
+            .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / size as f32).cos())
+            .collect();
+        self.buffer = vec![Complex::new(0.0, 0.0); size];
+    }
+}
+
+impl PitchEstimator for CepstrumEstimator {
+    fn estimate(&mut self, samples: &[f32], sample_rate: u32) -> Option<PitchEstimate> {
+        let size = fft_size(FFT_SIZE, sample_rate);
+        let max_lag = max_lag(sample_rate).min(size / 2 - 1);
+        let min_lag = min_lag(sample_rate);
+        if samples.len() < size || is_silent(samples) || min_lag + 2 >= max_lag {
+            return None;
+        }
+        self.set_size(size);
+        let (Some(forward), Some(inverse)) = (&self.forward, &self.inverse) else {
+            return None;
+        };
+
+        let frame = &samples[samples.len() - size..];
+        for ((value, sample), w) in self.buffer.iter_mut().zip(frame).zip(self.window.iter()) {
+            *value = Complex::new(sample * w, 0.0);
+        }
+        forward.process(&mut self.buffer);
+        let peak = self.buffer.iter().map(|c| c.norm()).fold(0.0, f32::max);
+        let floor = (peak * 10.0_f32.powf(-FLOOR_DB / 20.0)).max(f32::MIN_POSITIVE);
+        for value in self.buffer.iter_mut() {
+            *value = Complex::new(value.norm().max(floor).ln(), 0.0);
+        }
+        inverse.process(&mut self.buffer);
+
+        let cepstrum: Vec<f32> = self.buffer[..=max_lag + 1].iter().map(|c| c.re).collect();
+        // the spectral envelope makes up the low quefrencies, falling off from the origin over
+        // about the inverse of the bandwidth of the harmonics. Its tail would pass for a short
+        // period, so it is liftered off by searching from its first minimum on.
+        let envelope_end = (1..max_lag)
+            .find(|&i| cepstrum[i + 1] > cepstrum[i])
+            .unwrap_or(max_lag);
+        let range = min_lag.max(envelope_end)..=max_lag;
+        let lag = range
+            .clone()
+            .max_by(|&a, &b| cepstrum[a].total_cmp(&cepstrum[b]))?;
+        // still falling or rising at the edge of the range, not a peak
+        if lag == *range.start() || lag == max_lag {
+            return None;
+        }
+
+        let count = range.clone().count() as f32;
+        let mean = range.clone().map(|i| cepstrum[i]).sum::<f32>() / count;
+        let deviation = (range.map(|i| (cepstrum[i] - mean).powi(2)).sum::<f32>() / count).sqrt();
+        let prominence = (cepstrum[lag] - mean) / deviation.max(f32::MIN_POSITIVE);
+
+        let (offset, _) = parabolic_peak(cepstrum[lag - 1], cepstrum[lag], cepstrum[lag + 1]);
+        Some(PitchEstimate {
+            frequency: sample_rate as f32 / (lag as f32 + offset),
+            confidence: ((prominence - MIN_PROMINENCE) / (FULL_PROMINENCE - MIN_PROMINENCE))
+                .clamp(0.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,96 @@
+use crate::analysis::spectrum::{parabolic_peak, SpectrumAnalyzer};
+
+use super::{fft_size, is_silent, PitchEstimate, PitchEstimator, MAX_FREQUENCY, MIN_FREQUENCY};
+
+/// FFT length at 48 kHz, scaled with the sample rate
+const FFT_SIZE: usize = 8192;
+/// Harmonics multiplied, the fundamental included
+const HARMONICS: usize = 5;
+/// Bins either side of a harmonic counted towards its energy
+const HARMONIC_WIDTH_BINS: usize = 2;
+/// Bins further below the strongest are raised to this, so missing harmonics don't veto a
+/// candidate with their noise floor
+const FLOOR_DB: f32 = 60.0;
+/// A multiple of the best candidate within this of its product is taken instead, since a
+/// subharmonic collects the same harmonics plus floor
+const OCTAVE_TOLERANCE_DB: f32 = 6.0;
+
+/// Harmonic product spectrum (Schroeder, 1968): the spectrum compressed by 1 to 5 and
+/// multiplied, so the harmonics of one fundamental reinforce each other there. Confidence is
+/// the share of the spectrum's energy that lies on those harmonics, which stays low for
+/// inharmonic sources like bowls.
+pub struct HarmonicProductSpectrumEstimator {
+    /// Created for the current sample rate
+    analyzer: Option<(u32, SpectrumAnalyzer)>,
+}
+
+impl HarmonicProductSpectrumEstimator {
+    pub fn new() -> Self {
+        HarmonicProductSpectrumEstimator { analyzer: None }
+    }
+}
+
+impl PitchEstimator for HarmonicProductSpectrumEstimator {
+    fn estimate(&mut self, samples: &[f32], sample_rate: u32) -> Option<PitchEstimate> {
+        if is_silent(samples) {
+            return None;
+        }
+        if self.analyzer.as_ref().map(|(rate, _)| *rate) != Some(sample_rate) {
+            let analyzer = SpectrumAnalyzer::new(fft_size(FFT_SIZE, sample_rate));
+            self.analyzer = Some((sample_rate, analyzer));
+        }
+        let (_, analyzer) = self.analyzer.as_mut()?;
+        let spectrum = analyzer.analyze(samples, sample_rate);
+        let magnitudes = &spectrum.magnitudes_db;
+        let floor = magnitudes.iter().cloned().fold(f32::MIN, f32::max) - FLOOR_DB;
+
+        let first_bin = ((MIN_FREQUENCY / spectrum.bin_hz).ceil() as usize).max(1);
+        let last_bin =
+            ((MAX_FREQUENCY / spectrum.bin_hz) as usize).min((magnitudes.len() - 2) / HARMONICS);
+        if first_bin >= last_bin {
+            return None;
+        }
+
+        // a product of magnitudes is a sum in dB
+        let product = |bin: usize| -> f32 {
+            (1..=HARMONICS)
+                .map(|h| magnitudes[bin * h].max(floor))
+                .sum()
+        };
+        let best = (first_bin..=last_bin).max_by(|&a, &b| product(a).total_cmp(&product(b)))?;
+        // the highest multiple about as good as the best, searched around each multiple
+        let bin = (2..=HARMONICS)
+            .rev()
+            .filter_map(|multiple| {
+                let centre = best * multiple;
+                (centre - multiple..=(centre + multiple).min(last_bin))
+                    .max_by(|&a, &b| product(a).total_cmp(&product(b)))
+            })
+            .find(|&bin| product(bin) >= product(best) - OCTAVE_TOLERANCE_DB)
+            .unwrap_or(best);
+        // refine on the strongest harmonic, the fundamental may be missing
+        let harmonic = (1..=HARMONICS)
+            .max_by(|&a, &b| magnitudes[bin * a].total_cmp(&magnitudes[bin * b]))
+            .unwrap_or(1);
+        let peak = bin * harmonic;
+        let (offset, _) =
+            parabolic_peak(magnitudes[peak - 1], magnitudes[peak], magnitudes[peak + 1]);
+        let frequency = spectrum.frequency(peak as f32 + offset) / harmonic as f32;
+
+        let power = |db: f32| 10.0_f32.powf(db / 10.0);
+        let total: f32 = magnitudes.iter().map(|&db| power(db)).sum();
+        let harmonic_power: f32 = (1..=HARMONICS)
+            .flat_map(|h| {
+                let centre = bin * h;
+                centre.saturating_sub(HARMONIC_WIDTH_BINS)
+                    ..(centre + HARMONIC_WIDTH_BINS + 1).min(magnitudes.len())
+            })
+            .map(|i| power(magnitudes[i]))
+            .sum();
+
+        Some(PitchEstimate {
+            frequency,
+            confidence: (harmonic_power / total.max(f32::MIN_POSITIVE)).clamp(0.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,265 @@
+mod autocorrelation;
+mod cepstrum;
+mod hps;
+mod spectral_peak;
+mod yin;
+
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+pub use autocorrelation::AutocorrelationEstimator;
+pub use cepstrum::CepstrumEstimator;
+pub use hps::HarmonicProductSpectrumEstimator;
+pub use spectral_peak::SpectralPeakEstimator;
+pub use yin::{PyinEstimator, YinEstimator};
+
+/// Range searched by all estimators, from large bowls to high voices
+pub const MIN_FREQUENCY: f32 = 40.0;
+pub const MAX_FREQUENCY: f32 = 2000.0;
+/// Input whose most recent `SILENCE_WINDOW` samples are quieter than this has no pitch
+const SILENCE_DB: f32 = -60.0;
+const SILENCE_WINDOW: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PitchEstimate {
+    pub frequency: f32,
+    /// 0 to 1, how sure the estimator is. Each algorithm derives it from its own measure, so
+    /// values are comparable over time but only roughly between algorithms.
+    pub confidence: f32,
+}
+
+/// A fundamental frequency estimator working on the most recent input
+pub trait PitchEstimator {
+    /// Estimate from the end of `samples`. Estimators may keep state between calls, so they
+    /// expect consecutive frames of one signal.
+    fn estimate(&mut self, samples: &[f32], sample_rate: u32) -> Option<PitchEstimate>;
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PitchAlgorithm {
+    /// Lowest strong FFT peak, as used for the partial analysis
+    SpectralPeak,
+    Yin,
+    Pyin,
+    HarmonicProductSpectrum,
+    Cepstrum,
+    Autocorrelation,
+}
+
+impl PitchAlgorithm {
+    pub const ALL: [PitchAlgorithm; 6] = [
+        PitchAlgorithm::SpectralPeak,
+        PitchAlgorithm::Yin,
+        PitchAlgorithm::Pyin,
+        PitchAlgorithm::HarmonicProductSpectrum,
+        PitchAlgorithm::Cepstrum,
+        PitchAlgorithm::Autocorrelation,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PitchAlgorithm::SpectralPeak => "Spectral Peak",
+            PitchAlgorithm::Yin => "YIN",
+            PitchAlgorithm::Pyin => "pYIN",
+            PitchAlgorithm::HarmonicProductSpectrum => "Harmonic Product Spectrum",
+            PitchAlgorithm::Cepstrum => "Cepstrum",
+            PitchAlgorithm::Autocorrelation => "Autocorrelation",
+        }
+    }
+
+    pub fn create(self) -> Box<dyn PitchEstimator> {
+        match self {
+            PitchAlgorithm::SpectralPeak => Box::new(SpectralPeakEstimator::new()),
+            PitchAlgorithm::Yin => Box::new(YinEstimator::new()),
+            PitchAlgorithm::Pyin => Box::new(PyinEstimator::new()),
+            PitchAlgorithm::HarmonicProductSpectrum => {
+                Box::new(HarmonicProductSpectrumEstimator::new())
+            }
+            PitchAlgorithm::Cepstrum => Box::new(CepstrumEstimator::new()),
+            PitchAlgorithm::Autocorrelation => Box::new(AutocorrelationEstimator::new()),
+        }
+    }
+}
+
+/// How an estimator fared over a whole recording
+#[derive(Clone, Copy, Debug)]
+pub struct PitchSummary {
+    pub median_frequency: f32,
+    pub mean_confidence: f32,
+    /// Share of the frames with a pitch
+    pub voiced: f32,
+    /// Mean absolute deviation from the median in cents, low for a steady estimate
+    pub spread_cents: f32,
+}
+
+/// Run an estimator over a recording in hops of `hop_seconds`, each estimate seeing the input
+/// up to that point as it would live. None if it never found a pitch.
+pub fn summarize(
+    algorithm: PitchAlgorithm,
+    samples: &[f32],
+    sample_rate: u32,
+    hop_seconds: f32,
+) -> Option<PitchSummary> {
+    let hop = ((hop_seconds * sample_rate as f32) as usize).max(1);
+    let mut estimator = algorithm.create();
+    let mut frames = 0;
+    let mut estimates = Vec::new();
+    for end in (hop..=samples.len()).step_by(hop) {
+        frames += 1;
+        estimates.extend(estimator.estimate(&samples[..end], sample_rate));
+    }
+    if estimates.is_empty() {
+        return None;
+    }
+
+    let mut frequencies: Vec<f32> = estimates.iter().map(|e| e.frequency).collect();
+    frequencies.sort_by(f32::total_cmp);
+    let median_frequency = frequencies[frequencies.len() / 2];
+    let count = estimates.len() as f32;
+    Some(PitchSummary {
+        median_frequency,
+        mean_confidence: estimates.iter().map(|e| e.confidence).sum::<f32>() / count,
+        voiced: count / frames as f32,
+        spread_cents: frequencies
+            .iter()
+            .map(|f| 1200.0 * (f / median_frequency).log2().abs())
+            .sum::<f32>()
+            / count,
+    })
+}
+
+fn is_silent(samples: &[f32]) -> bool {
+    let samples = &samples[samples.len().saturating_sub(SILENCE_WINDOW)..];
+    let power = samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32;
+    10.0 * power.max(1e-12).log10() < SILENCE_DB
+}
+
+/// FFT length giving the resolution `base` has at 48 kHz
+fn fft_size(base: usize, sample_rate: u32) -> usize {
+    (base * sample_rate as usize / 48000)
+        .next_power_of_two()
+        .max(base)
+}
+
+/// Longest period searched, in samples
+fn max_lag(sample_rate: u32) -> usize {
+    (sample_rate as f32 / MIN_FREQUENCY).ceil() as usize
+}
+
+/// Shortest period searched, in samples
+fn min_lag(sample_rate: u32) -> usize {
+    ((sample_rate as f32 / MAX_FREQUENCY).floor() as usize).max(2)
+}
+
+/// Cross-correlation of `window` with `samples` for lags `0..lags`, computed with FFTs.
+/// Plans are cached since the sizes rarely change.
+struct Correlator {
+    planner: FftPlanner<f32>,
+    size: usize,
+    forward: Option<Arc<dyn Fft<f32>>>,
+    inverse: Option<Arc<dyn Fft<f32>>>,
+    a: Vec<Complex<f32>>,
+    b: Vec<Complex<f32>>,
+}
+
+impl Correlator {
+    fn new() -> Self {
+        Correlator {
+            planner: FftPlanner::new(),
+            size: 0,
+            forward: None,
+            inverse: None,
+            a: Vec::new(),
+            b: Vec::new(),
+        }
+    }
+
+    /// `out[τ] = Σ window[j] · samples[j + τ]`, `samples` being at least `window.len() + lags`
+    /// long
+    fn correlate(&mut self, window: &[f32], samples: &[f32], lags: usize, out: &mut Vec<f32>) {
+        let size = (window.len() + lags).next_power_of_two() * 2;
+        if size != self.size {
+            self.size = size;
+            self.forward = Some(self.planner.plan_fft_forward(size));
+            self.inverse = Some(self.planner.plan_fft_inverse(size));
+        }
+        let (Some(forward), Some(inverse)) = (&self.forward, &self.inverse) else {
+            return;
+        };
+
+        let to_complex = |values: &[f32], buffer: &mut Vec<Complex<f32>>| {
+            buffer.clear();
+            buffer.extend(values.iter().map(|&v| Complex::new(v, 0.0)));
+            buffer.resize(size, Complex::new(0.0, 0.0));
+        };
+        to_complex(window, &mut self.a);
+        to_complex(
+            &samples[..(window.len() + lags).min(samples.len())],
+            &mut self.b,
+        );
+        forward.process(&mut self.a);
+        forward.process(&mut self.b);
+        for (a, b) in self.a.iter_mut().zip(self.b.iter()) {
+            *a = a.conj() * b;
+        }
+        inverse.process(&mut self.a);
+
+        out.clear();
+        out.extend(self.a[..lags].iter().map(|c| c.re / size as f32));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fundamental with its second and third harmonic
+    fn harmonic_tone(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| {
+                let t = n as f32 / sample_rate as f32;
+                [(1.0, 0.5), (2.0, 0.3), (3.0, 0.2)]
+                    .iter()
+                    .map(|(harmonic, amplitude)| {
+                        amplitude * (2.0 * std::f32::consts::PI * harmonic * frequency * t).sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn estimators_find_harmonic_tones() {
+        for sample_rate in [44100, 48000, 96000] {
+            for frequency in [41.0, 55.0, 110.0, 196.0, 440.0, 880.0, 1500.0, 1900.0] {
+                let tone = harmonic_tone(frequency, sample_rate, 16384);
+                for algorithm in PitchAlgorithm::ALL {
+                    let estimate = algorithm.create().estimate(&tone, sample_rate);
+                    let cents = estimate.map(|e| 1200.0 * (e.frequency / frequency).log2());
+                    // the cepstrum's peak leans on the tail of the spectral envelope for low
+                    // tones, the others are within a few cents
+                    assert!(
+                        cents.is_some_and(|cents| cents.abs() < 25.0),
+                        "{}: {} Hz at {} Hz estimated as {:?}",
+                        algorithm.name(),
+                        frequency,
+                        sample_rate,
+                        estimate
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn silence_has_no_pitch() {
+        let silence = vec![0.0; 16384];
+        for algorithm in PitchAlgorithm::ALL {
+            assert_eq!(algorithm.create().estimate(&silence, 48000), None);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::analysis::frame::{FrameAnalysis, FFT_SIZE};
+use crate::analysis::spectrum::SpectrumAnalyzer;
+
+use super::{is_silent, PitchEstimate, PitchEstimator, MAX_FREQUENCY, MIN_FREQUENCY};
+
+/// The fundamental of the partial analysis: the lowest strong peak of a long FFT. Confidence
+/// is its amplitude relative to the strongest peak.
+pub struct SpectralPeakEstimator {
+    analyzer: SpectrumAnalyzer,
+}
+
+impl SpectralPeakEstimator {
+    pub fn new() -> Self {
+        SpectralPeakEstimator {
+            analyzer: SpectrumAnalyzer::new(FFT_SIZE),
+        }
+    }
+}
+
+impl PitchEstimator for SpectralPeakEstimator {
+    fn estimate(&mut self, samples: &[f32], sample_rate: u32) -> Option<PitchEstimate> {
+        if is_silent(samples) {
+            return None;
+        }
+        let frame = FrameAnalysis::new(&mut self.analyzer, samples, sample_rate);
+        let fundamental = frame.fundamental?;
+        if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&fundamental.frequency) {
+            return None;
+        }
+        let strongest = frame.peaks.first()?;
+
+        Some(PitchEstimate {
+            frequency: fundamental.frequency,
+            confidence: 10.0_f32.powf((fundamental.magnitude_db - strongest.magnitude_db) / 20.0),
+        })
+    }
+}
@@ -0,0 +1,203 @@
+use crate::analysis::spectrum::parabolic_peak;
+
+use super::{is_silent, max_lag, min_lag, Correlator, PitchEstimate, PitchEstimator};
+
+/// Shortest integration window. It grows to the longest period at high sample rates.
+const MIN_WINDOW: usize = 2048;
+/// Dip of the normalised difference accepted as the period in YIN
+const YIN_THRESHOLD: f32 = 0.15;
+/// pYIN thresholds, spread evenly over (0, 1]
+const PYIN_THRESHOLDS: usize = 100;
+/// Beta(2, 18) distribution over the thresholds, mean 0.1, as proposed for pYIN
+const PYIN_BETA_A: f32 = 2.0;
+const PYIN_BETA_B: f32 = 18.0;
+/// Share of a threshold's probability given to the global minimum when no dip is below it
+const PYIN_ABSOLUTE_MIN_WEIGHT: f32 = 0.01;
+/// Width in cents of the preference for staying near the previous pitch
+const PYIN_TRANSITION_CENTS: f32 = 100.0;
+/// Weight of a candidate far from the previous pitch relative to one right on it
+const PYIN_JUMP_WEIGHT: f32 = 0.2;
+
+/// Cumulative mean normalised difference function of the most recent input, index being the
+/// lag. Shared by YIN and pYIN.
+struct Difference {
+    correlator: Correlator,
+    correlation: Vec<f32>,
+    values: Vec<f32>,
+}
+
+impl Difference {
+    fn new() -> Self {
+        Difference {
+            correlator: Correlator::new(),
+            correlation: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns false if there isn't enough input for the longest period
+    fn compute(&mut self, samples: &[f32], sample_rate: u32) -> bool {
+        let lags = max_lag(sample_rate) + 2;
+        let window = MIN_WINDOW.max(lags);
+        if samples.len() < window + lags {
+            return false;
+        }
+        let frame = &samples[samples.len() - window - lags..];
+
+        // d(τ) = Σ (x[j] - x[j + τ])² = E(0) + E(τ) - 2 r(τ), with E(τ) the energy of the window
+        // starting at τ
+        self.correlator
+            .correlate(&frame[..window], frame, lags, &mut self.correlation);
+        let mut energy: f32 = frame[..window].iter().map(|s| s * s).sum();
+        let first_energy = energy;
+
+        self.values.clear();
+        self.values.push(1.0);
+        let mut sum = 0.0;
+        for tau in 1..lags {
+            energy += frame[window + tau - 1].powi(2) - frame[tau - 1].powi(2);
+            let difference = (first_energy + energy - 2.0 * self.correlation[tau]).max(0.0);
+            sum += difference;
+            self.values.push(if sum > 0.0 {
+                difference * tau as f32 / sum
+            } else {
+                1.0
+            });
+        }
+        true
+    }
+
+    /// Follow a dip from `tau` down to its minimum
+    fn descend(&self, mut tau: usize) -> usize {
+        while tau + 2 < self.values.len() && self.values[tau + 1] < self.values[tau] {
+            tau += 1;
+        }
+        tau
+    }
+
+    /// First dip below `threshold` within the searched range
+    fn first_dip(&self, threshold: f32, sample_rate: u32) -> Option<usize> {
+        (min_lag(sample_rate)..self.values.len() - 1)
+            .find(|&tau| self.values[tau] < threshold)
+            .map(|tau| self.descend(tau))
+    }
+
+    fn global_min(&self, sample_rate: u32) -> Option<usize> {
+        (min_lag(sample_rate)..self.values.len() - 1)
+            .min_by(|&a, &b| self.values[a].total_cmp(&self.values[b]))
+    }
+
+    /// Period refined between lags and the difference value at it
+    fn refine(&self, tau: usize) -> (f32, f32) {
+        let (offset, value) =
+            parabolic_peak(self.values[tau - 1], self.values[tau], self.values[tau + 1]);
+        (tau as f32 + offset, value.max(0.0))
+    }
+}
+
+/// YIN (de Cheveigné and Kawahara, 2002): the first lag at which the cumulative mean
+/// normalised difference dips below a fixed threshold. Confidence is one minus the dip.
+pub struct YinEstimator {
+    difference: Difference,
+}
+
+impl YinEstimator {
+    pub fn new() -> Self {
+        YinEstimator {
+            difference: Difference::new(),
+        }
+    }
+}
+
+impl PitchEstimator for YinEstimator {
+    fn estimate(&mut self, samples: &[f32], sample_rate: u32) -> Option<PitchEstimate> {
+        if is_silent(samples) || !self.difference.compute(samples, sample_rate) {
+            return None;
+        }
+        let tau = self
+            .difference
+            .first_dip(YIN_THRESHOLD, sample_rate)
+            .or_else(|| self.difference.global_min(sample_rate))?;
+        let (period, dip) = self.difference.refine(tau);
+
+        Some(PitchEstimate {
+            frequency: sample_rate as f32 / period,
+            confidence: (1.0 - dip).clamp(0.0, 1.0),
+        })
+    }
+}
+
+/// Probabilistic YIN (Mauch and Dixon, 2014). Instead of one threshold, the dips found with a
+/// range of thresholds are weighted by a Beta prior over the thresholds, giving a probability
+/// per period candidate. The paper's HMM tracking is approximated by weighting candidates by
+/// their distance from the previous estimate.
+pub struct PyinEstimator {
+    difference: Difference,
+    /// Prior probability of each threshold
+    threshold_weights: Vec<f32>,
+    previous: Option<f32>,
+}
+
+impl PyinEstimator {
+    pub fn new() -> Self {
+        let thresholds = (1..=PYIN_THRESHOLDS).map(|i| i as f32 / PYIN_THRESHOLDS as f32);
+        let density: Vec<f32> = thresholds
+            .map(|t| t.powf(PYIN_BETA_A - 1.0) * (1.0 - t).max(0.0).powf(PYIN_BETA_B - 1.0))
+            .collect();
+        let total: f32 = density.iter().sum();
+
+        PyinEstimator {
+            difference: Difference::new(),
+            threshold_weights: density.iter().map(|d| d / total).collect(),
+            previous: None,
+        }
+    }
+}
+
+impl PitchEstimator for PyinEstimator {
+    fn estimate(&mut self, samples: &[f32], sample_rate: u32) -> Option<PitchEstimate> {
+        if is_silent(samples) || !self.difference.compute(samples, sample_rate) {
+            self.previous = None;
+            return None;
+        }
+
+        // probability of each candidate lag, summed over the thresholds finding it
+        let mut candidates: Vec<(usize, f32)> = Vec::new();
+        let global_min = self.difference.global_min(sample_rate)?;
+        for (i, weight) in self.threshold_weights.iter().enumerate() {
+            let threshold = (i + 1) as f32 / PYIN_THRESHOLDS as f32;
+            let (tau, probability) = match self.difference.first_dip(threshold, sample_rate) {
+                Some(tau) => (tau, *weight),
+                None => (global_min, weight * PYIN_ABSOLUTE_MIN_WEIGHT),
+            };
+            match candidates.iter_mut().find(|(t, _)| *t == tau) {
+                Some((_, p)) => *p += probability,
+                None => candidates.push((tau, probability)),
+            }
+        }
+
+        let previous = self.previous;
+        let transition = |frequency: f32| match previous {
+            Some(previous) => {
+                let cents = 1200.0 * (frequency / previous).log2();
+                let closeness = (-0.5 * (cents / PYIN_TRANSITION_CENTS).powi(2)).exp();
+                PYIN_JUMP_WEIGHT + (1.0 - PYIN_JUMP_WEIGHT) * closeness
+            }
+            None => 1.0,
+        };
+        let (period, probability) = candidates
+            .iter()
+            .map(|&(tau, p)| (self.difference.refine(tau).0, p))
+            .max_by(|a, b| {
+                let score = |(period, p): (f32, f32)| p * transition(sample_rate as f32 / period);
+                score(*a).total_cmp(&score(*b))
+            })?;
+
+        let frequency = sample_rate as f32 / period;
+        self.previous = Some(frequency);
+        Some(PitchEstimate {
+            frequency,
+            confidence: probability.clamp(0.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,117 @@
+use std::ops::Range;
+use std::path::Path;
+
+use crate::analysis::decay::{analyze_modes, ModeAnalysis};
+use crate::analysis::frame::{FrameAnalysis, FFT_SIZE};
+use crate::analysis::onset::{detect_onsets, segment_strikes, Onset, OnsetFunction};
+use crate::analysis::spectrum::SpectrumAnalyzer;
+
+/// Read a WAV file, mixing all channels down to mono
+pub fn load_wav(path: &Path) -> Result<(Vec<f32>, u32), hound::Error> {
+    let (channels, sample_rate) = load_wav_channels(path)?;
+    let count = channels.len() as f32;
+    let len = channels.iter().map(Vec::len).min().unwrap_or(0);
+    let samples = (0..len)
+        .map(|i| channels.iter().map(|channel| channel[i]).sum::<f32>() / count)
+        .collect();
+    Ok((samples, sample_rate))
+}
+
+/// Read a WAV file, keeping its channels apart. There is always at least one channel.
+pub fn load_wav_channels(path: &Path) -> Result<(Vec<Vec<f32>>, u32), hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    let separated = (0..channels)
+        .map(|channel| {
+            interleaved
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .copied()
+                .collect()
+        })
+        .collect();
+    Ok((separated, spec.sample_rate))
+}
+
+/// Write a mono 32-bit float WAV file
+pub fn save_wav(path: &Path, samples: &[f32], sample_rate: u32) -> Result<(), hound::Error> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()
+}
+
+/// Spectrum, partials and per-partial decay of a recorded strike
+pub struct RecordingAnalysis {
+    pub frame: FrameAnalysis,
+    pub modes: Vec<ModeAnalysis>,
+}
+
+/// The spectrum is taken from the frame starting at the loudest sample, which for a single
+/// strike is the attack, and the decay of each partial is followed through the whole file.
+pub fn analyze_recording(samples: &[f32], sample_rate: u32) -> RecordingAnalysis {
+    let strike = samples
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map_or(0, |(i, _)| i);
+    let frame_end = (strike + FFT_SIZE).min(samples.len());
+
+    let mut analyzer = SpectrumAnalyzer::new(FFT_SIZE);
+    let frame = FrameAnalysis::new(&mut analyzer, &samples[..frame_end], sample_rate);
+    let modes = match &frame.partials {
+        Some(partials) => analyze_modes(samples, sample_rate, partials),
+        None => Vec::new(),
+    };
+
+    RecordingAnalysis { frame, modes }
+}
+
+/// One strike of a recording with several
+pub struct StrikeAnalysis {
+    pub onset: Onset,
+    /// Samples of the strike within the recording
+    pub range: Range<usize>,
+    pub analysis: RecordingAnalysis,
+}
+
+/// Split a recording at its strikes and analyze each on its own
+pub fn analyze_strikes(
+    samples: &[f32],
+    sample_rate: u32,
+    function: OnsetFunction,
+    sensitivity: f32,
+) -> Vec<StrikeAnalysis> {
+    let onsets = detect_onsets(samples, sample_rate, function, sensitivity);
+    let ranges = segment_strikes(samples, &onsets);
+    onsets
+        .into_iter()
+        .zip(ranges)
+        .map(|(onset, range)| StrikeAnalysis {
+            onset,
+            analysis: analyze_recording(&samples[range.clone()], sample_rate),
+            range,
+        })
+        .collect()
+}
@@ -0,0 +1,102 @@
+use crate::catalog::bowl::Mode;
+
+/// Constants of Sethares' parametrisation of the Plomp–Levelt dissonance curve
+const CURVE_DECAY_1: f32 = 3.5;
+const CURVE_DECAY_2: f32 = 5.75;
+const CURVE_MAX: f32 = 0.24;
+const CRITICAL_BAND_SLOPE: f32 = 0.0207;
+const CRITICAL_BAND_OFFSET: f32 = 18.96;
+
+/// Sensory dissonance of two sine tones. Zero for unison, largest at about a quarter of the
+/// critical band and falling off again for wide intervals.
+pub fn pair_dissonance(f1: f32, a1: f32, f2: f32, a2: f32) -> f32 {
+    let s = CURVE_MAX / (CRITICAL_BAND_SLOPE * f1.min(f2) + CRITICAL_BAND_OFFSET);
+    let difference = (f2 - f1).abs();
+    a1.min(a2) * ((-CURVE_DECAY_1 * s * difference).exp() - (-CURVE_DECAY_2 * s * difference).exp())
+}
+
+/// Partials as frequency and linear amplitude, the strongest at 1 so that bowls are compared as
+/// if struck equally loud
+fn normalized_partials(modes: &[Mode]) -> Vec<(f32, f32)> {
+    let peak_db = modes.iter().map(|m| m.level_db).fold(f32::MIN, f32::max);
+    modes
+        .iter()
+        .map(|m| (m.frequency, 10.0_f32.powf((m.level_db - peak_db) / 20.0)))
+        .collect()
+}
+
+/// Roughness between the partials of a single bowl
+pub fn intrinsic_roughness(modes: &[Mode]) -> f32 {
+    let partials = normalized_partials(modes);
+    partials
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &(f1, a1))| {
+            partials[i + 1..]
+                .iter()
+                .map(move |&(f2, a2)| pair_dissonance(f1, a1, f2, a2))
+        })
+        .sum()
+}
+
+/// Roughness added by sounding two bowls together: only the pairs with one partial from each
+/// bowl count
+pub fn pairwise_roughness(a: &[Mode], b: &[Mode]) -> f32 {
+    let partials_b = normalized_partials(b);
+    normalized_partials(a)
+        .iter()
+        .flat_map(|&(f1, a1)| {
+            partials_b
+                .iter()
+                .map(move |&(f2, a2)| pair_dissonance(f1, a1, f2, a2))
+        })
+        .sum()
+}
+
+pub struct EnsembleRoughness {
+    /// Pairwise roughness, symmetric, with each bowl's intrinsic roughness on the diagonal
+    pub matrix: Vec<Vec<f32>>,
+    /// Roughness of all bowls sounding together
+    pub total: f32,
+}
+
+pub fn ensemble_roughness(bowls: &[&[Mode]]) -> EnsembleRoughness {
+    let matrix: Vec<Vec<f32>> = bowls
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            bowls
+                .iter()
+                .enumerate()
+                .map(|(j, b)| {
+                    if i == j {
+                        intrinsic_roughness(a)
+                    } else {
+                        pairwise_roughness(a, b)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    // the matrix holds every cross pair twice but each diagonal entry once
+    let total = matrix
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| row[i..].iter())
+        .sum();
+
+    EnsembleRoughness { matrix, total }
+}
+
+/// Candidates ordered from the smoothest to the roughest together with `chosen`, as index into
+/// `candidates` and pairwise roughness. Candidates without modes are left out.
+pub fn rank_complements(chosen: &[Mode], candidates: &[&[Mode]]) -> Vec<(usize, f32)> {
+    let mut ranking: Vec<(usize, f32)> = candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, modes)| !modes.is_empty())
+        .map(|(i, modes)| (i, pairwise_roughness(chosen, modes)))
+        .collect();
+    ranking.sort_by(|a, b| a.1.total_cmp(&b.1));
+    ranking
+}
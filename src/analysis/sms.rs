@@ -0,0 +1,592 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::analysis::spectrum::{find_peaks, SpectrumAnalyzer};
+use crate::audio_engine::stft::{frame_size, hann_window};
+
+/// Frames overlap by all but 1/HOP_DIVISOR of their length
+const HOP_DIVISOR: usize = 8;
+const MIN_FREQUENCY: f32 = 20.0;
+/// Peaks this far below the loudest peak of the recording are left to the noise
+const PEAK_RANGE_DB: f32 = 80.0;
+const MAX_PEAKS: usize = 60;
+/// A peak continues a track if it is this close to the track's last frequency, or one bin
+const MAX_JUMP_CENTS: f32 = 50.0;
+/// Shorter tracks are taken for noise
+const MIN_TRACK_SECONDS: f32 = 0.1;
+/// Bins either side of a partial's peak that belong to it rather than to the noise, the main
+/// lobe of the analysis window
+const LOBE_BINS: usize = 4;
+/// The noise envelope is kept in bands spaced evenly in log frequency from this frequency up
+const LOWEST_BAND_HZ: f32 = 50.0;
+const NOISE_BANDS: usize = 32;
+/// Coherent and power gain of the analysis window (Blackman-Harris) per sample
+const WINDOW_COHERENT_GAIN: f32 = 0.35875;
+const WINDOW_POWER_GAIN: f32 = 0.25796;
+/// Noise settings at or below this are off
+pub const NOISE_OFF_DB: f32 = -40.0;
+/// Longest decay extension, in multiples of the track's length
+const MAX_EXTENSION: f32 = 4.0;
+
+/// One partial followed from frame to frame
+#[derive(Clone, Debug)]
+pub struct SinusoidalTrack {
+    /// Frame the track starts at
+    pub start: usize,
+    /// Frequency and amplitude per frame from the start, amplitudes relative to full scale
+    pub frequencies: Vec<f32>,
+    pub amplitudes: Vec<f32>,
+}
+
+impl SinusoidalTrack {
+    /// Frequency and amplitude frames with the decay after the peak slowed or sped up by
+    /// `decay_scale`. The level is split into a straight decay line fitted after the peak and
+    /// the swell around it; only the line's slope changes, so beating is kept. A slowed decay
+    /// continues past the end of the track until it falls to the level the track ended at.
+    fn with_decay(&self, decay_scale: f32) -> (Vec<f32>, Vec<f32>) {
+        let mut frequencies = self.frequencies.clone();
+        let mut amplitudes = self.amplitudes.clone();
+        let levels: Vec<f32> = amplitudes
+            .iter()
+            .map(|a| 20.0 * a.max(1e-9).log10())
+            .collect();
+        let peak = levels
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(i, _)| i);
+        let Some((intercept, slope)) = fit_line(&levels[peak..]) else {
+            return (frequencies, amplitudes);
+        };
+        if slope >= 0.0 || decay_scale == 1.0 {
+            return (frequencies, amplitudes);
+        }
+
+        let line = |j: usize| intercept + slope * (j - peak) as f32;
+        let scaled = |j: usize| intercept + slope / decay_scale * (j - peak) as f32;
+        for (j, amplitude) in amplitudes.iter_mut().enumerate().skip(peak) {
+            let level = scaled(j) + levels[j] - line(j);
+            *amplitude = 10.0_f32.powf(level / 20.0);
+        }
+
+        if decay_scale > 1.0 {
+            let end_level = levels[levels.len() - 1];
+            let frequency = frequencies[frequencies.len() - 1];
+            let max_len = (self.frequencies.len() as f32 * MAX_EXTENSION) as usize;
+            let mut j = levels.len();
+            while j < max_len && scaled(j) > end_level {
+                frequencies.push(frequency);
+                amplitudes.push(10.0_f32.powf(scaled(j) / 20.0));
+                j += 1;
+            }
+        }
+        (frequencies, amplitudes)
+    }
+}
+
+/// A recording split into partials that are followed through it and a noise residual,
+/// described by the level of each band in each frame
+pub struct SmsAnalysis {
+    pub sample_rate: u32,
+    /// Samples from one frame to the next
+    pub hop: usize,
+    /// Length of the recording in samples
+    pub len: usize,
+    pub tracks: Vec<SinusoidalTrack>,
+    /// Standard deviation of the residual in each band of each frame, for white noise of
+    /// the same spectral density
+    pub noise: Vec<[f32; NOISE_BANDS]>,
+    /// Band edges in Hz, one more than there are bands
+    band_edges: Vec<f32>,
+}
+
+/// How the resynthesis differs from the recording
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SmsModifiers {
+    /// Factor every partial frequency is multiplied by
+    pub frequency_scale: f32,
+    /// Factor the decay times of the partials are multiplied by
+    pub decay_scale: f32,
+    /// Level of the noise relative to the recording
+    pub noise_gain_db: f32,
+}
+
+impl Default for SmsModifiers {
+    fn default() -> Self {
+        SmsModifiers {
+            frequency_scale: 1.0,
+            decay_scale: 1.0,
+            noise_gain_db: 0.0,
+        }
+    }
+}
+
+impl SmsAnalysis {
+    pub fn duration(&self) -> f32 {
+        self.len as f32 / self.sample_rate as f32
+    }
+
+    /// Centre of a frame in seconds
+    pub fn frame_time(&self, frame: usize) -> f32 {
+        (frame * self.hop) as f32 / self.sample_rate as f32
+    }
+}
+
+/// Follow the partials of a recording from frame to frame and measure what is left between
+/// them as band levels
+pub fn analyze_sms(samples: &[f32], sample_rate: u32) -> SmsAnalysis {
+    // the STFT frame separates partials a few tens of hertz apart
+    let size = frame_size(sample_rate);
+    let hop = size / HOP_DIVISOR;
+    // pad so that frame i is centred on sample i * hop
+    let mut padded = vec![0.0; size / 2];
+    padded.extend_from_slice(samples);
+    padded.resize(padded.len() + size, 0.0);
+
+    let mut analyzer = SpectrumAnalyzer::new(size);
+    let frame_count = samples.len() / hop + 1;
+    let spectra: Vec<_> = (0..frame_count)
+        .map(|i| analyzer.analyze(&padded[i * hop..i * hop + size], sample_rate))
+        .collect();
+    let bin_hz = sample_rate as f32 / size as f32;
+
+    let peaks: Vec<_> = spectra
+        .iter()
+        .map(|spectrum| find_peaks(spectrum, MIN_FREQUENCY, PEAK_RANGE_DB, MAX_PEAKS))
+        .collect();
+    let loudest = peaks
+        .iter()
+        .flatten()
+        .map(|p| p.magnitude_db)
+        .fold(f32::MIN, f32::max);
+    let threshold = loudest - PEAK_RANGE_DB;
+
+    // continue each track with the closest peak of the next frame, closest pairs first
+    let mut finished = Vec::new();
+    let mut active: Vec<SinusoidalTrack> = Vec::new();
+    for (frame, frame_peaks) in peaks.iter().enumerate() {
+        let frame_peaks: Vec<_> = frame_peaks
+            .iter()
+            .filter(|p| p.magnitude_db > threshold)
+            .collect();
+        let mut candidates: Vec<(usize, usize, f32)> = Vec::new();
+        for (t, track) in active.iter().enumerate() {
+            let last = track.frequencies[track.frequencies.len() - 1];
+            let max_jump = (last * (2.0_f32.powf(MAX_JUMP_CENTS / 1200.0) - 1.0)).max(bin_hz);
+            for (p, peak) in frame_peaks.iter().enumerate() {
+                let jump = (peak.frequency - last).abs();
+                if jump <= max_jump {
+                    candidates.push((t, p, jump));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+        let mut continued = vec![false; active.len()];
+        let mut used = vec![false; frame_peaks.len()];
+        for (t, p, _) in candidates {
+            if !continued[t] && !used[p] {
+                continued[t] = true;
+                used[p] = true;
+                active[t].frequencies.push(frame_peaks[p].frequency);
+                active[t]
+                    .amplitudes
+                    .push(10.0_f32.powf(frame_peaks[p].magnitude_db / 20.0));
+            }
+        }
+
+        let mut still_active = Vec::with_capacity(active.len());
+        for (track, continued) in active.into_iter().zip(continued) {
+            if continued {
+                still_active.push(track);
+            } else {
+                finished.push(track);
+            }
+        }
+        active = still_active;
+        for (peak, _) in frame_peaks.iter().zip(used).filter(|(_, used)| !used) {
+            active.push(SinusoidalTrack {
+                start: frame,
+                frequencies: vec![peak.frequency],
+                amplitudes: vec![10.0_f32.powf(peak.magnitude_db / 20.0)],
+            });
+        }
+    }
+    finished.extend(active);
+
+    let min_frames = (MIN_TRACK_SECONDS * sample_rate as f32 / hop as f32).ceil() as usize;
+    let mut tracks: Vec<SinusoidalTrack> = finished
+        .into_iter()
+        .filter(|track| track.frequencies.len() >= min_frames)
+        .collect();
+    tracks.sort_by_key(|track| track.start);
+
+    // what is left of each frame once the partials' main lobes are taken out
+    let nyquist = sample_rate as f32 / 2.0;
+    let band_edges: Vec<f32> = std::iter::once(0.0)
+        .chain((0..=NOISE_BANDS - 1).map(|k| {
+            LOWEST_BAND_HZ * (nyquist / LOWEST_BAND_HZ).powf(k as f32 / (NOISE_BANDS - 1) as f32)
+        }))
+        .collect();
+    let bins = size / 2 + 1;
+    let mut masks = vec![vec![false; bins]; frame_count];
+    for track in tracks.iter() {
+        for (j, frequency) in track.frequencies.iter().enumerate() {
+            let centre = (frequency / bin_hz).round() as usize;
+            let range = centre.saturating_sub(LOBE_BINS)..(centre + LOBE_BINS + 1).min(bins);
+            masks[track.start + j][range].fill(true);
+        }
+    }
+    // band amplitudes are scaled to full scale sines; this turns them into the deviation of
+    // white noise with the same density
+    let to_deviation = WINDOW_COHERENT_GAIN / 2.0 * (size as f32 / WINDOW_POWER_GAIN).sqrt();
+    let noise = spectra
+        .iter()
+        .zip(&masks)
+        .map(|(spectrum, mask)| {
+            let mut bands = [0.0; NOISE_BANDS];
+            let mut previous = 0.0;
+            for (band, edges) in bands.iter_mut().zip(band_edges.windows(2)) {
+                let first = (edges[0] / bin_hz).ceil() as usize;
+                let last = ((edges[1] / bin_hz).ceil() as usize).min(bins);
+                let (sum, count) = (first..last)
+                    .filter(|&bin| !mask[bin])
+                    .map(|bin| 10.0_f32.powf(spectrum.magnitudes_db[bin] / 10.0))
+                    .fold((0.0, 0), |(sum, count), power| (sum + power, count + 1));
+                // a band taken up by partials has the level of the band below
+                *band = if count > 0 {
+                    (sum / count as f32).sqrt() * to_deviation
+                } else {
+                    previous
+                };
+                previous = *band;
+            }
+            bands
+        })
+        .collect();
+
+    SmsAnalysis {
+        sample_rate,
+        hop,
+        len: samples.len(),
+        tracks,
+        noise,
+        band_edges,
+    }
+}
+
+/// Additive resynthesis of the tracks plus the noise, shaped by the band levels
+pub fn synthesize_sms(analysis: &SmsAnalysis, modifiers: &SmsModifiers) -> Vec<f32> {
+    let hop = analysis.hop;
+    let sample_rate = analysis.sample_rate as f64;
+    let nyquist = analysis.sample_rate as f32 / 2.0;
+
+    let tracks: Vec<(usize, Vec<f32>, Vec<f32>)> = analysis
+        .tracks
+        .iter()
+        .map(|track| {
+            let (frequencies, amplitudes) = track.with_decay(modifiers.decay_scale);
+            let frequencies = frequencies
+                .iter()
+                .map(|f| f * modifiers.frequency_scale)
+                .collect();
+            (track.start, frequencies, amplitudes)
+        })
+        .collect();
+    let len = tracks
+        .iter()
+        .map(|(start, frequencies, _)| (start + frequencies.len()) * hop)
+        .fold(analysis.len, usize::max);
+    let mut output = vec![0.0; len];
+
+    for (start, frequencies, amplitudes) in tracks.iter() {
+        // from silence one hop before the first frame to silence one hop after the last,
+        // frequency and amplitude interpolated between frames
+        let frame = |j: isize| -> (f32, f32) {
+            let last = frequencies.len() as isize - 1;
+            let frequency = frequencies[j.clamp(0, last) as usize];
+            let amplitude = if j < 0 || j > last || frequency >= nyquist {
+                0.0
+            } else {
+                amplitudes[j as usize]
+            };
+            (frequency, amplitude)
+        };
+        let mut phase = 0.0_f64;
+        for j in -1..frequencies.len() as isize {
+            let (f0, a0) = frame(j);
+            let (f1, a1) = frame(j + 1);
+            let first = (*start as isize + j) * hop as isize;
+            for k in 0..hop {
+                let Some(sample) = usize::try_from(first + k as isize)
+                    .ok()
+                    .and_then(|n| output.get_mut(n))
+                else {
+                    continue;
+                };
+                let t = k as f32 / hop as f32;
+                let frequency = f0 + (f1 - f0) * t;
+                *sample += (a0 + (a1 - a0) * t) * phase.cos() as f32;
+                phase = (phase + 2.0 * PI * frequency as f64 / sample_rate) % (2.0 * PI);
+            }
+        }
+    }
+
+    if modifiers.noise_gain_db > NOISE_OFF_DB {
+        let gain = 10.0_f32.powf(modifiers.noise_gain_db / 20.0);
+        synthesize_noise(analysis, gain, &mut output);
+    }
+    output
+}
+
+/// Overlap-add of Hann windowed frames of random phase, each with the spectral density of
+/// its analysis frame
+fn synthesize_noise(analysis: &SmsAnalysis, gain: f32, output: &mut [f32]) {
+    let size = analysis.hop * HOP_DIVISOR;
+    let bins = size / 2 + 1;
+    let bin_hz = analysis.sample_rate as f32 / size as f32;
+    let inverse: Arc<dyn Fft<f32>> = FftPlanner::new().plan_fft_inverse(size);
+    let window = hann_window(size);
+    // a spectrum of magnitude `scale` per bin, windowed and overlapped every hop, gives
+    // noise of unit deviation
+    let window_power: f32 = window.iter().map(|w| w * w).sum();
+    let scale = (size as f32 * analysis.hop as f32 / window_power).sqrt();
+
+    // bin of each band's centre, for interpolating the levels between them
+    let centres: Vec<f32> = analysis
+        .band_edges
+        .windows(2)
+        .map(|edges| (edges[0] * edges[1]).sqrt().max(edges[1] / 2.0) / bin_hz)
+        .collect();
+    let mut random = Random::new(0x5eed);
+    let mut buffer = vec![Complex::new(0.0, 0.0); size];
+
+    for (frame, bands) in analysis.noise.iter().enumerate() {
+        buffer.fill(Complex::new(0.0, 0.0));
+        let mut band = 0;
+        for bin in 1..bins - 1 {
+            let position = bin as f32;
+            while band + 1 < centres.len() && centres[band + 1] < position {
+                band += 1;
+            }
+            let deviation = if band + 1 == centres.len() || position <= centres[0] {
+                bands[band]
+            } else {
+                let t = (position - centres[band]) / (centres[band + 1] - centres[band]);
+                bands[band] + (bands[band + 1] - bands[band]) * t
+            };
+            let phase = random.next() * 2.0 * std::f32::consts::PI;
+            buffer[bin] = Complex::from_polar(deviation * scale * gain, phase);
+            buffer[size - bin] = buffer[bin].conj();
+        }
+        inverse.process(&mut buffer);
+
+        // centred on the frame's sample, as in the analysis
+        let first = (frame * analysis.hop) as isize - (size / 2) as isize;
+        for (n, (value, w)) in buffer.iter().zip(&window).enumerate() {
+            if let Some(sample) = usize::try_from(first + n as isize)
+                .ok()
+                .and_then(|i| output.get_mut(i))
+            {
+                *sample += value.re / size as f32 * w;
+            }
+        }
+    }
+}
+
+/// Least squares line through equally spaced values, as the value at the first and the
+/// change per step. None for fewer than three values.
+fn fit_line(values: &[f32]) -> Option<(f32, f32)> {
+    if values.len() < 3 {
+        return None;
+    }
+    let n = values.len() as f32;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_y = values.iter().sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (i, y) in values.iter().enumerate() {
+        let dx = i as f32 - mean_x;
+        covariance += dx * (y - mean_y);
+        variance += dx * dx;
+    }
+    let slope = covariance / variance;
+    Some((mean_y - slope * mean_x, slope))
+}
+
+/// Xorshift generator for the noise phases; the same analysis always sounds the same
+struct Random(u32);
+
+impl Random {
+    fn new(seed: u32) -> Self {
+        Random(seed.max(1))
+    }
+
+    /// Uniform in [0, 1)
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Partials of the given frequency and amplitude decaying at 6 dB per second
+    fn decaying(partials: &[(f32, f32)], seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|n| {
+                let t = n as f64 / SAMPLE_RATE as f64;
+                let envelope = 10.0_f64.powf(-6.0 * t / 20.0);
+                partials
+                    .iter()
+                    .map(|&(f, a)| (a as f64 * envelope * (2.0 * PI * f as f64 * t).sin()) as f32)
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Median of a track's frequencies
+    fn frequency(track: &SinusoidalTrack) -> f32 {
+        let mut frequencies = track.frequencies.clone();
+        frequencies.sort_by(f32::total_cmp);
+        frequencies[frequencies.len() / 2]
+    }
+
+    #[test]
+    fn follows_partials() {
+        let samples = decaying(&[(440.0, 0.5), (1130.0, 0.2)], 2.0);
+        let analysis = analyze_sms(&samples, SAMPLE_RATE);
+        assert_eq!(analysis.len, samples.len());
+        assert!((analysis.duration() - 2.0).abs() < 1e-6);
+
+        let mut tracks: Vec<&SinusoidalTrack> = analysis.tracks.iter().collect();
+        tracks.sort_by_key(|track| std::cmp::Reverse(track.frequencies.len()));
+        let mut frequencies: Vec<f32> = tracks[..2].iter().map(|t| frequency(t)).collect();
+        frequencies.sort_by(f32::total_cmp);
+        assert!((frequencies[0] - 440.0).abs() < 0.5, "{:?}", frequencies);
+        assert!((frequencies[1] - 1130.0).abs() < 0.5, "{:?}", frequencies);
+
+        // the frame in the middle of the recording, a second into the decay
+        let track = tracks.iter().find(|t| frequency(t) < 800.0).unwrap();
+        let frame = analysis.len / 2 / analysis.hop;
+        let amplitude = track.amplitudes[frame - track.start];
+        assert!(
+            (20.0 * (amplitude / 0.5).log10() + 6.0).abs() < 0.5,
+            "{}",
+            amplitude
+        );
+    }
+
+    #[test]
+    fn resynthesis_matches_the_recording() {
+        let samples = decaying(&[(440.0, 0.5), (1130.0, 0.2)], 2.0);
+        let analysis = analyze_sms(&samples, SAMPLE_RATE);
+        let modifiers = SmsModifiers {
+            noise_gain_db: NOISE_OFF_DB,
+            ..SmsModifiers::default()
+        };
+        let output = synthesize_sms(&analysis, &modifiers);
+        // tracks reaching the last frame run on to the end of its hop
+        assert!(output.len() >= samples.len() && output.len() <= samples.len() + analysis.hop);
+        // the phases of the resynthesis are free, so the levels are compared per window
+        for (a, b) in samples.chunks(4800).zip(output.chunks(4800)) {
+            let difference = 20.0 * (rms(b) / rms(a)).log10();
+            assert!(difference.abs() < 1.0, "{} dB", difference);
+        }
+    }
+
+    #[test]
+    fn frequency_scale_moves_the_partials() {
+        let samples = decaying(&[(440.0, 0.5)], 1.0);
+        let analysis = analyze_sms(&samples, SAMPLE_RATE);
+        let modifiers = SmsModifiers {
+            frequency_scale: 1.5,
+            noise_gain_db: NOISE_OFF_DB,
+            ..SmsModifiers::default()
+        };
+        let shifted = analyze_sms(&synthesize_sms(&analysis, &modifiers), SAMPLE_RATE);
+        let longest = shifted
+            .tracks
+            .iter()
+            .max_by_key(|track| track.frequencies.len())
+            .unwrap();
+        assert!((frequency(longest) - 660.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn decay_scale_stretches_the_decay() {
+        // rising for two frames, then falling 1 dB per frame with a small swell
+        let levels: Vec<f32> = (0..40)
+            .map(|j| match j {
+                0 => -10.0,
+                1 => -5.0,
+                _ => -(j as f32 - 2.0) + 0.3 * (j as f32).sin(),
+            })
+            .collect();
+        let track = SinusoidalTrack {
+            start: 0,
+            frequencies: vec![440.0; levels.len()],
+            amplitudes: levels.iter().map(|l| 10.0_f32.powf(l / 20.0)).collect(),
+        };
+        let (frequencies, amplitudes) = track.with_decay(1.0);
+        assert_eq!(amplitudes, track.amplitudes);
+        assert_eq!(frequencies, track.frequencies);
+
+        let (frequencies, amplitudes) = track.with_decay(2.0);
+        let levels_after: Vec<f32> = amplitudes.iter().map(|a| 20.0 * a.log10()).collect();
+        // falls at half the rate until it reaches the level the track ended at
+        assert!(frequencies.len() > 70 && frequencies.len() < 80);
+        assert_eq!(frequencies.len(), amplitudes.len());
+        assert!((levels_after[1] - levels[1]).abs() < 1e-3);
+        let slope = (levels_after[32] - levels_after[12]) / 20.0;
+        assert!((slope + 0.5).abs() < 0.05, "{}", slope);
+
+        let (_, amplitudes) = track.with_decay(0.5);
+        assert_eq!(amplitudes.len(), track.amplitudes.len());
+        assert!(amplitudes[30] < track.amplitudes[30]);
+    }
+
+    #[test]
+    fn noise_is_resynthesized_at_its_level() {
+        let mut random = Random::new(1);
+        let samples: Vec<f32> = (0..SAMPLE_RATE)
+            .map(|_| 0.2 * (random.next() - 0.5))
+            .collect();
+        let mut analysis = analyze_sms(&samples, SAMPLE_RATE);
+        let output = synthesize_sms(&analysis, &SmsModifiers::default());
+        let difference = 20.0 * (rms(&output) / rms(&samples)).log10();
+        assert!(difference.abs() < 1.5, "{} dB", difference);
+
+        // without the short tracks the noise forms by chance, only the noise gain applies
+        analysis.tracks.clear();
+        let level = |noise_gain_db| {
+            let modifiers = SmsModifiers {
+                noise_gain_db,
+                ..SmsModifiers::default()
+            };
+            20.0 * (rms(&synthesize_sms(&analysis, &modifiers)) / rms(&samples)).log10()
+        };
+        assert!(level(0.0).abs() < 0.5, "{} dB", level(0.0));
+        assert!((level(-12.0) + 12.0).abs() < 0.5, "{} dB", level(-12.0));
+        assert_eq!(level(NOISE_OFF_DB), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn fits_a_line() {
+        let (intercept, slope) = fit_line(&[3.0, 1.0, -1.0, -3.0]).unwrap();
+        assert!((intercept - 3.0).abs() < 1e-6 && (slope + 2.0).abs() < 1e-6);
+        assert!(fit_line(&[1.0, 2.0]).is_none());
+    }
+}
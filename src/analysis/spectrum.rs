@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+/// Lowest magnitude reported, in dB relative to a full scale sine
+pub const SPECTRUM_FLOOR_DB: f32 = -140.0;
+
+/// Magnitude spectrum of one analysis frame
+#[derive(Clone)]
+pub struct Spectrum {
+    /// Bin magnitudes in dB, 0 dB being a full scale sine centred on a bin
+    pub magnitudes_db: Vec<f32>,
+    /// Frequency spacing between bins in Hz
+    pub bin_hz: f32,
+}
+
+impl Spectrum {
+    pub fn frequency(&self, bin: f32) -> f32 {
+        bin * self.bin_hz
+    }
+}
+
+/// Spectral peak with its frequency refined between bins
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    pub frequency: f32,
+    pub magnitude_db: f32,
+}
+
+/// Blackman-Harris windowed FFT of a fixed size. The window's -92 dB side lobes keep leakage
+/// from strong partials from being picked up as peaks of their own.
+pub struct SpectrumAnalyzer {
+    size: usize,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(size: usize) -> Self {
+        let fft = FftPlanner::new().plan_fft_forward(size);
+        let window = (0..size)
+            .map(|n| {
+                let x = 2.0 * std::f32::consts::PI * n as f32 / size as f32;
+                0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos()
+            })
+            .collect();
+
+        SpectrumAnalyzer {
+            size,
+            fft,
+            window,
+            buffer: vec![Complex::new(0.0, 0.0); size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Analyze the most recent `size` samples. Shorter input is zero padded at the start.
+    pub fn analyze(&mut self, samples: &[f32], sample_rate: u32) -> Spectrum {
+        let start = samples.len().saturating_sub(self.size);
+        let samples = &samples[start..];
+        let offset = self.size - samples.len();
+
+        for (i, value) in self.buffer.iter_mut().enumerate() {
+            let sample = if i < offset { 0.0 } else { samples[i - offset] };
+            *value = Complex::new(sample * self.window[i], 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+
+        // a full scale sine has amplitude size / 2 after the transform, times the window's
+        // coherent gain
+        let scale = 2.0 / (self.size as f32 * 0.35875);
+        let magnitudes_db = self.buffer[..self.size / 2 + 1]
+            .iter()
+            .map(|c| (20.0 * (c.norm() * scale).log10()).max(SPECTRUM_FLOOR_DB))
+            .collect();
+
+        Spectrum {
+            magnitudes_db,
+            bin_hz: sample_rate as f32 / self.size as f32,
+        }
+    }
+}
+
+/// Local maxima within `range_db` of the strongest bin above `min_frequency`, strongest first.
+/// Frequencies are refined by fitting a parabola through the peak bin and its neighbours.
+pub fn find_peaks(
+    spectrum: &Spectrum,
+    min_frequency: f32,
+    range_db: f32,
+    max_peaks: usize,
+) -> Vec<Peak> {
+    let magnitudes = &spectrum.magnitudes_db;
+    let first_bin = ((min_frequency / spectrum.bin_hz).ceil() as usize).max(1);
+    if magnitudes.len() < 3 || first_bin >= magnitudes.len() - 1 {
+        return Vec::new();
+    }
+
+    let max_db = magnitudes[first_bin..]
+        .iter()
+        .cloned()
+        .fold(SPECTRUM_FLOOR_DB, f32::max);
+    let threshold = max_db - range_db;
+
+    let mut peaks: Vec<Peak> = (first_bin..magnitudes.len() - 1)
+        .filter(|&i| {
+            magnitudes[i] > threshold
+                && magnitudes[i] > magnitudes[i - 1]
+                && magnitudes[i] >= magnitudes[i + 1]
+        })
+        .map(|i| {
+            let (offset, magnitude_db) =
+                parabolic_peak(magnitudes[i - 1], magnitudes[i], magnitudes[i + 1]);
+            Peak {
+                frequency: spectrum.frequency(i as f32 + offset),
+                magnitude_db,
+            }
+        })
+        .collect();
+
+    peaks.sort_by(|a, b| b.magnitude_db.total_cmp(&a.magnitude_db));
+    peaks.truncate(max_peaks);
+    peaks
+}
+
+/// Vertex of the parabola through three equally spaced points, as an offset from the middle
+/// point and the interpolated value.
+pub fn parabolic_peak(left: f32, centre: f32, right: f32) -> (f32, f32) {
+    let denominator = left - 2.0 * centre + right;
+    if denominator.abs() < f32::EPSILON {
+        return (0.0, centre);
+    }
+    let offset = (0.5 * (left - right) / denominator).clamp(-0.5, 0.5);
+    (offset, centre - 0.25 * (left - right) * offset)
+}
+
+/// FFT based pitch estimate: the lowest peak within `range_db` of the strongest one. Singing
+/// bowls and most instruments have their strongest partials above the fundamental, but the
+/// fundamental itself is rarely far below them.
+pub fn estimate_fundamental(peaks: &[Peak], range_db: f32) -> Option<Peak> {
+    let strongest = peaks
+        .iter()
+        .map(|p| p.magnitude_db)
+        .fold(f32::MIN, f32::max);
+    peaks
+        .iter()
+        .filter(|p| p.magnitude_db >= strongest - range_db)
+        .min_by(|a, b| a.frequency.total_cmp(&b.frequency))
+        .copied()
+}
@@ -0,0 +1,344 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::comparison::compare;
+use crate::analysis::linear::solve;
+use crate::analysis::tuning::ratio_to_cents;
+use crate::catalog::bowl::Mode;
+
+/// Highest degree of the polynomial fitted across the takes
+pub const MAX_FIT_DEGREE: usize = 3;
+/// Fits whose equations are closer to singular than this are left out
+const MIN_CONDITION: f64 = 1e-12;
+
+#[derive(Debug)]
+pub enum SweepError {
+    Io(io::Error),
+    /// A session file that can't be read or written as TOML
+    Format(PathBuf, String),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::Io(e) => write!(f, "{}", e),
+            SweepError::Format(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl From<io::Error> for SweepError {
+    fn from(e: io::Error) -> Self {
+        SweepError::Io(e)
+    }
+}
+
+/// One take of a sweep: the parameter value it was made at and the modes measured in it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct SweepTake {
+    pub value: f32,
+    /// Recording the modes were measured from, if there is one
+    pub recording: Option<PathBuf>,
+    /// Ascending in frequency, the fundamental first
+    pub modes: Vec<Mode>,
+}
+
+/// Takes of one bowl with a single physical parameter changed between them, such as the water
+/// level, the striking position or the mallet. Stored as TOML like the catalog entries.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct SweepSession {
+    pub parameter: String,
+    pub unit: String,
+    pub notes: String,
+    /// Ascending in parameter value
+    pub takes: Vec<SweepTake>,
+}
+
+impl SweepSession {
+    pub fn load(path: &Path) -> Result<Self, SweepError> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut session: SweepSession = toml::from_str(&contents)
+            .map_err(|e| SweepError::Format(path.to_path_buf(), e.to_string()))?;
+        session.takes.sort_by(|a, b| a.value.total_cmp(&b.value));
+        Ok(session)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SweepError> {
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| SweepError::Format(path.to_path_buf(), e.to_string()))?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Insert a take, keeping the takes in order of their value. Takes at the same value
+    /// are kept in the order they were added.
+    pub fn add_take(&mut self, take: SweepTake) {
+        let index = self.takes.partition_point(|t| t.value <= take.value);
+        self.takes.insert(index, take);
+    }
+
+    /// Follow each partial through the takes in order of the parameter value. Each take's
+    /// partials are paired with the closest partial of the takes before, as in the bowl
+    /// comparison, so the steps between takes should move partials by less than its match
+    /// range. Partials without a counterpart start a track of their own.
+    pub fn tracks(&self) -> Vec<Track> {
+        let mut tracks: Vec<Track> = Vec::new();
+        for take in self.takes.iter() {
+            let latest: Vec<Mode> = tracks.iter().map(|track| track.latest().1).collect();
+            for row in compare(&latest, &take.modes).matches {
+                match (row.a, row.b) {
+                    (Some(i), Some(j)) => tracks[i].points.push((take.value, take.modes[j])),
+                    (None, Some(j)) => tracks.push(Track {
+                        points: vec![(take.value, take.modes[j])],
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        tracks.sort_by(|a, b| a.points[0].1.frequency.total_cmp(&b.points[0].1.frequency));
+        tracks
+    }
+}
+
+/// One partial followed across the takes of a sweep
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    /// Parameter value and mode for each take the partial was found in, ascending in value
+    pub points: Vec<(f32, Mode)>,
+}
+
+impl Track {
+    /// The partial at the lowest parameter value it was found at
+    pub fn first(&self) -> (f32, Mode) {
+        self.points[0]
+    }
+
+    fn latest(&self) -> (f32, Mode) {
+        self.points[self.points.len() - 1]
+    }
+
+    /// Parameter value against the quantity, for the takes where it is known
+    pub fn series(&self, quantity: SweepQuantity) -> Vec<(f64, f64)> {
+        let first = self.first().1;
+        self.points
+            .iter()
+            .filter_map(|(value, mode)| Some((*value as f64, quantity.of(mode, &first)? as f64)))
+            .collect()
+    }
+}
+
+/// What is plotted against the parameter
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SweepQuantity {
+    Frequency,
+    /// Interval from the partial at the lowest parameter value, so partials far apart in
+    /// frequency share a scale
+    Shift,
+    Level,
+    T60,
+}
+
+impl SweepQuantity {
+    pub const ALL: [SweepQuantity; 4] = [
+        SweepQuantity::Frequency,
+        SweepQuantity::Shift,
+        SweepQuantity::Level,
+        SweepQuantity::T60,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SweepQuantity::Frequency => "Frequency",
+            SweepQuantity::Shift => "Frequency Shift",
+            SweepQuantity::Level => "Level",
+            SweepQuantity::T60 => "T60",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            SweepQuantity::Frequency => "Hz",
+            SweepQuantity::Shift => "cents",
+            SweepQuantity::Level => "dB",
+            SweepQuantity::T60 => "s",
+        }
+    }
+
+    /// Value for a mode of a track that started at `first`
+    fn of(self, mode: &Mode, first: &Mode) -> Option<f32> {
+        match self {
+            SweepQuantity::Frequency => Some(mode.frequency),
+            SweepQuantity::Shift => {
+                Some(ratio_to_cents((mode.frequency / first.frequency) as f64) as f32)
+            }
+            SweepQuantity::Level => Some(mode.level_db),
+            SweepQuantity::T60 => mode.t60,
+        }
+    }
+}
+
+/// Least squares polynomial through a track's values. The parameter is centred and scaled
+/// internally, so parameters in large units don't make the fit ill-conditioned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolynomialFit {
+    /// Lowest power first, in the scaled parameter
+    coefficients: Vec<f64>,
+    centre: f64,
+    scale: f64,
+    /// Share of the variance of the values explained by the fit
+    pub r_squared: f64,
+}
+
+impl PolynomialFit {
+    /// None if there are fewer distinct parameter values than coefficients
+    pub fn new(points: &[(f64, f64)], degree: usize) -> Option<Self> {
+        let mut values: Vec<f64> = points.iter().map(|(x, _)| *x).collect();
+        values.sort_by(f64::total_cmp);
+        values.dedup();
+        if values.len() <= degree {
+            return None;
+        }
+        let centre = (values[0] + values[values.len() - 1]) / 2.0;
+        let scale = (values[values.len() - 1] - values[0]) / 2.0;
+        let scale = if scale > 0.0 { scale } else { 1.0 };
+
+        let size = degree + 1;
+        let mut matrix = vec![vec![0.0; size]; size];
+        let mut rhs = vec![0.0; size];
+        for (x, y) in points {
+            let u = (x - centre) / scale;
+            let powers: Vec<f64> = (0..size).map(|k| u.powi(k as i32)).collect();
+            for ((row, rhs), p) in matrix.iter_mut().zip(rhs.iter_mut()).zip(&powers) {
+                for (value, q) in row.iter_mut().zip(&powers) {
+                    *value += p * q;
+                }
+                *rhs += p * y;
+            }
+        }
+        let coefficients = solve(matrix, rhs, MIN_CONDITION)?;
+
+        let mut fit = PolynomialFit {
+            coefficients,
+            centre,
+            scale,
+            r_squared: 1.0,
+        };
+        let mean = points.iter().map(|(_, y)| y).sum::<f64>() / points.len() as f64;
+        let total: f64 = points.iter().map(|(_, y)| (y - mean).powi(2)).sum();
+        let error: f64 = points
+            .iter()
+            .map(|(x, y)| (y - fit.evaluate(*x)).powi(2))
+            .sum();
+        if total > 0.0 {
+            fit.r_squared = 1.0 - error / total;
+        }
+        Some(fit)
+    }
+
+    pub fn evaluate(&self, x: f64) -> f64 {
+        let u = (x - self.centre) / self.scale;
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |sum, coefficient| sum * u + coefficient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(frequency: f32) -> Mode {
+        Mode {
+            frequency,
+            level_db: 0.0,
+            t60: None,
+            beat_hz: None,
+        }
+    }
+
+    fn take(value: f32, frequencies: &[f32]) -> SweepTake {
+        SweepTake {
+            value,
+            recording: None,
+            modes: frequencies.iter().map(|&f| mode(f)).collect(),
+        }
+    }
+
+    #[test]
+    fn takes_are_kept_in_order() {
+        let mut session = SweepSession::default();
+        for (value, frequency) in [(2.0, 1.0), (0.0, 2.0), (2.0, 3.0), (1.0, 4.0)] {
+            session.add_take(take(value, &[frequency]));
+        }
+        let order: Vec<(f32, f32)> = session
+            .takes
+            .iter()
+            .map(|take| (take.value, take.modes[0].frequency))
+            .collect();
+        assert_eq!(order, vec![(0.0, 2.0), (1.0, 4.0), (2.0, 1.0), (2.0, 3.0)]);
+    }
+
+    #[test]
+    fn tracks_follow_moving_partials() {
+        let mut session = SweepSession::default();
+        // the partials sink as water is added, and a new one appears in the last take
+        session.add_take(take(0.0, &[200.0, 560.0]));
+        session.add_take(take(10.0, &[195.0, 545.0]));
+        session.add_take(take(20.0, &[190.0, 530.0, 1000.0]));
+
+        let tracks = session.tracks();
+        assert_eq!(tracks.len(), 3);
+        let frequencies: Vec<Vec<f32>> = tracks
+            .iter()
+            .map(|track| track.points.iter().map(|(_, m)| m.frequency).collect())
+            .collect();
+        assert_eq!(
+            frequencies,
+            vec![
+                vec![200.0, 195.0, 190.0],
+                vec![560.0, 545.0, 530.0],
+                vec![1000.0]
+            ]
+        );
+        assert_eq!(tracks[2].first().0, 20.0);
+
+        let shift = tracks[0].series(SweepQuantity::Shift);
+        assert_eq!(shift[0], (0.0, 0.0));
+        assert!((shift[2].1 - ratio_to_cents(190.0 / 200.0)).abs() < 1e-3);
+        // T60 wasn't measured
+        assert!(tracks[0].series(SweepQuantity::T60).is_empty());
+    }
+
+    #[test]
+    fn fit_recovers_a_polynomial() {
+        // large parameter values would make the unscaled equations ill-conditioned
+        let polynomial = |x: f64| 3.0 - 0.5 * (x - 1000.0) + 0.02 * (x - 1000.0).powi(3);
+        let points: Vec<(f64, f64)> = (0..8)
+            .map(|i| 1000.0 + i as f64 * 5.0)
+            .map(|x| (x, polynomial(x)))
+            .collect();
+        let fit = PolynomialFit::new(&points, 3).unwrap();
+        assert!((fit.r_squared - 1.0).abs() < 1e-9);
+        for x in [1000.0, 1012.5, 1035.0] {
+            assert!((fit.evaluate(x) - polynomial(x)).abs() < 1e-6);
+        }
+
+        let line = PolynomialFit::new(&points, 1).unwrap();
+        assert!(line.r_squared > 0.0 && line.r_squared < 1.0);
+    }
+
+    #[test]
+    fn fit_needs_more_values_than_its_degree() {
+        let points = [(1.0, 2.0), (1.0, 3.0), (2.0, 4.0)];
+        assert!(PolynomialFit::new(&points, 2).is_none());
+        let line = PolynomialFit::new(&points, 1).unwrap();
+        assert!((line.evaluate(1.0) - 2.5).abs() < 1e-12);
+        assert!((line.evaluate(2.0) - 4.0).abs() < 1e-12);
+    }
+}
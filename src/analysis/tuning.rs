@@ -0,0 +1,340 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Pitch class of A, which the reference pitch is given for
+const A: usize = 9;
+/// Octave the root of the scale is placed in
+const ROOT_OCTAVE: i32 = 4;
+
+/// 5-limit just intonation intervals of the chromatic scale above the root
+const JUST_RATIOS: [(u32, u32); 12] = [
+    (1, 1),
+    (16, 15),
+    (9, 8),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (45, 32),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (9, 5),
+    (15, 8),
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Temperament {
+    Equal,
+    Just,
+    /// Loaded from a Scala `.scl` file
+    Scala,
+}
+
+#[derive(Debug)]
+pub enum ScalaError {
+    Io(std::io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for ScalaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalaError::Io(e) => write!(f, "{}", e),
+            ScalaError::Parse(message) => write!(f, "invalid scale: {}", message),
+        }
+    }
+}
+
+/// Scale degrees as intervals above the root
+#[derive(Clone, PartialEq, Debug)]
+pub struct Scale {
+    pub description: String,
+    /// Degrees in cents, starting with the root at 0
+    pub degrees: Vec<f64>,
+    /// Interval after which the scale repeats, usually an octave
+    pub period: f64,
+}
+
+impl Scale {
+    pub fn equal_temperament() -> Self {
+        Scale {
+            description: String::from("12-TET"),
+            degrees: (0..12).map(|i| i as f64 * 100.0).collect(),
+            period: 1200.0,
+        }
+    }
+
+    pub fn just_intonation() -> Self {
+        Scale {
+            description: String::from("5-limit just intonation"),
+            degrees: JUST_RATIOS
+                .iter()
+                .map(|&(n, d)| ratio_to_cents(n as f64 / d as f64))
+                .collect(),
+            period: 1200.0,
+        }
+    }
+
+    pub fn load_scala(path: &Path) -> Result<Self, ScalaError> {
+        let text = std::fs::read_to_string(path).map_err(ScalaError::Io)?;
+        Self::parse_scala(&text)
+    }
+
+    /// Parse the Scala scale format: a description line, the number of notes, then one pitch
+    /// per line, either in cents (containing a '.') or as a ratio. The root is implied and the
+    /// last pitch is the period. Lines starting with '!' are comments.
+    pub fn parse_scala(text: &str) -> Result<Self, ScalaError> {
+        let mut lines = text.lines().filter(|line| !line.starts_with('!'));
+        let description = lines
+            .next()
+            .ok_or_else(|| ScalaError::Parse(String::from("missing description")))?
+            .trim()
+            .to_string();
+        let count_line = lines
+            .next()
+            .ok_or_else(|| ScalaError::Parse(String::from("missing note count")))?;
+        let count: usize = count_line
+            .split_whitespace()
+            .next()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| ScalaError::Parse(format!("bad note count '{}'", count_line.trim())))?;
+        if count == 0 {
+            return Err(ScalaError::Parse(String::from("scale has no notes")));
+        }
+
+        let mut pitches = Vec::with_capacity(count);
+        for line in lines {
+            if pitches.len() == count {
+                break;
+            }
+            let Some(token) = line.split_whitespace().next() else {
+                continue;
+            };
+            pitches.push(parse_pitch(token)?);
+        }
+        if pitches.len() < count {
+            return Err(ScalaError::Parse(format!(
+                "expected {} notes, found {}",
+                count,
+                pitches.len()
+            )));
+        }
+
+        let period = pitches.pop().unwrap_or(1200.0);
+        if period <= 0.0 {
+            return Err(ScalaError::Parse(String::from("period must be positive")));
+        }
+        let mut degrees = vec![0.0];
+        degrees.extend(pitches);
+
+        Ok(Scale {
+            description,
+            degrees,
+            period,
+        })
+    }
+
+    fn repeats_at_octave(&self) -> bool {
+        (self.period - 1200.0).abs() < 1e-6
+    }
+}
+
+fn parse_pitch(token: &str) -> Result<f64, ScalaError> {
+    let bad_pitch = || ScalaError::Parse(format!("bad pitch '{}'", token));
+
+    if token.contains('.') {
+        return token.parse().map_err(|_| bad_pitch());
+    }
+
+    let (numerator, denominator) = match token.split_once('/') {
+        Some((n, d)) => (n, d),
+        None => (token, "1"),
+    };
+    let numerator: f64 = numerator.parse().map_err(|_| bad_pitch())?;
+    let denominator: f64 = denominator.parse().map_err(|_| bad_pitch())?;
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return Err(bad_pitch());
+    }
+    Ok(ratio_to_cents(numerator / denominator))
+}
+
+pub fn ratio_to_cents(ratio: f64) -> f64 {
+    1200.0 * ratio.log2()
+}
+
+/// Nearest scale note to a frequency
+#[derive(Clone, PartialEq, Debug)]
+pub struct NoteEstimate {
+    pub name: String,
+    /// Deviation from the note, positive when sharp
+    pub cents: f32,
+    pub target_frequency: f32,
+}
+
+/// Maps frequencies to notes of a scale
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tuning {
+    /// Frequency of A4 in Hz
+    pub reference_a4: f32,
+    /// Pitch class the scale starts on, 0 being C
+    pub root: usize,
+    pub scale: Scale,
+}
+
+impl Tuning {
+    pub fn new(reference_a4: f32, root: usize, scale: Scale) -> Self {
+        Tuning {
+            reference_a4,
+            root: root % 12,
+            scale,
+        }
+    }
+
+    /// Root of the scale in octave 4, tuned equal tempered from the reference
+    fn root_frequency(&self) -> f64 {
+        self.reference_a4 as f64 * 2.0_f64.powf((self.root as f64 - A as f64) / 12.0)
+    }
+
+    pub fn nearest_note(&self, frequency: f32) -> Option<NoteEstimate> {
+        if frequency <= 0.0 || self.reference_a4 <= 0.0 {
+            return None;
+        }
+
+        let root_frequency = self.root_frequency();
+        let cents = ratio_to_cents(frequency as f64 / root_frequency);
+        let period = self.scale.period;
+        let mut period_index = (cents / period).floor() as i32;
+        let within = cents - period_index as f64 * period;
+
+        // the root of the next period is a candidate as well
+        let (mut degree, mut degree_cents) = self
+            .scale
+            .degrees
+            .iter()
+            .cloned()
+            .chain(std::iter::once(period))
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (within - a).abs().total_cmp(&(within - b).abs()))?;
+        if degree == self.scale.degrees.len() {
+            degree = 0;
+            degree_cents = 0.0;
+            period_index += 1;
+        }
+
+        let target_cents = period_index as f64 * period + degree_cents;
+        let target_frequency = root_frequency * 2.0_f64.powf(target_cents / 1200.0);
+
+        Some(NoteEstimate {
+            name: self.degree_name(degree, period_index),
+            cents: (cents - target_cents) as f32,
+            target_frequency: target_frequency as f32,
+        })
+    }
+
+    /// Chromatic scales get note names, anything else the degree above the root of its period
+    fn degree_name(&self, degree: usize, period_index: i32) -> String {
+        let octave_scale = self.scale.repeats_at_octave();
+        if octave_scale && self.scale.degrees.len() == 12 {
+            let pitch_class = self.root + degree;
+            let octave = ROOT_OCTAVE + period_index + (pitch_class / 12) as i32;
+            format!("{}{}", NOTE_NAMES[pitch_class % 12], octave)
+        } else if octave_scale {
+            format!(
+                "{}{} +{}",
+                NOTE_NAMES[self.root],
+                ROOT_OCTAVE + period_index,
+                degree
+            )
+        } else {
+            format!("P{} +{}", period_index, degree)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scala_files() {
+        let text = "! meantone.scl\n\
+                    !\n\
+                    Quarter-comma meantone, partial\n \
+                    4\n\
+                    !\n\
+                    76.04900\n\
+                    5/4 major third\n\
+                    3/2\n\
+                    2\n";
+        let scale = Scale::parse_scala(text).unwrap();
+        assert_eq!(scale.description, "Quarter-comma meantone, partial");
+        assert_eq!(scale.period, 1200.0);
+        assert_eq!(scale.degrees.len(), 4);
+        assert_eq!(scale.degrees[0], 0.0);
+        assert_eq!(scale.degrees[1], 76.049);
+        assert!((scale.degrees[2] - 386.3137).abs() < 1e-3);
+        assert!((scale.degrees[3] - 701.955).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rejects_invalid_scala_files() {
+        for text in [
+            "",
+            "no count\n",
+            "bad count\nmany\n",
+            "empty\n0\n",
+            "short\n3\n100.0\n2/1\n",
+            "bad pitch\n2\nfifth\n2/1\n",
+            "negative ratio\n1\n-3/2\n",
+            "zero period\n1\n0.0\n",
+        ] {
+            assert!(Scale::parse_scala(text).is_err(), "{:?} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn names_equal_tempered_notes() {
+        let tuning = Tuning::new(440.0, 0, Scale::equal_temperament());
+        let note = tuning.nearest_note(440.0).unwrap();
+        assert_eq!(note.name, "A4");
+        assert!(note.cents.abs() < 1e-3);
+
+        let note = tuning.nearest_note(261.63 * 1.01).unwrap();
+        assert_eq!(note.name, "C4");
+        assert!((note.cents - 17.2).abs() < 0.1, "{:?}", note);
+
+        // just below the next octave's root
+        let note = tuning.nearest_note(1040.0).unwrap();
+        assert_eq!(note.name, "C6");
+        assert!((note.target_frequency - 1046.5).abs() < 0.1);
+
+        assert!(tuning.nearest_note(0.0).is_none());
+    }
+
+    #[test]
+    fn other_scales_are_named_by_degree() {
+        // a root of D, degrees a fifth apart up to the octave
+        let tuning = Tuning::new(
+            440.0,
+            2,
+            Scale::parse_scala("fifths\n2\n3/2\n2/1\n").unwrap(),
+        );
+        let d4 = tuning.nearest_note(293.66).unwrap();
+        assert_eq!(d4.name, "D4 +0");
+        let a4 = tuning.nearest_note(440.0).unwrap();
+        assert_eq!(a4.name, "D4 +1");
+        // just, so 2 cents above the equal tempered A
+        assert!((a4.cents + 1.96).abs() < 0.05, "{:?}", a4);
+
+        let tritave = Scale::parse_scala("tritave\n1\n3/1\n").unwrap();
+        let tuning = Tuning::new(440.0, 9, tritave);
+        assert_eq!(tuning.nearest_note(1320.0).unwrap().name, "P1 +0");
+    }
+}
@@ -0,0 +1,309 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::analysis::spectrum::{parabolic_peak, Peak, SPECTRUM_FLOOR_DB};
+
+/// Sample rate after decimation, relative to the span. The low-pass passes the span and has
+/// reached its stop band where aliases would fold back into it.
+const OVERSAMPLING: f32 = 2.0;
+/// Length of the Blackman windowed low-pass in input samples is this over the transition width
+/// in cycles per sample
+const FILTER_LENGTH_FACTOR: f32 = 5.5;
+/// The captured signal is zero padded to this many times its length, so peaks are drawn
+/// smoothly and interpolated between closely spaced points
+const ZERO_PADDING: usize = 8;
+/// Narrowest band. The low-pass grows with the inverse of the span.
+const MIN_SPAN: f32 = 0.5;
+/// Captures stop growing after this long
+const MAX_CAPTURE_SECONDS: f32 = 1200.0;
+/// Peaks are looked for this far below the strongest one
+const PEAK_RANGE_DB: f32 = 40.0;
+const MAX_PEAKS: usize = 8;
+
+/// Spectrum of a narrow band, with bins finer than the whole-signal FFT can offer
+#[derive(Clone)]
+pub struct ZoomSpectrum {
+    /// Frequency of the first bin in Hz
+    pub start_frequency: f32,
+    pub bin_hz: f32,
+    /// Bin magnitudes in dB, 0 dB being a full scale sine
+    pub magnitudes_db: Vec<f32>,
+    /// Resolution set by the capture length, 1 / duration in Hz. Bins are spaced more finely
+    /// than this by the zero padding, but peaks closer than it can't be told apart.
+    pub resolution_hz: f32,
+}
+
+impl ZoomSpectrum {
+    pub fn frequency(&self, bin: f32) -> f32 {
+        self.start_frequency + bin * self.bin_hz
+    }
+
+    pub fn end_frequency(&self) -> f32 {
+        self.frequency(self.magnitudes_db.len().saturating_sub(1) as f32)
+    }
+
+    /// Local maxima within `PEAK_RANGE_DB` of the strongest, strongest first, refined between
+    /// bins
+    pub fn peaks(&self) -> Vec<Peak> {
+        let magnitudes = &self.magnitudes_db;
+        if magnitudes.len() < 3 {
+            return Vec::new();
+        }
+        let threshold =
+            magnitudes.iter().cloned().fold(SPECTRUM_FLOOR_DB, f32::max) - PEAK_RANGE_DB;
+
+        let mut peaks: Vec<Peak> = (1..magnitudes.len() - 1)
+            .filter(|&i| {
+                magnitudes[i] > threshold
+                    && magnitudes[i] > magnitudes[i - 1]
+                    && magnitudes[i] >= magnitudes[i + 1]
+            })
+            .map(|i| {
+                let (offset, magnitude_db) =
+                    parabolic_peak(magnitudes[i - 1], magnitudes[i], magnitudes[i + 1]);
+                Peak {
+                    frequency: self.frequency(i as f32 + offset),
+                    magnitude_db,
+                }
+            })
+            .collect();
+        peaks.sort_by(|a, b| b.magnitude_db.total_cmp(&a.magnitude_db));
+        peaks.truncate(MAX_PEAKS);
+        peaks
+    }
+}
+
+/// Heterodyne zoom analysis of one band: the input is shifted down so the band is centred on
+/// 0 Hz, low-pass filtered and decimated to a rate just above the span, and the long,
+/// slowly sampled result is transformed in one FFT. Fed in blocks of any size.
+pub struct ZoomAnalyzer {
+    centre: f64,
+    span: f32,
+    sample_rate: u32,
+    decimation: usize,
+    /// Low-pass taps, unity gain at DC
+    filter: Vec<f32>,
+    /// Shifted input still needed by the filter
+    mixed: Vec<Complex<f32>>,
+    /// Input samples seen, for the phase of the shift
+    position: u64,
+    /// Input samples until the next decimated output
+    countdown: usize,
+    /// Decimated band, centred on 0 Hz
+    band: Vec<Complex<f32>>,
+    max_band_len: usize,
+}
+
+impl ZoomAnalyzer {
+    pub fn new(centre: f32, span: f32, sample_rate: u32) -> Self {
+        let span = span.clamp(MIN_SPAN, sample_rate as f32 / (2.0 * OVERSAMPLING));
+        let decimation = ((sample_rate as f32 / (OVERSAMPLING * span)).floor() as usize).max(1);
+        let decimated_rate = sample_rate as f32 / decimation as f32;
+
+        // flat to the edge of the span, stopping where the decimated rate folds back onto it
+        let transition = (decimated_rate - span) / sample_rate as f32;
+        let taps = ((FILTER_LENGTH_FACTOR / transition.max(1e-6)).ceil() as usize) | 1;
+        let cutoff = 0.5 * decimated_rate / sample_rate as f32;
+        let middle = (taps / 2) as f32;
+        let mut filter: Vec<f32> = (0..taps)
+            .map(|n| {
+                let x = n as f32 - middle;
+                let sinc = if x == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * std::f32::consts::PI * cutoff * x).sin() / (std::f32::consts::PI * x)
+                };
+                let phase = 2.0 * std::f32::consts::PI * n as f32 / (taps - 1).max(1) as f32;
+                sinc * (0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos())
+            })
+            .collect();
+        let gain: f32 = filter.iter().sum();
+        for tap in filter.iter_mut() {
+            *tap /= gain;
+        }
+
+        ZoomAnalyzer {
+            centre: centre as f64,
+            span,
+            sample_rate,
+            decimation,
+            filter,
+            mixed: Vec::new(),
+            position: 0,
+            countdown: 0,
+            band: Vec::new(),
+            max_band_len: (MAX_CAPTURE_SECONDS * decimated_rate) as usize,
+        }
+    }
+
+    pub fn centre(&self) -> f32 {
+        self.centre as f32
+    }
+
+    pub fn span(&self) -> f32 {
+        self.span
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length of the band captured so far in seconds. The filter delays the start by half
+    /// its length.
+    pub fn seconds(&self) -> f32 {
+        (self.band.len() * self.decimation) as f32 / self.sample_rate as f32
+    }
+
+    /// True once the capture has reached its maximum length
+    pub fn is_full(&self) -> bool {
+        self.band.len() >= self.max_band_len
+    }
+
+    pub fn process(&mut self, samples: &[f32]) {
+        if self.is_full() {
+            return;
+        }
+        let step = -2.0 * std::f64::consts::PI * self.centre / self.sample_rate as f64;
+        for &sample in samples {
+            // the phase is computed from the position rather than accumulated, so it doesn't
+            // drift over long captures
+            let phase = (step * self.position as f64) % (2.0 * std::f64::consts::PI);
+            let (sin, cos) = phase.sin_cos();
+            self.mixed
+                .push(Complex::new(sample * cos as f32, sample * sin as f32));
+            self.position += 1;
+
+            if self.mixed.len() < self.filter.len() {
+                continue;
+            }
+            if self.countdown == 0 {
+                let start = self.mixed.len() - self.filter.len();
+                let output = self.mixed[start..]
+                    .iter()
+                    .zip(self.filter.iter())
+                    .fold(Complex::new(0.0, 0.0), |sum, (x, h)| sum + x * h);
+                self.band.push(output);
+                self.countdown = self.decimation;
+                if self.is_full() {
+                    break;
+                }
+            }
+            self.countdown -= 1;
+        }
+
+        let keep = self.filter.len();
+        if self.mixed.len() > 4 * keep {
+            self.mixed.drain(..self.mixed.len() - keep);
+        }
+    }
+
+    /// Spectrum of everything captured so far. None until the capture is a few samples long.
+    pub fn spectrum(&self) -> Option<ZoomSpectrum> {
+        let len = self.band.len();
+        if len < 8 {
+            return None;
+        }
+        let size = (len * ZERO_PADDING).next_power_of_two();
+        let mut buffer = vec![Complex::new(0.0, 0.0); size];
+        for (n, (value, sample)) in buffer.iter_mut().zip(self.band.iter()).enumerate() {
+            let window =
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / (len - 1) as f32).cos();
+            *value = sample * window;
+        }
+        FftPlanner::new()
+            .plan_fft_forward(size)
+            .process(&mut buffer);
+
+        // a full scale sine shifted to 0 Hz has amplitude 1/2, and the Hann window's coherent
+        // gain is 1/2
+        let scale = 4.0 / len as f32;
+        let decimated_rate = self.sample_rate as f32 / self.decimation as f32;
+        let bin_hz = decimated_rate / size as f32;
+        let half_bins = ((0.5 * self.span / bin_hz) as usize).min(size / 2 - 1);
+        let magnitudes_db = (0..=2 * half_bins)
+            .map(|i| {
+                // negative offsets wrap around to the end of the FFT
+                let bin = (i + size - half_bins) % size;
+                (20.0 * (buffer[bin].norm() * scale).log10()).max(SPECTRUM_FLOOR_DB)
+            })
+            .collect();
+
+        Some(ZoomSpectrum {
+            start_frequency: self.centre as f32 - half_bins as f32 * bin_hz,
+            bin_hz,
+            magnitudes_db,
+            resolution_hz: decimated_rate / len as f32,
+        })
+    }
+}
+
+/// Zoom spectrum of a band of a whole recording
+pub fn zoom_recording(
+    samples: &[f32],
+    sample_rate: u32,
+    centre: f32,
+    span: f32,
+) -> Option<ZoomSpectrum> {
+    let mut analyzer = ZoomAnalyzer::new(centre, span, sample_rate);
+    analyzer.process(samples);
+    analyzer.spectrum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    fn sines(partials: &[(f32, f32)], seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|n| {
+                let t = n as f64 / SAMPLE_RATE as f64;
+                partials
+                    .iter()
+                    .map(|&(f, a)| a * (2.0 * std::f64::consts::PI * f as f64 * t).sin() as f32)
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resolves_a_doublet() {
+        let samples = sines(&[(220.0, 0.5), (220.4, 0.25)], 30.0);
+        let spectrum = zoom_recording(&samples, SAMPLE_RATE, 220.0, 4.0).unwrap();
+        assert!(spectrum.resolution_hz < 0.1);
+        assert!(spectrum.start_frequency <= 218.0 && spectrum.end_frequency() >= 222.0);
+
+        let peaks = spectrum.peaks();
+        assert!(peaks.len() >= 2, "{:?}", peaks);
+        let (strong, weak) = (peaks[0], peaks[1]);
+        assert!((strong.frequency - 220.0).abs() < 0.02, "{:?}", strong);
+        assert!((weak.frequency - 220.4).abs() < 0.02, "{:?}", weak);
+        // full scale is 0 dB
+        assert!((strong.magnitude_db + 6.0).abs() < 1.0, "{:?}", strong);
+        assert!((weak.magnitude_db + 12.0).abs() < 1.0, "{:?}", weak);
+    }
+
+    #[test]
+    fn rejects_partials_outside_the_span() {
+        let samples = sines(&[(220.0, 0.01), (230.0, 1.0)], 30.0);
+        let spectrum = zoom_recording(&samples, SAMPLE_RATE, 220.0, 4.0).unwrap();
+        let peaks = spectrum.peaks();
+        assert!((peaks[0].frequency - 220.0).abs() < 0.02, "{:?}", peaks);
+        assert!((peaks[0].magnitude_db + 40.0).abs() < 1.0, "{:?}", peaks);
+    }
+
+    #[test]
+    fn block_size_does_not_change_the_spectrum() {
+        let samples = sines(&[(440.0, 0.5)], 10.0);
+        let whole = zoom_recording(&samples, SAMPLE_RATE, 441.0, 8.0).unwrap();
+
+        let mut analyzer = ZoomAnalyzer::new(441.0, 8.0, SAMPLE_RATE);
+        assert!(analyzer.spectrum().is_none());
+        for block in samples.chunks(517) {
+            analyzer.process(block);
+        }
+        let blocks = analyzer.spectrum().unwrap();
+        assert_eq!(whole.magnitudes_db, blocks.magnitudes_db);
+    }
+}
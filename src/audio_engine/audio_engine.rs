@@ -27,7 +27,6 @@ pub struct AudioContext {
 
 impl AudioContext {
     pub fn new(sample_rate: u32) -> Self {
-        // TODO: implement host selection feature
         let host = cpal::default_host();
         let input_device = host.default_input_device();
         let output_device = host.default_output_device();
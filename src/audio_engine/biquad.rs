@@ -0,0 +1,74 @@
+/// Second order IIR section in transposed direct form II. Coefficients are normalized so
+/// that a0 = 1.
+#[derive(Clone)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    pub fn new(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Second order Butterworth high pass (RBJ cookbook)
+    pub fn highpass(sample_rate: f64, frequency: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency);
+        Biquad::new(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Second order Butterworth low pass (RBJ cookbook)
+    pub fn lowpass(sample_rate: f64, frequency: f64) -> Self {
+        let (cos, alpha) = Self::prewarp(sample_rate, frequency);
+        Biquad::new(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    fn prewarp(sample_rate: f64, frequency: f64) -> (f64, f64) {
+        let w0 = 2.0 * std::f64::consts::PI * frequency / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * std::f64::consts::FRAC_1_SQRT_2))
+    }
+
+    /// Take over the response of `other`, keeping the state so a sweep doesn't click
+    pub fn set_coefficients(&mut self, other: &Biquad) {
+        self.b0 = other.b0;
+        self.b1 = other.b1;
+        self.b2 = other.b2;
+        self.a1 = other.a1;
+        self.a2 = other.a2;
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let x = x as f64;
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y as f32
+    }
+}
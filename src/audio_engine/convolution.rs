@@ -0,0 +1,422 @@
+use std::f32::consts::FRAC_PI_2;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::analysis::recording::load_wav;
+use crate::audio_engine::params::{AtomicF32, Handover, SmoothedValue};
+use crate::audio_engine::resampler::resample;
+
+/// Partition length, rounded up to a power of two. It is the latency of the wet signal, and
+/// shorter partitions cost more per second of impulse response.
+const PARTITION_SECONDS: f32 = 0.01;
+/// Longer impulse responses are cut, to bound the work per block
+pub const MAX_IR_SECONDS: f32 = 10.0;
+pub const MAX_PRE_DELAY_MS: f32 = 250.0;
+/// The tail of the impulse response below this level relative to its peak is dropped
+const TAIL_FLOOR: f32 = 1e-4;
+/// Time constant of wet/dry changes
+const SMOOTHING_TIME: f32 = 0.02;
+
+/// Partition length at a sample rate, which is also the latency of the wet signal
+pub fn partition_size(sample_rate: u32) -> usize {
+    ((PARTITION_SECONDS * sample_rate as f32) as usize).next_power_of_two()
+}
+
+/// A measured impulse response as loaded, before it is fitted to the stream's sample rate
+pub struct ImpulseResponse {
+    path: PathBuf,
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl ImpulseResponse {
+    /// Load a WAV file, mixed down to mono
+    pub fn load(path: &Path) -> Result<Self, hound::Error> {
+        let (samples, sample_rate) = load_wav(path)?;
+        Ok(ImpulseResponse {
+            path: path.to_path_buf(),
+            samples,
+            sample_rate,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    /// The response at `sample_rate`, cut to `MAX_IR_SECONDS` and its silent tail and scaled to
+    /// unit energy, so the wet level doesn't depend on how loud the response was recorded
+    fn prepare(&self, sample_rate: u32) -> Vec<f32> {
+        let mut samples = resample(&self.samples, self.sample_rate, sample_rate);
+        samples.truncate((MAX_IR_SECONDS * sample_rate as f32) as usize);
+
+        let peak = samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+        let end = samples
+            .iter()
+            .rposition(|s| s.abs() > peak * TAIL_FLOOR)
+            .map_or(0, |last| last + 1);
+        samples.truncate(end);
+
+        let energy = samples.iter().map(|s| s * s).sum::<f32>().sqrt();
+        if energy > 0.0 {
+            for sample in samples.iter_mut() {
+                *sample /= energy;
+            }
+        }
+        samples
+    }
+}
+
+/// Uniformly partitioned overlap-save convolution. The impulse response is split into
+/// partitions of one block, each transformed once; every block of input is transformed once,
+/// kept in a frequency-domain delay line and multiplied with all the partitions. The output is
+/// one block late, however long the response. Nothing is allocated after construction.
+pub struct PartitionedConvolver {
+    block: usize,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    scratch: Vec<Complex<f32>>,
+    /// Bins 0 to Nyquist of each partition's transform, one after the other
+    partitions: Vec<Complex<f32>>,
+    /// Bins of the last input blocks, as many as there are partitions, in a ring
+    delay_line: Vec<Complex<f32>>,
+    /// Slot of the newest block in `delay_line`
+    newest: usize,
+    accumulator: Vec<Complex<f32>>,
+    spectrum: Vec<Complex<f32>>,
+    /// The previous block of input followed by the one being filled
+    input: Vec<f32>,
+    /// Output of the last complete block, played out while the next one fills
+    output: Vec<f32>,
+    position: usize,
+}
+
+impl PartitionedConvolver {
+    pub fn new(impulse_response: &[f32], block: usize) -> Self {
+        let size = 2 * block;
+        let bins = block + 1;
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(size);
+        let inverse = planner.plan_fft_inverse(size);
+        let scratch_len = forward
+            .get_inplace_scratch_len()
+            .max(inverse.get_inplace_scratch_len());
+        let mut scratch = vec![Complex::new(0.0, 0.0); scratch_len];
+
+        let count = impulse_response.len().div_ceil(block);
+        let mut partitions = Vec::with_capacity(count * bins);
+        let mut spectrum = vec![Complex::new(0.0, 0.0); size];
+        for partition in impulse_response.chunks(block) {
+            spectrum.fill(Complex::new(0.0, 0.0));
+            for (value, &sample) in spectrum.iter_mut().zip(partition) {
+                // the inverse transform's 1/N folded into the partitions
+                value.re = sample / size as f32;
+            }
+            forward.process_with_scratch(&mut spectrum, &mut scratch);
+            partitions.extend_from_slice(&spectrum[..bins]);
+        }
+
+        PartitionedConvolver {
+            block,
+            forward,
+            inverse,
+            scratch,
+            partitions,
+            delay_line: vec![Complex::new(0.0, 0.0); count * bins],
+            newest: 0,
+            accumulator: vec![Complex::new(0.0, 0.0); bins],
+            spectrum,
+            input: vec![0.0; size],
+            output: vec![0.0; block],
+            position: 0,
+        }
+    }
+
+    /// Number of partitions the response was split into
+    pub fn partition_count(&self) -> usize {
+        self.partitions.len() / (self.block + 1)
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_line.fill(Complex::new(0.0, 0.0));
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.position = 0;
+    }
+
+    /// Take one input sample and return the output one block earlier
+    pub fn process_sample(&mut self, sample: f32) -> f32 {
+        self.input[self.block + self.position] = sample;
+        let output = self.output[self.position];
+        self.position += 1;
+        if self.position == self.block {
+            self.process_block();
+            self.position = 0;
+        }
+        output
+    }
+
+    fn process_block(&mut self) {
+        let block = self.block;
+        let bins = block + 1;
+        let count = self.partition_count();
+        if count == 0 {
+            self.input.copy_within(block.., 0);
+            return;
+        }
+
+        for (value, &sample) in self.spectrum.iter_mut().zip(&self.input) {
+            *value = Complex::new(sample, 0.0);
+        }
+        self.forward
+            .process_with_scratch(&mut self.spectrum, &mut self.scratch);
+        self.newest = (self.newest + 1) % count;
+        self.delay_line[self.newest * bins..(self.newest + 1) * bins]
+            .copy_from_slice(&self.spectrum[..bins]);
+
+        // partition p meets the block from p blocks ago
+        self.accumulator.fill(Complex::new(0.0, 0.0));
+        for (p, partition) in self.partitions.chunks_exact(bins).enumerate() {
+            let slot = (self.newest + count - p) % count;
+            let delayed = &self.delay_line[slot * bins..(slot + 1) * bins];
+            for ((sum, x), h) in self.accumulator.iter_mut().zip(delayed).zip(partition) {
+                *sum += x * h;
+            }
+        }
+
+        self.spectrum[..bins].copy_from_slice(&self.accumulator);
+        for k in 1..block {
+            self.spectrum[2 * block - k] = self.accumulator[k].conj();
+        }
+        self.inverse
+            .process_with_scratch(&mut self.spectrum, &mut self.scratch);
+
+        // the first half is wrapped around by the circular convolution, the second is valid
+        for (out, value) in self.output.iter_mut().zip(&self.spectrum[block..]) {
+            *out = value.re;
+        }
+        self.input.copy_within(block.., 0);
+    }
+}
+
+/// Reverb controls and impulse response, shared between the UI and the audio thread
+pub struct ConvolutionParams {
+    enabled: AtomicBool,
+    /// 0.0 is only the dry signal, 1.0 only the reverb
+    mix: AtomicF32,
+    pre_delay_ms: AtomicF32,
+    impulse_response: Mutex<Option<Arc<ImpulseResponse>>>,
+    /// Convolvers for a new impulse response, built off the audio thread
+    handover: Handover<PartitionedConvolver>,
+}
+
+impl ConvolutionParams {
+    pub fn new() -> Self {
+        ConvolutionParams {
+            enabled: AtomicBool::new(false),
+            mix: AtomicF32::new(0.3),
+            pre_delay_ms: AtomicF32::new(0.0),
+            impulse_response: Mutex::new(None),
+            handover: Handover::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn mix(&self) -> f32 {
+        self.mix.load()
+    }
+
+    pub fn set_mix(&self, mix: f32) {
+        self.mix.store(mix.clamp(0.0, 1.0));
+    }
+
+    pub fn pre_delay_ms(&self) -> f32 {
+        self.pre_delay_ms.load()
+    }
+
+    pub fn set_pre_delay_ms(&self, ms: f32) {
+        self.pre_delay_ms.store(ms.clamp(0.0, MAX_PRE_DELAY_MS));
+    }
+
+    pub fn impulse_response(&self) -> Option<Arc<ImpulseResponse>> {
+        self.impulse_response.lock().unwrap().clone()
+    }
+
+    /// Replace the impulse response. With a stream running at `sample_rate`, the new
+    /// convolver is prepared here and picked up by the audio thread on its next callback.
+    pub fn set_impulse_response(
+        &self,
+        impulse_response: Option<ImpulseResponse>,
+        sample_rate: Option<u32>,
+    ) {
+        let impulse_response = impulse_response.map(Arc::new);
+        *self.impulse_response.lock().unwrap() = impulse_response.clone();
+
+        if let Some(sample_rate) = sample_rate {
+            let convolver = Self::build_convolver(impulse_response.as_deref(), sample_rate);
+            self.handover.send(convolver);
+        }
+    }
+
+    fn build_convolver(
+        impulse_response: Option<&ImpulseResponse>,
+        sample_rate: u32,
+    ) -> PartitionedConvolver {
+        let samples = impulse_response.map_or_else(Vec::new, |ir| ir.prepare(sample_rate));
+        PartitionedConvolver::new(&samples, partition_size(sample_rate))
+    }
+}
+
+/// Audio thread side of the reverb: pre-delay, convolution and the wet/dry mix. The channels
+/// of the output share one mono reverb.
+pub struct Reverb {
+    convolver: PartitionedConvolver,
+    mix: SmoothedValue,
+    pre_delay: Vec<f32>,
+    write: usize,
+    sample_rate: u32,
+    active: bool,
+}
+
+impl Reverb {
+    /// Built with the stream, from the impulse response loaded at the time
+    pub fn new(params: &ConvolutionParams, sample_rate: u32) -> Self {
+        let convolver =
+            ConvolutionParams::build_convolver(params.impulse_response().as_deref(), sample_rate);
+        // a convolver prepared for a previous stream is of no use
+        params.handover.clear();
+
+        Reverb {
+            convolver,
+            mix: SmoothedValue::new(params.mix(), sample_rate, SMOOTHING_TIME),
+            pre_delay: vec![0.0; (MAX_PRE_DELAY_MS / 1000.0 * sample_rate as f32) as usize + 1],
+            write: 0,
+            sample_rate,
+            active: false,
+        }
+    }
+
+    /// Process a buffer of interleaved frames
+    pub fn process(&mut self, params: &ConvolutionParams, buffer: &mut [f32], num_channels: usize) {
+        if params
+            .handover
+            .receive(|convolver| std::mem::replace(&mut self.convolver, convolver))
+        {
+            self.active = false;
+        }
+
+        let enabled = params.enabled();
+        if !enabled {
+            self.active = false;
+            return;
+        }
+        if !self.active {
+            // start from silence rather than the reverb tail left from before
+            self.convolver.reset();
+            self.pre_delay.fill(0.0);
+            self.active = true;
+        }
+
+        // the convolution already delays the wet signal by a block, so only the rest of the
+        // pre-delay is added
+        let pre_delay = (params.pre_delay_ms() / 1000.0 * self.sample_rate as f32).round() as usize;
+        let delay = pre_delay
+            .saturating_sub(self.convolver.block)
+            .min(self.pre_delay.len() - 1);
+        self.mix.set_target(params.mix());
+
+        for frame in buffer.chunks_mut(num_channels) {
+            let len = self.pre_delay.len();
+            self.pre_delay[self.write] = frame.iter().sum::<f32>() / frame.len() as f32;
+            let delayed = self.pre_delay[(self.write + len - delay) % len];
+            self.write = (self.write + 1) % len;
+            let wet = self.convolver.process_sample(delayed);
+
+            // the reverb is largely uncorrelated with the dry signal, so an equal power
+            // crossfade keeps the level
+            let angle = self.mix.next() * FRAC_PI_2;
+            let (dry_gain, wet_gain) = (angle.cos(), angle.sin());
+            for sample in frame.iter_mut() {
+                *sample = *sample * dry_gain + wet * wet_gain;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise in -1 to 1
+    fn noise(len: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) as f32 / (1 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partitioned_matches_direct_convolution() {
+        let block = 64;
+        // several partitions, the last one partly filled
+        let impulse_response: Vec<f32> = noise(300, 1)
+            .iter()
+            .enumerate()
+            .map(|(n, s)| s * (-(n as f32) / 80.0).exp())
+            .collect();
+        let input = noise(1000, 2);
+
+        let mut convolver = PartitionedConvolver::new(&impulse_response, block);
+        assert_eq!(convolver.partition_count(), 5);
+        let output: Vec<f32> = input
+            .iter()
+            .chain(std::iter::repeat_n(&0.0, block))
+            .map(|&s| convolver.process_sample(s))
+            .collect();
+
+        for n in 0..input.len() {
+            let direct: f32 = impulse_response
+                .iter()
+                .enumerate()
+                .filter(|(k, _)| *k <= n)
+                .map(|(k, h)| h * input[n - k])
+                .sum();
+            // one block late
+            let partitioned = output[n + block];
+            assert!(
+                (partitioned - direct).abs() < 1e-4,
+                "sample {}: {} instead of {}",
+                n,
+                partitioned,
+                direct
+            );
+        }
+    }
+
+    #[test]
+    fn empty_response_is_silent() {
+        let mut convolver = PartitionedConvolver::new(&[], 64);
+        assert_eq!(convolver.partition_count(), 0);
+        assert!(noise(500, 3)
+            .iter()
+            .all(|&s| convolver.process_sample(s) == 0.0));
+    }
+}
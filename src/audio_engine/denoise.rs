@@ -0,0 +1,251 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::audio_engine::params::{db_to_gain, AtomicF32};
+
+/// Spectral subtraction removes this many times the noise power, trading some of the signal
+/// for less musical noise
+const OVERSUBTRACTION: f32 = 2.0;
+pub const MAX_REDUCTION_DB: f32 = 40.0;
+pub const MAX_SMOOTHING: f32 = 0.99;
+
+/// How the gain of each bin is derived from its level over the noise profile
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DenoiseMethod {
+    /// Power spectral subtraction, the gain smoothed over time
+    SpectralSubtraction,
+    /// Wiener gain from the decision-directed a priori SNR estimate (Ephraim and Malah), which
+    /// leaves less musical noise
+    Wiener,
+}
+
+impl DenoiseMethod {
+    pub const ALL: [DenoiseMethod; 2] = [DenoiseMethod::SpectralSubtraction, DenoiseMethod::Wiener];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DenoiseMethod::SpectralSubtraction => "Spectral Subtraction",
+            DenoiseMethod::Wiener => "Wiener",
+        }
+    }
+}
+
+/// Noise power per bin summed over the frames it was learned from
+struct NoiseProfile {
+    power_sum: Vec<f32>,
+    frames: u32,
+}
+
+/// Noise reduction controls and the learned noise profile, shared between the UI and the
+/// audio thread. The profile is accumulated by the audio thread while learning and copied to
+/// its own denoiser when learning stops.
+pub struct DenoiseParams {
+    enabled: AtomicBool,
+    wiener: AtomicBool,
+    reduction_db: AtomicF32,
+    smoothing: AtomicF32,
+    learning: AtomicBool,
+    profile: Mutex<NoiseProfile>,
+    /// Frames in the profile, readable without locking
+    profile_frames: AtomicU32,
+    /// Bumped when the profile is finished or cleared
+    generation: AtomicU32,
+}
+
+impl DenoiseParams {
+    pub fn new() -> Self {
+        DenoiseParams {
+            enabled: AtomicBool::new(false),
+            wiener: AtomicBool::new(true),
+            reduction_db: AtomicF32::new(20.0),
+            smoothing: AtomicF32::new(0.98),
+            learning: AtomicBool::new(false),
+            profile: Mutex::new(NoiseProfile {
+                power_sum: Vec::new(),
+                frames: 0,
+            }),
+            profile_frames: AtomicU32::new(0),
+            generation: AtomicU32::new(0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn method(&self) -> DenoiseMethod {
+        if self.wiener.load(Ordering::Relaxed) {
+            DenoiseMethod::Wiener
+        } else {
+            DenoiseMethod::SpectralSubtraction
+        }
+    }
+
+    pub fn set_method(&self, method: DenoiseMethod) {
+        self.wiener
+            .store(method == DenoiseMethod::Wiener, Ordering::Relaxed);
+    }
+
+    /// Most the noise is attenuated, in dB
+    pub fn reduction_db(&self) -> f32 {
+        self.reduction_db.load()
+    }
+
+    pub fn set_reduction_db(&self, db: f32) {
+        self.reduction_db.store(db.clamp(0.0, MAX_REDUCTION_DB));
+    }
+
+    /// Weight of the previous frame in the gain, from 0 (none) to `MAX_SMOOTHING`
+    pub fn smoothing(&self) -> f32 {
+        self.smoothing.load()
+    }
+
+    pub fn set_smoothing(&self, smoothing: f32) {
+        self.smoothing.store(smoothing.clamp(0.0, MAX_SMOOTHING));
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learning.load(Ordering::Relaxed)
+    }
+
+    /// Learn a new profile from the input until `stop_learning`. Only noise should be playing.
+    pub fn start_learning(&self) {
+        let mut profile = self.profile.lock().unwrap();
+        profile.power_sum.fill(0.0);
+        profile.frames = 0;
+        self.profile_frames.store(0, Ordering::Relaxed);
+        self.learning.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stop_learning(&self) {
+        self.learning.store(false, Ordering::Relaxed);
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear_profile(&self) {
+        self.learning.store(false, Ordering::Relaxed);
+        let mut profile = self.profile.lock().unwrap();
+        profile.frames = 0;
+        self.profile_frames.store(0, Ordering::Relaxed);
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of STFT frames the profile was learned from, 0 if there is none
+    pub fn profile_frames(&self) -> u32 {
+        self.profile_frames.load(Ordering::Relaxed)
+    }
+}
+
+/// Audio thread side of the noise reduction, applied to each STFT frame
+pub struct SpectralDenoiser {
+    /// Mean noise power per bin, empty without a profile
+    noise: Vec<f32>,
+    generation: Option<u32>,
+    gains: Vec<f32>,
+    /// Power of the cleaned previous frame, for the decision-directed estimate
+    clean_power: Vec<f32>,
+}
+
+impl SpectralDenoiser {
+    /// `bins` is the number of bins up to Nyquist of the STFT it runs in. The shared profile is
+    /// sized for them here, so learning doesn't allocate on the audio thread; a profile of
+    /// another size, learned at another sample rate, is dropped.
+    pub fn new(params: &DenoiseParams, bins: usize) -> Self {
+        let mut profile = params.profile.lock().unwrap();
+        if profile.power_sum.len() != bins {
+            profile.power_sum = vec![0.0; bins];
+            profile.frames = 0;
+            params.profile_frames.store(0, Ordering::Relaxed);
+        }
+        drop(profile);
+
+        SpectralDenoiser {
+            noise: Vec::with_capacity(bins),
+            generation: None,
+            gains: vec![1.0; bins],
+            clean_power: vec![0.0; bins],
+        }
+    }
+
+    /// Learn from and, if enabled, denoise one frame's bins 0 to Nyquist
+    pub fn process(&mut self, params: &DenoiseParams, spectrum: &mut [Complex<f32>]) {
+        if params.is_learning() {
+            // skipped if the UI holds the lock, losing a frame of the profile at worst
+            let profile = params
+                .profile
+                .try_lock()
+                .ok()
+                .filter(|profile| profile.power_sum.len() == spectrum.len());
+            if let Some(mut profile) = profile {
+                for (sum, value) in profile.power_sum.iter_mut().zip(spectrum.iter()) {
+                    *sum += value.norm_sqr();
+                }
+                profile.frames += 1;
+                params
+                    .profile_frames
+                    .store(profile.frames, Ordering::Relaxed);
+            }
+        } else {
+            self.update_profile(params);
+        }
+
+        if !params.enabled() || self.noise.len() != spectrum.len() {
+            self.gains.fill(1.0);
+            return;
+        }
+
+        let floor = db_to_gain(-params.reduction_db());
+        let smoothing = params.smoothing();
+        let method = params.method();
+        for (k, value) in spectrum.iter_mut().enumerate() {
+            let power = value.norm_sqr();
+            let noise = self.noise[k].max(f32::MIN_POSITIVE);
+            let gain = match method {
+                DenoiseMethod::SpectralSubtraction => {
+                    let gain = (1.0 - OVERSUBTRACTION * noise / power.max(f32::MIN_POSITIVE))
+                        .max(0.0)
+                        .sqrt();
+                    smoothing * self.gains[k] + (1.0 - smoothing) * gain
+                }
+                DenoiseMethod::Wiener => {
+                    let posterior_snr = power / noise;
+                    let prior_snr = smoothing * self.clean_power[k] / noise
+                        + (1.0 - smoothing) * (posterior_snr - 1.0).max(0.0);
+                    prior_snr / (1.0 + prior_snr)
+                }
+            }
+            .max(floor);
+
+            self.gains[k] = gain;
+            self.clean_power[k] = gain * gain * power;
+            *value *= gain;
+        }
+    }
+
+    /// Take over a newly finished or cleared profile
+    fn update_profile(&mut self, params: &DenoiseParams) {
+        let generation = params.generation.load(Ordering::Relaxed);
+        if self.generation == Some(generation) {
+            return;
+        }
+        let Ok(profile) = params.profile.try_lock() else {
+            return;
+        };
+        self.noise.clear();
+        // a profile from a different frame size, i.e. sample rate, doesn't apply
+        if profile.frames > 0 && profile.power_sum.len() == self.gains.len() {
+            let frames = profile.frames as f32;
+            self.noise
+                .extend(profile.power_sum.iter().map(|sum| sum / frames));
+        }
+        self.generation = Some(generation);
+    }
+}
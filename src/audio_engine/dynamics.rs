@@ -0,0 +1,615 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio_engine::biquad::Biquad;
+use crate::audio_engine::params::{db_to_gain, gain_to_db, AtomicF32};
+
+pub const MAX_LOOKAHEAD_MS: f32 = 20.0;
+/// Sidechain high pass at or below this is off
+pub const SIDECHAIN_HIGHPASS_OFF: f32 = 10.0;
+/// Sidechain low pass at or above this is off
+pub const SIDECHAIN_LOWPASS_OFF: f32 = 20000.0;
+
+/// The processors of the dynamics chain, in the order they are applied
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DynamicsKind {
+    /// Lowers the signal by the full range below the threshold
+    Gate,
+    /// Downward expander, lowering the signal below the threshold by the ratio
+    Expander,
+    /// Feed-forward compressor, lowering the signal above the threshold by the ratio
+    Compressor,
+    /// Brickwall limiter that sees peaks coming by delaying the signal, so none pass the
+    /// threshold
+    Limiter,
+}
+
+impl DynamicsKind {
+    pub const ALL: [DynamicsKind; 4] = [
+        DynamicsKind::Gate,
+        DynamicsKind::Expander,
+        DynamicsKind::Compressor,
+        DynamicsKind::Limiter,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DynamicsKind::Gate => "Gate",
+            DynamicsKind::Expander => "Expander",
+            DynamicsKind::Compressor => "Compressor",
+            DynamicsKind::Limiter => "Limiter",
+        }
+    }
+
+    /// Whether a ratio shapes the curve
+    pub fn has_ratio(self) -> bool {
+        matches!(self, DynamicsKind::Expander | DynamicsKind::Compressor)
+    }
+
+    /// Whether the gain reduction is limited to a range
+    pub fn has_range(self) -> bool {
+        matches!(self, DynamicsKind::Gate | DynamicsKind::Expander)
+    }
+
+    /// Gain change in dB at a detected level of the gate, expander or compressor, with the
+    /// knee centred on the threshold (Giannoulis, Massberg and Reiss, 2012)
+    fn gain_db(self, level_db: f32, params: &StageParams) -> f32 {
+        let over = level_db - params.threshold_db();
+        let knee = params.knee_db();
+        let half_knee = knee / 2.0;
+        let ratio = params.ratio();
+        let range = params.range_db();
+
+        match self {
+            DynamicsKind::Gate => {
+                if over >= half_knee {
+                    0.0
+                } else if over > -half_knee {
+                    -range * (half_knee - over) / knee
+                } else {
+                    -range
+                }
+            }
+            DynamicsKind::Expander => {
+                let slope = ratio - 1.0;
+                let gain = if over >= half_knee {
+                    0.0
+                } else if over > -half_knee {
+                    -slope * (over - half_knee).powi(2) / (2.0 * knee)
+                } else {
+                    slope * over
+                };
+                gain.max(-range)
+            }
+            DynamicsKind::Compressor => {
+                let slope = 1.0 / ratio - 1.0;
+                if over <= -half_knee {
+                    0.0
+                } else if over < half_knee {
+                    slope * (over + half_knee).powi(2) / (2.0 * knee)
+                } else {
+                    slope * over
+                }
+            }
+            // limited with lookahead instead, see `Limiter`
+            DynamicsKind::Limiter => 0.0,
+        }
+    }
+}
+
+/// Controls of one processor, set from the UI and read once per callback by the audio thread
+pub struct StageParams {
+    kind: DynamicsKind,
+    enabled: AtomicBool,
+    threshold_db: AtomicF32,
+    ratio: AtomicF32,
+    knee_db: AtomicF32,
+    attack_ms: AtomicF32,
+    release_ms: AtomicF32,
+    range_db: AtomicF32,
+    lookahead_ms: AtomicF32,
+    sidechain_highpass_hz: AtomicF32,
+    sidechain_lowpass_hz: AtomicF32,
+    /// Most gain reduction in dB since the UI last read it
+    gain_reduction_db: AtomicF32,
+}
+
+impl StageParams {
+    pub fn new(kind: DynamicsKind) -> Self {
+        let (threshold_db, ratio, attack_ms, release_ms, range_db) = match kind {
+            DynamicsKind::Gate => (-50.0, 1.0, 1.0, 100.0, 60.0),
+            DynamicsKind::Expander => (-40.0, 2.0, 5.0, 100.0, 40.0),
+            DynamicsKind::Compressor => (-18.0, 4.0, 10.0, 150.0, 0.0),
+            DynamicsKind::Limiter => (-1.0, 1.0, 0.0, 100.0, 0.0),
+        };
+
+        StageParams {
+            kind,
+            enabled: AtomicBool::new(false),
+            threshold_db: AtomicF32::new(threshold_db),
+            ratio: AtomicF32::new(ratio),
+            knee_db: AtomicF32::new(6.0),
+            attack_ms: AtomicF32::new(attack_ms),
+            release_ms: AtomicF32::new(release_ms),
+            range_db: AtomicF32::new(range_db),
+            lookahead_ms: AtomicF32::new(5.0),
+            sidechain_highpass_hz: AtomicF32::new(SIDECHAIN_HIGHPASS_OFF),
+            sidechain_lowpass_hz: AtomicF32::new(SIDECHAIN_LOWPASS_OFF),
+            gain_reduction_db: AtomicF32::new(0.0),
+        }
+    }
+
+    pub fn kind(&self) -> DynamicsKind {
+        self.kind
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Level in dBFS where the processor starts acting; the ceiling of the limiter
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db.load()
+    }
+
+    pub fn set_threshold_db(&self, db: f32) {
+        self.threshold_db.store(db.min(0.0));
+    }
+
+    /// Compression ratio, or the expander's slope below the threshold
+    pub fn ratio(&self) -> f32 {
+        self.ratio.load()
+    }
+
+    pub fn set_ratio(&self, ratio: f32) {
+        self.ratio.store(ratio.max(1.0));
+    }
+
+    /// Width in dB of the soft transition around the threshold
+    pub fn knee_db(&self) -> f32 {
+        self.knee_db.load()
+    }
+
+    pub fn set_knee_db(&self, db: f32) {
+        self.knee_db.store(db.max(0.0));
+    }
+
+    /// Time for the detector to follow a rising level by ~63%
+    pub fn attack_ms(&self) -> f32 {
+        self.attack_ms.load()
+    }
+
+    pub fn set_attack_ms(&self, ms: f32) {
+        self.attack_ms.store(ms.max(0.0));
+    }
+
+    /// Time for the detector, or the limiter's gain, to recover by ~63%
+    pub fn release_ms(&self) -> f32 {
+        self.release_ms.load()
+    }
+
+    pub fn set_release_ms(&self, ms: f32) {
+        self.release_ms.store(ms.max(0.0));
+    }
+
+    /// Most the gate or expander lowers the signal, in dB
+    pub fn range_db(&self) -> f32 {
+        self.range_db.load()
+    }
+
+    pub fn set_range_db(&self, db: f32) {
+        self.range_db.store(db.max(0.0));
+    }
+
+    /// How far ahead the limiter sees, which is also the delay it adds
+    pub fn lookahead_ms(&self) -> f32 {
+        self.lookahead_ms.load()
+    }
+
+    pub fn set_lookahead_ms(&self, ms: f32) {
+        self.lookahead_ms.store(ms.clamp(0.0, MAX_LOOKAHEAD_MS));
+    }
+
+    /// High pass of the detector, so rumble and handling noise don't drive the processor
+    pub fn sidechain_highpass_hz(&self) -> f32 {
+        self.sidechain_highpass_hz.load()
+    }
+
+    pub fn set_sidechain_highpass_hz(&self, hz: f32) {
+        self.sidechain_highpass_hz
+            .store(hz.max(SIDECHAIN_HIGHPASS_OFF));
+    }
+
+    /// Low pass of the detector, so hiss or the click of the strike doesn't drive the
+    /// processor
+    pub fn sidechain_lowpass_hz(&self) -> f32 {
+        self.sidechain_lowpass_hz.load()
+    }
+
+    pub fn set_sidechain_lowpass_hz(&self, hz: f32) {
+        self.sidechain_lowpass_hz
+            .store(hz.min(SIDECHAIN_LOWPASS_OFF));
+    }
+
+    /// Most gain reduction in dB since the previous reading
+    pub fn read_gain_reduction_db(&self) -> f32 {
+        self.gain_reduction_db.swap(0.0)
+    }
+}
+
+/// Controls of the whole dynamics chain
+pub struct DynamicsParams {
+    stages: [StageParams; 4],
+}
+
+impl DynamicsParams {
+    pub fn new() -> Self {
+        DynamicsParams {
+            stages: DynamicsKind::ALL.map(StageParams::new),
+        }
+    }
+
+    /// In the order they are applied
+    pub fn stages(&self) -> &[StageParams] {
+        &self.stages
+    }
+
+    pub fn stage(&self, kind: DynamicsKind) -> &StageParams {
+        &self.stages[kind as usize]
+    }
+}
+
+/// One-pole coefficient reaching ~63% of a step in `ms`
+fn time_coefficient(ms: f32, sample_rate: u32) -> f32 {
+    if ms <= 0.0 {
+        0.0
+    } else {
+        (-1000.0 / (ms * sample_rate as f32)).exp()
+    }
+}
+
+/// Audio thread side of the gate, expander or compressor: sidechain filters, a peak level
+/// detector with attack and release, and the static curve applied to the signal
+struct Detector {
+    sample_rate: u32,
+    highpass: Biquad,
+    lowpass: Biquad,
+    highpass_hz: f32,
+    lowpass_hz: f32,
+    envelope: f32,
+    active: bool,
+}
+
+impl Detector {
+    fn new(sample_rate: u32) -> Self {
+        Detector {
+            sample_rate,
+            highpass: Biquad::highpass(sample_rate as f64, SIDECHAIN_HIGHPASS_OFF as f64),
+            lowpass: Biquad::lowpass(sample_rate as f64, SIDECHAIN_LOWPASS_OFF as f64),
+            highpass_hz: 0.0,
+            lowpass_hz: 0.0,
+            envelope: 0.0,
+            active: false,
+        }
+    }
+
+    fn process(&mut self, params: &StageParams, buffer: &mut [f32]) {
+        if !params.enabled() {
+            self.active = false;
+            return;
+        }
+        if !self.active {
+            self.envelope = 0.0;
+            self.active = true;
+        }
+
+        // filters are kept below Nyquist, where they stay stable
+        let highest = 0.45 * self.sample_rate as f32;
+        let highpass_hz = params.sidechain_highpass_hz();
+        let highpass = highpass_hz > SIDECHAIN_HIGHPASS_OFF;
+        if highpass && highpass_hz != self.highpass_hz {
+            self.highpass_hz = highpass_hz;
+            self.highpass.set_coefficients(&Biquad::highpass(
+                self.sample_rate as f64,
+                highpass_hz.min(highest) as f64,
+            ));
+        }
+        let lowpass_hz = params.sidechain_lowpass_hz();
+        let lowpass = lowpass_hz < SIDECHAIN_LOWPASS_OFF && lowpass_hz < highest;
+        if lowpass && lowpass_hz != self.lowpass_hz {
+            self.lowpass_hz = lowpass_hz;
+            self.lowpass
+                .set_coefficients(&Biquad::lowpass(self.sample_rate as f64, lowpass_hz as f64));
+        }
+
+        let attack = time_coefficient(params.attack_ms(), self.sample_rate);
+        let release = time_coefficient(params.release_ms(), self.sample_rate);
+        let kind = params.kind();
+        let mut reduction = 0.0_f32;
+
+        for sample in buffer.iter_mut() {
+            let mut detected = *sample;
+            if highpass {
+                detected = self.highpass.process(detected);
+            }
+            if lowpass {
+                detected = self.lowpass.process(detected);
+            }
+
+            let level = detected.abs();
+            let coefficient = if level > self.envelope {
+                attack
+            } else {
+                release
+            };
+            self.envelope = level + coefficient * (self.envelope - level);
+
+            let gain_db = kind.gain_db(gain_to_db(self.envelope), params);
+            reduction = reduction.max(-gain_db);
+            *sample *= db_to_gain(gain_db);
+        }
+        params.gain_reduction_db.fetch_max(reduction);
+    }
+}
+
+/// Audio thread side of the limiter. The signal is delayed by the lookahead; the gain each
+/// sample needs is held at its minimum over the lookahead and averaged over it, so the gain
+/// has fully come down by the time a peak comes out and never lets it past the ceiling. The
+/// detector sees the signal unfiltered, as anything else would let peaks through.
+struct Limiter {
+    sample_rate: u32,
+    /// Lookahead in samples; the windows are one longer
+    lookahead: usize,
+    delay: Vec<f32>,
+    delay_position: usize,
+    /// Index and required gain of the candidates for the window's minimum, increasing
+    minimum: VecDeque<(usize, f32)>,
+    index: usize,
+    release_gain: f32,
+    averaged: Vec<f32>,
+    average_sum: f64,
+    average_position: usize,
+    active: bool,
+}
+
+impl Limiter {
+    fn new(sample_rate: u32) -> Self {
+        let max_lookahead = (MAX_LOOKAHEAD_MS / 1000.0 * sample_rate as f32).ceil() as usize;
+
+        Limiter {
+            sample_rate,
+            lookahead: 0,
+            delay: vec![0.0; max_lookahead + 1],
+            delay_position: 0,
+            minimum: VecDeque::with_capacity(max_lookahead + 2),
+            index: 0,
+            release_gain: 1.0,
+            averaged: vec![1.0; max_lookahead + 1],
+            average_sum: 0.0,
+            average_position: 0,
+            active: false,
+        }
+    }
+
+    fn reset(&mut self, lookahead: usize) {
+        self.lookahead = lookahead;
+        self.delay.fill(0.0);
+        self.delay_position = 0;
+        self.minimum.clear();
+        self.index = 0;
+        self.release_gain = 1.0;
+        self.averaged.fill(1.0);
+        self.average_sum = (lookahead + 1) as f64;
+        self.average_position = 0;
+    }
+
+    fn process(&mut self, params: &StageParams, buffer: &mut [f32]) {
+        if !params.enabled() {
+            self.active = false;
+            return;
+        }
+        let lookahead = ((params.lookahead_ms() / 1000.0 * self.sample_rate as f32).round()
+            as usize)
+            .clamp(1, self.delay.len() - 1);
+        if !self.active || lookahead != self.lookahead {
+            self.reset(lookahead);
+            self.active = true;
+        }
+
+        let ceiling = db_to_gain(params.threshold_db());
+        let release = time_coefficient(params.release_ms(), self.sample_rate);
+        let window = lookahead + 1;
+        let mut lowest_gain = 1.0_f32;
+
+        for sample in buffer.iter_mut() {
+            let level = sample.abs();
+            let required = if level > ceiling {
+                ceiling / level
+            } else {
+                1.0
+            };
+
+            // sliding minimum over the window
+            while self
+                .minimum
+                .back()
+                .is_some_and(|&(_, gain)| gain >= required)
+            {
+                self.minimum.pop_back();
+            }
+            self.minimum.push_back((self.index, required));
+            while self
+                .minimum
+                .front()
+                .is_some_and(|&(index, _)| index + window <= self.index)
+            {
+                self.minimum.pop_front();
+            }
+            self.index += 1;
+            let held = self.minimum.front().map_or(1.0, |&(_, gain)| gain);
+
+            // instant attack, as the averaging below already smooths it
+            self.release_gain = if held < self.release_gain {
+                held
+            } else {
+                held + release * (self.release_gain - held)
+            };
+
+            self.average_sum += (self.release_gain - self.averaged[self.average_position]) as f64;
+            self.averaged[self.average_position] = self.release_gain;
+            self.average_position = (self.average_position + 1) % window;
+            let gain = (self.average_sum / window as f64) as f32;
+
+            let delayed = self.delay[self.delay_position];
+            self.delay[self.delay_position] = *sample;
+            self.delay_position = (self.delay_position + 1) % lookahead;
+
+            *sample = delayed * gain;
+            lowest_gain = lowest_gain.min(gain);
+        }
+        params.gain_reduction_db.fetch_max(-gain_to_db(lowest_gain));
+    }
+}
+
+/// Audio thread side of the dynamics chain: gate, expander, compressor and limiter in turn,
+/// each skipped while disabled
+pub struct Dynamics {
+    detectors: [Detector; 3],
+    limiter: Limiter,
+}
+
+impl Dynamics {
+    pub fn new(sample_rate: u32) -> Self {
+        Dynamics {
+            detectors: std::array::from_fn(|_| Detector::new(sample_rate)),
+            limiter: Limiter::new(sample_rate),
+        }
+    }
+
+    pub fn process(&mut self, params: &DynamicsParams, buffer: &mut [f32]) {
+        for (detector, stage) in self.detectors.iter_mut().zip(params.stages()) {
+            detector.process(stage, buffer);
+        }
+        self.limiter
+            .process(params.stage(DynamicsKind::Limiter), buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn enabled(params: &DynamicsParams, kind: DynamicsKind) -> &StageParams {
+        let stage = params.stage(kind);
+        stage.set_enabled(true);
+        stage
+    }
+
+    #[test]
+    fn static_curves() {
+        // threshold -18 dB, ratio 4, knee 6 dB
+        let compressor = StageParams::new(DynamicsKind::Compressor);
+        let gain = |level| DynamicsKind::Compressor.gain_db(level, &compressor);
+        assert_eq!(gain(-30.0), 0.0);
+        assert_eq!(gain(-21.0), 0.0);
+        assert!((gain(-18.0) + 0.75 * 6.0 / 8.0).abs() < 1e-5);
+        assert!((gain(-6.0) + 9.0).abs() < 1e-5);
+
+        // threshold -40 dB, ratio 2, range 40 dB
+        let expander = StageParams::new(DynamicsKind::Expander);
+        let gain = |level| DynamicsKind::Expander.gain_db(level, &expander);
+        assert_eq!(gain(-30.0), 0.0);
+        assert!((gain(-50.0) + 10.0).abs() < 1e-5);
+        assert_eq!(gain(-120.0), -40.0);
+
+        // threshold -50 dB, range 60 dB
+        let gate = StageParams::new(DynamicsKind::Gate);
+        let gain = |level| DynamicsKind::Gate.gain_db(level, &gate);
+        assert_eq!(gain(-40.0), 0.0);
+        assert!((gain(-50.0) + 30.0).abs() < 1e-5);
+        assert_eq!(gain(-60.0), -60.0);
+    }
+
+    #[test]
+    fn compressor_settles_on_its_curve() {
+        let params = DynamicsParams::new();
+        let stage = enabled(&params, DynamicsKind::Compressor);
+        let mut dynamics = Dynamics::new(SAMPLE_RATE);
+        let mut buffer = vec![0.5; SAMPLE_RATE as usize];
+        dynamics.process(&params, &mut buffer);
+
+        // about 12 dB over the threshold at a ratio of 4
+        let reduction = 0.75 * (gain_to_db(0.5) + 18.0);
+        let expected = 0.5 * db_to_gain(-reduction);
+        assert!((buffer[buffer.len() - 1] - expected).abs() < 1e-5);
+        assert!((stage.read_gain_reduction_db() - reduction).abs() < 1e-3);
+        assert_eq!(stage.read_gain_reduction_db(), 0.0);
+    }
+
+    #[test]
+    fn sidechain_filter_ignores_rumble() {
+        let params = DynamicsParams::new();
+        let stage = enabled(&params, DynamicsKind::Compressor);
+        stage.set_sidechain_highpass_hz(1000.0);
+        let mut dynamics = Dynamics::new(SAMPLE_RATE);
+        let mut buffer: Vec<f32> = (0..SAMPLE_RATE)
+            .map(|n| {
+                0.5 * (2.0 * std::f32::consts::PI * 40.0 * n as f32 / SAMPLE_RATE as f32).sin()
+            })
+            .collect();
+        dynamics.process(&params, &mut buffer);
+        assert!(stage.read_gain_reduction_db() < 0.5);
+    }
+
+    #[test]
+    fn disabled_stages_pass_the_signal() {
+        let params = DynamicsParams::new();
+        let mut dynamics = Dynamics::new(SAMPLE_RATE);
+        let input: Vec<f32> = (0..1000).map(|n| (n as f32 * 0.01).sin()).collect();
+        let mut buffer = input.clone();
+        dynamics.process(&params, &mut buffer);
+        assert_eq!(buffer, input);
+    }
+
+    #[test]
+    fn limiter_holds_the_ceiling() {
+        let params = DynamicsParams::new();
+        let stage = enabled(&params, DynamicsKind::Limiter);
+        stage.set_threshold_db(-6.0);
+        let ceiling = db_to_gain(-6.0);
+        let mut dynamics = Dynamics::new(SAMPLE_RATE);
+
+        // a quiet tone with single sample spikes, in blocks of an odd size
+        let input: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|n| {
+                if n % 4801 == 3000 {
+                    1.0
+                } else {
+                    0.3 * (n as f32 * 0.05).sin()
+                }
+            })
+            .collect();
+        let mut output = input.clone();
+        for block in output.chunks_mut(317) {
+            dynamics.process(&params, block);
+        }
+
+        assert!(output.iter().all(|s| s.abs() <= ceiling + 1e-6));
+        assert!((stage.read_gain_reduction_db() - 6.0).abs() < 1e-3);
+        // delayed by the lookahead, and untouched before the first spike
+        let lookahead = (0.005 * SAMPLE_RATE as f32) as usize;
+        assert!(output[..lookahead].iter().all(|&s| s == 0.0));
+        for n in 0..2500 {
+            assert_eq!(output[n + lookahead], input[n]);
+        }
+    }
+}
@@ -1,11 +1,33 @@
 use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{InputCallbackInfo, OutputCallbackInfo, StreamConfig};
 use ringbuf::{Consumer, HeapRb, Producer, SharedRb};
-use rustfft::num_complex::Complex;
-use rustfft::FftPlanner;
+
+use crate::audio_engine::convolution::{ConvolutionParams, ImpulseResponse, Reverb};
+use crate::audio_engine::denoise::{DenoiseParams, SpectralDenoiser};
+use crate::audio_engine::dynamics::{Dynamics, DynamicsParams};
+use crate::audio_engine::meters::{InputMeters, MeterProcessor};
+use crate::audio_engine::monitor::{Monitor, MonitorParams};
+use crate::audio_engine::phase_vocoder::{semitones_to_ratio, PhaseVocoder, PitchShiftParams};
+use crate::audio_engine::player::{Clip, Player, PlayerParams};
+use crate::audio_engine::resampler::{DriftCompensator, Resampler, ResamplerFilterHandover};
+use crate::audio_engine::stats::{IOStats, IOStatsSnapshot};
+use crate::audio_engine::stft::StftStage;
+use crate::audio_engine::tap::AnalysisTap;
+
+/// Delay between input and output in ms
+const LATENCY_MS: f32 = 500.0;
+
+/// Highest sample rate offered for selection
+const MAX_SAMPLE_RATE: u32 = 192000;
+
+/// How often devices are re-enumerated while a port waits for its device to be plugged in
+const DEVICE_SCAN_INTERVAL: Duration = Duration::from_secs(2);
 
 type ConsumerT = Consumer<f32, Arc<SharedRb<f32, Vec<MaybeUninit<f32>>>>>;
 type ProducerT = Producer<f32, Arc<SharedRb<f32, Vec<MaybeUninit<f32>>>>>;
@@ -27,6 +49,9 @@ impl Clone for RingBufferRole {
 struct RingBuffer {
     producer: RingBufferRole,
     consumer: RingBufferRole,
+    /// Sample rate of the data written by the producer, so the consumer can convert it to its
+    /// own rate.
+    sample_rate: Arc<AtomicU32>,
 }
 
 impl RingBuffer {
@@ -37,130 +62,376 @@ impl RingBuffer {
         RingBuffer {
             producer: RingBufferRole::Producer(Arc::new(Mutex::new(producer))),
             consumer: RingBufferRole::Consumer(Arc::new(Mutex::new(consumer))),
+            sample_rate: Arc::new(AtomicU32::new(0)),
         }
     }
 }
 
+/// State shared between the UI thread and the stream callbacks
+struct SharedState {
+    stats: IOStats,
+    /// Run the input through the STFT passes
+    frequency_domain_processing: AtomicBool,
+    denoise: DenoiseParams,
+    pitch_shift: PitchShiftParams,
+    dynamics: DynamicsParams,
+    convolution: ConvolutionParams,
+    player: PlayerParams,
+    resampler_filter: ResamplerFilterHandover,
+    monitor: MonitorParams,
+    meters: InputMeters,
+    analysis_tap: AnalysisTap,
+}
+
+/// Stream parameters chosen by the user
+#[derive(Clone, Copy, PartialEq)]
+pub struct StreamOptions {
+    /// Devices that don't support this rate run at their default rate
+    pub sample_rate: u32,
+    /// Frames per callback
+    pub buffer_size: u32,
+    /// Input channel fed to processing and monitored on both output channels
+    pub input_channel: usize,
+}
+
 enum PortType {
     Input,
     Output,
 }
 
+/// Devices of one direction found by enumerating a host
+struct PortScan {
+    devices: Vec<cpal::Device>,
+    default_name: Option<String>,
+}
+
+/// Result of a background device scan
+struct DeviceScan {
+    input: PortScan,
+    output: PortScan,
+}
+
 /// Supports single enabled device
 struct AudioPort {
     port_type: PortType,
     devices: Vec<cpal::Device>,
     enabled_device_index: Option<usize>,
+    /// Device explicitly chosen by the user. Reconnected to when it reappears after unplugging.
+    preferred_device_name: Option<String>,
     stream: Option<cpal::Stream>,
+    options: StreamOptions,
+    /// Rate the current stream actually runs at
+    sample_rate: Option<u32>,
+    playing: bool,
+    /// Set from the stream error callback when the device is unplugged
+    device_lost: Arc<AtomicBool>,
     buffer: RingBufferRole,
+    buffer_sample_rate: Arc<AtomicU32>,
+    shared: Arc<SharedState>,
 }
 
 impl AudioPort {
     pub fn new(
         host: &cpal::Host,
         buffer: &RingBuffer,
-        sample_rate: u32,
+        shared: &Arc<SharedState>,
+        options: StreamOptions,
         port_type: PortType,
     ) -> Self {
-        let mut devices: Vec<cpal::Device>;
-        let mut enabled_device_index: usize = 0;
-        let mut stream: cpal::Stream;
-        let mut shared_buffer_ptr: RingBufferRole;
-
-        match port_type {
-            PortType::Input => {
-                // two copies, one to store for future stream creation, one to be consumed by stream creation now.
-                shared_buffer_ptr = buffer.producer.clone();
-                let producer = buffer.producer.clone();
-
-                devices = host.input_devices().unwrap().collect();
-
-                // get default device index
-                let default_device = host.default_input_device().unwrap();
-                for (i, d) in devices.iter().enumerate() {
-                    if d.name().unwrap() == default_device.name().unwrap() {
-                        enabled_device_index = i;
-                    }
-                }
-
-                stream = Self::build_stream(&default_device, producer);
-                stream.pause().unwrap();
+        // one copy of the buffer handle is stored for future stream creation, others are
+        // consumed by each stream as it is built.
+        let shared_buffer_ptr = match port_type {
+            PortType::Input => buffer.producer.clone(),
+            PortType::Output => buffer.consumer.clone(),
+        };
+
+        let mut port = AudioPort {
+            port_type,
+            devices: Vec::new(),
+            enabled_device_index: None,
+            preferred_device_name: None,
+            stream: None,
+            options,
+            sample_rate: None,
+            playing: false,
+            device_lost: Arc::new(AtomicBool::new(false)),
+            buffer: shared_buffer_ptr,
+            buffer_sample_rate: buffer.sample_rate.clone(),
+            shared: shared.clone(),
+        };
+
+        let scan = Self::scan(host, &port.port_type);
+        port.devices = scan.devices;
+        if let Some(index) = port.default_device_index(scan.default_name.as_deref()) {
+            if let Err(e) = port.enable_device(index) {
+                eprintln!("Could not open default device: {}", e);
             }
+        }
+        port
+    }
 
-            PortType::Output => {
-                shared_buffer_ptr = buffer.consumer.clone();
-                let consumer = buffer.consumer.clone();
+    /// Enumerate the host's devices. On ALSA this opens every PCM, so devices held by a
+    /// running stream may be missing from the result.
+    fn scan(host: &cpal::Host, port_type: &PortType) -> PortScan {
+        let (devices, default_device) = match port_type {
+            PortType::Input => (
+                host.input_devices().map(|d| d.collect()),
+                host.default_input_device(),
+            ),
+            PortType::Output => (
+                host.output_devices().map(|d| d.collect()),
+                host.default_output_device(),
+            ),
+        };
+        PortScan {
+            devices: devices.unwrap_or_default(),
+            default_name: default_device.and_then(|d| d.name().ok()),
+        }
+    }
 
-                devices = host.output_devices().unwrap().collect();
+    /// Index of the host's default device in the enumerated list. Some hosts (e.g. JACK when no
+    /// server is running) expose no default device, in which case the first device is used.
+    fn default_device_index(&self, default_name: Option<&str>) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|d| d.name().ok().as_deref() == default_name)
+            .or(if self.devices.is_empty() {
+                None
+            } else {
+                Some(0)
+            })
+    }
 
-                let default_device = host.default_output_device().unwrap();
-                for (i, d) in devices.iter().enumerate() {
-                    if d.name().unwrap() == default_device.name().unwrap() {
-                        enabled_device_index = i;
-                    }
-                }
+    fn find_device_index(&self, name: &str) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|d| d.name().is_ok_and(|n| n == name))
+    }
 
-                stream = Self::build_stream(&default_device, consumer);
-                stream.pause().unwrap();
-            }
-        }
+    fn enabled_device_name(&self) -> Option<String> {
+        self.enabled_device_index
+            .and_then(|i| self.devices.get(i))
+            .and_then(|d| d.name().ok())
+    }
 
-        AudioPort {
-            port_type,
-            devices,
-            enabled_device_index: Some(enabled_device_index),
-            stream: Some(stream),
-            buffer: shared_buffer_ptr,
+    fn open_stream(&mut self) {
+        self.playing = true;
+        if let Some(stream) = self.stream.as_ref() {
+            self.apply_play_state(stream);
         }
     }
 
-    fn open_stream(&self) {
-        self.stream
-            .as_ref()
-            .expect("No stream found")
-            .play()
-            .unwrap()
+    fn close_stream(&mut self) {
+        self.playing = false;
+        if let Some(stream) = self.stream.as_ref() {
+            self.apply_play_state(stream);
+        }
     }
 
-    fn close_stream(&self) {
-        self.stream
-            .as_ref()
-            .expect("No stream found")
-            .pause()
-            .unwrap()
+    /// Play or pause the stream. A device that went away is left to the rescan, like a
+    /// stream error.
+    fn apply_play_state(&self, stream: &cpal::Stream) {
+        let result = if self.playing {
+            stream.play().map_err(|e| e.to_string())
+        } else {
+            stream.pause().map_err(|e| e.to_string())
+        };
+        if let Err(e) = result {
+            eprintln!(
+                "Could not {} stream: {}",
+                if self.playing { "play" } else { "pause" },
+                e
+            );
+            self.device_lost.store(true, Ordering::Relaxed);
+        }
     }
 
     fn get_device_names(&self) -> Vec<String> {
-        self.devices.iter().map(|d| d.name().unwrap()).collect()
+        self.devices
+            .iter()
+            .map(|d| {
+                d.name()
+                    .unwrap_or_else(|_| String::from("<unknown device>"))
+            })
+            .collect()
     }
 
     fn get_enabled_device_index(&self) -> usize {
-        match self.enabled_device_index {
-            Some(i) => i,
-            None => 0,
-        }
+        self.enabled_device_index.unwrap_or_default()
     }
 
+    /// Enable the device at index on behalf of the user and remember it as the preferred device.
     fn set_enabled_device_index(&mut self, index: usize) {
-        self.enabled_device_index = Some(index);
+        self.preferred_device_name = self.devices.get(index).and_then(|d| d.name().ok());
+        if let Err(e) = self.enable_device(index) {
+            eprintln!("Could not open device: {}", e);
+        }
+    }
+
+    /// Build a stream for the device at index, keeping the current play/pause state.
+    fn enable_device(&mut self, index: usize) -> Result<(), cpal::BuildStreamError> {
         let device = self
             .devices
             .get(index)
-            .expect("Could not find device to enable");
+            .ok_or(cpal::BuildStreamError::DeviceNotAvailable)?;
+
+        // drop the old stream first, some backends only allow one open stream per device
+        self.stream = None;
+        self.enabled_device_index = Some(index);
+        self.device_lost.store(false, Ordering::Relaxed);
+
+        let sample_rate =
+            Self::choose_sample_rate(device, &self.port_type, self.options.sample_rate);
+        self.sample_rate = Some(sample_rate);
 
-        self.stream = Some(Self::build_stream(device, self.buffer.clone()));
+        let stream = Self::build_stream(
+            device,
+            sample_rate,
+            self.options,
+            self.buffer.clone(),
+            self.buffer_sample_rate.clone(),
+            self.shared.clone(),
+            self.device_lost.clone(),
+        )?;
+        self.apply_play_state(&stream);
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    /// Name of the device the user chose, falling back to the enabled one
+    fn get_selected_device_name(&self) -> Option<String> {
+        self.preferred_device_name
+            .clone()
+            .or_else(|| self.enabled_device_name())
+    }
+
+    fn select_device_by_name(&mut self, name: &str) {
+        match self.find_device_index(name) {
+            Some(index) => self.set_enabled_device_index(index),
+            None => self.preferred_device_name = Some(name.to_string()),
+        }
+    }
+
+    fn set_options(&mut self, options: StreamOptions) {
+        if options == self.options {
+            return;
+        }
+        self.options = options;
+        if let Some(index) = self.enabled_device_index {
+            if let Err(e) = self.enable_device(index) {
+                eprintln!("Could not reopen device: {}", e);
+            }
+        }
+    }
+
+    /// Whether the port has no running stream or runs another device than the user chose, so
+    /// it should keep scanning for its device.
+    fn is_waiting_for_device(&self) -> bool {
+        self.stream.is_none()
+            || self
+                .preferred_device_name
+                .as_ref()
+                .is_some_and(|name| self.enabled_device_name().as_ref() != Some(name))
+    }
+
+    /// Take over the devices of a scan. Falls back to the default device if the enabled one
+    /// was lost and switches back to the preferred device once it is available again.
+    fn refresh_devices(&mut self, scan: PortScan) {
+        let enabled_device = self
+            .enabled_device_index
+            .and_then(|i| self.devices.get(i))
+            .cloned();
+        let enabled_name = self.enabled_device_name();
+        let lost = self.device_lost.load(Ordering::Relaxed) || self.stream.is_none();
+        self.devices = scan.devices;
+
+        let mut enabled_index = enabled_name
+            .as_deref()
+            .and_then(|n| self.find_device_index(n));
+        // a device held by our own stream can be missing from the scan, it is only given up
+        // once the stream reports it lost
+        if enabled_index.is_none() && !lost {
+            if let Some(device) = enabled_device {
+                self.devices.push(device);
+                enabled_index = Some(self.devices.len() - 1);
+            }
+        }
+        let preferred_index = self
+            .preferred_device_name
+            .as_deref()
+            .and_then(|n| self.find_device_index(n));
+
+        let target = match (preferred_index, enabled_index) {
+            (Some(p), Some(e)) if p == e && !lost => None,
+            (Some(p), _) => Some(p),
+            (None, Some(_)) if !lost => None,
+            (None, _) => self.default_device_index(scan.default_name.as_deref()),
+        };
+
+        match target {
+            Some(index) => {
+                if let Err(e) = self.enable_device(index) {
+                    eprintln!("Could not reconnect device: {}", e);
+                    self.stream = None;
+                }
+            }
+            None => self.enabled_device_index = enabled_index,
+        }
+
+        if self.devices.is_empty() {
+            self.stream = None;
+            self.enabled_device_index = None;
+        }
     }
 
-    fn build_stream(device: &cpal::Device, shared_buffer_ptr: RingBufferRole) -> cpal::Stream {
-        let sample_rate = 44100;
-        let buffer_size = 512;
+    /// Use the requested rate if the device supports it, otherwise the device's default rate.
+    fn choose_sample_rate(device: &cpal::Device, port_type: &PortType, requested: u32) -> u32 {
+        let requested = cpal::SampleRate(requested);
+        let supported = match port_type {
+            PortType::Input => device
+                .supported_input_configs()
+                .map(|configs| configs.collect::<Vec<_>>()),
+            PortType::Output => device
+                .supported_output_configs()
+                .map(|configs| configs.collect::<Vec<_>>()),
+        };
+        let supports_requested = supported.is_ok_and(|configs| {
+            configs.iter().any(|c| {
+                c.sample_format() == cpal::SampleFormat::F32
+                    && c.min_sample_rate() <= requested
+                    && requested <= c.max_sample_rate()
+            })
+        });
+        if supports_requested {
+            return requested.0;
+        }
+
+        let default_config = match port_type {
+            PortType::Input => device.default_input_config(),
+            PortType::Output => device.default_output_config(),
+        };
+        default_config.map_or(requested.0, |c| c.sample_rate().0)
+    }
+
+    fn build_stream(
+        device: &cpal::Device,
+        sample_rate: u32,
+        options: StreamOptions,
+        shared_buffer_ptr: RingBufferRole,
+        buffer_sample_rate: Arc<AtomicU32>,
+        shared: Arc<SharedState>,
+        device_lost: Arc<AtomicBool>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError> {
+        let buffer_size = options.buffer_size;
         let num_channels = 2;
-        let enabled_channel: usize = 0;
-        assert!(enabled_channel < num_channels as usize);
+        let enabled_channel = options.input_channel.min(num_channels as usize - 1);
 
-        let latency = 500.0; // delay in ms
-        let latency_frames = (latency / 1000.0) * sample_rate as f32;
-        let latency_samples = latency_frames as usize * num_channels as usize;
+        // An unplugged device is reported here; it is picked up by `refresh_devices` rather than
+        // handled on the audio thread.
+        let process_error = move |e: cpal::StreamError| match e {
+            cpal::StreamError::DeviceNotAvailable => device_lost.store(true, Ordering::Relaxed),
+            cpal::StreamError::BackendSpecific { err } => eprintln!("Stream error: {}", err),
+        };
 
         match shared_buffer_ptr {
             RingBufferRole::Producer(p) => {
@@ -170,70 +441,102 @@ impl AudioPort {
                     buffer_size: cpal::BufferSize::Fixed(buffer_size),
                 };
 
+                // the output side resamples from this rate to its own
+                buffer_sample_rate.store(sample_rate, Ordering::Relaxed);
+                shared.resampler_filter.prepare(sample_rate);
+                shared.analysis_tap.set_sample_rate(sample_rate);
+
+                // the buffer may already hold data when a stream is rebuilt
+                let mut guard = p.lock().unwrap();
+                let latency_samples =
+                    latency_samples(sample_rate, num_channels as usize).min(guard.capacity() / 2);
                 for _ in 0..latency_samples {
-                    p.lock().unwrap().push(0.0).unwrap();
+                    if guard.push(0.0).is_err() {
+                        break;
+                    }
                 }
-
-                let mut fft_planner = FftPlanner::new();
+                drop(guard);
+
+                let mut denoise_stage = StftStage::new(sample_rate, buffer_size as usize);
+                let mut denoiser =
+                    SpectralDenoiser::new(&shared.denoise, denoise_stage.size() / 2 + 1);
+                let mut pitch_stage = StftStage::new(sample_rate, buffer_size as usize);
+                let mut vocoder = PhaseVocoder::new(pitch_stage.size());
+                let mut dynamics = Dynamics::new(sample_rate);
+                let mut monitor = Monitor::new(&shared.monitor, sample_rate);
+                let mut meter_processor = MeterProcessor::new(sample_rate);
+                // one channel of a callback, reserved so the callbacks don't allocate
+                let mut dry_buffer = Vec::<f32>::with_capacity(buffer_size as usize);
+                let mut out_buffer = Vec::<f32>::with_capacity(buffer_size as usize);
+                let mut frequency_domain = false;
 
                 let process_in_data = move |data: &[f32], _: &InputCallbackInfo| {
-                    let mut output_fell_behind = false;
-                    let fft_size = data.len() / num_channels as usize;
-                    println!("data at 5 {}", data[5]);
-
-                    // forward fft
-                    let forward_fft =
-                        fft_planner.plan_fft(fft_size, rustfft::FftDirection::Forward);
-
-                    let mut fft_buffer = data
-                        .iter()
-                        .enumerate()
-                        .filter(|&(i, _)| i % num_channels as usize == enabled_channel)
-                        .map(|(_, &d)| Complex::new(d, 0.0))
-                        .collect::<Vec<Complex<f32>>>();
-
-                    forward_fft.process(&mut fft_buffer);
-
-                    // apply frequency domain processing
-
-                    // inverse fft
-                    let inverse_fft =
-                        fft_planner.plan_fft(fft_size, rustfft::FftDirection::Inverse);
-
-                    inverse_fft.process(&mut fft_buffer);
-                    for n in fft_buffer.iter_mut() {
-                        *n *= 1.0 / ((fft_size as f32).sqrt() * (fft_size as f32).sqrt());
+                    let callback_start = Instant::now();
+                    let callback_frames = data.len() / num_channels as usize;
+
+                    meter_processor.process(&shared.meters, data, num_channels as usize);
+
+                    dry_buffer.clear();
+                    dry_buffer.extend(
+                        data.iter()
+                            .skip(enabled_channel)
+                            .step_by(num_channels as usize),
+                    );
+                    monitor.process_input(&shared.monitor, &mut dry_buffer);
+
+                    out_buffer.clear();
+                    out_buffer.extend_from_slice(&dry_buffer);
+
+                    let enabled = shared.frequency_domain_processing.load(Ordering::Relaxed);
+                    if enabled && !frequency_domain {
+                        // start from silence rather than frames left from before
+                        denoise_stage.reset();
+                        pitch_stage.reset();
+                    }
+                    frequency_domain = enabled;
+
+                    if frequency_domain {
+                        // overlapping STFT frames, each stage delaying the signal by one frame
+                        // while it is in use. The analysis views see the denoised input so the
+                        // noise floor doesn't bias the partials and decay fits.
+                        let denoise = shared.denoise.enabled() || shared.denoise.is_learning();
+                        denoise_stage.process(denoise, &mut out_buffer, |spectrum| {
+                            denoiser.process(&shared.denoise, spectrum)
+                        });
+                        shared.analysis_tap.push(&out_buffer);
+
+                        let semitones = shared.pitch_shift.semitones();
+                        let pitch = semitones_to_ratio(semitones);
+                        let hop = pitch_stage.hop();
+                        pitch_stage.process(semitones != 0.0, &mut out_buffer, |spectrum| {
+                            vocoder.process(spectrum, hop, hop, pitch)
+                        });
+                    } else {
+                        shared.analysis_tap.push(&dry_buffer);
                     }
-
-                    println!("fft_buffer at 5 {}", fft_buffer[5]);
 
                     // apply time domain processing
+                    dynamics.process(&shared.dynamics, &mut out_buffer);
+
+                    monitor.process_output(&shared.monitor, &dry_buffer, &mut out_buffer);
 
                     // push data to shared buffer (duplicated for L/R output)
 
                     let mut guard = p.lock().expect("Could not aquire lock");
-                    for _ in 0..2 {
-                        let num_pushed =
-                            guard.push_iter(&mut fft_buffer.iter().map(|&d| d.re).into_iter());
-                        if num_pushed != fft_buffer.len() {
-                            output_fell_behind = true;
-                        }
+                    let mut frames = out_buffer.iter().flat_map(|&d| [d, d]);
+                    let num_pushed = guard.push_iter(&mut frames);
+                    if num_pushed != out_buffer.len() * 2 {
+                        // output stream fell behind; try increasing latency
+                        shared.stats.record_overrun();
                     }
+                    drop(guard);
 
-                    if output_fell_behind {
-                        eprintln!("Output stream fell behind; try increasing latency")
-                    }
+                    let period =
+                        Duration::from_secs_f64(callback_frames as f64 / sample_rate as f64);
+                    shared.stats.input.record(callback_start, period);
                 };
 
-                let stream = device
-                    .build_input_stream(
-                        &config,
-                        process_in_data,
-                        |_e| panic!("Could not init input stream"),
-                        None,
-                    )
-                    .expect("Could not build input stream");
-                stream
+                device.build_input_stream(&config, process_in_data, process_error, None)
             }
             RingBufferRole::Consumer(c) => {
                 let config = StreamConfig {
@@ -242,70 +545,239 @@ impl AudioPort {
                     buffer_size: cpal::BufferSize::Fixed(buffer_size),
                 };
 
+                // Input and output devices rarely share a clock. Convert from the input rate and
+                // keep the ring buffer fill level steady to absorb clock drift.
+                let input_rate = buffer_sample_rate.load(Ordering::Relaxed).max(1);
+                shared.resampler_filter.reset(sample_rate);
+                let mut resampler = Resampler::new(num_channels as usize, input_rate, sample_rate);
+                // built once the input stream has set its rate
+                let mut drift_compensator: Option<DriftCompensator> = None;
+                let mut player = Player::new(&shared.player, sample_rate);
+                let mut reverb = Reverb::new(&shared.convolution, sample_rate);
+
                 let process_out_data = move |data: &mut [f32], _: &OutputCallbackInfo| {
-                    let mut input_fell_behind = false;
+                    let callback_start = Instant::now();
                     let mut guard = c.lock().expect("Could not aquire lock");
-                    for d in data {
-                        *d = match guard.pop() {
-                            Some(s) => s,
-                            None => {
-                                input_fell_behind = true;
-                                0.0
-                            }
-                        };
+                    shared
+                        .stats
+                        .record_buffer_fill(guard.len(), guard.capacity());
+
+                    let frames = data.len() / num_channels as usize;
+                    let period = Duration::from_secs_f64(frames as f64 / sample_rate as f64);
+
+                    let input_rate = buffer_sample_rate.load(Ordering::Relaxed);
+                    let input_rebuilt = shared.resampler_filter.update(&mut resampler);
+                    if input_rate > 0 {
+                        let target_fill = latency_samples(input_rate, num_channels as usize)
+                            .min(guard.capacity() / 2);
+                        match drift_compensator.as_mut() {
+                            Some(drift) if input_rebuilt => drift.set_target_fill(target_fill),
+                            Some(_) => {}
+                            None => drift_compensator = Some(DriftCompensator::new(target_fill)),
+                        }
+                    }
+                    if let Some(drift) = drift_compensator.as_mut() {
+                        let correction = drift.update(guard.len(), period.as_secs_f64());
+                        resampler.set_correction(correction);
                     }
+
+                    let input_fell_behind = !resampler.process(data, || guard.pop());
                     if input_fell_behind {
-                        eprintln!("Input stream fell behind; try increasing latency")
+                        // input stream fell behind; try increasing latency
+                        shared.stats.record_underrun();
                     }
+                    drop(guard);
+                    player.process(&shared.player, data, num_channels as usize);
+                    // after the player, so clips such as a resynthesis sound in the space too
+                    reverb.process(&shared.convolution, data, num_channels as usize);
+
+                    shared.stats.output.record(callback_start, period);
                 };
-                let stream = device
-                    .build_output_stream(
-                        &config,
-                        process_out_data,
-                        |_e| panic!("Could not init output stream"),
-                        None,
-                    )
-                    .expect("Could not build output stream");
-                stream
+                device.build_output_stream(&config, process_out_data, process_error, None)
             }
         }
     }
 }
 
+/// Number of interleaved samples that make up the monitoring latency at the given rate
+fn latency_samples(sample_rate: u32, num_channels: usize) -> usize {
+    let latency_frames = (LATENCY_MS / 1000.0) * sample_rate as f32;
+    latency_frames as usize * num_channels
+}
+
 pub struct IOManager {
+    host_ids: Vec<cpal::HostId>,
     host: cpal::Host,
     output_buffer: RingBuffer,
     output_port: AudioPort,
     input_port: AudioPort,
-    pub sample_rate: u32,
+    shared: Arc<SharedState>,
+    options: StreamOptions,
+    /// Enumeration running off the UI thread, as it can take a while on ALSA
+    device_scan: Option<JoinHandle<Option<DeviceScan>>>,
+    last_device_scan: Instant,
 }
 
 impl IOManager {
     pub fn new() -> Self {
+        let host_ids = cpal::available_hosts();
         let host = cpal::default_host();
-
-        let sample_rate = 44100;
-        let num_channels = 2;
-
-        let latency = 500.0; // delay in ms
-        let latency_frames = (latency / 1000.0) * sample_rate as f32;
-        let latency_samples = latency_frames as usize * num_channels as usize;
-
-        // ring buffer space is twice the necessary size for the stream to make room for latency
-        let output_buffer = RingBuffer::new(latency_samples * 2);
-        let output_port = AudioPort::new(&host, &output_buffer, 44100, PortType::Output);
-        let input_port = AudioPort::new(&host, &output_buffer, 44100, PortType::Input);
-        let sample_rate = 44100;
+        let options = StreamOptions {
+            sample_rate: 44100,
+            buffer_size: 512,
+            input_channel: 0,
+        };
+        let shared = Arc::new(SharedState {
+            stats: IOStats::new(),
+            frequency_domain_processing: AtomicBool::new(true),
+            denoise: DenoiseParams::new(),
+            pitch_shift: PitchShiftParams::new(),
+            dynamics: DynamicsParams::new(),
+            convolution: ConvolutionParams::new(),
+            player: PlayerParams::new(),
+            resampler_filter: ResamplerFilterHandover::new(),
+            monitor: MonitorParams::new(),
+            meters: InputMeters::new(),
+            analysis_tap: AnalysisTap::new(MAX_SAMPLE_RATE as usize),
+        });
+        let (output_buffer, output_port, input_port) = Self::build_ports(&host, &shared, options);
 
         IOManager {
+            host_ids,
             host,
             output_buffer,
             output_port,
             input_port,
-            sample_rate,
+            shared,
+            options,
+            device_scan: None,
+            last_device_scan: Instant::now(),
         }
     }
 
+    /// Create the shared ring buffer and both ports on the default devices of `host`.
+    fn build_ports(
+        host: &cpal::Host,
+        shared: &Arc<SharedState>,
+        options: StreamOptions,
+    ) -> (RingBuffer, AudioPort, AudioPort) {
+        let num_channels = 2;
+
+        // ring buffer space is twice the necessary size for the stream to make room for latency.
+        // Sized for the highest rate so devices can change rate without reallocating.
+        let output_buffer = RingBuffer::new(latency_samples(MAX_SAMPLE_RATE, num_channels) * 2);
+        let output_port = AudioPort::new(host, &output_buffer, shared, options, PortType::Output);
+        let input_port = AudioPort::new(host, &output_buffer, shared, options, PortType::Input);
+
+        (output_buffer, output_port, input_port)
+    }
+
+    /// Get a list of audio backends compiled into this build (ALSA, JACK, ...) for display
+    pub fn get_host_names(&self) -> Vec<String> {
+        self.host_ids
+            .iter()
+            .map(|id| id.name().to_string())
+            .collect()
+    }
+
+    pub fn get_current_host_index(&self) -> usize {
+        self.host_ids
+            .iter()
+            .position(|&id| id == self.host.id())
+            .unwrap_or(0)
+    }
+
+    pub fn get_current_host_name(&self) -> String {
+        self.host.id().name().to_string()
+    }
+
+    /// Switch to the host found at index. Device lists, ports and streams are rebuilt on the
+    /// new host's default devices, playing if the old ones were.
+    pub fn set_host(&mut self, index: usize) -> Result<(), cpal::HostUnavailable> {
+        let host_id = *self.host_ids.get(index).ok_or(cpal::HostUnavailable)?;
+        if host_id == self.host.id() {
+            return Ok(());
+        }
+
+        let host = cpal::host_from_id(host_id)?;
+        let input_playing = self.input_port.playing;
+        let output_playing = self.output_port.playing;
+
+        // release the old streams before opening devices on the new host, and leave a scan of
+        // the old host unapplied
+        self.device_scan = None;
+        self.input_port.close_stream();
+        self.output_port.close_stream();
+        self.input_port.stream = None;
+        self.output_port.stream = None;
+
+        let (output_buffer, output_port, input_port) =
+            Self::build_ports(&host, &self.shared, self.options);
+        self.host = host;
+        self.output_buffer = output_buffer;
+        self.output_port = output_port;
+        self.input_port = input_port;
+        if output_playing {
+            self.output_port.open_stream();
+        }
+        if input_playing {
+            self.input_port.open_stream();
+        }
+        Ok(())
+    }
+
+    /// Apply a finished device scan, and start one if a stream reported its device unplugged
+    /// or, every scan interval, while a port waits for its device. Running streams are not
+    /// rescanned otherwise, as enumeration can't see the devices they hold. Cheap enough to
+    /// call once per UI frame.
+    pub fn poll_devices(&mut self) {
+        if self
+            .device_scan
+            .as_ref()
+            .is_some_and(|scan| scan.is_finished())
+        {
+            let scan = self.device_scan.take().and_then(|scan| scan.join().ok());
+            if let Some(scan) = scan.flatten() {
+                self.input_port.refresh_devices(scan.input);
+                self.output_port.refresh_devices(scan.output);
+            }
+        }
+
+        let lost = self.input_port.device_lost.load(Ordering::Relaxed)
+            || self.output_port.device_lost.load(Ordering::Relaxed);
+        let waiting =
+            self.input_port.is_waiting_for_device() || self.output_port.is_waiting_for_device();
+        if lost || (waiting && self.last_device_scan.elapsed() >= DEVICE_SCAN_INTERVAL) {
+            self.refresh_devices();
+        }
+    }
+
+    /// Re-enumerate devices in the background, recovering ports whose device was unplugged.
+    /// The device lists update on a later poll.
+    pub fn refresh_devices(&mut self) {
+        if self.device_scan.is_some() {
+            return;
+        }
+        self.last_device_scan = Instant::now();
+        let host_id = self.host.id();
+        self.device_scan = Some(thread::spawn(move || {
+            let host = cpal::host_from_id(host_id).ok()?;
+            Some(DeviceScan {
+                input: AudioPort::scan(&host, &PortType::Input),
+                output: AudioPort::scan(&host, &PortType::Output),
+            })
+        }));
+    }
+
+    /// Dropout counters, buffer fill level and callback timing since the last reset
+    pub fn get_stats(&self) -> IOStatsSnapshot {
+        self.shared.stats.snapshot()
+    }
+
+    pub fn reset_stats(&self) {
+        self.shared.stats.reset();
+    }
+
     /// Get a list of input devices for display
     pub fn get_input_device_names(&self) -> Vec<String> {
         self.input_port.get_device_names()
@@ -324,9 +796,124 @@ impl IOManager {
         self.output_port.get_enabled_device_index()
     }
 
-    // TODO: streams should be rebuilt. this will update sample rate properly
+    /// Name of the input device the user chose, even while it is unplugged
+    pub fn get_input_device_name(&self) -> Option<String> {
+        self.input_port.get_selected_device_name()
+    }
+
+    /// Name of the output device the user chose, even while it is unplugged
+    pub fn get_output_device_name(&self) -> Option<String> {
+        self.output_port.get_selected_device_name()
+    }
+
+    pub fn get_stream_options(&self) -> StreamOptions {
+        self.options
+    }
+
+    /// Rebuild both streams with new options. Devices that don't support the sample rate keep
+    /// running at their default rate and are resampled.
+    pub fn set_stream_options(&mut self, options: StreamOptions) {
+        self.options = options;
+        self.input_port.set_options(options);
+        self.output_port.set_options(options);
+    }
+
     pub fn set_sample_rate(&mut self, new_sample_rate: u32) {
-        self.sample_rate = new_sample_rate;
+        self.set_stream_options(StreamOptions {
+            sample_rate: new_sample_rate,
+            ..self.options
+        });
+    }
+
+    pub fn set_buffer_size(&mut self, buffer_size: u32) {
+        self.set_stream_options(StreamOptions {
+            buffer_size,
+            ..self.options
+        });
+    }
+
+    pub fn set_input_channel(&mut self, input_channel: usize) {
+        self.set_stream_options(StreamOptions {
+            input_channel,
+            ..self.options
+        });
+    }
+
+    /// Rate the input stream actually runs at
+    pub fn get_input_sample_rate(&self) -> Option<u32> {
+        self.input_port.sample_rate
+    }
+
+    /// Rate the output stream actually runs at
+    pub fn get_output_sample_rate(&self) -> Option<u32> {
+        self.output_port.sample_rate
+    }
+
+    pub fn get_frequency_domain_processing(&self) -> bool {
+        self.shared
+            .frequency_domain_processing
+            .load(Ordering::Relaxed)
+    }
+
+    pub fn set_frequency_domain_processing(&self, enabled: bool) {
+        self.shared
+            .frequency_domain_processing
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// Noise reduction of the frequency domain pass and its noise profile
+    pub fn denoise(&self) -> &DenoiseParams {
+        &self.shared.denoise
+    }
+
+    /// Live pitch shift of the frequency domain pass
+    pub fn pitch_shift(&self) -> &PitchShiftParams {
+        &self.shared.pitch_shift
+    }
+
+    /// Gate, expander, compressor and limiter of the time domain chain
+    pub fn dynamics(&self) -> &DynamicsParams {
+        &self.shared.dynamics
+    }
+
+    /// Wet/dry and pre-delay of the convolution reverb
+    pub fn convolution(&self) -> &ConvolutionParams {
+        &self.shared.convolution
+    }
+
+    /// Replace the reverb's impulse response, fitted to the running output stream if any
+    pub fn set_impulse_response(&self, impulse_response: Option<ImpulseResponse>) {
+        self.shared
+            .convolution
+            .set_impulse_response(impulse_response, self.get_output_sample_rate());
+    }
+
+    /// Playback of clips such as a resynthesis through the output
+    pub fn player(&self) -> &PlayerParams {
+        &self.shared.player
+    }
+
+    /// Replace the clip to play, fitted to the running output stream if any
+    pub fn set_clip(&self, clip: Option<Clip>) {
+        self.shared
+            .player
+            .set_clip(clip, self.get_output_sample_rate());
+    }
+
+    /// Gain, mute and dry/processed mix of the monitored signal
+    pub fn monitor(&self) -> &MonitorParams {
+        &self.shared.monitor
+    }
+
+    /// Levels of the captured input, before any gain or processing
+    pub fn meters(&self) -> &InputMeters {
+        &self.shared.meters
+    }
+
+    /// Input channel after input gain, for the analysis views. Denoised, and one STFT frame
+    /// late, while frequency domain processing is on and the denoiser is enabled or learning.
+    pub fn analysis_tap(&self) -> &AnalysisTap {
+        &self.shared.analysis_tap
     }
 
     /// Builds a stream for the divice found at index.
@@ -338,19 +925,30 @@ impl IOManager {
         self.input_port.set_enabled_device_index(index);
     }
 
-    pub fn play_output(&self) {
+    /// Select an output device by name. If it is not connected the current device is kept and
+    /// the named one is switched to once it appears.
+    pub fn select_output_device(&mut self, name: &str) {
+        self.output_port.select_device_by_name(name);
+    }
+
+    /// Select an input device by name, see `select_output_device`.
+    pub fn select_input_device(&mut self, name: &str) {
+        self.input_port.select_device_by_name(name);
+    }
+
+    pub fn play_output(&mut self) {
         self.output_port.open_stream();
     }
 
-    pub fn pause_output(&self) {
+    pub fn pause_output(&mut self) {
         self.output_port.close_stream();
     }
 
-    pub fn play_input(&self) {
+    pub fn play_input(&mut self) {
         self.input_port.open_stream();
     }
 
-    pub fn pause_input(&self) {
+    pub fn pause_input(&mut self) {
         self.input_port.close_stream();
     }
 }
@@ -0,0 +1,286 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::audio_engine::biquad::Biquad;
+use crate::audio_engine::params::{gain_to_db, AtomicF32};
+use crate::audio_engine::resampler::{bessel_i0, sinc};
+
+/// Number of input channels metered
+pub const NUM_METER_CHANNELS: usize = 2;
+
+/// Sample peaks at or above this count as clipping
+const CLIP_LEVEL: f32 = 0.999;
+/// Time constant of the RMS level
+const RMS_TIME: f32 = 0.3;
+/// Loudness is integrated in 100 ms blocks (EBU R128 / ITU-R BS.1770)
+const BLOCKS_PER_SECOND: u32 = 10;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Oversampling factor and interpolation filter length per phase for true peak detection
+const TRUE_PEAK_PHASES: usize = 4;
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// Levels of one channel, written by the audio thread
+struct ChannelMeter {
+    /// Highest sample since the UI last read it
+    peak: AtomicF32,
+    /// Highest interpolated sample since the UI last read it
+    true_peak: AtomicF32,
+    rms: AtomicF32,
+    /// K-weighted mean square over the momentary and short-term windows
+    momentary: AtomicF32,
+    short_term: AtomicF32,
+    clipped: AtomicBool,
+}
+
+impl ChannelMeter {
+    fn new() -> Self {
+        ChannelMeter {
+            peak: AtomicF32::new(0.0),
+            true_peak: AtomicF32::new(0.0),
+            rms: AtomicF32::new(0.0),
+            momentary: AtomicF32::new(0.0),
+            short_term: AtomicF32::new(0.0),
+            clipped: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Input level meters shared between the audio thread and the UI
+pub struct InputMeters {
+    channels: [ChannelMeter; NUM_METER_CHANNELS],
+}
+
+/// Levels of one channel in dB. Peaks cover the time since the previous reading.
+#[derive(Clone, Copy)]
+pub struct ChannelReading {
+    pub peak_db: f32,
+    pub true_peak_db: f32,
+    pub rms_db: f32,
+    pub momentary_lufs: f32,
+    pub short_term_lufs: f32,
+    pub clipped: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct MeterReadings {
+    pub channels: [ChannelReading; NUM_METER_CHANNELS],
+    /// Loudness of all channels combined
+    pub momentary_lufs: f32,
+    pub short_term_lufs: f32,
+}
+
+impl InputMeters {
+    pub fn new() -> Self {
+        InputMeters {
+            channels: [ChannelMeter::new(), ChannelMeter::new()],
+        }
+    }
+
+    /// Read current levels, restarting peak detection.
+    pub fn read(&self) -> MeterReadings {
+        let mut momentary = 0.0;
+        let mut short_term = 0.0;
+
+        let channels = std::array::from_fn(|i| {
+            let meter = &self.channels[i];
+            momentary += meter.momentary.load();
+            short_term += meter.short_term.load();
+
+            ChannelReading {
+                peak_db: gain_to_db(meter.peak.swap(0.0)),
+                true_peak_db: gain_to_db(meter.true_peak.swap(0.0)),
+                rms_db: gain_to_db(meter.rms.load()),
+                momentary_lufs: mean_square_to_lufs(meter.momentary.load()),
+                short_term_lufs: mean_square_to_lufs(meter.short_term.load()),
+                clipped: meter.clipped.load(Ordering::Relaxed),
+            }
+        });
+
+        MeterReadings {
+            channels,
+            momentary_lufs: mean_square_to_lufs(momentary),
+            short_term_lufs: mean_square_to_lufs(short_term),
+        }
+    }
+
+    pub fn reset_clip(&self) {
+        for meter in self.channels.iter() {
+            meter.clipped.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+fn mean_square_to_lufs(mean_square: f32) -> f32 {
+    -0.691 + 10.0 * mean_square.max(1e-20).log10()
+}
+
+/// Per channel state of the meter processor
+struct ChannelState {
+    k_shelf: Biquad,
+    k_highpass: Biquad,
+    rms_mean_square: f32,
+    /// Sum of K-weighted squares in the current 100 ms block
+    block_sum: f64,
+    /// Sums of the most recent blocks, indexed circularly
+    block_sums: [f64; SHORT_TERM_BLOCKS],
+    /// Last input samples for the true peak interpolator, oldest first
+    history: [f32; TRUE_PEAK_TAPS],
+}
+
+/// Audio thread side of the input meters
+pub struct MeterProcessor {
+    channels: Vec<ChannelState>,
+    block_len: usize,
+    block_position: usize,
+    block_index: usize,
+    rms_coefficient: f32,
+    /// `TRUE_PEAK_PHASES * TRUE_PEAK_TAPS` interpolation coefficients
+    true_peak_filter: Vec<f32>,
+}
+
+impl MeterProcessor {
+    pub fn new(sample_rate: u32) -> Self {
+        let (k_shelf, k_highpass) = k_weighting(sample_rate as f64);
+        let channels = (0..NUM_METER_CHANNELS)
+            .map(|_| ChannelState {
+                k_shelf: k_shelf.clone(),
+                k_highpass: k_highpass.clone(),
+                rms_mean_square: 0.0,
+                block_sum: 0.0,
+                block_sums: [0.0; SHORT_TERM_BLOCKS],
+                history: [0.0; TRUE_PEAK_TAPS],
+            })
+            .collect();
+
+        MeterProcessor {
+            channels,
+            block_len: (sample_rate / BLOCKS_PER_SECOND).max(1) as usize,
+            block_position: 0,
+            block_index: 0,
+            rms_coefficient: 1.0 - (-1.0 / (RMS_TIME * sample_rate as f32)).exp(),
+            true_peak_filter: true_peak_filter(),
+        }
+    }
+
+    /// Meter one callback's worth of interleaved input.
+    pub fn process(&mut self, meters: &InputMeters, data: &[f32], num_channels: usize) {
+        let mut peaks = [0.0_f32; NUM_METER_CHANNELS];
+        let mut true_peaks = [0.0_f32; NUM_METER_CHANNELS];
+
+        for frame in data.chunks(num_channels) {
+            for (i, (channel, &sample)) in self.channels.iter_mut().zip(frame.iter()).enumerate() {
+                channel.rms_mean_square +=
+                    self.rms_coefficient * (sample * sample - channel.rms_mean_square);
+
+                let weighted = channel.k_highpass.process(channel.k_shelf.process(sample));
+                channel.block_sum += (weighted * weighted) as f64;
+
+                peaks[i] = peaks[i].max(sample.abs());
+
+                // evaluate the signal between the samples to catch inter-sample peaks
+                channel.history.copy_within(1.., 0);
+                channel.history[TRUE_PEAK_TAPS - 1] = sample;
+                for phase in self.true_peak_filter.chunks(TRUE_PEAK_TAPS) {
+                    let value: f32 = phase
+                        .iter()
+                        .zip(channel.history.iter())
+                        .map(|(c, s)| c * s)
+                        .sum();
+                    true_peaks[i] = true_peaks[i].max(value.abs());
+                }
+            }
+
+            self.block_position += 1;
+            if self.block_position == self.block_len {
+                self.block_position = 0;
+                self.finish_block(meters);
+            }
+        }
+
+        for (i, (channel, meter)) in self.channels.iter().zip(meters.channels.iter()).enumerate() {
+            meter.peak.fetch_max(peaks[i]);
+            meter.true_peak.fetch_max(true_peaks[i]);
+            meter.rms.store(channel.rms_mean_square.sqrt());
+            if peaks[i] >= CLIP_LEVEL || true_peaks[i] > 1.0 {
+                meter.clipped.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn finish_block(&mut self, meters: &InputMeters) {
+        let block_len = self.block_len as f64;
+        self.block_index = (self.block_index + 1) % SHORT_TERM_BLOCKS;
+
+        for (channel, meter) in self.channels.iter_mut().zip(meters.channels.iter()) {
+            channel.block_sums[self.block_index] = channel.block_sum;
+            channel.block_sum = 0.0;
+
+            let mut momentary = 0.0;
+            for i in 0..MOMENTARY_BLOCKS {
+                let index = (self.block_index + SHORT_TERM_BLOCKS - i) % SHORT_TERM_BLOCKS;
+                momentary += channel.block_sums[index];
+            }
+            let short_term: f64 = channel.block_sums.iter().sum();
+
+            meter
+                .momentary
+                .store((momentary / (MOMENTARY_BLOCKS as f64 * block_len)) as f32);
+            meter
+                .short_term
+                .store((short_term / (SHORT_TERM_BLOCKS as f64 * block_len)) as f32);
+        }
+    }
+}
+
+/// The two stages of the BS.1770 K-weighting filter, a high shelf modelling the head and a
+/// high pass, derived for any sample rate.
+fn k_weighting(sample_rate: f64) -> (Biquad, Biquad) {
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+    let vh = 10.0_f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let shelf = Biquad::new(
+        vh + vb * k / q + k * k,
+        2.0 * (k * k - vh),
+        vh - vb * k / q + k * k,
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+    );
+
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+    let highpass = Biquad::new(
+        1.0,
+        -2.0,
+        1.0,
+        1.0 + k / q + k * k,
+        2.0 * (k * k - 1.0),
+        1.0 - k / q + k * k,
+    );
+
+    (shelf, highpass)
+}
+
+/// Windowed-sinc interpolator evaluating the signal at `TRUE_PEAK_PHASES` points between
+/// two samples, for inter-sample peak detection.
+fn true_peak_filter() -> Vec<f32> {
+    let half = (TRUE_PEAK_TAPS / 2) as f64;
+    let beta = 6.0;
+    let window_norm = bessel_i0(beta);
+
+    let mut filter = Vec::with_capacity(TRUE_PEAK_PHASES * TRUE_PEAK_TAPS);
+    for p in 0..TRUE_PEAK_PHASES {
+        let frac = p as f64 / TRUE_PEAK_PHASES as f64;
+        for j in 0..TRUE_PEAK_TAPS {
+            let d = j as f64 + 1.0 - half - frac;
+            let x = d / half;
+            let window = bessel_i0(beta * (1.0 - x * x).max(0.0).sqrt()) / window_norm;
+            filter.push((sinc(d) * window) as f32);
+        }
+    }
+    filter
+}
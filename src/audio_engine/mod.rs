@@ -1 +1,14 @@
+pub mod biquad;
+pub mod convolution;
+pub mod denoise;
+pub mod dynamics;
 pub mod io_manager;
+pub mod meters;
+pub mod monitor;
+pub mod params;
+pub mod phase_vocoder;
+pub mod player;
+pub mod resampler;
+pub mod stats;
+pub mod stft;
+pub mod tap;
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::audio_engine::params::{db_to_gain, AtomicF32, SmoothedValue};
+
+/// Time constant of gain and mix changes
+const SMOOTHING_TIME: f32 = 0.02;
+/// Safety limiter ceiling, -1 dBFS
+const LIMITER_CEILING: f32 = 0.891;
+/// Time for the limiter gain reduction to recover by ~63%
+const LIMITER_RELEASE: f32 = 0.1;
+
+/// Monitoring controls, set from the UI and read once per callback by the audio thread.
+pub struct MonitorParams {
+    input_gain_db: AtomicF32,
+    output_gain_db: AtomicF32,
+    /// 0.0 is the unprocessed input, 1.0 the processed signal
+    mix: AtomicF32,
+    mute: AtomicBool,
+    limiter: AtomicBool,
+}
+
+impl MonitorParams {
+    pub fn new() -> Self {
+        MonitorParams {
+            input_gain_db: AtomicF32::new(0.0),
+            output_gain_db: AtomicF32::new(0.0),
+            mix: AtomicF32::new(1.0),
+            mute: AtomicBool::new(false),
+            limiter: AtomicBool::new(true),
+        }
+    }
+
+    pub fn input_gain_db(&self) -> f32 {
+        self.input_gain_db.load()
+    }
+
+    pub fn set_input_gain_db(&self, db: f32) {
+        self.input_gain_db.store(db);
+    }
+
+    pub fn output_gain_db(&self) -> f32 {
+        self.output_gain_db.load()
+    }
+
+    pub fn set_output_gain_db(&self, db: f32) {
+        self.output_gain_db.store(db);
+    }
+
+    pub fn mix(&self) -> f32 {
+        self.mix.load()
+    }
+
+    pub fn set_mix(&self, mix: f32) {
+        self.mix.store(mix.clamp(0.0, 1.0));
+    }
+
+    pub fn mute(&self) -> bool {
+        self.mute.load(Ordering::Relaxed)
+    }
+
+    pub fn set_mute(&self, mute: bool) {
+        self.mute.store(mute, Ordering::Relaxed);
+    }
+
+    pub fn limiter(&self) -> bool {
+        self.limiter.load(Ordering::Relaxed)
+    }
+
+    pub fn set_limiter(&self, enabled: bool) {
+        self.limiter.store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Audio thread side of the monitoring stage. Applies input gain ahead of processing, then
+/// crossfades between the dry and processed signal, applies output gain, mute and a safety
+/// limiter before the result is sent to the output.
+pub struct Monitor {
+    input_gain: SmoothedValue,
+    output_gain: SmoothedValue,
+    mix: SmoothedValue,
+    limiter_envelope: f32,
+    limiter_release: f32,
+}
+
+impl Monitor {
+    pub fn new(params: &MonitorParams, sample_rate: u32) -> Self {
+        let output_gain = if params.mute() {
+            0.0
+        } else {
+            db_to_gain(params.output_gain_db())
+        };
+
+        Monitor {
+            input_gain: SmoothedValue::new(
+                db_to_gain(params.input_gain_db()),
+                sample_rate,
+                SMOOTHING_TIME,
+            ),
+            output_gain: SmoothedValue::new(output_gain, sample_rate, SMOOTHING_TIME),
+            mix: SmoothedValue::new(params.mix(), sample_rate, SMOOTHING_TIME),
+            limiter_envelope: 0.0,
+            limiter_release: (-1.0 / (LIMITER_RELEASE * sample_rate as f32)).exp(),
+        }
+    }
+
+    pub fn process_input(&mut self, params: &MonitorParams, input: &mut [f32]) {
+        self.input_gain
+            .set_target(db_to_gain(params.input_gain_db()));
+        for sample in input.iter_mut() {
+            *sample *= self.input_gain.next();
+        }
+    }
+
+    /// Mix `dry` (after input gain) into `processed` and apply the output stage in place.
+    pub fn process_output(&mut self, params: &MonitorParams, dry: &[f32], processed: &mut [f32]) {
+        let output_gain = if params.mute() {
+            0.0
+        } else {
+            db_to_gain(params.output_gain_db())
+        };
+        self.output_gain.set_target(output_gain);
+        self.mix.set_target(params.mix());
+        let limiter = params.limiter();
+
+        for (sample, &dry) in processed.iter_mut().zip(dry.iter()) {
+            // dry and processed are strongly correlated, so a linear crossfade keeps the level
+            let mix = self.mix.next();
+            let mut out = (dry + (*sample - dry) * mix) * self.output_gain.next();
+
+            if limiter {
+                // instant attack peak envelope, exponential release
+                self.limiter_envelope = out.abs().max(self.limiter_envelope * self.limiter_release);
+                if self.limiter_envelope > LIMITER_CEILING {
+                    out *= LIMITER_CEILING / self.limiter_envelope;
+                }
+            }
+
+            *sample = out.clamp(-1.0, 1.0);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+/// f32 that can be shared between the UI and audio threads without locking
+pub struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    pub fn new(value: f32) -> Self {
+        AtomicF32(AtomicU32::new(value.to_bits()))
+    }
+
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn store(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed)
+    }
+
+    pub fn swap(&self, value: f32) -> f32 {
+        f32::from_bits(self.0.swap(value.to_bits(), Ordering::Relaxed))
+    }
+
+    /// Raise the stored value to `value` if it is larger. Only valid for non-negative values,
+    /// whose bit patterns sort the same way as the numbers. Zero is skipped, as the sign bit
+    /// of -0.0 would sort it above every other value.
+    pub fn fetch_max(&self, value: f32) {
+        debug_assert!(value >= 0.0);
+        if value > 0.0 {
+            self.0.fetch_max(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// A value built on the UI thread for the audio thread, such as a filter or a clip, and the
+/// one it replaced handed back, so neither is allocated or freed on the audio thread. The
+/// audio thread only ever tries the lock and picks the value up on a later callback if the
+/// UI thread holds it.
+pub struct Handover<T> {
+    slots: Mutex<HandoverSlots<T>>,
+}
+
+struct HandoverSlots<T> {
+    pending: Option<T>,
+    retired: Option<T>,
+}
+
+impl<T> Handover<T> {
+    pub fn new() -> Self {
+        Handover {
+            slots: Mutex::new(HandoverSlots {
+                pending: None,
+                retired: None,
+            }),
+        }
+    }
+
+    /// Hand `value` to the audio thread, freeing the one it handed back last
+    pub fn send(&self, value: T) {
+        let mut slots = self.slots.lock().unwrap();
+        slots.retired = None;
+        slots.pending = Some(value);
+    }
+
+    /// Drop the values in flight, as when the audio side was rebuilt and has no use for them.
+    /// Not for the audio thread.
+    pub fn clear(&self) {
+        let mut slots = self.slots.lock().unwrap();
+        slots.pending = None;
+        slots.retired = None;
+    }
+
+    /// Pass a pending value to `install`, which puts it in place and returns the value it
+    /// replaced. Returns whether there was one. Called on the audio thread.
+    pub fn receive(&self, install: impl FnOnce(T) -> T) -> bool {
+        if let Ok(mut slots) = self.slots.try_lock() {
+            if let Some(value) = slots.pending.take() {
+                slots.retired = Some(install(value));
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// One-pole smoothing of a parameter toward its target, to avoid zipper noise and clicks
+/// when a value changes from the UI.
+pub struct SmoothedValue {
+    current: f32,
+    target: f32,
+    coefficient: f32,
+}
+
+impl SmoothedValue {
+    pub fn new(value: f32, sample_rate: u32, time_constant: f32) -> Self {
+        let mut smoothed = SmoothedValue {
+            current: value,
+            target: value,
+            coefficient: 1.0,
+        };
+        smoothed.set_time_constant(sample_rate, time_constant);
+        smoothed
+    }
+
+    /// `time_constant` in seconds to get ~63% of the way to the target
+    pub fn set_time_constant(&mut self, sample_rate: u32, time_constant: f32) {
+        self.coefficient = if time_constant <= 0.0 || sample_rate == 0 {
+            1.0
+        } else {
+            1.0 - (-1.0 / (time_constant * sample_rate as f32)).exp()
+        };
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    pub fn next(&mut self) -> f32 {
+        self.current += self.coefficient * (self.target - self.current);
+        self.current
+    }
+}
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-10).log10()
+}
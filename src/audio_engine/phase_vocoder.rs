@@ -0,0 +1,250 @@
+use std::f32::consts::PI;
+
+use rustfft::num_complex::Complex;
+
+use crate::audio_engine::params::AtomicF32;
+use crate::audio_engine::stft::{frame_size, FrameTransform, OVERLAP};
+
+/// Bins this far below the strongest one aren't taken as peaks
+const PEAK_FLOOR_DB: f32 = -100.0;
+/// A peak is continued from the previous frame's peak if it moved at most this many bins
+const PEAK_TRACKING_BINS: usize = 2;
+/// Range of the ratios, limited by the smearing of the transients and partials
+pub const MIN_STRETCH: f32 = 0.25;
+pub const MAX_STRETCH: f32 = 8.0;
+pub const MAX_PITCH_SEMITONES: f32 = 12.0;
+
+pub fn semitones_to_ratio(semitones: f32) -> f32 {
+    2.0_f32.powf(semitones / 12.0)
+}
+
+fn wrap_phase(phase: f32) -> f32 {
+    phase - 2.0 * PI * (phase / (2.0 * PI)).round()
+}
+
+/// Phase vocoder with identity phase locking (Laroche and Dolson, 1999). Each spectral peak
+/// carries the bins around it, its region of influence, and those bins keep their phase
+/// relative to the peak, which avoids the phasiness of a bin-by-bin vocoder. Pitch is shifted
+/// by moving whole regions in frequency, so time and pitch can be changed independently and no
+/// resampling is needed.
+pub struct PhaseVocoder {
+    size: usize,
+    shifted: Vec<Complex<f32>>,
+    magnitudes: Vec<f32>,
+    phases: Vec<f32>,
+    previous_phases: Vec<f32>,
+    /// Bins of the peaks of the current frame
+    peaks: Vec<usize>,
+    /// Analysis bin and synthesis phase of the peaks of the previous frame
+    previous_peaks: Vec<(usize, f32)>,
+    tracked_peaks: Vec<(usize, f32)>,
+    /// False until a frame has been analyzed, as the phase advance needs a previous frame
+    started: bool,
+}
+
+impl PhaseVocoder {
+    /// `size` is the length of the transform the spectra come from
+    pub fn new(size: usize) -> Self {
+        let bins = size / 2 + 1;
+
+        PhaseVocoder {
+            size,
+            shifted: vec![Complex::new(0.0, 0.0); bins],
+            magnitudes: vec![0.0; bins],
+            phases: vec![0.0; bins],
+            previous_phases: vec![0.0; bins],
+            peaks: Vec::with_capacity(bins / 2),
+            previous_peaks: Vec::with_capacity(bins / 2),
+            tracked_peaks: Vec::with_capacity(bins / 2),
+            started: false,
+        }
+    }
+
+    /// Modify one frame's bins 0 to Nyquist in place. The hops are the distances from the
+    /// previous frame in the input and in the output; their ratio is the time stretch.
+    /// `pitch` scales all frequencies.
+    pub fn process(
+        &mut self,
+        spectrum: &mut [Complex<f32>],
+        analysis_hop: usize,
+        synthesis_hop: usize,
+        pitch: f32,
+    ) {
+        for (k, value) in spectrum.iter().enumerate() {
+            self.magnitudes[k] = value.norm();
+            self.phases[k] = value.arg();
+        }
+        self.find_peaks();
+
+        // unchanged time and pitch pass the spectrum through, so the path is transparent
+        if pitch == 1.0 && analysis_hop == synthesis_hop {
+            self.previous_peaks.clear();
+            self.previous_peaks
+                .extend(self.peaks.iter().map(|&k| (k, self.phases[k])));
+        } else {
+            self.shift_regions(analysis_hop, synthesis_hop, pitch);
+            spectrum.copy_from_slice(&self.shifted);
+        }
+        std::mem::swap(&mut self.phases, &mut self.previous_phases);
+        self.started = true;
+    }
+
+    /// Local maxima over two bins either side, within the floor of the strongest
+    fn find_peaks(&mut self) {
+        let magnitudes = &self.magnitudes;
+        let strongest = magnitudes.iter().cloned().fold(0.0, f32::max);
+        let floor = strongest * 10.0_f32.powf(PEAK_FLOOR_DB / 20.0);
+
+        self.peaks.clear();
+        self.peaks
+            .extend((2..magnitudes.len().saturating_sub(2)).filter(|&k| {
+                let m = magnitudes[k];
+                m > floor
+                    && m > magnitudes[k - 1]
+                    && m >= magnitudes[k + 1]
+                    && m > magnitudes[k - 2]
+                    && m >= magnitudes[k + 2]
+            }));
+    }
+
+    /// Move each peak's region of influence to its shifted frequency, advancing the peak's
+    /// phase by its true frequency over the synthesis hop
+    fn shift_regions(&mut self, analysis_hop: usize, synthesis_hop: usize, pitch: f32) {
+        let size = self.size;
+        let bins = size / 2 + 1;
+        self.shifted.fill(Complex::new(0.0, 0.0));
+        self.tracked_peaks.clear();
+
+        for (i, &peak) in self.peaks.iter().enumerate() {
+            // instantaneous frequency in radians per sample from the phase advance
+            let bin_frequency = 2.0 * PI * peak as f32 / size as f32;
+            let frequency = if self.started && analysis_hop > 0 {
+                let advance = self.phases[peak]
+                    - self.previous_phases[peak]
+                    - bin_frequency * analysis_hop as f32;
+                bin_frequency + wrap_phase(advance) / analysis_hop as f32
+            } else {
+                bin_frequency
+            };
+
+            let previous = self
+                .previous_peaks
+                .iter()
+                .filter(|(bin, _)| bin.abs_diff(peak) <= PEAK_TRACKING_BINS)
+                .min_by_key(|(bin, _)| bin.abs_diff(peak));
+            let phase = match previous {
+                Some(&(_, phase)) => wrap_phase(phase + pitch * frequency * synthesis_hop as f32),
+                // a new peak starts from its analysed phase
+                None => self.phases[peak],
+            };
+            self.tracked_peaks.push((peak, phase));
+
+            // regions end halfway to the neighbouring peaks
+            let low = if i == 0 {
+                0
+            } else {
+                (self.peaks[i - 1] + peak) / 2 + 1
+            };
+            let high = self
+                .peaks
+                .get(i + 1)
+                .map_or(bins, |&next| (peak + next) / 2 + 1);
+            let target = (peak as f32 * pitch).round() as isize;
+            let offset = target - peak as isize;
+            let rotation = phase - self.phases[peak];
+            for k in low..high {
+                let shifted = k as isize + offset;
+                if shifted <= 0 || shifted >= bins as isize - 1 {
+                    continue;
+                }
+                self.shifted[shifted as usize] +=
+                    Complex::from_polar(self.magnitudes[k], self.phases[k] + rotation);
+            }
+        }
+        std::mem::swap(&mut self.previous_peaks, &mut self.tracked_peaks);
+    }
+}
+
+/// Pitch shift setting, set from the UI and read once per callback by the audio thread
+pub struct PitchShiftParams {
+    semitones: AtomicF32,
+}
+
+impl PitchShiftParams {
+    pub fn new() -> Self {
+        PitchShiftParams {
+            semitones: AtomicF32::new(0.0),
+        }
+    }
+
+    pub fn semitones(&self) -> f32 {
+        self.semitones.load()
+    }
+
+    pub fn set_semitones(&self, semitones: f32) {
+        self.semitones
+            .store(semitones.clamp(-MAX_PITCH_SEMITONES, MAX_PITCH_SEMITONES));
+    }
+}
+
+/// Time stretch and pitch shift a whole recording. `stretch` scales the length, `pitch` all
+/// frequencies.
+pub fn stretch_recording(samples: &[f32], sample_rate: u32, stretch: f32, pitch: f32) -> Vec<f32> {
+    let stretch = stretch.clamp(MIN_STRETCH, MAX_STRETCH) as f64;
+    let size = frame_size(sample_rate);
+    let half = size / 2;
+    let mut transform = FrameTransform::new(size);
+    let mut vocoder = PhaseVocoder::new(size);
+
+    // the shorter hop keeps the full overlap, so frames are never spread apart
+    let base_hop = (size / OVERLAP) as f64;
+    let analysis_hop = base_hop / stretch.max(1.0);
+    let synthesis_hop = analysis_hop * stretch;
+
+    let output_len = (samples.len() as f64 * stretch).round() as usize;
+    // output shifted by half a frame, as frames are centred on their positions
+    let mut output = vec![0.0; output_len + size];
+    let mut weights = vec![0.0; output_len + size];
+    let mut frame = vec![0.0; size];
+    let mut synthesized = vec![0.0; size];
+    let (mut previous_analysis, mut previous_synthesis) = (0, 0);
+
+    for i in 0.. {
+        let analysis = (i as f64 * analysis_hop).round() as usize;
+        let synthesis = (i as f64 * synthesis_hop).round() as usize;
+        if analysis > samples.len() + half || synthesis > output_len + half {
+            break;
+        }
+        for (n, value) in frame.iter_mut().enumerate() {
+            *value = (analysis + n)
+                .checked_sub(half)
+                .and_then(|index| samples.get(index))
+                .copied()
+                .unwrap_or(0.0);
+        }
+        let hops = if i == 0 {
+            (
+                analysis_hop.round() as usize,
+                synthesis_hop.round() as usize,
+            )
+        } else {
+            (analysis - previous_analysis, synthesis - previous_synthesis)
+        };
+        vocoder.process(transform.analyze(&frame), hops.0, hops.1, pitch);
+        transform.synthesize(&mut synthesized);
+        (previous_analysis, previous_synthesis) = (analysis, synthesis);
+
+        for n in 0..size.min(output.len().saturating_sub(synthesis)) {
+            output[synthesis + n] += synthesized[n];
+            weights[synthesis + n] += transform.window()[n] * transform.window()[n];
+        }
+    }
+
+    output
+        .iter()
+        .zip(weights.iter())
+        .skip(half)
+        .take(output_len)
+        .map(|(sample, weight)| if *weight > 1e-3 { sample / weight } else { 0.0 })
+        .collect()
+}
@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::audio_engine::params::{db_to_gain, AtomicF32, Handover, SmoothedValue};
+use crate::audio_engine::resampler::resample;
+
+/// Time constant of gain changes
+const SMOOTHING_TIME: f32 = 0.02;
+
+/// A mono sound to play through the output, such as a resynthesis
+pub struct Clip {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl Clip {
+    pub fn new(samples: Vec<f32>, sample_rate: u32) -> Self {
+        Clip {
+            samples,
+            sample_rate,
+        }
+    }
+}
+
+/// Clip playback controls, shared between the UI and the audio thread
+pub struct PlayerParams {
+    clip: Mutex<Option<Arc<Clip>>>,
+    /// Samples of a new clip at the stream's rate
+    handover: Handover<Vec<f32>>,
+    gain_db: AtomicF32,
+    /// Requests from the UI, taken by the audio thread
+    start: AtomicBool,
+    stop: AtomicBool,
+    /// Set by the audio thread while the clip plays
+    playing: AtomicBool,
+    /// Share of the clip played
+    progress: AtomicF32,
+}
+
+impl PlayerParams {
+    pub fn new() -> Self {
+        PlayerParams {
+            clip: Mutex::new(None),
+            handover: Handover::new(),
+            gain_db: AtomicF32::new(0.0),
+            start: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+            playing: AtomicBool::new(false),
+            progress: AtomicF32::new(0.0),
+        }
+    }
+
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db.load()
+    }
+
+    pub fn set_gain_db(&self, db: f32) {
+        self.gain_db.store(db);
+    }
+
+    /// Play the clip from the start
+    pub fn play(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+        self.start.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.start.store(false, Ordering::Relaxed);
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.load(Ordering::Relaxed) || self.start.load(Ordering::Relaxed)
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress.load()
+    }
+
+    pub fn clip(&self) -> Option<Arc<Clip>> {
+        self.clip.lock().unwrap().clone()
+    }
+
+    /// Replace the clip, stopping playback. With an output stream running at `sample_rate`,
+    /// the clip is converted to its rate here and picked up on the next callback.
+    pub fn set_clip(&self, clip: Option<Clip>, sample_rate: Option<u32>) {
+        let clip = clip.map(Arc::new);
+        *self.clip.lock().unwrap() = clip.clone();
+        self.stop();
+
+        if let Some(sample_rate) = sample_rate {
+            self.handover
+                .send(Self::prepare(clip.as_deref(), sample_rate));
+        }
+    }
+
+    fn prepare(clip: Option<&Clip>, sample_rate: u32) -> Vec<f32> {
+        clip.map_or_else(Vec::new, |clip| {
+            resample(&clip.samples, clip.sample_rate, sample_rate)
+        })
+    }
+}
+
+/// Audio thread side of the clip playback, mixed into every output channel
+pub struct Player {
+    samples: Vec<f32>,
+    position: usize,
+    playing: bool,
+    gain: SmoothedValue,
+}
+
+impl Player {
+    /// Built with the output stream, from the clip loaded at the time
+    pub fn new(params: &PlayerParams, sample_rate: u32) -> Self {
+        let samples = PlayerParams::prepare(params.clip().as_deref(), sample_rate);
+        // samples prepared for a previous stream are of no use
+        params.handover.clear();
+        params.playing.store(false, Ordering::Relaxed);
+
+        Player {
+            samples,
+            position: 0,
+            playing: false,
+            gain: SmoothedValue::new(db_to_gain(params.gain_db()), sample_rate, SMOOTHING_TIME),
+        }
+    }
+
+    pub fn process(&mut self, params: &PlayerParams, data: &mut [f32], num_channels: usize) {
+        if params
+            .handover
+            .receive(|samples| std::mem::replace(&mut self.samples, samples))
+        {
+            self.playing = false;
+        }
+        if params.stop.swap(false, Ordering::Relaxed) {
+            self.playing = false;
+        }
+        if params.start.swap(false, Ordering::Relaxed) {
+            self.position = 0;
+            self.playing = true;
+        }
+
+        if self.playing {
+            self.gain.set_target(db_to_gain(params.gain_db()));
+            for frame in data.chunks_mut(num_channels) {
+                let Some(&sample) = self.samples.get(self.position) else {
+                    self.playing = false;
+                    break;
+                };
+                let sample = sample * self.gain.next();
+                for value in frame.iter_mut() {
+                    *value += sample;
+                }
+                self.position += 1;
+            }
+            params
+                .progress
+                .store(self.position as f32 / self.samples.len().max(1) as f32);
+        }
+        params.playing.store(self.playing, Ordering::Relaxed);
+    }
+}
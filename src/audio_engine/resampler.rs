@@ -0,0 +1,408 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::audio_engine::params::Handover;
+
+/// Number of input samples each output sample is computed from
+const TAPS: usize = 32;
+/// Number of fractional delays the windowed-sinc kernel is tabulated at
+const PHASES: usize = 256;
+/// Kaiser window shape, ~80 dB stopband attenuation
+const KAISER_BETA: f64 = 8.0;
+/// Fraction of the output Nyquist frequency kept in the passband
+const PASSBAND: f64 = 0.92;
+
+/// Polyphase windowed-sinc resampler for interleaved frames.
+///
+/// Pulls input frames on demand, so it can sit between a ring buffer and a stream callback
+/// whose rates differ. The ratio can be nudged continuously for drift compensation; a new
+/// nominal ratio comes with a new anti-aliasing filter, built off the audio thread.
+pub struct Resampler {
+    num_channels: usize,
+    /// Input samples consumed per output sample
+    ratio: f64,
+    /// Position of the next output sample between the two newest input frames
+    position: f64,
+    /// Last `TAPS` input frames per channel, oldest first
+    history: Vec<Vec<f32>>,
+    filter: ResamplerFilter,
+}
+
+impl Resampler {
+    pub fn new(num_channels: usize, input_rate: u32, output_rate: u32) -> Self {
+        let filter = ResamplerFilter::new(input_rate, output_rate);
+        Resampler {
+            num_channels,
+            ratio: filter.ratio,
+            position: 0.0,
+            history: vec![vec![0.0; TAPS]; num_channels],
+            filter,
+        }
+    }
+
+    /// Change the conversion ratio to the filter's, keeping the signal history. Returns the
+    /// previous filter, so it isn't freed on the audio thread.
+    pub fn set_filter(&mut self, filter: ResamplerFilter) -> ResamplerFilter {
+        self.ratio = filter.ratio;
+        std::mem::replace(&mut self.filter, filter)
+    }
+
+    /// Scale the nominal ratio by `correction` (close to 1.0) without rebuilding the filter.
+    pub fn set_correction(&mut self, correction: f64) {
+        self.ratio = self.filter.ratio * correction;
+    }
+
+    /// Fill the interleaved `output` buffer. `next_input` is called once per input sample in
+    /// interleaved order and returns `None` when no input is available, in which case silence
+    /// is resampled in its place and `false` is returned.
+    pub fn process<F>(&mut self, output: &mut [f32], mut next_input: F) -> bool
+    where
+        F: FnMut() -> Option<f32>,
+    {
+        let mut had_input = true;
+
+        for frame in output.chunks_mut(self.num_channels) {
+            while self.position >= 1.0 {
+                for channel in self.history.iter_mut() {
+                    channel.copy_within(1.., 0);
+                    channel[TAPS - 1] = next_input().unwrap_or_else(|| {
+                        had_input = false;
+                        0.0
+                    });
+                }
+                self.position -= 1.0;
+            }
+
+            // linear interpolation between the two nearest tabulated phases
+            let phase = self.position * PHASES as f64;
+            let index = (phase as usize).min(PHASES - 1);
+            let blend = (phase - index as f64) as f32;
+            let coefficients = &self.filter.coefficients;
+            let lower = &coefficients[index * TAPS..(index + 1) * TAPS];
+            let upper = &coefficients[(index + 1) * TAPS..(index + 2) * TAPS];
+
+            for (sample, channel) in frame.iter_mut().zip(self.history.iter()) {
+                let mut acc = 0.0;
+                for j in 0..TAPS {
+                    let coefficient = lower[j] + (upper[j] - lower[j]) * blend;
+                    acc += channel[j] * coefficient;
+                }
+                *sample = acc;
+            }
+
+            self.position += self.ratio;
+        }
+
+        had_input
+    }
+}
+
+/// Anti-aliasing filter of the resampler for one nominal ratio
+pub struct ResamplerFilter {
+    /// Input samples consumed per output sample
+    ratio: f64,
+    /// `(PHASES + 1) * TAPS` kernel coefficients
+    coefficients: Vec<f32>,
+}
+
+impl ResamplerFilter {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let ratio = input_rate as f64 / output_rate as f64;
+        ResamplerFilter {
+            ratio,
+            coefficients: Self::build(ratio),
+        }
+    }
+
+    fn build(ratio: f64) -> Vec<f32> {
+        // when downsampling the cutoff moves below the input Nyquist frequency
+        let cutoff = PASSBAND * (1.0 / ratio).min(1.0);
+        let half = (TAPS / 2) as f64;
+        let window_norm = bessel_i0(KAISER_BETA);
+
+        let mut filter = Vec::with_capacity((PHASES + 1) * TAPS);
+        for p in 0..=PHASES {
+            let frac = p as f64 / PHASES as f64;
+            let start = filter.len();
+            for j in 0..TAPS {
+                // distance from the interpolated instant to input sample j
+                let d = j as f64 + 1.0 - half - frac;
+                let x = d / half;
+                let window = if x.abs() <= 1.0 {
+                    bessel_i0(KAISER_BETA * (1.0 - x * x).sqrt()) / window_norm
+                } else {
+                    0.0
+                };
+                filter.push((cutoff * sinc(cutoff * d) * window) as f32);
+            }
+
+            // normalize each phase to unity DC gain
+            let sum: f32 = filter[start..].iter().sum();
+            for c in filter[start..].iter_mut() {
+                *c /= sum;
+            }
+        }
+        filter
+    }
+}
+
+/// Resampler filters for a new input rate, built when the input stream is and picked up by
+/// the output stream's resampler
+pub struct ResamplerFilterHandover {
+    /// Rate of the output stream, 0 without one
+    output_rate: AtomicU32,
+    handover: Handover<ResamplerFilter>,
+}
+
+impl ResamplerFilterHandover {
+    pub fn new() -> Self {
+        ResamplerFilterHandover {
+            output_rate: AtomicU32::new(0),
+            handover: Handover::new(),
+        }
+    }
+
+    /// An output stream was built at `output_rate`. Its resampler starts with a filter for
+    /// the current rates, so filters prepared for a previous stream are of no use.
+    pub fn reset(&self, output_rate: u32) {
+        self.output_rate.store(output_rate, Ordering::Relaxed);
+        self.handover.clear();
+    }
+
+    /// An input stream was built at `input_rate`
+    pub fn prepare(&self, input_rate: u32) {
+        let output_rate = self.output_rate.load(Ordering::Relaxed);
+        if output_rate == 0 {
+            return;
+        }
+        self.handover
+            .send(ResamplerFilter::new(input_rate, output_rate));
+    }
+
+    /// Hand a prepared filter to the resampler. Returns whether there was one, that is an
+    /// input stream was built since the last call. Called on the audio thread.
+    pub fn update(&self, resampler: &mut Resampler) -> bool {
+        self.handover.receive(|filter| resampler.set_filter(filter))
+    }
+}
+
+/// Convert a whole mono signal to another rate, as when preparing a file for a stream. The
+/// output starts at the first input sample and covers the whole input.
+pub fn resample(samples: &[f32], input_rate: u32, output_rate: u32) -> Vec<f32> {
+    if input_rate == output_rate {
+        return samples.to_vec();
+    }
+    let mut resampler = Resampler::new(1, input_rate, output_rate);
+    // a stream's output lags the newest input frame by half the filter. Pulling that many
+    // frames before the first output removes the delay, and the zeros after the signal
+    // flush its tail out of the filter.
+    resampler.position = (TAPS / 2 + 1) as f64;
+    let len = samples.len() as u64 * output_rate as u64 / input_rate as u64;
+    let mut resampled = vec![0.0; len as usize];
+    let mut input = samples.iter().copied().chain(std::iter::repeat(0.0));
+    resampler.process(&mut resampled, || input.next());
+    resampled
+}
+
+/// Keeps the fill level of a ring buffer between two free-running clocks at a target by
+/// adjusting the resampling ratio.
+///
+/// The buffer integrates the rate difference, so a proportional term alone settles with a
+/// small offset and the integral term only has to remove that offset. Its gain is kept well
+/// below the proportional gain, which leaves the loop damped instead of slowly oscillating.
+pub struct DriftCompensator {
+    target_fill: f64,
+    /// None until the first measurement after a (re)start
+    smoothed_fill: Option<f64>,
+    integral: f64,
+}
+
+impl DriftCompensator {
+    /// Time constant of the smoothing of the measured fill level, in seconds
+    const SMOOTHING_SECONDS: f64 = 0.5;
+    /// Correction per relative fill error. With the buffer holding half a second of latency
+    /// the error decays with a time constant of about ten seconds.
+    const PROPORTIONAL_GAIN: f64 = 0.05;
+    /// Correction per relative fill error and second
+    const INTEGRAL_GAIN: f64 = 0.001;
+    /// Largest ratio correction, in parts per one
+    const MAX_CORRECTION: f64 = 0.01;
+
+    pub fn new(target_fill: usize) -> Self {
+        DriftCompensator {
+            target_fill: target_fill as f64,
+            smoothed_fill: None,
+            integral: 0.0,
+        }
+    }
+
+    /// Start over at a new target, as when the producer's stream was rebuilt
+    pub fn set_target_fill(&mut self, target_fill: usize) {
+        *self = Self::new(target_fill);
+    }
+
+    /// Feed the current fill level and the seconds since the last update, and get the ratio
+    /// correction to apply. A fuller buffer means the producer runs fast, so the consumer has
+    /// to read faster and vice versa.
+    pub fn update(&mut self, fill: usize, elapsed: f64) -> f64 {
+        if self.target_fill <= 0.0 {
+            return 1.0;
+        }
+
+        let fill = fill as f64;
+        let smoothed_fill = match self.smoothed_fill {
+            Some(smoothed) => {
+                let smoothing = (elapsed / Self::SMOOTHING_SECONDS).min(1.0);
+                smoothed + smoothing * (fill - smoothed)
+            }
+            None => fill,
+        };
+        self.smoothed_fill = Some(smoothed_fill);
+        let error = (smoothed_fill - self.target_fill) / self.target_fill;
+
+        // the integral is held while the correction is at its limit, so a large initial error
+        // doesn't wind it up into an overshoot
+        let integral = self.integral + error * elapsed;
+        let correction = Self::PROPORTIONAL_GAIN * error + Self::INTEGRAL_GAIN * integral;
+        if correction.abs() < Self::MAX_CORRECTION {
+            self.integral = integral;
+        }
+        let correction = Self::PROPORTIONAL_GAIN * error + Self::INTEGRAL_GAIN * self.integral;
+
+        1.0 + correction.clamp(-Self::MAX_CORRECTION, Self::MAX_CORRECTION)
+    }
+}
+
+pub fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let px = std::f64::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+/// Zeroth order modified Bessel function of the first kind
+pub fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..50 {
+        term *= (half_x / k as f64) * (half_x / k as f64);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a ring buffer between a producer clock `offset` parts per one faster than the
+    /// consumer for `seconds`, starting `initial_fill` samples full, and return the fill after
+    /// each consumer callback
+    fn simulate_drift(offset: f64, initial_fill: f64, seconds: f64) -> Vec<f64> {
+        const RATE: f64 = 96000.0;
+        const CONSUMER_BLOCK: f64 = 1024.0;
+        const PRODUCER_BLOCK: f64 = 882.0;
+        let target = RATE / 2.0;
+        let elapsed = CONSUMER_BLOCK / RATE;
+
+        let mut drift = DriftCompensator::new(target as usize);
+        let mut fill = initial_fill;
+        let mut produced = 0.0;
+        let mut correction = 1.0;
+        let mut fills = Vec::new();
+        for _ in 0..(seconds / elapsed) as usize {
+            // the producer delivers whole blocks, so the measured fill jitters
+            produced += CONSUMER_BLOCK * (1.0 + offset);
+            while produced >= PRODUCER_BLOCK {
+                produced -= PRODUCER_BLOCK;
+                fill += PRODUCER_BLOCK;
+            }
+            fill -= CONSUMER_BLOCK * correction;
+            correction = drift.update(fill as usize, elapsed);
+            fills.push(fill);
+        }
+        fills
+    }
+
+    #[test]
+    fn drift_settles_at_target() {
+        let target = 48000.0;
+        for offset in [-300e-6, 100e-6, 500e-6] {
+            let fills = simulate_drift(offset, target, 600.0);
+            let settled = &fills[fills.len() - 1000..];
+            let mean = settled.iter().sum::<f64>() / settled.len() as f64;
+            assert!(
+                (mean - target).abs() < 0.001 * target,
+                "offset {}: fill {} for target {}",
+                offset,
+                mean,
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn drift_recovers_without_overshoot() {
+        let target = 48000.0;
+        let fills = simulate_drift(0.0, target / 2.0, 300.0);
+        let highest = fills.iter().copied().fold(f64::MIN, f64::max);
+        // damped: the fill may cross the target by a small fraction of the initial error
+        assert!(
+            highest < target + 0.1 * target / 2.0,
+            "overshoot to {}",
+            highest
+        );
+        let last = *fills.last().unwrap();
+        assert!((last - target).abs() < 0.02 * target, "fill {}", last);
+    }
+
+    #[test]
+    fn resample_keeps_impulses_in_place() {
+        for (input_rate, output_rate) in [(48000, 96000), (44100, 48000), (48000, 44100)] {
+            let len = 4800;
+            // one impulse at the start, one in the middle and one in the tail of the filter
+            for position in [0, 1234, len - 3] {
+                let mut impulse = vec![0.0; len];
+                impulse[position] = 1.0;
+                let resampled = resample(&impulse, input_rate, output_rate);
+                assert_eq!(
+                    resampled.len(),
+                    len * output_rate as usize / input_rate as usize
+                );
+
+                let peak = resampled
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .map(|(i, _)| i)
+                    .unwrap();
+                let expected = position as f64 * output_rate as f64 / input_rate as f64;
+                assert!(
+                    (peak as f64 - expected).abs() <= 0.5,
+                    "{} -> {} Hz: impulse at {} found at {}, expected {}",
+                    input_rate,
+                    output_rate,
+                    position,
+                    peak,
+                    expected
+                );
+                assert!(resampled[peak] > 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn drift_restart_clears_integral() {
+        let mut drift = DriftCompensator::new(1000);
+        for _ in 0..1000 {
+            drift.update(1500, 0.01);
+        }
+        drift.set_target_fill(1000);
+        assert_eq!(drift.update(1000, 0.01), 1.0);
+    }
+}
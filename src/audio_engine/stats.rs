@@ -0,0 +1,160 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::audio_engine::params::AtomicF32;
+
+/// Number of callback duration buckets, each 10% of the callback period wide. The last bucket
+/// collects every callback that overran its period.
+pub const HISTOGRAM_BINS: usize = 11;
+
+/// Weight of the newest callback in the smoothed load estimate
+const LOAD_SMOOTHING: f32 = 0.05;
+
+/// Timing of one stream's callbacks. Written lock-free from the audio thread.
+pub struct CallbackStats {
+    callbacks: AtomicU64,
+    late_callbacks: AtomicU64,
+    histogram: [AtomicU64; HISTOGRAM_BINS],
+    /// Smoothed ratio of time spent in the callback to the callback period
+    load: AtomicF32,
+}
+
+impl CallbackStats {
+    fn new() -> Self {
+        CallbackStats {
+            callbacks: AtomicU64::new(0),
+            late_callbacks: AtomicU64::new(0),
+            histogram: Default::default(),
+            load: AtomicF32::new(0.0),
+        }
+    }
+
+    /// Record a callback that started at `start` and had `period` worth of audio to process.
+    pub fn record(&self, start: Instant, period: Duration) {
+        if period.is_zero() {
+            return;
+        }
+        let load = start.elapsed().as_secs_f32() / period.as_secs_f32();
+
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+        if load > 1.0 {
+            self.late_callbacks.fetch_add(1, Ordering::Relaxed);
+        }
+        let bin = ((load * 10.0) as usize).min(HISTOGRAM_BINS - 1);
+        self.histogram[bin].fetch_add(1, Ordering::Relaxed);
+
+        // only the audio thread writes, besides a reset, so a load/store pair is enough
+        let smoothed = self.load.load();
+        self.load
+            .store(smoothed + LOAD_SMOOTHING * (load - smoothed));
+    }
+
+    fn snapshot(&self) -> CallbackStatsSnapshot {
+        let mut histogram = [0; HISTOGRAM_BINS];
+        for (count, bin) in histogram.iter_mut().zip(self.histogram.iter()) {
+            *count = bin.load(Ordering::Relaxed);
+        }
+
+        CallbackStatsSnapshot {
+            callbacks: self.callbacks.load(Ordering::Relaxed),
+            late_callbacks: self.late_callbacks.load(Ordering::Relaxed),
+            histogram,
+            load: self.load.load(),
+        }
+    }
+
+    fn reset(&self) {
+        self.callbacks.store(0, Ordering::Relaxed);
+        self.late_callbacks.store(0, Ordering::Relaxed);
+        for bin in self.histogram.iter() {
+            bin.store(0, Ordering::Relaxed);
+        }
+        self.load.store(0.0);
+    }
+}
+
+/// Dropout counters and buffer state shared between the streams and the UI.
+pub struct IOStats {
+    pub input: CallbackStats,
+    pub output: CallbackStats,
+    /// Output callbacks that found the ring buffer empty
+    underruns: AtomicU64,
+    /// Input callbacks that found the ring buffer full
+    overruns: AtomicU64,
+    buffer_fill: AtomicUsize,
+    buffer_capacity: AtomicUsize,
+}
+
+impl IOStats {
+    pub fn new() -> Self {
+        IOStats {
+            input: CallbackStats::new(),
+            output: CallbackStats::new(),
+            underruns: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+            buffer_fill: AtomicUsize::new(0),
+            buffer_capacity: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn record_underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_overrun(&self) {
+        self.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_buffer_fill(&self, fill: usize, capacity: usize) {
+        self.buffer_fill.store(fill, Ordering::Relaxed);
+        self.buffer_capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> IOStatsSnapshot {
+        IOStatsSnapshot {
+            input: self.input.snapshot(),
+            output: self.output.snapshot(),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            buffer_fill: self.buffer_fill.load(Ordering::Relaxed),
+            buffer_capacity: self.buffer_capacity.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.input.reset();
+        self.output.reset();
+        self.underruns.store(0, Ordering::Relaxed);
+        self.overruns.store(0, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CallbackStatsSnapshot {
+    pub callbacks: u64,
+    pub late_callbacks: u64,
+    pub histogram: [u64; HISTOGRAM_BINS],
+    /// Estimated share of the callback period spent processing, 1.0 is fully loaded
+    pub load: f32,
+}
+
+/// Point in time copy of `IOStats` for display
+#[derive(Clone, Copy)]
+pub struct IOStatsSnapshot {
+    pub input: CallbackStatsSnapshot,
+    pub output: CallbackStatsSnapshot,
+    pub underruns: u64,
+    pub overruns: u64,
+    pub buffer_fill: usize,
+    pub buffer_capacity: usize,
+}
+
+impl IOStatsSnapshot {
+    pub fn buffer_fill_ratio(&self) -> f32 {
+        if self.buffer_capacity == 0 {
+            0.0
+        } else {
+            self.buffer_fill as f32 / self.buffer_capacity as f32
+        }
+    }
+}
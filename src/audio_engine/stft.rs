@@ -0,0 +1,251 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+/// Frame length, rounded up to a power of two. Long enough to resolve the closely spaced
+/// partials of a bowl, at the cost of smearing the strike a little.
+const FRAME_SECONDS: f32 = 0.08;
+/// Frames of the streaming STFT overlap by this factor
+pub const OVERLAP: usize = 8;
+/// Crossfade when a bypassable stage is switched
+const BYPASS_FADE_SECONDS: f32 = 0.01;
+
+/// STFT frame length at a sample rate, which is also the delay of each streaming STFT stage
+pub fn frame_size(sample_rate: u32) -> usize {
+    ((FRAME_SECONDS * sample_rate as f32) as usize)
+        .next_power_of_two()
+        .max(256)
+}
+
+/// Periodic Hann window of the STFT frames, also used by the offline overlap-add synthesis
+pub fn hann_window(size: usize) -> Vec<f32> {
+    (0..size)
+        .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / size as f32).cos())
+        .collect()
+}
+
+/// Hann windowed FFT of one frame and the windowed inverse, shared by the streaming STFT and
+/// the offline processors. Spectral processors only see and change the bins up to Nyquist;
+/// the rest are mirrored from them before the inverse.
+pub struct FrameTransform {
+    size: usize,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+    spectrum: Vec<Complex<f32>>,
+}
+
+impl FrameTransform {
+    pub fn new(size: usize) -> Self {
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(size);
+        let inverse = planner.plan_fft_inverse(size);
+        let scratch_len = forward
+            .get_inplace_scratch_len()
+            .max(inverse.get_inplace_scratch_len());
+
+        FrameTransform {
+            size,
+            forward,
+            inverse,
+            window: hann_window(size),
+            scratch: vec![Complex::new(0.0, 0.0); scratch_len],
+            spectrum: vec![Complex::new(0.0, 0.0); size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn window(&self) -> &[f32] {
+        &self.window
+    }
+
+    /// Window and transform `frame`, returning bins 0 to Nyquist
+    pub fn analyze(&mut self, frame: &[f32]) -> &mut [Complex<f32>] {
+        for ((value, sample), w) in self.spectrum.iter_mut().zip(frame).zip(&self.window) {
+            *value = Complex::new(sample * w, 0.0);
+        }
+        self.forward
+            .process_with_scratch(&mut self.spectrum, &mut self.scratch);
+        &mut self.spectrum[..self.size / 2 + 1]
+    }
+
+    /// Inverse transform of the spectrum left by `analyze`, windowed again for overlap-add
+    pub fn synthesize(&mut self, output: &mut [f32]) {
+        let size = self.size;
+        self.spectrum[0].im = 0.0;
+        self.spectrum[size / 2].im = 0.0;
+        for k in 1..size / 2 {
+            self.spectrum[size - k] = self.spectrum[k].conj();
+        }
+        self.inverse
+            .process_with_scratch(&mut self.spectrum, &mut self.scratch);
+
+        let scale = 1.0 / size as f32;
+        for ((out, value), w) in output.iter_mut().zip(&self.spectrum).zip(&self.window) {
+            *out = value.re * scale * w;
+        }
+    }
+}
+
+/// Streaming STFT with overlap-add resynthesis. Works in place on blocks of any size, hands
+/// each frame's spectrum to a processor and delays the signal by one frame. Nothing is
+/// allocated after construction.
+pub struct Stft {
+    transform: FrameTransform,
+    hop: usize,
+    /// Summed squared window at the hop, dividing out the overlap-add gain
+    normalization: f32,
+    input: Vec<f32>,
+    /// Finished output, the first hop of it being played out
+    output: Vec<f32>,
+    accumulator: Vec<f32>,
+    frame: Vec<f32>,
+    /// Write position in `input`, within the last hop of the frame
+    position: usize,
+}
+
+impl Stft {
+    pub fn new(sample_rate: u32) -> Self {
+        let size = frame_size(sample_rate);
+        let hop = size / OVERLAP;
+        let transform = FrameTransform::new(size);
+        let normalization = transform.window().iter().map(|w| w * w).sum::<f32>() / hop as f32;
+
+        Stft {
+            transform,
+            hop,
+            normalization,
+            input: vec![0.0; size],
+            output: vec![0.0; size],
+            accumulator: vec![0.0; size],
+            frame: vec![0.0; size],
+            position: size - hop,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.transform.size()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Drop the frames in flight, so processing resumes from silence
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.accumulator.fill(0.0);
+        self.position = self.size() - self.hop;
+    }
+
+    /// `process_spectrum` gets bins 0 to Nyquist of each frame and may change them
+    pub fn process(
+        &mut self,
+        buffer: &mut [f32],
+        mut process_spectrum: impl FnMut(&mut [Complex<f32>]),
+    ) {
+        let size = self.size();
+        let last_hop = size - self.hop;
+        for sample in buffer.iter_mut() {
+            self.input[self.position] = *sample;
+            *sample = self.output[self.position - last_hop];
+            self.position += 1;
+
+            if self.position == size {
+                process_spectrum(self.transform.analyze(&self.input));
+                self.transform.synthesize(&mut self.frame);
+                for (accumulated, value) in self.accumulator.iter_mut().zip(&self.frame) {
+                    *accumulated += value / self.normalization;
+                }
+                self.output[..self.hop].copy_from_slice(&self.accumulator[..self.hop]);
+                self.accumulator.copy_within(self.hop.., 0);
+                self.accumulator[size - self.hop..].fill(0.0);
+                self.input.copy_within(self.hop.., 0);
+                self.position = last_hop;
+            }
+        }
+    }
+}
+
+/// Streaming STFT stage that adds no delay while it is bypassed. Switched on, it starts from
+/// silence, keeps passing the input until its first frame is out and then crossfades to the
+/// processed signal. Switched off, it crossfades back to the input.
+pub struct StftStage {
+    stft: Stft,
+    active: bool,
+    /// Samples since the stage was last switched
+    elapsed: usize,
+    fade_len: usize,
+    dry: Vec<f32>,
+}
+
+impl StftStage {
+    /// `max_block` is the longest buffer to be processed, so crossfades don't allocate
+    pub fn new(sample_rate: u32, max_block: usize) -> Self {
+        let fade_len = ((BYPASS_FADE_SECONDS * sample_rate as f32) as usize).max(1);
+        StftStage {
+            stft: Stft::new(sample_rate),
+            active: false,
+            elapsed: fade_len,
+            fade_len,
+            dry: Vec::with_capacity(max_block),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.stft.size()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.stft.hop()
+    }
+
+    /// Bypass the stage without a crossfade, as when the processing around it stops
+    pub fn reset(&mut self) {
+        self.active = false;
+        self.elapsed = self.fade_len;
+    }
+
+    /// Run the STFT on `buffer` while `active` or fading out, bypass it otherwise
+    pub fn process(
+        &mut self,
+        active: bool,
+        buffer: &mut [f32],
+        process_spectrum: impl FnMut(&mut [Complex<f32>]),
+    ) {
+        if active != self.active {
+            self.active = active;
+            self.elapsed = 0;
+            if active {
+                self.stft.reset();
+            }
+        }
+        // the processed signal is faded in once the first frame is through
+        let fade_start = if active { self.stft.size() } else { 0 };
+        let fade_end = fade_start + self.fade_len;
+        if !active && self.elapsed >= fade_end {
+            return;
+        }
+        if active && self.elapsed >= fade_end {
+            self.stft.process(buffer, process_spectrum);
+            return;
+        }
+
+        self.dry.clear();
+        self.dry.extend_from_slice(buffer);
+        self.stft.process(buffer, process_spectrum);
+        for (n, (sample, dry)) in buffer.iter_mut().zip(&self.dry).enumerate() {
+            let t = (self.elapsed + n).saturating_sub(fade_start) as f32 / self.fade_len as f32;
+            let processed = if active { t.min(1.0) } else { 1.0 - t.min(1.0) };
+            *sample = dry + (*sample - dry) * processed;
+        }
+        self.elapsed += buffer.len();
+    }
+}
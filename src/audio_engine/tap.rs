@@ -0,0 +1,48 @@
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ringbuf::{Consumer, HeapRb, Producer, SharedRb};
+
+type ConsumerT = Consumer<f32, Arc<SharedRb<f32, Vec<MaybeUninit<f32>>>>>;
+type ProducerT = Producer<f32, Arc<SharedRb<f32, Vec<MaybeUninit<f32>>>>>;
+
+/// Copy of the captured input handed from the input callback to analysis on the UI thread.
+/// Samples that don't fit are dropped; analysis only needs the most recent signal.
+pub struct AnalysisTap {
+    producer: Mutex<ProducerT>,
+    consumer: Mutex<ConsumerT>,
+    /// Rate of the samples currently being written
+    sample_rate: AtomicU32,
+}
+
+impl AnalysisTap {
+    pub fn new(capacity: usize) -> Self {
+        let (producer, consumer) = HeapRb::<f32>::new(capacity).split();
+
+        AnalysisTap {
+            producer: Mutex::new(producer),
+            consumer: Mutex::new(consumer),
+            sample_rate: AtomicU32::new(0),
+        }
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: u32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    /// Called from the input callback
+    pub fn push(&self, samples: &[f32]) {
+        if let Ok(mut producer) = self.producer.try_lock() {
+            producer.push_slice(samples);
+        }
+    }
+
+    /// Move everything written since the last call to `out`. Returns the sample rate of the
+    /// data, 0 before the first input stream was built.
+    pub fn drain(&self, out: &mut Vec<f32>) -> u32 {
+        let mut consumer = self.consumer.lock().expect("Could not aquire lock");
+        out.extend(consumer.pop_iter());
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+}
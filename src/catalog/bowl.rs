@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::decay::ModeAnalysis;
+
+/// One measured vibration mode
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Mode {
+    pub frequency: f32,
+    pub level_db: f32,
+    /// Time to decay by 60 dB in seconds
+    pub t60: Option<f32>,
+    /// Rate of the swell from a split mode
+    pub beat_hz: Option<f32>,
+}
+
+impl From<&ModeAnalysis> for Mode {
+    fn from(mode: &ModeAnalysis) -> Self {
+        Mode {
+            frequency: mode.frequency,
+            level_db: mode.level_db,
+            t60: mode.t60,
+            beat_hz: mode.beat_hz,
+        }
+    }
+}
+
+/// A catalogued bowl: physical description, recordings of it and its measured modes
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct Bowl {
+    /// Unique, used as the file name in the catalog
+    pub id: String,
+    pub name: String,
+    pub diameter_mm: Option<f32>,
+    pub weight_g: Option<f32>,
+    pub material: String,
+    pub origin: String,
+    pub notes: String,
+    pub recordings: Vec<PathBuf>,
+    /// Ascending in frequency, the fundamental first
+    pub modes: Vec<Mode>,
+}
+
+impl Bowl {
+    pub fn new(id: &str) -> Self {
+        Bowl {
+            id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn fundamental(&self) -> Option<f32> {
+        self.modes.first().map(|mode| mode.frequency)
+    }
+
+    /// Decay time of the fundamental
+    pub fn t60(&self) -> Option<f32> {
+        self.modes.first().and_then(|mode| mode.t60)
+    }
+
+    /// Set a metadata field from text, as used by the command line. Empty values clear
+    /// optional fields.
+    pub fn set_field(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let parse_number = |value: &str| -> Result<Option<f32>, String> {
+            if value.is_empty() {
+                return Ok(None);
+            }
+            value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("'{}' is not a number", value))
+        };
+
+        match field {
+            "name" => self.name = value.to_string(),
+            "diameter" => self.diameter_mm = parse_number(value)?,
+            "weight" => self.weight_g = parse_number(value)?,
+            "material" => self.material = value.to_string(),
+            "origin" => self.origin = value.to_string(),
+            "notes" => self.notes = value.to_string(),
+            _ => return Err(format!("unknown field '{}'", field)),
+        }
+        Ok(())
+    }
+}
+
+/// Ids become file names, so they are limited to letters, digits, '-' and '_'
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
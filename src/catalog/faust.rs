@@ -0,0 +1,359 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use crate::analysis::modal::ModalModel;
+use crate::analysis::spectrum::SpectrumAnalyzer;
+use crate::catalog::bowl::Bowl;
+
+/// Rendering used to check an exported file against the modal synth
+const CHECK_SAMPLE_RATE: u32 = 48000;
+/// Length of the renderings, compared by their magnitude spectra. In single precision the
+/// mode filters of low partials with long decays are detuned by a few thousandths of a hertz,
+/// which drifts their phase by a few percent over the rendering but leaves their spectrum in
+/// place.
+const CHECK_FFT_SIZE: usize = 65536;
+/// Largest difference between the spectra of the file and the modal synth that passes the
+/// round trip. Single modes come within -45 dB; the slowly beating pairs of split modes, whose
+/// phase drift changes the beat, within -40 dB.
+pub const ROUND_TRIP_LIMIT_DB: f32 = -30.0;
+
+const FREQUENCIES: &str = "modeFreqs";
+const T60S: &str = "modeT60s";
+const GAINS: &str = "modeGains";
+
+#[derive(Debug)]
+pub enum FaustError {
+    Io(io::Error),
+    /// A file without the mode lists this exporter writes
+    Format(String),
+    NoModes(String),
+}
+
+impl fmt::Display for FaustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaustError::Io(e) => write!(f, "{}", e),
+            FaustError::Format(e) => write!(f, "not an exported bowl: {}", e),
+            FaustError::NoModes(id) => write!(f, "bowl '{}' has no measured modes", id),
+        }
+    }
+}
+
+impl From<io::Error> for FaustError {
+    fn from(e: io::Error) -> Self {
+        FaustError::Io(e)
+    }
+}
+
+/// The mode lists of an exported file, as the Faust code sees them
+#[derive(Clone, Debug, PartialEq)]
+pub struct FaustBank {
+    pub frequencies: Vec<f32>,
+    pub t60s: Vec<f32>,
+    /// Gains of the `pm.modeFilter`s
+    pub gains: Vec<f32>,
+}
+
+/// Faust program of the bowl's modal model: a bank of `pm.modeFilter`s driven by the audio
+/// input plus a strike button. A mode filter answers an impulse with twice its gain, so the
+/// gains are half the resonator amplitudes.
+pub fn to_faust(bowl: &Bowl) -> Result<String, FaustError> {
+    let model = ModalModel::from_modes(&bowl.modes);
+    if model.resonators.is_empty() {
+        return Err(FaustError::NoModes(bowl.id.clone()));
+    }
+    let list = |values: Vec<f32>| -> String {
+        values
+            .into_iter()
+            .map(faust_number)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let frequencies = list(model.resonators.iter().map(|r| r.frequency).collect());
+    let t60s = list(model.resonators.iter().map(|r| r.t60).collect());
+    let gains = list(model.resonators.iter().map(|r| r.amplitude / 2.0).collect());
+    let name = if bowl.name.is_empty() {
+        &bowl.id
+    } else {
+        &bowl.name
+    };
+
+    Ok(format!(
+        "\
+// Modal model of bowl '{id}', exported by singing_bowl_analysis.
+// Split modes are a pair of resonators the beat rate apart.
+declare name \"{name}\";
+declare description \"{description}\";
+
+import(\"stdfaust.lib\");
+
+nModes = {count};
+{FREQUENCIES} = ({frequencies});
+{T60S} = ({t60s});
+{GAINS} = ({gains});
+
+// modes at or above the Nyquist frequency of the running rate are muted, as in the app
+mode(f, t60, g) = pm.modeFilter(f, t60, g * (f < ma.SR / 2));
+bowl = _ <: par(i, nModes, mode(ba.take(i + 1, {FREQUENCIES}), ba.take(i + 1, {T60S}), ba.take(i + 1, {GAINS}))) :> _;
+strike = button(\"strike\") : ba.impulsify;
+
+process = _, strike : + : bowl;
+",
+        id = bowl.id,
+        name = faust_string(name),
+        description = faust_string(&bowl.notes),
+        count = model.resonators.len(),
+    ))
+}
+
+/// Read the mode lists back from an exported file
+pub fn parse_faust(text: &str) -> Result<FaustBank, FaustError> {
+    let list = |name: &str| -> Result<Vec<f32>, FaustError> {
+        let line = text
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name)?.trim().strip_prefix('='))
+            .ok_or_else(|| FaustError::Format(format!("no {} list", name)))?;
+        let values = line
+            .trim()
+            .strip_prefix('(')
+            .and_then(|line| line.strip_suffix(");"))
+            .ok_or_else(|| FaustError::Format(format!("{} is not a list", name)))?;
+        values
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| FaustError::Format(format!("'{}' is not a number", value.trim())))
+            })
+            .collect()
+    };
+
+    let bank = FaustBank {
+        frequencies: list(FREQUENCIES)?,
+        t60s: list(T60S)?,
+        gains: list(GAINS)?,
+    };
+    if bank.t60s.len() != bank.frequencies.len() || bank.gains.len() != bank.frequencies.len() {
+        return Err(FaustError::Format(String::from(
+            "lists of different lengths",
+        )));
+    }
+    Ok(bank)
+}
+
+/// Response of the bank to a unit strike, computed the way `pm.modeFilter` does in single
+/// precision. Modes at or above the Nyquist frequency are muted like in the exported code.
+pub fn render_bank(bank: &FaustBank, sample_rate: u32, len: usize) -> Vec<f32> {
+    let mut output = vec![0.0; len];
+    let nyquist = sample_rate as f32 / 2.0;
+    let modes = bank.frequencies.iter().zip(&bank.t60s).zip(&bank.gains);
+    for ((frequency, t60), gain) in modes {
+        if *frequency >= nyquist {
+            continue;
+        }
+        let w = 2.0 * std::f32::consts::PI * frequency / sample_rate as f32;
+        let r = 0.001_f32.powf(1.0 / (t60 * sample_rate as f32));
+        let (a1, a2) = (-2.0 * r * w.cos(), r * r);
+        // tf2 with b0 = 1, b1 = 0, b2 = -1
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        for (n, sample) in output.iter_mut().enumerate() {
+            let x = if n == 0 { 1.0 } else { 0.0 };
+            let y = x - x2 - a1 * y1 - a2 * y2;
+            (x2, x1, y2, y1) = (x1, x, y1, y);
+            *sample += y * gain;
+        }
+    }
+    output
+}
+
+/// Outcome of rendering an exported file against the modal synth
+pub struct RoundTrip {
+    pub resonators: usize,
+    /// Level of the difference between the magnitude spectra relative to the modal synth
+    pub error_db: f32,
+}
+
+impl RoundTrip {
+    pub fn passed(&self) -> bool {
+        self.error_db <= ROUND_TRIP_LIMIT_DB
+    }
+}
+
+/// Render the mode lists of an exported file and the app's modal synth of the bowl, and
+/// measure how far apart their spectra are
+pub fn round_trip(text: &str, bowl: &Bowl) -> Result<RoundTrip, FaustError> {
+    let bank = parse_faust(text)?;
+    let exported = render_bank(&bank, CHECK_SAMPLE_RATE, CHECK_FFT_SIZE);
+    let synth = ModalModel::from_modes(&bowl.modes).render(CHECK_SAMPLE_RATE, CHECK_FFT_SIZE);
+
+    let mut analyzer = SpectrumAnalyzer::new(CHECK_FFT_SIZE);
+    let magnitudes = |signal: &[f32], analyzer: &mut SpectrumAnalyzer| -> Vec<f64> {
+        analyzer
+            .analyze(signal, CHECK_SAMPLE_RATE)
+            .magnitudes_db
+            .iter()
+            .map(|db| 10.0_f64.powf(*db as f64 / 20.0))
+            .collect()
+    };
+    let exported = magnitudes(&exported, &mut analyzer);
+    let synth = magnitudes(&synth, &mut analyzer);
+    let energy: f64 = synth.iter().map(|s| s.powi(2)).sum();
+    let error: f64 = exported
+        .iter()
+        .zip(&synth)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum();
+    Ok(RoundTrip {
+        resonators: bank.frequencies.len(),
+        error_db: 10.0 * (error.max(1e-30) / energy.max(1e-30)).log10() as f32,
+    })
+}
+
+/// Compile the file with the Faust compiler. None if it isn't installed.
+pub fn compile(path: &Path) -> Option<Result<(), String>> {
+    let output = std::env::temp_dir().join("singing_bowl_analysis_faust.cpp");
+    let result = Command::new("faust")
+        .arg(path)
+        .arg("-o")
+        .arg(&output)
+        .output();
+    let _ = std::fs::remove_file(&output);
+    match result {
+        Ok(result) if result.status.success() => Some(Ok(())),
+        Ok(result) => Some(Err(String::from_utf8_lossy(&result.stderr)
+            .trim()
+            .to_string())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => Some(Err(e.to_string())),
+    }
+}
+
+/// Decimal with a point, so Faust reads it as a float
+fn faust_number(value: f32) -> String {
+    let text = value.to_string();
+    if text.contains('.') {
+        text
+    } else {
+        text + ".0"
+    }
+}
+
+fn faust_string(text: &str) -> String {
+    text.replace(['"', '\\'], "'").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::bowl::Mode;
+
+    fn bowl() -> Bowl {
+        let mut bowl = Bowl::new("test");
+        bowl.name = String::from("Test \"Bowl\"");
+        bowl.modes = vec![
+            Mode {
+                frequency: 212.4,
+                level_db: -12.0,
+                t60: Some(28.0),
+                beat_hz: Some(0.8),
+            },
+            Mode {
+                frequency: 586.1,
+                level_db: -20.0,
+                t60: Some(11.0),
+                beat_hz: None,
+            },
+            Mode {
+                frequency: 1120.5,
+                level_db: -31.0,
+                t60: None,
+                beat_hz: None,
+            },
+        ];
+        bowl
+    }
+
+    #[test]
+    fn exported_lists_parse_back() {
+        let bowl = bowl();
+        let bank = parse_faust(&to_faust(&bowl).unwrap()).unwrap();
+        let model = ModalModel::from_modes(&bowl.modes);
+        // the beating mode becomes a pair of resonators
+        assert_eq!(bank.frequencies.len(), 4);
+        for (i, resonator) in model.resonators.iter().enumerate() {
+            assert_eq!(bank.frequencies[i], resonator.frequency);
+            assert_eq!(bank.t60s[i], resonator.t60);
+            assert_eq!(bank.gains[i], resonator.amplitude / 2.0);
+        }
+    }
+
+    #[test]
+    fn export_renders_like_modal_synth() {
+        let bowl = bowl();
+        let result = round_trip(&to_faust(&bowl).unwrap(), &bowl).unwrap();
+        assert_eq!(result.resonators, 4);
+        assert!(result.passed(), "difference {} dB", result.error_db);
+    }
+
+    #[test]
+    fn modes_above_nyquist_are_muted() {
+        let mut bowl = bowl();
+        bowl.modes.push(Mode {
+            frequency: 30000.0,
+            level_db: -6.0,
+            t60: Some(5.0),
+            beat_hz: None,
+        });
+        let code = to_faust(&bowl).unwrap();
+        assert!(code.contains("(f < ma.SR / 2)"));
+        // the 48 kHz check rendering can't hold the mode, the exported bank leaves it out like
+        // the modal synth
+        let result = round_trip(&code, &bowl).unwrap();
+        assert_eq!(result.resonators, 5);
+        assert!(result.passed(), "difference {} dB", result.error_db);
+    }
+
+    #[test]
+    fn corrupted_gains_fail_round_trip() {
+        let bowl = bowl();
+        let code = to_faust(&bowl).unwrap();
+        let bank = parse_faust(&code).unwrap();
+        // the gains written as the resonator amplitudes, missing the mode filter's factor
+        let gains: Vec<String> = bank.gains.iter().map(|g| faust_number(g * 2.0)).collect();
+        let corrupted: String = code
+            .lines()
+            .map(|line| {
+                if line.starts_with(GAINS) {
+                    format!("{} = ({});\n", GAINS, gains.join(", "))
+                } else {
+                    format!("{}\n", line)
+                }
+            })
+            .collect();
+        let result = round_trip(&corrupted, &bowl).unwrap();
+        assert!(!result.passed(), "difference {} dB", result.error_db);
+    }
+
+    #[test]
+    fn exported_file_compiles() {
+        let path = std::env::temp_dir().join("singing_bowl_analysis_test.dsp");
+        std::fs::write(&path, to_faust(&bowl()).unwrap()).unwrap();
+        let result = compile(&path);
+        let _ = std::fs::remove_file(&path);
+        match result {
+            Some(result) => result.unwrap(),
+            None => eprintln!("faust not found, compilation skipped"),
+        }
+    }
+
+    #[test]
+    fn bowl_without_modes_is_not_exported() {
+        assert!(matches!(
+            to_faust(&Bowl::new("empty")),
+            Err(FaustError::NoModes(_))
+        ));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod bowl;
+pub mod faust;
+pub mod query;
+pub mod store;
@@ -0,0 +1,304 @@
+use std::fmt;
+
+use crate::catalog::bowl::Bowl;
+
+/// Tolerance of a plain number, e.g. "f0 440", relative to the value
+const EQUAL_TOLERANCE: f32 = 0.005;
+/// Units that may follow a number and are ignored
+const UNITS: [&str; 4] = ["hz", "mm", "s", "g"];
+
+#[derive(Debug)]
+pub struct QueryError(String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid query: {}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum NumberField {
+    Fundamental,
+    T60,
+    Diameter,
+    Weight,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TextField {
+    Id,
+    Name,
+    Material,
+    Origin,
+    Notes,
+    /// Any of the above
+    Any,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Comparison {
+    Between(f32, f32),
+    Greater(f32),
+    GreaterOrEqual(f32),
+    Less(f32),
+    LessOrEqual(f32),
+    About(f32),
+}
+
+/// Constructor of a comparison from its operand
+type ComparisonFn = fn(f32) -> Comparison;
+
+#[derive(Clone, PartialEq, Debug)]
+enum Term {
+    Number(NumberField, Comparison),
+    /// Case insensitive substring match
+    Text(TextField, String),
+}
+
+/// Catalog search, a comma separated list of conditions that all have to match, e.g.
+/// `f0 180-200, t60 > 30, material bronze`. Numeric fields are `f0`, `t60`, `diameter` and
+/// `weight`; text fields `id`, `name`, `material`, `origin` and `notes`. A term without a
+/// field searches all text fields.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, QueryError> {
+        let terms = text
+            .split(',')
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .map(parse_term)
+            .collect::<Result<_, _>>()?;
+        Ok(Query { terms })
+    }
+
+    pub fn matches(&self, bowl: &Bowl) -> bool {
+        self.terms.iter().all(|term| match term {
+            Term::Number(field, comparison) => {
+                let value = match field {
+                    NumberField::Fundamental => bowl.fundamental(),
+                    NumberField::T60 => bowl.t60(),
+                    NumberField::Diameter => bowl.diameter_mm,
+                    NumberField::Weight => bowl.weight_g,
+                };
+                value.is_some_and(|value| comparison.matches(value))
+            }
+            Term::Text(field, pattern) => {
+                let contains = |text: &str| text.to_lowercase().contains(pattern.as_str());
+                match field {
+                    TextField::Id => contains(&bowl.id),
+                    TextField::Name => contains(&bowl.name),
+                    TextField::Material => contains(&bowl.material),
+                    TextField::Origin => contains(&bowl.origin),
+                    TextField::Notes => contains(&bowl.notes),
+                    TextField::Any => [
+                        &bowl.id,
+                        &bowl.name,
+                        &bowl.material,
+                        &bowl.origin,
+                        &bowl.notes,
+                    ]
+                    .iter()
+                    .any(|text| contains(text)),
+                }
+            }
+        })
+    }
+}
+
+impl Comparison {
+    fn matches(&self, value: f32) -> bool {
+        match *self {
+            Comparison::Between(low, high) => value >= low && value <= high,
+            Comparison::Greater(x) => value > x,
+            Comparison::GreaterOrEqual(x) => value >= x,
+            Comparison::Less(x) => value < x,
+            Comparison::LessOrEqual(x) => value <= x,
+            Comparison::About(x) => (value - x).abs() <= x.abs() * EQUAL_TOLERANCE,
+        }
+    }
+}
+
+fn parse_term(term: &str) -> Result<Term, QueryError> {
+    let lower = term.to_lowercase();
+    let split = lower
+        .find(|c: char| c.is_whitespace() || "<>=:~".contains(c))
+        .unwrap_or(lower.len());
+    let (name, rest) = lower.split_at(split);
+
+    let number_field = match name {
+        "f0" | "fundamental" | "pitch" => Some(NumberField::Fundamental),
+        "t60" | "decay" => Some(NumberField::T60),
+        "diameter" => Some(NumberField::Diameter),
+        "weight" => Some(NumberField::Weight),
+        _ => None,
+    };
+    if let Some(field) = number_field {
+        return Ok(Term::Number(field, parse_comparison(rest.trim())?));
+    }
+
+    let text_field = match name {
+        "id" => Some(TextField::Id),
+        "name" => Some(TextField::Name),
+        "material" => Some(TextField::Material),
+        "origin" => Some(TextField::Origin),
+        "notes" => Some(TextField::Notes),
+        _ => None,
+    };
+    match text_field {
+        Some(field) => {
+            let pattern = rest.trim().trim_start_matches(['=', ':', '~']).trim();
+            Ok(Term::Text(field, pattern.to_string()))
+        }
+        None => Ok(Term::Text(TextField::Any, lower.trim().to_string())),
+    }
+}
+
+fn parse_comparison(text: &str) -> Result<Comparison, QueryError> {
+    let operators: [(&str, ComparisonFn); 7] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::About),
+        (":", Comparison::About),
+        ("~", Comparison::About),
+    ];
+    for (operator, comparison) in operators {
+        if let Some(value) = text.strip_prefix(operator) {
+            return Ok(comparison(parse_number(value)?));
+        }
+    }
+
+    if let Some(range) = text.strip_prefix("between ") {
+        let (low, high) = range
+            .split_once(" and ")
+            .ok_or_else(|| QueryError(format!("expected 'between <a> and <b>' in '{}'", text)))?;
+        return between(parse_number(low)?, parse_number(high)?);
+    }
+    // a leading '-' would be a negative number, not a range
+    if let Some((low, high)) = text.get(1..).and_then(|rest| rest.split_once('-')) {
+        return between(parse_number(&text[..1 + low.len()])?, parse_number(high)?);
+    }
+
+    Ok(Comparison::About(parse_number(text)?))
+}
+
+/// Bounds in either order, so "f0 200-180" means "f0 180-200"
+fn between(low: f32, high: f32) -> Result<Comparison, QueryError> {
+    Ok(Comparison::Between(low.min(high), low.max(high)))
+}
+
+fn parse_number(text: &str) -> Result<f32, QueryError> {
+    let text = text.trim();
+    let number = UNITS
+        .iter()
+        .find_map(|unit| text.strip_suffix(unit))
+        .unwrap_or(text)
+        .trim();
+    // "nan" and "inf" parse, but compare with nothing sensibly
+    number
+        .parse()
+        .ok()
+        .filter(|number: &f32| number.is_finite())
+        .ok_or_else(|| QueryError(format!("'{}' is not a number", text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::bowl::Mode;
+
+    fn comparison(text: &str) -> Comparison {
+        match Query::parse(text).unwrap().terms.as_slice() {
+            [Term::Number(_, comparison)] => *comparison,
+            terms => panic!("'{}' parsed as {:?}", text, terms),
+        }
+    }
+
+    #[test]
+    fn parses_comparisons() {
+        assert_eq!(comparison("f0 180-200"), Comparison::Between(180.0, 200.0));
+        assert_eq!(
+            comparison("f0 between 180 and 200 hz"),
+            Comparison::Between(180.0, 200.0)
+        );
+        assert_eq!(comparison("t60 > 30"), Comparison::Greater(30.0));
+        assert_eq!(comparison("t60>=30s"), Comparison::GreaterOrEqual(30.0));
+        assert_eq!(comparison("weight < 900 g"), Comparison::Less(900.0));
+        assert_eq!(
+            comparison("diameter <= 150mm"),
+            Comparison::LessOrEqual(150.0)
+        );
+        assert_eq!(comparison("pitch 440"), Comparison::About(440.0));
+        assert_eq!(comparison("f0 ~ 440"), Comparison::About(440.0));
+    }
+
+    #[test]
+    fn inverted_range_is_swapped() {
+        assert_eq!(comparison("f0 200-180"), Comparison::Between(180.0, 200.0));
+        assert_eq!(
+            comparison("f0 between 200 and 180"),
+            Comparison::Between(180.0, 200.0)
+        );
+    }
+
+    #[test]
+    fn leading_minus_is_a_sign() {
+        assert_eq!(comparison("t60 -5"), Comparison::About(-5.0));
+        assert_eq!(comparison("t60 -5-3"), Comparison::Between(-5.0, 3.0));
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        for text in [
+            "f0 nan",
+            "f0 inf",
+            "f0 180-nan",
+            "t60 > -inf",
+            "f0 loud",
+            "f0 between 180",
+            "weight",
+        ] {
+            assert!(Query::parse(text).is_err(), "'{}' was accepted", text);
+        }
+    }
+
+    #[test]
+    fn parses_text_terms() {
+        let query = Query::parse("Material: Bronze, nepal").unwrap();
+        assert_eq!(
+            query.terms,
+            vec![
+                Term::Text(TextField::Material, String::from("bronze")),
+                Term::Text(TextField::Any, String::from("nepal")),
+            ]
+        );
+        assert_eq!(Query::parse(" , ").unwrap(), Query::default());
+    }
+
+    #[test]
+    fn matches_all_terms() {
+        let mut bowl = Bowl::new("b1");
+        bowl.material = String::from("Bronze");
+        bowl.origin = String::from("Nepal");
+        bowl.modes = vec![Mode {
+            frequency: 190.0,
+            level_db: 0.0,
+            t60: Some(35.0),
+            beat_hz: None,
+        }];
+
+        let matches = |text: &str| Query::parse(text).unwrap().matches(&bowl);
+        assert!(matches("f0 180-200, t60 > 30, material bronze"));
+        assert!(matches("f0 190.5"));
+        assert!(matches("nepal"));
+        assert!(!matches("f0 180-200, t60 > 40"));
+        assert!(!matches("f0 200-220"));
+        // a bowl without a weight doesn't match any weight
+        assert!(!matches("weight > 0"));
+    }
+}
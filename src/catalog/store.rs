@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::catalog::bowl::{is_valid_id, Bowl};
+use crate::catalog::query::Query;
+
+const APP_DIR: &str = "singing_bowl_analysis";
+const CATALOG_DIR: &str = "catalog";
+const BOWL_EXTENSION: &str = "toml";
+
+#[derive(Debug)]
+pub enum CatalogError {
+    Io(io::Error),
+    /// A bowl file that can't be read or written as TOML
+    Format(PathBuf, String),
+    InvalidId(String),
+    NotFound(String),
+    NoDataDir,
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::Io(e) => write!(f, "{}", e),
+            CatalogError::Format(path, e) => write!(f, "{}: {}", path.display(), e),
+            CatalogError::InvalidId(id) => {
+                write!(f, "invalid id '{}', use letters, digits, '-' and '_'", id)
+            }
+            CatalogError::NotFound(id) => write!(f, "no bowl with id '{}'", id),
+            CatalogError::NoDataDir => write!(f, "no user data directory"),
+        }
+    }
+}
+
+impl From<io::Error> for CatalogError {
+    fn from(e: io::Error) -> Self {
+        CatalogError::Io(e)
+    }
+}
+
+/// Bowl catalog stored as one TOML file per bowl, so entries can be diffed, synced and edited
+/// by hand. Lives in the user's data directory
+/// (`$XDG_DATA_HOME/singing_bowl_analysis/catalog` on Linux).
+pub struct Catalog {
+    dir: PathBuf,
+    /// Sorted by id
+    bowls: Vec<Bowl>,
+}
+
+impl Catalog {
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(APP_DIR).join(CATALOG_DIR))
+    }
+
+    pub fn open_default() -> Result<Self, CatalogError> {
+        Self::open(&Self::default_dir().ok_or(CatalogError::NoDataDir)?)
+    }
+
+    /// Load every bowl in `dir`, creating it if needed. Files that don't parse are reported
+    /// and skipped rather than making the whole catalog unusable.
+    pub fn open(dir: &Path) -> Result<Self, CatalogError> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut bowls = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(BOWL_EXTENSION) {
+                continue;
+            }
+            let contents = std::fs::read_to_string(&path)?;
+            match toml::from_str::<Bowl>(&contents) {
+                Ok(bowl) => bowls.push(bowl),
+                Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        bowls.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(Catalog {
+            dir: dir.to_path_buf(),
+            bowls,
+        })
+    }
+
+    pub fn bowls(&self) -> &[Bowl] {
+        &self.bowls
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Bowl> {
+        self.bowls.iter().find(|bowl| bowl.id == id)
+    }
+
+    pub fn search(&self, query: &Query) -> Vec<&Bowl> {
+        self.bowls
+            .iter()
+            .filter(|bowl| query.matches(bowl))
+            .collect()
+    }
+
+    /// Add or replace a bowl and write it to disk
+    pub fn save(&mut self, bowl: Bowl) -> Result<(), CatalogError> {
+        if !is_valid_id(&bowl.id) {
+            return Err(CatalogError::InvalidId(bowl.id));
+        }
+
+        let path = self.path(&bowl.id);
+        let contents = toml::to_string_pretty(&bowl)
+            .map_err(|e| CatalogError::Format(path.clone(), e.to_string()))?;
+        std::fs::write(&path, contents)?;
+
+        match self.bowls.binary_search_by(|b| b.id.cmp(&bowl.id)) {
+            Ok(index) => self.bowls[index] = bowl,
+            Err(index) => self.bowls.insert(index, bowl),
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<Bowl, CatalogError> {
+        let index = self
+            .bowls
+            .iter()
+            .position(|bowl| bowl.id == id)
+            .ok_or_else(|| CatalogError::NotFound(id.to_string()))?;
+        std::fs::remove_file(self.path(id))?;
+        Ok(self.bowls.remove(index))
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id).with_extension(BOWL_EXTENSION)
+    }
+}
@@ -0,0 +1,195 @@
+use std::path::{Path, PathBuf};
+
+use crate::catalog::bowl::Bowl;
+use crate::catalog::faust::{compile, round_trip, to_faust, ROUND_TRIP_LIMIT_DB};
+use crate::catalog::query::Query;
+use crate::catalog::store::Catalog;
+
+const USAGE: &str = "\
+Usage: singing_bowl_analysis [command]
+
+Without a command the GUI is started.
+
+Catalog commands:
+  catalog list                        List all bowls
+  catalog search <query>              e.g. \"f0 180-200, t60 > 30, material bronze\"
+  catalog show <id>                   Show a bowl with its modes and recordings
+  catalog add <id> [field=value ...]  Add a bowl
+  catalog set <id> field=value ...    Change fields of a bowl
+  catalog link <id> <recording>       Link a recording to a bowl
+  catalog remove <id>                 Remove a bowl
+  catalog faust <id> <file.dsp>       Export a bowl's modes as a Faust modal model
+  catalog faust-check <id> <file.dsp> Render an exported file against the modal synth
+
+Fields: name, diameter (mm), weight (g), material, origin, notes";
+
+/// Run a command line command, returning the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["catalog", rest @ ..] => run_catalog(rest),
+        ["help" | "--help" | "-h"] => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("unknown command\n\n{}", USAGE)),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn run_catalog(args: &[&str]) -> Result<(), String> {
+    let mut catalog = Catalog::open_default().map_err(|e| e.to_string())?;
+
+    match args {
+        ["list"] => print_bowls(catalog.bowls().iter()),
+        ["search", query @ ..] => {
+            let query = Query::parse(&query.join(" ")).map_err(|e| e.to_string())?;
+            print_bowls(catalog.search(&query).into_iter());
+        }
+        ["show", id] => {
+            let bowl = catalog
+                .get(id)
+                .ok_or_else(|| format!("no bowl with id '{}'", id))?;
+            print_bowl(bowl);
+        }
+        ["add", id, fields @ ..] => {
+            if catalog.get(id).is_some() {
+                return Err(format!("bowl '{}' already exists", id));
+            }
+            let mut bowl = Bowl::new(id);
+            set_fields(&mut bowl, fields)?;
+            catalog.save(bowl).map_err(|e| e.to_string())?;
+        }
+        ["set", id, fields @ ..] => {
+            let mut bowl = catalog
+                .get(id)
+                .cloned()
+                .ok_or_else(|| format!("no bowl with id '{}'", id))?;
+            set_fields(&mut bowl, fields)?;
+            catalog.save(bowl).map_err(|e| e.to_string())?;
+        }
+        ["link", id, recording] => {
+            let mut bowl = catalog
+                .get(id)
+                .cloned()
+                .ok_or_else(|| format!("no bowl with id '{}'", id))?;
+            let path = PathBuf::from(recording);
+            let path = path.canonicalize().unwrap_or(path);
+            if !bowl.recordings.contains(&path) {
+                bowl.recordings.push(path);
+            }
+            catalog.save(bowl).map_err(|e| e.to_string())?;
+        }
+        ["remove", id] => {
+            catalog.remove(id).map_err(|e| e.to_string())?;
+        }
+        ["faust", id, output] => {
+            let bowl = catalog
+                .get(id)
+                .ok_or_else(|| format!("no bowl with id '{}'", id))?;
+            let code = to_faust(bowl).map_err(|e| e.to_string())?;
+            std::fs::write(output, code).map_err(|e| format!("{}: {}", output, e))?;
+            println!("Wrote {}", output);
+            check_faust(bowl, output)?;
+        }
+        ["faust-check", id, file] => {
+            let bowl = catalog
+                .get(id)
+                .ok_or_else(|| format!("no bowl with id '{}'", id))?;
+            check_faust(bowl, file)?;
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+/// Render an exported file against the modal synth of the bowl, and compile it if the Faust
+/// compiler is installed
+fn check_faust(bowl: &Bowl, file: &str) -> Result<(), String> {
+    let code = std::fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+    let result = round_trip(&code, bowl).map_err(|e| format!("{}: {}", file, e))?;
+    println!(
+        "Round trip: {} resonators, {:.1} dB from the modal synth",
+        result.resonators, result.error_db
+    );
+    match compile(Path::new(file)) {
+        Some(Ok(())) => println!("Compiled with faust"),
+        Some(Err(e)) => return Err(format!("faust failed to compile {}:\n{}", file, e)),
+        None => println!("faust not found, compilation skipped"),
+    }
+    if !result.passed() {
+        return Err(format!(
+            "round trip failed: difference above {:.0} dB",
+            ROUND_TRIP_LIMIT_DB
+        ));
+    }
+    Ok(())
+}
+
+fn set_fields(bowl: &mut Bowl, fields: &[&str]) -> Result<(), String> {
+    for field in fields {
+        let (name, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("expected field=value, got '{}'", field))?;
+        bowl.set_field(name, value)?;
+    }
+    Ok(())
+}
+
+fn format_optional(value: Option<f32>, precision: usize, unit: &str) -> String {
+    match value {
+        Some(value) => format!("{:.*} {}", precision, value, unit),
+        None => String::from("-"),
+    }
+}
+
+fn print_bowls<'a>(bowls: impl Iterator<Item = &'a Bowl>) {
+    println!(
+        "{:<16} {:<24} {:>12} {:>10} {:<12} {:<12}",
+        "ID", "NAME", "F0", "T60", "MATERIAL", "ORIGIN"
+    );
+    for bowl in bowls {
+        println!(
+            "{:<16} {:<24} {:>12} {:>10} {:<12} {:<12}",
+            bowl.id,
+            bowl.name,
+            format_optional(bowl.fundamental(), 1, "Hz"),
+            format_optional(bowl.t60(), 1, "s"),
+            bowl.material,
+            bowl.origin
+        );
+    }
+}
+
+fn print_bowl(bowl: &Bowl) {
+    println!("ID:       {}", bowl.id);
+    println!("Name:     {}", bowl.name);
+    println!("Diameter: {}", format_optional(bowl.diameter_mm, 0, "mm"));
+    println!("Weight:   {}", format_optional(bowl.weight_g, 0, "g"));
+    println!("Material: {}", bowl.material);
+    println!("Origin:   {}", bowl.origin);
+    println!("Notes:    {}", bowl.notes);
+
+    println!("\nModes:");
+    for mode in bowl.modes.iter() {
+        println!(
+            "  {:>10.2} Hz  {:>7.1} dB  T60 {}  Beat {}",
+            mode.frequency,
+            mode.level_db,
+            format_optional(mode.t60, 1, "s"),
+            format_optional(mode.beat_hz, 2, "Hz")
+        );
+    }
+
+    println!("\nRecordings:");
+    for recording in bowl.recordings.iter() {
+        println!("  {}", recording.display());
+    }
+}
@@ -1,10 +1,22 @@
+mod analysis;
 mod audio_engine;
+mod catalog;
+mod cli;
+mod recorder;
+mod settings;
 mod user_interface;
 
 //use user_interface::UserInterface;
 
 fn main() {
-    let io = audio_engine::io_manager::IOManager::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
+    let settings = settings::Settings::load();
+    let mut io = audio_engine::io_manager::IOManager::new();
+    settings.apply(&mut io);
     let ui = user_interface::ui::UserInterface::new();
-    ui.run(io);
+    ui.run(io, settings);
 }
@@ -0,0 +1,264 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::onset::Onset;
+use crate::audio_engine::params::db_to_gain;
+use crate::settings::RecorderSettings;
+
+/// Takes are cut after this long even if the signal never decays
+const MAX_TAKE_SECONDS: f32 = 600.0;
+/// Extra history kept beyond the pre-roll, covering the latency of the strike detector
+const DETECTION_LATENCY_SECONDS: f32 = 0.1;
+
+/// What starts a take
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerMode {
+    /// Only the record button
+    Manual,
+    /// The input peak crossing the trigger level
+    Level,
+    /// A strike found by the onset detector
+    Onset,
+}
+
+#[derive(Debug)]
+pub enum RecorderError {
+    NoDirectory,
+    Io(std::io::Error),
+    Wav(hound::Error),
+}
+
+impl std::fmt::Display for RecorderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RecorderError::NoDirectory => write!(f, "no directory for recordings"),
+            RecorderError::Io(e) => write!(f, "{}", e),
+            RecorderError::Wav(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for RecorderError {
+    fn from(e: std::io::Error) -> Self {
+        RecorderError::Io(e)
+    }
+}
+
+impl From<hound::Error> for RecorderError {
+    fn from(e: hound::Error) -> Self {
+        RecorderError::Wav(e)
+    }
+}
+
+struct Take {
+    path: PathBuf,
+    writer: hound::WavWriter<BufWriter<File>>,
+    samples: u64,
+    /// Consecutive samples below the stop level
+    quiet_samples: u64,
+    /// Started by a trigger rather than the record button, and so stopped automatically
+    triggered: bool,
+}
+
+/// Writes the input to WAV files, started by hand or by a trigger. A history of the input is
+/// kept at all times so a triggered take begins the pre-roll before the trigger.
+pub struct Recorder {
+    settings: RecorderSettings,
+    /// Waiting for a trigger
+    armed: bool,
+    sample_rate: u32,
+    /// Most recent input, oldest first
+    history: VecDeque<f32>,
+    take: Option<Take>,
+    /// Finished takes, oldest first
+    takes: Vec<PathBuf>,
+}
+
+impl Recorder {
+    pub fn new(settings: &RecorderSettings) -> Self {
+        Recorder {
+            settings: settings.clone(),
+            armed: false,
+            sample_rate: 0,
+            history: VecDeque::new(),
+            take: None,
+            takes: Vec::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &RecorderSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: RecorderSettings) {
+        if settings.trigger == TriggerMode::Manual {
+            self.armed = false;
+        }
+        self.settings = settings;
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    /// Wait for triggers, taking one take per trigger until disarmed
+    pub fn set_armed(&mut self, armed: bool) {
+        self.armed = armed && self.settings.trigger != TriggerMode::Manual;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.take.is_some()
+    }
+
+    /// Length of the current take in seconds
+    pub fn take_seconds(&self) -> Option<f32> {
+        self.take
+            .as_ref()
+            .map(|take| take.samples as f32 / self.sample_rate.max(1) as f32)
+    }
+
+    pub fn takes(&self) -> &[PathBuf] {
+        &self.takes
+    }
+
+    /// Start a take by hand, including the pre-roll. It runs until `stop`.
+    pub fn start(&mut self) -> Result<(), RecorderError> {
+        if self.take.is_some() || self.sample_rate == 0 {
+            return Ok(());
+        }
+        let pre_roll = self.pre_roll_samples().min(self.history.len());
+        self.start_take(self.history.len() - pre_roll, false)
+    }
+
+    pub fn stop(&mut self) -> Result<(), RecorderError> {
+        let Some(take) = self.take.take() else {
+            return Ok(());
+        };
+        take.writer.finalize()?;
+        self.takes.push(take.path);
+        Ok(())
+    }
+
+    /// Feed the newest input. `onsets` are the strikes detected in it, `end_position` the
+    /// detector's sample count at the end of `samples`.
+    pub fn process(
+        &mut self,
+        samples: &[f32],
+        sample_rate: u32,
+        onsets: &[Onset],
+        end_position: u64,
+    ) -> Result<(), RecorderError> {
+        if sample_rate != self.sample_rate {
+            self.stop()?;
+            self.history.clear();
+            self.sample_rate = sample_rate;
+        }
+        if samples.is_empty() || sample_rate == 0 {
+            return Ok(());
+        }
+
+        self.history.extend(samples);
+
+        if let Some(take) = &mut self.take {
+            for &sample in samples {
+                take.writer.write_sample(sample)?;
+            }
+            take.samples += samples.len() as u64;
+
+            let stop_level = db_to_gain(self.settings.stop_level_db);
+            match samples.iter().rposition(|s| s.abs() >= stop_level) {
+                Some(loud) => take.quiet_samples = (samples.len() - 1 - loud) as u64,
+                None => take.quiet_samples += samples.len() as u64,
+            }
+            let hold = (self.settings.stop_hold_seconds * sample_rate as f32) as u64;
+            let max_len = (MAX_TAKE_SECONDS * sample_rate as f32) as u64;
+            if (take.triggered && take.quiet_samples >= hold) || take.samples >= max_len {
+                self.stop()?;
+            }
+        } else if self.armed {
+            if let Some(trigger) = self.find_trigger(samples, onsets, end_position) {
+                let start = trigger.saturating_sub(self.pre_roll_samples());
+                self.start_take(start, true)?;
+            }
+        }
+
+        let keep =
+            self.pre_roll_samples() + (DETECTION_LATENCY_SECONDS * sample_rate as f32) as usize;
+        if self.history.len() > keep {
+            self.history.drain(..self.history.len() - keep);
+        }
+        Ok(())
+    }
+
+    /// Position of the trigger in the history, which ends with `samples`
+    fn find_trigger(&self, samples: &[f32], onsets: &[Onset], end_position: u64) -> Option<usize> {
+        let previous_len = self.history.len() - samples.len();
+        match self.settings.trigger {
+            TriggerMode::Manual => None,
+            TriggerMode::Level => {
+                let level = db_to_gain(self.settings.trigger_level_db);
+                let offset = samples.iter().position(|s| s.abs() >= level)?;
+                Some(previous_len + offset)
+            }
+            // onsets lag the input, so they usually lie before the newest samples
+            TriggerMode::Onset => onsets.first().map(|onset| {
+                let age = end_position.saturating_sub(onset.sample) as usize;
+                self.history.len().saturating_sub(age)
+            }),
+        }
+    }
+
+    fn pre_roll_samples(&self) -> usize {
+        (self.settings.pre_roll_seconds.max(0.0) * self.sample_rate as f32) as usize
+    }
+
+    /// Begin writing a take with the history from `start` on
+    fn start_take(&mut self, start: usize, triggered: bool) -> Result<(), RecorderError> {
+        let directory = self
+            .settings
+            .directory()
+            .ok_or(RecorderError::NoDirectory)?;
+        std::fs::create_dir_all(&directory)?;
+        let path = take_path(&directory);
+
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec)?;
+        for &sample in self.history.range(start.min(self.history.len())..) {
+            writer.write_sample(sample)?;
+        }
+
+        self.take = Some(Take {
+            path,
+            writer,
+            samples: (self.history.len() - start.min(self.history.len())) as u64,
+            quiet_samples: 0,
+            triggered,
+        });
+        Ok(())
+    }
+}
+
+/// `take_<unix time>.wav`, numbered if several takes start within a second
+fn take_path(directory: &Path) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let mut path = directory.join(format!("take_{}.wav", seconds));
+    let mut number = 2;
+    while path.exists() {
+        path = directory.join(format!("take_{}_{}.wav", seconds, number));
+        number += 1;
+    }
+    path
+}
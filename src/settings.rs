@@ -0,0 +1,465 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::onset::OnsetFunction;
+use crate::analysis::pitch::PitchAlgorithm;
+use crate::analysis::tuning::Temperament;
+use crate::audio_engine::convolution::ImpulseResponse;
+use crate::audio_engine::denoise::DenoiseMethod;
+use crate::audio_engine::dynamics::{DynamicsKind, DynamicsParams, StageParams};
+use crate::audio_engine::io_manager::{IOManager, StreamOptions};
+use crate::recorder::TriggerMode;
+
+const APP_DIR: &str = "singing_bowl_analysis";
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// Everything restored on the next launch. Stored as TOML in the user's config directory
+/// (`$XDG_CONFIG_HOME/singing_bowl_analysis/settings.toml` on Linux).
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings,
+    pub dsp: DspSettings,
+    pub tuner: TunerSettings,
+    pub onset: OnsetSettings,
+    pub recorder: RecorderSettings,
+    pub ui: UiSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub host: Option<String>,
+    /// Devices are stored by name since indices change between sessions
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+    pub sample_rate: u32,
+    pub buffer_size: u32,
+    /// Input channel fed to processing
+    pub input_channel: usize,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            host: None,
+            input_device: None,
+            output_device: None,
+            sample_rate: 44100,
+            buffer_size: 512,
+            input_channel: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct DspSettings {
+    pub frequency_domain_processing: bool,
+    /// Live pitch shift of the frequency domain pass
+    pub pitch_shift_semitones: f32,
+    pub monitor: MonitorSettings,
+    pub noise_reduction: NoiseReductionSettings,
+    pub dynamics: DynamicsSettings,
+    pub reverb: ReverbSettings,
+    /// Estimator behind the tuner
+    pub pitch_algorithm: PitchAlgorithm,
+}
+
+impl Default for DspSettings {
+    fn default() -> Self {
+        DspSettings {
+            frequency_domain_processing: true,
+            pitch_shift_semitones: 0.0,
+            monitor: MonitorSettings::default(),
+            noise_reduction: NoiseReductionSettings::default(),
+            dynamics: DynamicsSettings::default(),
+            reverb: ReverbSettings::default(),
+            pitch_algorithm: PitchAlgorithm::SpectralPeak,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct MonitorSettings {
+    pub input_gain_db: f32,
+    pub output_gain_db: f32,
+    /// 0.0 is the unprocessed input, 1.0 the processed signal
+    pub mix: f32,
+    pub mute: bool,
+    pub limiter: bool,
+}
+
+impl Default for MonitorSettings {
+    fn default() -> Self {
+        MonitorSettings {
+            input_gain_db: 0.0,
+            output_gain_db: 0.0,
+            mix: 1.0,
+            mute: false,
+            limiter: true,
+        }
+    }
+}
+
+/// Noise reduction of the frequency domain pass. The noise profile itself is learned again
+/// each session, as the noise of the room and the gain staging change.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct NoiseReductionSettings {
+    pub enabled: bool,
+    pub method: DenoiseMethod,
+    /// Most the noise is attenuated, in dB
+    pub reduction_db: f32,
+    pub smoothing: f32,
+}
+
+impl Default for NoiseReductionSettings {
+    fn default() -> Self {
+        NoiseReductionSettings {
+            enabled: false,
+            method: DenoiseMethod::Wiener,
+            reduction_db: 20.0,
+            smoothing: 0.98,
+        }
+    }
+}
+
+/// Gate, expander, compressor and limiter of the time domain chain
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct DynamicsSettings {
+    pub gate: DynamicsStageSettings,
+    pub expander: DynamicsStageSettings,
+    pub compressor: DynamicsStageSettings,
+    pub limiter: DynamicsStageSettings,
+}
+
+impl Default for DynamicsSettings {
+    fn default() -> Self {
+        DynamicsSettings::capture(&DynamicsParams::new())
+    }
+}
+
+impl DynamicsSettings {
+    fn capture(params: &DynamicsParams) -> Self {
+        let stage = |kind| DynamicsStageSettings::capture(params.stage(kind));
+        DynamicsSettings {
+            gate: stage(DynamicsKind::Gate),
+            expander: stage(DynamicsKind::Expander),
+            compressor: stage(DynamicsKind::Compressor),
+            limiter: stage(DynamicsKind::Limiter),
+        }
+    }
+
+    fn apply(&self, params: &DynamicsParams) {
+        self.gate.apply(params.stage(DynamicsKind::Gate));
+        self.expander.apply(params.stage(DynamicsKind::Expander));
+        self.compressor
+            .apply(params.stage(DynamicsKind::Compressor));
+        self.limiter.apply(params.stage(DynamicsKind::Limiter));
+    }
+}
+
+/// Controls of one dynamics processor; those it doesn't use are kept anyway
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct DynamicsStageSettings {
+    pub enabled: bool,
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub knee_db: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub range_db: f32,
+    pub lookahead_ms: f32,
+    pub sidechain_highpass_hz: f32,
+    pub sidechain_lowpass_hz: f32,
+}
+
+impl Default for DynamicsStageSettings {
+    fn default() -> Self {
+        DynamicsStageSettings::capture(&StageParams::new(DynamicsKind::Compressor))
+    }
+}
+
+impl DynamicsStageSettings {
+    fn capture(params: &StageParams) -> Self {
+        DynamicsStageSettings {
+            enabled: params.enabled(),
+            threshold_db: params.threshold_db(),
+            ratio: params.ratio(),
+            knee_db: params.knee_db(),
+            attack_ms: params.attack_ms(),
+            release_ms: params.release_ms(),
+            range_db: params.range_db(),
+            lookahead_ms: params.lookahead_ms(),
+            sidechain_highpass_hz: params.sidechain_highpass_hz(),
+            sidechain_lowpass_hz: params.sidechain_lowpass_hz(),
+        }
+    }
+
+    fn apply(&self, params: &StageParams) {
+        params.set_enabled(self.enabled);
+        params.set_threshold_db(self.threshold_db);
+        params.set_ratio(self.ratio);
+        params.set_knee_db(self.knee_db);
+        params.set_attack_ms(self.attack_ms);
+        params.set_release_ms(self.release_ms);
+        params.set_range_db(self.range_db);
+        params.set_lookahead_ms(self.lookahead_ms);
+        params.set_sidechain_highpass_hz(self.sidechain_highpass_hz);
+        params.set_sidechain_lowpass_hz(self.sidechain_lowpass_hz);
+    }
+}
+
+/// Convolution reverb on the output path
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReverbSettings {
+    pub enabled: bool,
+    /// WAV file of the impulse response, loaded again on launch
+    pub impulse_response: Option<PathBuf>,
+    /// 0.0 is only the dry signal, 1.0 only the reverb
+    pub mix: f32,
+    pub pre_delay_ms: f32,
+}
+
+impl Default for ReverbSettings {
+    fn default() -> Self {
+        ReverbSettings {
+            enabled: false,
+            impulse_response: None,
+            mix: 0.3,
+            pre_delay_ms: 0.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TunerSettings {
+    /// Frequency of A4 in Hz
+    pub reference_a4: f32,
+    pub temperament: Temperament,
+    /// Pitch class the scale starts on, 0 being C
+    pub root: usize,
+    pub scala_file: Option<PathBuf>,
+}
+
+impl Default for TunerSettings {
+    fn default() -> Self {
+        TunerSettings {
+            reference_a4: 440.0,
+            temperament: Temperament::Equal,
+            root: 0,
+            scala_file: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct OnsetSettings {
+    pub function: OnsetFunction,
+    /// 0 detects only clear strikes, 1 also soft ones
+    pub sensitivity: f32,
+}
+
+impl Default for OnsetSettings {
+    fn default() -> Self {
+        OnsetSettings {
+            function: OnsetFunction::ComplexDomain,
+            sensitivity: 0.5,
+        }
+    }
+}
+
+/// Configuration of sound-activated recording
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RecorderSettings {
+    /// Where takes are written, the data directory if unset
+    pub directory: Option<PathBuf>,
+    pub trigger: TriggerMode,
+    /// Peak level in dBFS starting a take in level mode
+    pub trigger_level_db: f32,
+    /// Input before the trigger included in the take
+    pub pre_roll_seconds: f32,
+    /// A triggered take stops once the level stays below this for `stop_hold_seconds`
+    pub stop_level_db: f32,
+    pub stop_hold_seconds: f32,
+}
+
+impl Default for RecorderSettings {
+    fn default() -> Self {
+        RecorderSettings {
+            directory: None,
+            trigger: TriggerMode::Onset,
+            trigger_level_db: -30.0,
+            pre_roll_seconds: 0.5,
+            stop_level_db: -60.0,
+            stop_hold_seconds: 3.0,
+        }
+    }
+}
+
+impl RecorderSettings {
+    /// Directory takes are written to
+    pub fn directory(&self) -> Option<PathBuf> {
+        self.directory
+            .clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join(APP_DIR).join("recordings")))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct UiSettings {
+    /// imgui window layout in its ini format
+    pub layout: Option<String>,
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR).join(SETTINGS_FILE))
+    }
+
+    /// Load the settings file, falling back to defaults if it is missing or unreadable.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Settings::default();
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Could not parse {}: {}", path.display(), e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)
+    }
+
+    /// Restore the audio and DSP state. Missing hosts or devices leave the defaults in place;
+    /// a missing device is still remembered and reconnected when it is plugged in.
+    pub fn apply(&self, io_manager: &mut IOManager) {
+        let audio = &self.audio;
+
+        if let Some(host) = &audio.host {
+            match io_manager.get_host_names().iter().position(|h| h == host) {
+                Some(index) => {
+                    if let Err(e) = io_manager.set_host(index) {
+                        eprintln!("Could not restore host {}: {}", host, e);
+                    }
+                }
+                None => eprintln!("Saved host {} is not available", host),
+            }
+        }
+
+        io_manager.set_stream_options(StreamOptions {
+            sample_rate: audio.sample_rate,
+            buffer_size: audio.buffer_size,
+            input_channel: audio.input_channel,
+        });
+
+        if let Some(device) = &audio.input_device {
+            io_manager.select_input_device(device);
+        }
+        if let Some(device) = &audio.output_device {
+            io_manager.select_output_device(device);
+        }
+
+        io_manager.set_frequency_domain_processing(self.dsp.frequency_domain_processing);
+        io_manager
+            .pitch_shift()
+            .set_semitones(self.dsp.pitch_shift_semitones);
+
+        let monitor = io_manager.monitor();
+        monitor.set_input_gain_db(self.dsp.monitor.input_gain_db);
+        monitor.set_output_gain_db(self.dsp.monitor.output_gain_db);
+        monitor.set_mix(self.dsp.monitor.mix);
+        monitor.set_mute(self.dsp.monitor.mute);
+        monitor.set_limiter(self.dsp.monitor.limiter);
+
+        let denoise = io_manager.denoise();
+        let noise_reduction = &self.dsp.noise_reduction;
+        denoise.set_enabled(noise_reduction.enabled);
+        denoise.set_method(noise_reduction.method);
+        denoise.set_reduction_db(noise_reduction.reduction_db);
+        denoise.set_smoothing(noise_reduction.smoothing);
+
+        self.dsp.dynamics.apply(io_manager.dynamics());
+
+        let reverb = &self.dsp.reverb;
+        let convolution = io_manager.convolution();
+        convolution.set_enabled(reverb.enabled);
+        convolution.set_mix(reverb.mix);
+        convolution.set_pre_delay_ms(reverb.pre_delay_ms);
+        if let Some(path) = &reverb.impulse_response {
+            match ImpulseResponse::load(path) {
+                Ok(impulse_response) => io_manager.set_impulse_response(Some(impulse_response)),
+                Err(e) => eprintln!("Could not load impulse response {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Update the audio and DSP sections from the current state.
+    pub fn capture(&mut self, io_manager: &IOManager) {
+        let options = io_manager.get_stream_options();
+
+        self.audio = AudioSettings {
+            host: Some(io_manager.get_current_host_name()),
+            input_device: io_manager.get_input_device_name(),
+            output_device: io_manager.get_output_device_name(),
+            sample_rate: options.sample_rate,
+            buffer_size: options.buffer_size,
+            input_channel: options.input_channel,
+        };
+        self.dsp.frequency_domain_processing = io_manager.get_frequency_domain_processing();
+        self.dsp.pitch_shift_semitones = io_manager.pitch_shift().semitones();
+
+        let monitor = io_manager.monitor();
+        self.dsp.monitor = MonitorSettings {
+            input_gain_db: monitor.input_gain_db(),
+            output_gain_db: monitor.output_gain_db(),
+            mix: monitor.mix(),
+            mute: monitor.mute(),
+            limiter: monitor.limiter(),
+        };
+
+        let denoise = io_manager.denoise();
+        self.dsp.noise_reduction = NoiseReductionSettings {
+            enabled: denoise.enabled(),
+            method: denoise.method(),
+            reduction_db: denoise.reduction_db(),
+            smoothing: denoise.smoothing(),
+        };
+
+        self.dsp.dynamics = DynamicsSettings::capture(io_manager.dynamics());
+
+        let convolution = io_manager.convolution();
+        self.dsp.reverb = ReverbSettings {
+            enabled: convolution.enabled(),
+            impulse_response: convolution
+                .impulse_response()
+                .map(|ir| ir.path().to_path_buf()),
+            mix: convolution.mix(),
+            pre_delay_ms: convolution.pre_delay_ms(),
+        };
+    }
+}
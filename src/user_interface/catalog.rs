@@ -0,0 +1,243 @@
+use std::path::PathBuf;
+
+use crate::analysis::decay::analyze_modes;
+use crate::analysis::live::LiveAnalysis;
+use crate::catalog::bowl::{Bowl, Mode};
+use crate::catalog::query::Query;
+use crate::catalog::store::Catalog;
+
+const LIST_WIDTH: f32 = 260.0;
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// Browse, search and edit the bowl catalog
+pub struct CatalogPanel {
+    catalog: Result<Catalog, String>,
+    query_text: String,
+    query: Result<Query, String>,
+    /// Id of the bowl being edited
+    selected: Option<String>,
+    /// Working copy of the selected bowl, written on save
+    editing: Option<Bowl>,
+    new_id: String,
+    recording_path: String,
+    confirm_delete: bool,
+    /// Last failed save or delete
+    error: Option<String>,
+}
+
+impl CatalogPanel {
+    pub fn new() -> Self {
+        CatalogPanel {
+            catalog: Catalog::open_default().map_err(|e| e.to_string()),
+            query_text: String::new(),
+            query: Ok(Query::default()),
+            selected: None,
+            editing: None,
+            new_id: String::new(),
+            recording_path: String::new(),
+            confirm_delete: false,
+            error: None,
+        }
+    }
+
+    pub fn catalog(&self) -> Option<&Catalog> {
+        self.catalog.as_ref().ok()
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        let catalog = match &mut self.catalog {
+            Ok(catalog) => catalog,
+            Err(e) => {
+                ui.text_colored(ERROR_COLOR, format!("Could not open the catalog: {}", e));
+                return;
+            }
+        };
+
+        let mut select = None;
+        ui.child_window("catalog_list")
+            .size([LIST_WIDTH, 0.0])
+            .border(true)
+            .build(|| {
+                if ui
+                    .input_text("##query", &mut self.query_text)
+                    .hint("f0 180-200, t60 > 30")
+                    .build()
+                {
+                    self.query = Query::parse(&self.query_text).map_err(|e| e.to_string());
+                }
+                match &self.query {
+                    Ok(query) => {
+                        for bowl in catalog.search(query) {
+                            let label = match bowl.fundamental() {
+                                Some(f0) => format!("{}  {:.1} Hz##{}", bowl.id, f0, bowl.id),
+                                None => format!("{}##{}", bowl.id, bowl.id),
+                            };
+                            let selected = self.selected.as_deref() == Some(bowl.id.as_str());
+                            if ui.selectable_config(label).selected(selected).build() {
+                                select = Some(bowl.clone());
+                            }
+                        }
+                    }
+                    Err(e) => ui.text_colored(ERROR_COLOR, e),
+                }
+
+                ui.separator();
+                ui.input_text("##new_id", &mut self.new_id)
+                    .hint("new id")
+                    .build();
+                ui.same_line();
+                if ui.button("New") {
+                    let id = self.new_id.trim();
+                    if catalog.get(id).is_some() {
+                        self.error = Some(format!("Bowl '{}' already exists", id));
+                    } else {
+                        match catalog.save(Bowl::new(id)) {
+                            Ok(()) => {
+                                select = catalog.get(id).cloned();
+                                self.new_id.clear();
+                            }
+                            Err(e) => self.error = Some(e.to_string()),
+                        }
+                    }
+                }
+            });
+
+        if let Some(bowl) = select {
+            self.selected = Some(bowl.id.clone());
+            self.editing = Some(bowl);
+            self.confirm_delete = false;
+            self.error = None;
+        }
+
+        ui.same_line();
+        ui.child_window("catalog_details").build(|| {
+            if let Some(error) = &self.error {
+                ui.text_colored(ERROR_COLOR, error);
+            }
+            let Some(bowl) = &mut self.editing else {
+                ui.text("Select or create a bowl");
+                return;
+            };
+
+            ui.text(format!("ID: {}", bowl.id));
+            ui.input_text("Name", &mut bowl.name).build();
+            optional_number(ui, "Diameter (mm)", &mut bowl.diameter_mm);
+            optional_number(ui, "Weight (g)", &mut bowl.weight_g);
+            ui.input_text("Material", &mut bowl.material).build();
+            ui.input_text("Origin", &mut bowl.origin).build();
+            ui.input_text_multiline("Notes", &mut bowl.notes, [0.0, 60.0])
+                .build();
+
+            ui.separator();
+            ui.text("Modes");
+            if ui.button("Capture from Input") {
+                bowl.modes = capture_modes(analysis);
+            }
+            ui.same_line();
+            if ui.button("Clear Modes") {
+                bowl.modes.clear();
+            }
+            for mode in bowl.modes.iter() {
+                let format_optional = |value: Option<f32>, unit: &str| match value {
+                    Some(value) => format!("{:.1} {}", value, unit),
+                    None => String::from("-"),
+                };
+                ui.text(format!(
+                    "{:>10.2} Hz  {:>7.1} dB  T60 {}  Beat {}",
+                    mode.frequency,
+                    mode.level_db,
+                    format_optional(mode.t60, "s"),
+                    format_optional(mode.beat_hz, "Hz")
+                ));
+            }
+
+            ui.separator();
+            ui.text("Recordings");
+            let mut unlink = None;
+            for (i, recording) in bowl.recordings.iter().enumerate() {
+                if ui.small_button(format!("x##recording{}", i)) {
+                    unlink = Some(i);
+                }
+                ui.same_line();
+                ui.text(recording.display().to_string());
+            }
+            if let Some(i) = unlink {
+                bowl.recordings.remove(i);
+            }
+            ui.input_text("##recording_path", &mut self.recording_path)
+                .hint("path to recording")
+                .build();
+            ui.same_line();
+            if ui.button("Link") && !self.recording_path.trim().is_empty() {
+                bowl.recordings
+                    .push(PathBuf::from(self.recording_path.trim()));
+                self.recording_path.clear();
+            }
+
+            ui.separator();
+            let saved = catalog.get(&bowl.id);
+            let modified = saved != Some(&*bowl);
+            if ui.button("Save") {
+                match catalog.save(bowl.clone()) {
+                    Ok(()) => self.error = None,
+                    Err(e) => self.error = Some(e.to_string()),
+                }
+            }
+            ui.same_line();
+            if ui.button("Revert") {
+                if let Some(saved) = catalog.get(&bowl.id) {
+                    *bowl = saved.clone();
+                }
+            }
+            ui.same_line();
+            if !self.confirm_delete {
+                if ui.button("Delete") {
+                    self.confirm_delete = true;
+                }
+            } else {
+                ui.text("Delete this bowl?");
+                ui.same_line();
+                if ui.button("Yes") {
+                    match catalog.remove(&bowl.id) {
+                        Ok(_) => {
+                            self.selected = None;
+                            self.editing = None;
+                            self.error = None;
+                        }
+                        Err(e) => self.error = Some(e.to_string()),
+                    }
+                    self.confirm_delete = false;
+                    return;
+                }
+                ui.same_line();
+                if ui.button("No") {
+                    self.confirm_delete = false;
+                }
+            }
+            if modified {
+                ui.text("Unsaved changes");
+            }
+        });
+    }
+}
+
+/// Number field where 0 means unknown
+fn optional_number(ui: &imgui::Ui, label: &str, value: &mut Option<f32>) {
+    let mut number = value.unwrap_or(0.0);
+    if ui.input_float(label, &mut number).build() {
+        *value = (number > 0.0).then_some(number);
+    }
+}
+
+/// Current partials of the live input, with decay times and beating measured over the input
+/// history
+pub fn capture_modes(analysis: &LiveAnalysis) -> Vec<Mode> {
+    let Some(partials) = analysis.partials() else {
+        return Vec::new();
+    };
+
+    analyze_modes(analysis.history(), analysis.sample_rate(), partials)
+        .iter()
+        .map(Mode::from)
+        .collect()
+}
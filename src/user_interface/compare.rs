@@ -0,0 +1,366 @@
+use std::path::{Path, PathBuf};
+
+use crate::analysis::comparison::{compare, Similarity};
+use crate::analysis::decay::{mode_envelope, DecayCurve};
+use crate::analysis::recording::{analyze_recording, load_wav};
+use crate::analysis::spectrum::Spectrum;
+use crate::catalog::bowl::{Bowl, Mode};
+use crate::catalog::store::Catalog;
+use crate::user_interface::spectrum::{PlotArea, GRID, GRID_LABEL, PLOT_BACKGROUND};
+
+const PLOT_HEIGHT: f32 = 240.0;
+const SOURCE_NAMES: [&str; 2] = ["Recording", "Catalog"];
+/// Colors of the two bowls in the plots
+const COLORS: [[f32; 4]; 2] = [[0.3, 0.75, 1.0, 1.0], [1.0, 0.55, 0.2, 1.0]];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+/// Range of the decay plot
+const DECAY_RANGE_DB: f32 = 60.0;
+
+/// One side of the comparison: modes for the numbers, and where a recording is available the
+/// spectrum and a decay curve per mode
+struct Subject {
+    label: String,
+    spectrum: Option<Spectrum>,
+    modes: Vec<Mode>,
+    /// Aligned with `modes`, empty without a recording
+    curves: Vec<DecayCurve>,
+}
+
+impl Subject {
+    fn from_recording(path: &Path) -> Result<Self, String> {
+        let (samples, sample_rate) =
+            load_wav(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let analysis = analyze_recording(&samples, sample_rate);
+
+        Ok(Subject {
+            label: path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into(),
+            ),
+            spectrum: Some(analysis.frame.spectrum),
+            modes: analysis.modes.iter().map(Mode::from).collect(),
+            curves: analysis.modes.into_iter().map(|mode| mode.curve).collect(),
+        })
+    }
+
+    /// Uses the catalogued modes. The first linked recording that can be read provides the
+    /// spectrum and decay curves.
+    fn from_bowl(bowl: &Bowl) -> Self {
+        let recording = bowl.recordings.iter().find_map(|path| load_wav(path).ok());
+        let (spectrum, curves) = match recording {
+            Some((samples, sample_rate)) => {
+                let analysis = analyze_recording(&samples, sample_rate);
+                let curves = bowl
+                    .modes
+                    .iter()
+                    .map(|mode| mode_envelope(&samples, sample_rate, mode.frequency))
+                    .collect();
+                (Some(analysis.frame.spectrum), curves)
+            }
+            None => (None, Vec::new()),
+        };
+
+        Subject {
+            label: if bowl.name.is_empty() {
+                bowl.id.clone()
+            } else {
+                format!("{} ({})", bowl.name, bowl.id)
+            },
+            spectrum,
+            modes: bowl.modes.clone(),
+            curves,
+        }
+    }
+}
+
+struct Side {
+    source: usize,
+    path: String,
+    bowl_index: usize,
+    subject: Option<Subject>,
+    error: Option<String>,
+}
+
+impl Side {
+    fn new() -> Self {
+        Side {
+            source: 0,
+            path: String::new(),
+            bowl_index: 0,
+            subject: None,
+            error: None,
+        }
+    }
+
+    /// Source selection. Returns true when a new subject was loaded.
+    fn build(&mut self, ui: &imgui::Ui, index: usize, catalog: Option<&Catalog>) -> bool {
+        let _id = ui.push_id_usize(index);
+        ui.text_colored(COLORS[index], if index == 0 { "Bowl A" } else { "Bowl B" });
+        ui.combo_simple_string("Source", &mut self.source, &SOURCE_NAMES);
+
+        let mut loaded = None;
+        if self.source == 0 {
+            ui.input_text("WAV File", &mut self.path).build();
+            if ui.button("Load") {
+                loaded = Some(Subject::from_recording(&PathBuf::from(self.path.trim())));
+            }
+        } else {
+            match catalog.filter(|c| !c.bowls().is_empty()) {
+                Some(catalog) => {
+                    let bowls = catalog.bowls();
+                    self.bowl_index = self.bowl_index.min(bowls.len() - 1);
+                    ui.combo("Bowl", &mut self.bowl_index, bowls, |bowl| {
+                        std::borrow::Cow::Borrowed(bowl.id.as_str())
+                    });
+                    if ui.button("Load") {
+                        loaded = Some(Ok(Subject::from_bowl(&bowls[self.bowl_index])));
+                    }
+                }
+                None => ui.text("The catalog is empty"),
+            }
+        }
+
+        let changed = matches!(loaded, Some(Ok(_)));
+        match loaded {
+            Some(Ok(subject)) => {
+                self.subject = Some(subject);
+                self.error = None;
+            }
+            Some(Err(e)) => self.error = Some(e),
+            None => {}
+        }
+
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        } else if let Some(subject) = &self.subject {
+            ui.text(format!("{}: {} modes", subject.label, subject.modes.len()));
+        }
+        changed
+    }
+}
+
+/// Two recordings or catalogued bowls side by side, with a similarity score
+pub struct ComparisonPanel {
+    sides: [Side; 2],
+    similarity: Option<Similarity>,
+    /// Row of the partial table whose decay curves are plotted
+    decay_row: usize,
+}
+
+impl ComparisonPanel {
+    pub fn new() -> Self {
+        ComparisonPanel {
+            sides: [Side::new(), Side::new()],
+            similarity: None,
+            decay_row: 0,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, catalog: Option<&Catalog>) {
+        ui.columns(2, "compare_sources", false);
+        let mut changed = self.sides[0].build(ui, 0, catalog);
+        ui.next_column();
+        changed |= self.sides[1].build(ui, 1, catalog);
+        ui.columns(1, "compare_sources", false);
+
+        let [Some(a), Some(b)] = [&self.sides[0].subject, &self.sides[1].subject] else {
+            return;
+        };
+        if changed || self.similarity.is_none() {
+            self.similarity = Some(compare(&a.modes, &b.modes));
+            self.decay_row = 0;
+        }
+        let Some(similarity) = &self.similarity else {
+            return;
+        };
+
+        ui.separator();
+        build_scores(ui, similarity);
+
+        ui.separator();
+        build_spectra(ui, [a, b]);
+
+        ui.separator();
+        build_partial_table(ui, [a, b], similarity);
+
+        ui.separator();
+        build_decay(ui, [a, b], similarity, &mut self.decay_row);
+    }
+}
+
+fn build_decay(
+    ui: &imgui::Ui,
+    subjects: [&Subject; 2],
+    similarity: &Similarity,
+    decay_row: &mut usize,
+) {
+    let rows: Vec<(usize, usize)> = similarity
+        .matches
+        .iter()
+        .filter_map(|m| Some((m.a?, m.b?)))
+        .filter(|&(i, j)| i < subjects[0].curves.len() && j < subjects[1].curves.len())
+        .collect();
+    if rows.is_empty() {
+        ui.text("Decay curves need recordings of both bowls");
+        return;
+    }
+
+    let labels: Vec<String> = rows
+        .iter()
+        .map(|&(i, j)| {
+            format!(
+                "{:.1} Hz / {:.1} Hz",
+                subjects[0].modes[i].frequency, subjects[1].modes[j].frequency
+            )
+        })
+        .collect();
+    *decay_row = (*decay_row).min(rows.len() - 1);
+    ui.combo_simple_string("Decay of", decay_row, &labels);
+
+    let (i, j) = rows[*decay_row];
+    draw_decay_plot(ui, [&subjects[0].curves[i], &subjects[1].curves[j]]);
+}
+
+fn build_scores(ui: &imgui::Ui, similarity: &Similarity) {
+    let format_optional = |value: Option<f32>, format: &dyn Fn(f32) -> String| match value {
+        Some(value) => format(value),
+        None => String::from("-"),
+    };
+
+    ui.set_window_font_scale(1.5);
+    ui.text(format!("Similarity {:.0} / 100", similarity.score));
+    ui.set_window_font_scale(1.0);
+    ui.text(format!(
+        "Partial distance: {}   Matched partials: {:.0}%",
+        format_optional(similarity.partial_distance_cents, &|d| format!(
+            "{:.1} cents",
+            d
+        )),
+        similarity.coverage * 100.0
+    ));
+    ui.text(format!(
+        "T60 ratio A/B: {}   Beat rate difference: {}",
+        format_optional(similarity.t60_ratio, &|r| format!("{:.2}", r)),
+        format_optional(similarity.beat_difference_hz, &|d| format!("{:.2} Hz", d))
+    ));
+}
+
+fn build_spectra(ui: &imgui::Ui, subjects: [&Subject; 2]) {
+    let [x, y] = ui.cursor_screen_pos();
+    let width = ui.content_region_avail()[0];
+    let area = PlotArea {
+        min: [x, y],
+        max: [x + width, y + PLOT_HEIGHT],
+        min_frequency: 20.0,
+        max_frequency: 10000.0,
+        log_frequency: true,
+        floor_db: -100.0,
+    };
+
+    let draw_list = ui.get_window_draw_list();
+    draw_list.with_clip_rect_intersect(area.min, area.max, || {
+        area.draw_grid(&draw_list);
+        for (subject, color) in subjects.iter().zip(COLORS) {
+            match &subject.spectrum {
+                Some(spectrum) => area.draw_spectrum(&draw_list, spectrum, color),
+                // without a recording only the modes are known
+                None => {
+                    for mode in subject.modes.iter() {
+                        area.draw_marker(&draw_list, mode.frequency, color, None);
+                    }
+                }
+            }
+        }
+    });
+    ui.dummy([width, PLOT_HEIGHT]);
+}
+
+fn build_partial_table(ui: &imgui::Ui, subjects: [&Subject; 2], similarity: &Similarity) {
+    let headers = [
+        "A Frequency",
+        "A T60",
+        "A Beat",
+        "B Frequency",
+        "B T60",
+        "B Beat",
+        "Cents",
+    ];
+    ui.columns(headers.len() as i32, "compare_partials", false);
+    for header in headers {
+        ui.text(header);
+        ui.next_column();
+    }
+    ui.separator();
+
+    let format_optional = |value: Option<f32>, unit: &str| match value {
+        Some(value) => format!("{:.2} {}", value, unit),
+        None => String::from("-"),
+    };
+    for row in similarity.matches.iter() {
+        for (index, subject) in [row.a, row.b].into_iter().zip(subjects) {
+            let mode = index.map(|i| subject.modes[i]);
+            ui.text(format_optional(mode.map(|m| m.frequency), "Hz"));
+            ui.next_column();
+            ui.text(format_optional(mode.and_then(|m| m.t60), "s"));
+            ui.next_column();
+            ui.text(format_optional(mode.and_then(|m| m.beat_hz), "Hz"));
+            ui.next_column();
+        }
+        ui.text(match row.cents {
+            Some(cents) => format!("{:+.1}", cents),
+            None => String::from("-"),
+        });
+        ui.next_column();
+    }
+    ui.columns(1, "compare_partials", false);
+}
+
+/// Level over time of two partials, each relative to its own peak
+fn draw_decay_plot(ui: &imgui::Ui, curves: [&DecayCurve; 2]) {
+    let [x, y] = ui.cursor_screen_pos();
+    let width = ui.content_region_avail()[0];
+    let (min, max) = ([x, y], [x + width, y + PLOT_HEIGHT]);
+    let duration = curves
+        .iter()
+        .map(|c| c.levels_db.len() as f32 * c.frame_seconds)
+        .fold(0.0, f32::max)
+        .max(1.0);
+
+    let draw_list = ui.get_window_draw_list();
+    draw_list.with_clip_rect_intersect(min, max, || {
+        draw_list
+            .add_rect(min, max, PLOT_BACKGROUND)
+            .filled(true)
+            .build();
+        let mut db = 0.0;
+        while db > -DECAY_RANGE_DB {
+            let line_y = y + PLOT_HEIGHT * db / -DECAY_RANGE_DB;
+            draw_list
+                .add_line([x, line_y], [max[0], line_y], GRID)
+                .build();
+            draw_list.add_text([x + 2.0, line_y], GRID_LABEL, format!("{} dB", db));
+            db -= 20.0;
+        }
+        draw_list.add_text(
+            [max[0] - 60.0, max[1] - 14.0],
+            GRID_LABEL,
+            format!("{:.1} s", duration),
+        );
+
+        for (curve, color) in curves.iter().zip(COLORS) {
+            let peak = curve.levels_db.iter().cloned().fold(f32::MIN, f32::max);
+            let points: Vec<[f32; 2]> = curve
+                .levels_db
+                .iter()
+                .enumerate()
+                .map(|(i, db)| {
+                    let t = i as f32 * curve.frame_seconds / duration;
+                    let level = ((db - peak) / -DECAY_RANGE_DB).clamp(0.0, 1.0);
+                    [x + t * width, y + level * PLOT_HEIGHT]
+                })
+                .collect();
+            draw_list.add_polyline(points, color).build();
+        }
+    });
+    ui.dummy([width, PLOT_HEIGHT]);
+}
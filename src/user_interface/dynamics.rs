@@ -0,0 +1,162 @@
+use crate::audio_engine::dynamics::{
+    DynamicsKind, StageParams, MAX_LOOKAHEAD_MS, SIDECHAIN_HIGHPASS_OFF, SIDECHAIN_LOWPASS_OFF,
+};
+use crate::audio_engine::io_manager::IOManager;
+
+/// Full scale of the gain reduction meters
+const GAIN_REDUCTION_SCALE_DB: f32 = 24.0;
+const MAX_RATIO: f32 = 20.0;
+const MAX_KNEE_DB: f32 = 24.0;
+const MAX_RANGE_DB: f32 = 80.0;
+const MAX_ATTACK_MS: f32 = 200.0;
+const MAX_RELEASE_MS: f32 = 2000.0;
+const MAX_SIDECHAIN_HIGHPASS: f32 = 2000.0;
+const MIN_SIDECHAIN_LOWPASS: f32 = 500.0;
+
+/// Controls and gain reduction meters of the gate, expander, compressor and limiter
+pub fn build_dynamics_panel(ui: &imgui::Ui, io_manager: &IOManager) {
+    for stage in io_manager.dynamics().stages() {
+        let kind = stage.kind();
+        let _id = ui.push_id_usize(kind as usize);
+
+        // read every frame, so the meter doesn't jump to a peak from while it was closed
+        let reduction_db = stage.read_gain_reduction_db();
+        let Some(_node) = ui.tree_node(kind.name()) else {
+            continue;
+        };
+
+        let mut enabled = stage.enabled();
+        if ui.checkbox("Enabled", &mut enabled) {
+            stage.set_enabled(enabled);
+        }
+        imgui::ProgressBar::new((reduction_db / GAIN_REDUCTION_SCALE_DB).clamp(0.0, 1.0))
+            .overlay_text(format!("Gain reduction {:.1} dB", reduction_db))
+            .build(ui);
+
+        let threshold_label = if kind == DynamicsKind::Limiter {
+            "Ceiling"
+        } else {
+            "Threshold"
+        };
+        let mut threshold_db = stage.threshold_db();
+        if ui
+            .slider_config(threshold_label, -80.0, 0.0)
+            .display_format("%.1f dB")
+            .build(&mut threshold_db)
+        {
+            stage.set_threshold_db(threshold_db);
+        }
+
+        if kind.has_ratio() {
+            let mut ratio = stage.ratio();
+            if ui
+                .slider_config("Ratio", 1.0, MAX_RATIO)
+                .display_format("%.1f:1")
+                .flags(imgui::SliderFlags::LOGARITHMIC)
+                .build(&mut ratio)
+            {
+                stage.set_ratio(ratio);
+            }
+        }
+        if kind.has_range() {
+            let mut range_db = stage.range_db();
+            if ui
+                .slider_config("Range", 0.0, MAX_RANGE_DB)
+                .display_format("%.0f dB")
+                .build(&mut range_db)
+            {
+                stage.set_range_db(range_db);
+            }
+        }
+
+        if kind == DynamicsKind::Limiter {
+            build_limiter(ui, io_manager, stage);
+        } else {
+            build_detector(ui, stage);
+        }
+    }
+}
+
+/// Knee, ballistics and sidechain filters of the gate, expander or compressor
+fn build_detector(ui: &imgui::Ui, stage: &StageParams) {
+    let mut knee_db = stage.knee_db();
+    if ui
+        .slider_config("Knee", 0.0, MAX_KNEE_DB)
+        .display_format("%.1f dB")
+        .build(&mut knee_db)
+    {
+        stage.set_knee_db(knee_db);
+    }
+
+    let mut attack_ms = stage.attack_ms();
+    if ui
+        .slider_config("Attack", 0.0, MAX_ATTACK_MS)
+        .display_format("%.1f ms")
+        .flags(imgui::SliderFlags::LOGARITHMIC)
+        .build(&mut attack_ms)
+    {
+        stage.set_attack_ms(attack_ms);
+    }
+    build_release(ui, stage);
+
+    ui.text_disabled("Sidechain");
+    let mut highpass_hz = stage.sidechain_highpass_hz();
+    let format = if highpass_hz <= SIDECHAIN_HIGHPASS_OFF {
+        "Off"
+    } else {
+        "%.0f Hz"
+    };
+    if ui
+        .slider_config("High Pass", SIDECHAIN_HIGHPASS_OFF, MAX_SIDECHAIN_HIGHPASS)
+        .display_format(format)
+        .flags(imgui::SliderFlags::LOGARITHMIC)
+        .build(&mut highpass_hz)
+    {
+        stage.set_sidechain_highpass_hz(highpass_hz);
+    }
+
+    let mut lowpass_hz = stage.sidechain_lowpass_hz();
+    let format = if lowpass_hz >= SIDECHAIN_LOWPASS_OFF {
+        "Off"
+    } else {
+        "%.0f Hz"
+    };
+    if ui
+        .slider_config("Low Pass", MIN_SIDECHAIN_LOWPASS, SIDECHAIN_LOWPASS_OFF)
+        .display_format(format)
+        .flags(imgui::SliderFlags::LOGARITHMIC)
+        .build(&mut lowpass_hz)
+    {
+        stage.set_sidechain_lowpass_hz(lowpass_hz);
+    }
+}
+
+fn build_limiter(ui: &imgui::Ui, io_manager: &IOManager, stage: &StageParams) {
+    let mut lookahead_ms = stage.lookahead_ms();
+    if ui
+        .slider_config("Lookahead", 0.0, MAX_LOOKAHEAD_MS)
+        .display_format("%.1f ms")
+        .build(&mut lookahead_ms)
+    {
+        stage.set_lookahead_ms(lookahead_ms);
+    }
+    build_release(ui, stage);
+
+    if let Some(sample_rate) = io_manager.get_input_sample_rate() {
+        // the lookahead is at least one sample
+        let latency_ms = lookahead_ms.max(1000.0 / sample_rate as f32);
+        ui.text_disabled(format!("Adds {:.1} ms latency", latency_ms));
+    }
+}
+
+fn build_release(ui: &imgui::Ui, stage: &StageParams) {
+    let mut release_ms = stage.release_ms();
+    if ui
+        .slider_config("Release", 1.0, MAX_RELEASE_MS)
+        .display_format("%.0f ms")
+        .flags(imgui::SliderFlags::LOGARITHMIC)
+        .build(&mut release_ms)
+    {
+        stage.set_release_ms(release_ms);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::analysis::roughness::{ensemble_roughness, rank_complements};
+use crate::catalog::bowl::Bowl;
+use crate::catalog::store::Catalog;
+
+const LIST_WIDTH: f32 = 220.0;
+const SUGGESTIONS: usize = 5;
+const SMOOTH_COLOR: [f32; 4] = [0.4, 0.9, 0.4, 1.0];
+const ROUGH_COLOR: [f32; 4] = [1.0, 0.35, 0.3, 1.0];
+
+/// Roughness of a set of catalogued bowls sounding together, and the bowls that go best with a
+/// chosen one
+pub struct EnsemblePanel {
+    /// Ids of the bowls in the set, in catalog order
+    set: Vec<String>,
+    /// Bowl to find complements for
+    chosen: usize,
+}
+
+impl EnsemblePanel {
+    pub fn new() -> Self {
+        EnsemblePanel {
+            set: Vec::new(),
+            chosen: 0,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, catalog: Option<&Catalog>) {
+        let bowls: Vec<&Bowl> = catalog
+            .map(|c| c.bowls().iter().filter(|b| !b.modes.is_empty()).collect())
+            .unwrap_or_default();
+        if bowls.is_empty() {
+            ui.text("Capture the modes of some bowls in the catalog first");
+            return;
+        }
+        self.set.retain(|id| bowls.iter().any(|b| &b.id == id));
+
+        ui.child_window("ensemble_set")
+            .size([LIST_WIDTH, 0.0])
+            .border(true)
+            .build(|| {
+                ui.text("Set");
+                for bowl in bowls.iter() {
+                    let mut in_set = self.set.contains(&bowl.id);
+                    if ui.checkbox(&bowl.id, &mut in_set) {
+                        if in_set {
+                            self.set.push(bowl.id.clone());
+                        } else {
+                            self.set.retain(|id| id != &bowl.id);
+                        }
+                    }
+                }
+            });
+        self.set
+            .sort_by_key(|id| bowls.iter().position(|b| &b.id == id));
+
+        ui.same_line();
+        ui.child_window("ensemble_results").build(|| {
+            self.build_matrix(ui, &bowls);
+            ui.separator();
+            self.build_suggestions(ui, &bowls);
+        });
+    }
+
+    fn build_matrix(&self, ui: &imgui::Ui, bowls: &[&Bowl]) {
+        let members: Vec<&Bowl> = self
+            .set
+            .iter()
+            .filter_map(|id| bowls.iter().find(|b| &b.id == id).copied())
+            .collect();
+        if members.len() < 2 {
+            ui.text("Select at least two bowls");
+            return;
+        }
+
+        let modes: Vec<&[_]> = members.iter().map(|b| b.modes.as_slice()).collect();
+        let roughness = ensemble_roughness(&modes);
+        ui.text(format!(
+            "Roughness of the whole set: {:.3}",
+            roughness.total
+        ));
+        ui.text("Pairwise roughness, lower is more consonant. The diagonal is the bowl alone.");
+
+        let max = roughness
+            .matrix
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f32::MIN_POSITIVE, f32::max);
+        ui.columns(members.len() as i32 + 1, "consonance_matrix", true);
+        ui.next_column();
+        for bowl in members.iter() {
+            ui.text(&bowl.id);
+            ui.next_column();
+        }
+        ui.separator();
+        for (bowl, row) in members.iter().zip(roughness.matrix.iter()) {
+            ui.text(&bowl.id);
+            ui.next_column();
+            for value in row {
+                ui.text_colored(blend(value / max), format!("{:.3}", value));
+                ui.next_column();
+            }
+        }
+        ui.columns(1, "consonance_matrix", false);
+    }
+
+    fn build_suggestions(&mut self, ui: &imgui::Ui, bowls: &[&Bowl]) {
+        self.chosen = self.chosen.min(bowls.len() - 1);
+        ui.combo("Complement", &mut self.chosen, bowls, |bowl| {
+            std::borrow::Cow::Borrowed(bowl.id.as_str())
+        });
+
+        let chosen = bowls[self.chosen];
+        let others: Vec<&Bowl> = bowls
+            .iter()
+            .filter(|b| b.id != chosen.id)
+            .copied()
+            .collect();
+        let modes: Vec<&[_]> = others.iter().map(|b| b.modes.as_slice()).collect();
+        let ranking = rank_complements(&chosen.modes, &modes);
+        let Some(max) = ranking.last().map(|&(_, r)| r.max(f32::MIN_POSITIVE)) else {
+            ui.text("No other bowls to compare with");
+            return;
+        };
+
+        for &(index, roughness) in ranking.iter().take(SUGGESTIONS) {
+            let bowl = others[index];
+            ui.text_colored(
+                blend(roughness / max),
+                format!(
+                    "{:<16} {:>8.1} Hz  roughness {:.3}",
+                    bowl.id,
+                    bowl.fundamental().unwrap_or(0.0),
+                    roughness
+                ),
+            );
+            if !self.set.contains(&bowl.id) {
+                ui.same_line();
+                if ui.small_button(format!("Add to Set##{}", bowl.id)) {
+                    self.set.push(bowl.id.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Color from smooth at 0 to rough at 1
+fn blend(t: f32) -> [f32; 4] {
+    let t = t.clamp(0.0, 1.0);
+    let mut color = SMOOTH_COLOR;
+    for (c, rough) in color.iter_mut().zip(ROUGH_COLOR) {
+        *c += (rough - *c) * t;
+    }
+    color
+}
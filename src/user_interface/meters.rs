@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+use crate::audio_engine::meters::{InputMeters, NUM_METER_CHANNELS};
+
+/// Bottom of the meter scale in dBFS
+const METER_FLOOR_DB: f32 = -60.0;
+/// How long the peak hold marker stays before falling back to the current peak
+const PEAK_HOLD_TIME: Duration = Duration::from_millis(1500);
+const BAR_HEIGHT: f32 = 10.0;
+const CLIP_WIDTH: f32 = 14.0;
+
+const BAR_BACKGROUND: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+const BAR_RMS: [f32; 4] = [0.1, 0.6, 0.2, 1.0];
+const BAR_PEAK: [f32; 4] = [0.3, 0.85, 0.35, 1.0];
+const BAR_HOT: [f32; 4] = [0.9, 0.75, 0.1, 1.0];
+const PEAK_HOLD: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const CLIP_OFF: [f32; 4] = [0.3, 0.1, 0.1, 1.0];
+const CLIP_ON: [f32; 4] = [1.0, 0.1, 0.1, 1.0];
+
+struct PeakHold {
+    level_db: f32,
+    since: Instant,
+}
+
+/// Draws the input meters and keeps the display-only peak hold state.
+pub struct MeterDisplay {
+    holds: [PeakHold; NUM_METER_CHANNELS],
+}
+
+impl MeterDisplay {
+    pub fn new() -> Self {
+        MeterDisplay {
+            holds: std::array::from_fn(|_| PeakHold {
+                level_db: METER_FLOOR_DB,
+                since: Instant::now(),
+            }),
+        }
+    }
+
+    /// One bar per channel showing sample peak over RMS, with peak hold and a clip indicator
+    /// that stays lit until clicked.
+    pub fn build(&mut self, ui: &imgui::Ui, meters: &InputMeters) {
+        let readings = meters.read();
+        let draw_list = ui.get_window_draw_list();
+        let width = ui.content_region_avail()[0] - CLIP_WIDTH - 2.0;
+
+        for (i, (channel, hold)) in readings
+            .channels
+            .iter()
+            .zip(self.holds.iter_mut())
+            .enumerate()
+        {
+            let peak_db = channel.peak_db.max(channel.true_peak_db);
+            if peak_db >= hold.level_db || hold.since.elapsed() >= PEAK_HOLD_TIME {
+                hold.level_db = peak_db;
+                hold.since = Instant::now();
+            }
+
+            let [x, y] = ui.cursor_screen_pos();
+            let bottom = y + BAR_HEIGHT;
+            let level_x = |db: f32| x + width * scale(db);
+
+            draw_list
+                .add_rect([x, y], [x + width, bottom], BAR_BACKGROUND)
+                .filled(true)
+                .build();
+            let peak_color = if peak_db > -6.0 { BAR_HOT } else { BAR_PEAK };
+            draw_list
+                .add_rect([x, y], [level_x(peak_db), bottom], peak_color)
+                .filled(true)
+                .build();
+            draw_list
+                .add_rect(
+                    [x, y + 3.0],
+                    [level_x(channel.rms_db), bottom - 3.0],
+                    BAR_RMS,
+                )
+                .filled(true)
+                .build();
+            let hold_x = level_x(hold.level_db);
+            draw_list
+                .add_line([hold_x, y], [hold_x, bottom], PEAK_HOLD)
+                .build();
+
+            let clip_x = x + width + 2.0;
+            let clip_color = if channel.clipped { CLIP_ON } else { CLIP_OFF };
+            draw_list
+                .add_rect([clip_x, y], [clip_x + CLIP_WIDTH, bottom], clip_color)
+                .filled(true)
+                .build();
+
+            ui.dummy([width + 2.0, BAR_HEIGHT]);
+            ui.same_line();
+            if ui.invisible_button(format!("clip##{}", i), [CLIP_WIDTH, BAR_HEIGHT]) {
+                meters.reset_clip();
+            }
+            if ui.is_item_hovered() {
+                ui.tooltip_text("Clip indicator, click to reset");
+            }
+
+            ui.text(format!(
+                "Ch {}  Pk {:.1}  TP {:.1}  RMS {:.1}",
+                i + 1,
+                channel.peak_db,
+                channel.true_peak_db,
+                channel.rms_db
+            ));
+            ui.text(format!(
+                "      M {:.1}  S {:.1} LUFS",
+                channel.momentary_lufs, channel.short_term_lufs
+            ));
+        }
+
+        ui.text(format!(
+            "Total M {:.1}  S {:.1} LUFS",
+            readings.momentary_lufs, readings.short_term_lufs
+        ));
+    }
+}
+
+/// Position of a level on the bar, 0.0 at the floor and 1.0 at full scale
+fn scale(db: f32) -> f32 {
+    ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0)
+}
@@ -1,2 +1,20 @@
+mod catalog;
+mod compare;
+mod dynamics;
+mod ensemble;
+mod meters;
+mod nodes;
+mod partials;
+mod pitch;
+mod recorder;
+mod reverb;
 mod setup;
+mod shapes;
+mod sms;
+mod spectrum;
+mod stretch;
+mod strikes;
+mod sweep;
+mod tuner;
 pub mod ui;
+mod zoom;
@@ -0,0 +1,373 @@
+use std::path::PathBuf;
+
+use crate::analysis::nodal::{
+    map_sequential, map_simultaneous, max_order, min_positions, NodalError, NodalPattern, Position,
+};
+use crate::analysis::recording::load_wav_channels;
+use crate::user_interface::spectrum::{GRID, GRID_LABEL, PLOT_BACKGROUND, SPECTRUM_LINE};
+
+const POLAR_SIZE: f32 = 200.0;
+const POLAR_MARGIN: f32 = 16.0;
+/// Points the fitted pattern is drawn with
+const PATTERN_POINTS: usize = 180;
+const NODE_LINE: [f32; 4] = [1.0, 0.3, 0.6, 0.5];
+/// Measured points in phase and in antiphase with the loudest position
+const IN_PHASE: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const ANTIPHASE: [f32; 4] = [0.3, 0.9, 0.5, 1.0];
+/// Angle between added takes. Amplitude patterns repeat every 180 degrees, so the default
+/// takes in 40 degree steps around the bowl sample them every 20 degrees.
+const TAKE_SPACING_DEG: f32 = 40.0;
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// How the positions around the bowl were captured
+#[derive(Clone, Copy, PartialEq)]
+enum CaptureMode {
+    /// One multi-channel take, a mic on each channel
+    Simultaneous,
+    /// One take per position
+    Sequential,
+}
+
+struct SequentialTake {
+    angle_deg: f32,
+    path: String,
+}
+
+/// Measures how each partial's amplitude changes around the bowl, fits its mode order and
+/// orientation and draws its polar pattern
+pub struct NodesPanel {
+    mode: CaptureMode,
+    /// Multi-channel take for the simultaneous mode
+    path: String,
+    /// Angle of each channel, comma separated; equally spaced if empty
+    angles: String,
+    takes: Vec<SequentialTake>,
+    /// The last channel of each sequential take is a mic that stayed in place
+    reference_channel: bool,
+    patterns: Vec<NodalPattern>,
+    error: Option<String>,
+}
+
+impl NodesPanel {
+    pub fn new() -> Self {
+        NodesPanel {
+            mode: CaptureMode::Simultaneous,
+            path: String::new(),
+            angles: String::new(),
+            takes: (0..min_positions(false))
+                .map(|i| SequentialTake {
+                    angle_deg: i as f32 * TAKE_SPACING_DEG,
+                    path: String::new(),
+                })
+                .collect(),
+            reference_channel: false,
+            patterns: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui) {
+        ui.radio_button("Simultaneous", &mut self.mode, CaptureMode::Simultaneous);
+        ui.same_line();
+        ui.radio_button("Sequential", &mut self.mode, CaptureMode::Sequential);
+
+        match self.mode {
+            CaptureMode::Simultaneous => {
+                ui.text_disabled("One multi-channel WAV file with a mic on each channel");
+                ui.input_text("WAV File", &mut self.path).build();
+                ui.input_text("Angles", &mut self.angles)
+                    .hint("e.g. 0, 45, 90 (equally spaced if empty)")
+                    .build();
+            }
+            CaptureMode::Sequential => self.build_takes(ui),
+        }
+
+        if ui.button("Map Nodes") {
+            match self.map() {
+                Ok(patterns) => {
+                    self.patterns = patterns;
+                    self.error = None;
+                }
+                Err(e) => {
+                    self.patterns.clear();
+                    self.error = Some(e);
+                }
+            }
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+
+        if let Some(positions) = self.patterns.first().map(|p| p.measurements.len()) {
+            let with_phase = self.patterns[0].measurements[0].phase_deg.is_some();
+            ui.separator();
+            ui.text(format!(
+                "{} positions{}, orders up to {} can be told apart",
+                positions,
+                if with_phase {
+                    " with phase"
+                } else {
+                    ", amplitude only"
+                },
+                max_order(positions, with_phase)
+            ));
+            build_table(ui, &self.patterns);
+            ui.separator();
+            build_polar_plots(ui, &self.patterns);
+        }
+    }
+
+    /// Patterns of the last mapping
+    pub fn patterns(&self) -> &[NodalPattern] {
+        &self.patterns
+    }
+
+    fn build_takes(&mut self, ui: &imgui::Ui) {
+        ui.text_disabled("One WAV file per position, each aligned on its strike");
+        ui.text_disabled(format!(
+            "At least {} positions, {} with a reference mic",
+            min_positions(false),
+            min_positions(true)
+        ));
+        let mut removed = None;
+        for (i, take) in self.takes.iter_mut().enumerate() {
+            let _id = ui.push_id_usize(i);
+            ui.set_next_item_width(80.0);
+            ui.input_float("##angle", &mut take.angle_deg)
+                .display_format("%.0f deg")
+                .build();
+            ui.same_line();
+            ui.set_next_item_width(ui.content_region_avail()[0] - 30.0);
+            ui.input_text("##path", &mut take.path).build();
+            ui.same_line();
+            if ui.small_button("x") {
+                removed = Some(i);
+            }
+        }
+        if let Some(i) = removed {
+            self.takes.remove(i);
+        }
+        if ui.button("Add Position") {
+            let angle_deg = self
+                .takes
+                .last()
+                .map_or(0.0, |take| take.angle_deg + TAKE_SPACING_DEG);
+            self.takes.push(SequentialTake {
+                angle_deg: angle_deg.rem_euclid(360.0),
+                path: String::new(),
+            });
+        }
+        ui.checkbox("Reference Mic", &mut self.reference_channel);
+        if ui.is_item_hovered() {
+            ui.tooltip_text(
+                "The last channel of each take is a mic that stays in place, giving the phase \
+                 and making the result independent of how hard each strike was",
+            );
+        }
+    }
+
+    fn map(&self) -> Result<Vec<NodalPattern>, String> {
+        match self.mode {
+            CaptureMode::Simultaneous => {
+                let path = PathBuf::from(self.path.trim());
+                let (channels, sample_rate) =
+                    load_wav_channels(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let angles = parse_angles(&self.angles, channels.len())?;
+                let channels: Vec<&[f32]> = channels.iter().map(Vec::as_slice).collect();
+                map_simultaneous(&channels, &angles, sample_rate).map_err(|e| e.to_string())
+            }
+            CaptureMode::Sequential => {
+                let mut loaded = Vec::with_capacity(self.takes.len());
+                for take in &self.takes {
+                    let path = PathBuf::from(take.path.trim());
+                    let (channels, sample_rate) = load_wav_channels(&path)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    if self.reference_channel && channels.len() < 2 {
+                        return Err(format!("{}: no reference channel", path.display()));
+                    }
+                    loaded.push((take.angle_deg, channels, sample_rate));
+                }
+                let sample_rate = loaded.first().map_or(0, |(_, _, rate)| *rate);
+                if loaded.iter().any(|(_, _, rate)| *rate != sample_rate) {
+                    return Err(NodalError::SampleRateMismatch.to_string());
+                }
+
+                let positions: Vec<Position> = loaded
+                    .iter()
+                    .map(|(angle_deg, channels, _)| Position {
+                        angle_deg: *angle_deg,
+                        samples: &channels[0],
+                        reference: self
+                            .reference_channel
+                            .then(|| channels[channels.len() - 1].as_slice()),
+                    })
+                    .collect();
+                map_sequential(&positions, sample_rate).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Angles in degrees from a comma separated list, or equally spaced if it is empty
+fn parse_angles(text: &str, channels: usize) -> Result<Vec<f32>, String> {
+    if text.trim().is_empty() {
+        return Ok((0..channels)
+            .map(|i| i as f32 * 360.0 / channels as f32)
+            .collect());
+    }
+    let angles = text
+        .split(',')
+        .map(|angle| {
+            angle
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("'{}' is not an angle", angle.trim()))
+        })
+        .collect::<Result<Vec<f32>, String>>()?;
+    if angles.len() != channels {
+        return Err(format!("{} angles for {} channels", angles.len(), channels));
+    }
+    Ok(angles)
+}
+
+fn build_table(ui: &imgui::Ui, patterns: &[NodalPattern]) {
+    let headers = [
+        "Frequency",
+        "Ring Mode",
+        "Fitted Order",
+        "Antinode",
+        "Node Depth",
+        "Residual",
+        "Beat",
+    ];
+    ui.columns(headers.len() as i32, "nodal_modes", false);
+    for header in headers {
+        ui.text(header);
+        ui.next_column();
+    }
+    ui.separator();
+
+    for pattern in patterns {
+        ui.text(format!("{:.2} Hz", pattern.partial.frequency));
+        ui.next_column();
+        ui.text(format!("n = {}", pattern.partial.ring_mode));
+        ui.next_column();
+        match &pattern.fit {
+            Some(fit) => {
+                let cells = [
+                    format!("n = {}", fit.order),
+                    format!("{:.0} deg", fit.antinode_deg),
+                    format!("{:.0}%", fit.node_depth * 100.0),
+                    format!("{:.0}%", fit.residual * 100.0),
+                ];
+                for cell in cells {
+                    ui.text(cell);
+                    ui.next_column();
+                }
+            }
+            None => {
+                for _ in 0..4 {
+                    ui.text_disabled("-");
+                    ui.next_column();
+                }
+            }
+        }
+        match pattern.beat_hz {
+            Some(beat_hz) => ui.text(format!("{:.2} Hz", beat_hz)),
+            None => ui.text_disabled("-"),
+        }
+        ui.next_column();
+    }
+    ui.columns(1, "nodal_modes", false);
+}
+
+/// A polar diagram per partial: measured amplitudes as points, the fitted pattern as a line
+/// and its nodal lines. 0 degrees is at the top, angles increase clockwise.
+fn build_polar_plots(ui: &imgui::Ui, patterns: &[NodalPattern]) {
+    let per_row = ((ui.content_region_avail()[0] / (POLAR_SIZE + POLAR_MARGIN)) as usize).max(1);
+    for (i, pattern) in patterns.iter().enumerate() {
+        if i % per_row != 0 {
+            ui.same_line();
+        }
+        let _id = ui.push_id_usize(i);
+        ui.group(|| {
+            draw_polar(ui, pattern);
+            let label = match &pattern.fit {
+                Some(fit) => format!("{:.1} Hz, n = {}", pattern.partial.frequency, fit.order),
+                None => format!("{:.1} Hz", pattern.partial.frequency),
+            };
+            ui.text(label);
+        });
+    }
+}
+
+fn draw_polar(ui: &imgui::Ui, pattern: &NodalPattern) {
+    let [x, y] = ui.cursor_screen_pos();
+    let radius = POLAR_SIZE / 2.0 - POLAR_MARGIN;
+    let centre = [x + POLAR_SIZE / 2.0, y + POLAR_SIZE / 2.0];
+    let point = |angle_deg: f32, r: f32| {
+        let (sin, cos) = angle_deg.to_radians().sin_cos();
+        [centre[0] + r * radius * sin, centre[1] - r * radius * cos]
+    };
+
+    let fitted_peak = pattern.fit.map_or(0.0, |fit| {
+        (0..PATTERN_POINTS)
+            .map(|k| fit.amplitude(k as f32 * 360.0 / PATTERN_POINTS as f32))
+            .fold(0.0, f32::max)
+    });
+    let measured_peak = pattern
+        .measurements
+        .iter()
+        .map(|m| m.amplitude)
+        .fold(0.0, f32::max);
+    let scale = 1.0 / fitted_peak.max(measured_peak).max(f32::MIN_POSITIVE);
+
+    let draw_list = ui.get_window_draw_list();
+    draw_list
+        .add_rect([x, y], [x + POLAR_SIZE, y + POLAR_SIZE], PLOT_BACKGROUND)
+        .filled(true)
+        .build();
+    for r in [0.5, 1.0] {
+        draw_list.add_circle(centre, r * radius, GRID).build();
+    }
+    for spoke in 0..12 {
+        let angle = spoke as f32 * 30.0;
+        draw_list.add_line(centre, point(angle, 1.0), GRID).build();
+    }
+    for angle in [0.0, 90.0, 180.0, 270.0] {
+        let [lx, ly] = point(angle, 1.08);
+        draw_list.add_text([lx - 8.0, ly - 7.0], GRID_LABEL, format!("{:.0}", angle));
+    }
+
+    if let Some(fit) = &pattern.fit {
+        for node in fit.nodes_deg() {
+            draw_list
+                .add_line(centre, point(node, 1.0), NODE_LINE)
+                .build();
+        }
+        let points: Vec<[f32; 2]> = (0..=PATTERN_POINTS)
+            .map(|k| {
+                let angle = k as f32 * 360.0 / PATTERN_POINTS as f32;
+                point(angle, fit.amplitude(angle) * scale)
+            })
+            .collect();
+        draw_list.add_polyline(points, SPECTRUM_LINE).build();
+    }
+
+    for measurement in &pattern.measurements {
+        let color = match measurement.phase_deg {
+            Some(phase) if phase.abs() > 90.0 => ANTIPHASE,
+            _ => IN_PHASE,
+        };
+        draw_list
+            .add_circle(
+                point(measurement.angle_deg, measurement.amplitude * scale),
+                3.0,
+                color,
+            )
+            .filled(true)
+            .build();
+    }
+
+    ui.dummy([POLAR_SIZE, POLAR_SIZE]);
+}
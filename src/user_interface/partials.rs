@@ -0,0 +1,56 @@
+use crate::analysis::live::LiveAnalysis;
+
+/// Table of partial ratios with their deviation from the harmonic series and the thin-ring
+/// model, and the summary inharmonicity.
+pub fn build_partials_panel(ui: &imgui::Ui, analysis: &LiveAnalysis) {
+    let Some(partials) = analysis.partials() else {
+        ui.text("No partials detected");
+        return;
+    };
+
+    ui.text(format!("Fundamental: {:.2} Hz", partials.fundamental));
+    ui.text(format!(
+        "Inharmonicity: {:.0} cents RMS from the harmonic series",
+        partials.inharmonicity_cents
+    ));
+    ui.text(format!(
+        "Ring model deviation: {:.0} cents RMS",
+        partials.ring_model_deviation_cents
+    ));
+    ui.separator();
+
+    let headers = [
+        "Frequency",
+        "Ratio",
+        "Harmonic",
+        "Cents",
+        "Ring Mode",
+        "Model Ratio",
+        "Cents",
+        "Level",
+    ];
+    ui.columns(headers.len() as i32, "partial_ratios", false);
+    for header in headers {
+        ui.text(header);
+        ui.next_column();
+    }
+    ui.separator();
+
+    for partial in partials.partials.iter() {
+        let cells = [
+            format!("{:.2} Hz", partial.frequency),
+            format!("{:.3}", partial.ratio),
+            format!("{}", partial.harmonic),
+            format!("{:+.0}", partial.harmonic_deviation_cents),
+            format!("{}", partial.ring_mode),
+            format!("{:.3}", partial.ring_ratio),
+            format!("{:+.0}", partial.ring_deviation_cents),
+            format!("{:.1} dB", partial.magnitude_db),
+        ];
+        for cell in cells {
+            ui.text(cell);
+            ui.next_column();
+        }
+    }
+    ui.columns(1, "partial_ratios", false);
+}
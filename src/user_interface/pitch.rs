@@ -0,0 +1,167 @@
+use std::path::PathBuf;
+
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::pitch::{
+    summarize, PitchAlgorithm, PitchEstimate, PitchEstimator, PitchSummary, MAX_FREQUENCY,
+    MIN_FREQUENCY,
+};
+use crate::analysis::recording::load_wav;
+use crate::analysis::tuning::{Scale, Tuning};
+
+/// Spacing of the estimates taken from a recording
+const SUMMARY_HOP_SECONDS: f32 = 0.05;
+const CONFIDENCE_BAR_SIZE: [f32; 2] = [120.0, 0.0];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// All pitch estimators side by side on the live input or a recording, to see which suits
+/// bowls, voice or test tones best
+pub struct PitchPanel {
+    /// One estimator per algorithm, in `PitchAlgorithm::ALL` order
+    estimators: Vec<Box<dyn PitchEstimator>>,
+    estimates: Vec<Option<PitchEstimate>>,
+    sample_rate: u32,
+    tuning: Tuning,
+    path: String,
+    /// Summary per algorithm of the last analyzed recording
+    summaries: Option<Vec<Option<PitchSummary>>>,
+    error: Option<String>,
+}
+
+impl PitchPanel {
+    pub fn new() -> Self {
+        PitchPanel {
+            estimators: PitchAlgorithm::ALL.iter().map(|a| a.create()).collect(),
+            estimates: vec![None; PitchAlgorithm::ALL.len()],
+            sample_rate: 0,
+            tuning: Tuning::new(440.0, 0, Scale::equal_temperament()),
+            path: String::new(),
+            summaries: None,
+            error: None,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        ui.text(format!(
+            "Searching {:.0} to {:.0} Hz. Selected for the tuner: {}",
+            MIN_FREQUENCY,
+            MAX_FREQUENCY,
+            analysis.pitch_algorithm().name()
+        ));
+        ui.separator();
+
+        let sample_rate = analysis.sample_rate();
+        if sample_rate != self.sample_rate {
+            // estimators keep state from frame to frame
+            self.estimators = PitchAlgorithm::ALL.iter().map(|a| a.create()).collect();
+            self.sample_rate = sample_rate;
+        }
+        if sample_rate == 0 {
+            ui.text("Start the input to compare the estimators live");
+        } else {
+            for (estimate, estimator) in self.estimates.iter_mut().zip(self.estimators.iter_mut()) {
+                *estimate = estimator.estimate(analysis.history(), sample_rate);
+            }
+            self.build_live(ui);
+        }
+
+        ui.separator();
+        ui.text("Compare on a Recording");
+        ui.input_text("WAV File", &mut self.path).build();
+        ui.same_line();
+        if ui.button("Analyze") {
+            let path = PathBuf::from(self.path.trim());
+            match load_wav(&path) {
+                Ok((samples, sample_rate)) => {
+                    self.summaries = Some(
+                        PitchAlgorithm::ALL
+                            .iter()
+                            .map(|&algorithm| {
+                                summarize(algorithm, &samples, sample_rate, SUMMARY_HOP_SECONDS)
+                            })
+                            .collect(),
+                    );
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(format!("{}: {}", path.display(), e)),
+            }
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+        if let Some(summaries) = &self.summaries {
+            build_summaries(ui, summaries);
+        }
+    }
+
+    fn build_live(&self, ui: &imgui::Ui) {
+        let headers = ["Algorithm", "Frequency", "Note", "Confidence"];
+        ui.columns(headers.len() as i32, "pitch_live", false);
+        for header in headers {
+            ui.text(header);
+            ui.next_column();
+        }
+        ui.separator();
+
+        for (algorithm, estimate) in PitchAlgorithm::ALL.iter().zip(self.estimates.iter()) {
+            ui.text(algorithm.name());
+            ui.next_column();
+            match estimate {
+                Some(estimate) => {
+                    ui.text(format!("{:.2} Hz", estimate.frequency));
+                    ui.next_column();
+                    match self.tuning.nearest_note(estimate.frequency) {
+                        Some(note) => ui.text(format!("{} {:+.1}", note.name, note.cents)),
+                        None => ui.text("-"),
+                    }
+                    ui.next_column();
+                    imgui::ProgressBar::new(estimate.confidence)
+                        .size(CONFIDENCE_BAR_SIZE)
+                        .build(ui);
+                }
+                None => {
+                    ui.text_disabled("-");
+                    ui.next_column();
+                    ui.text_disabled("-");
+                    ui.next_column();
+                    ui.text_disabled("no pitch");
+                }
+            }
+            ui.next_column();
+        }
+        ui.columns(1, "pitch_live", false);
+    }
+}
+
+fn build_summaries(ui: &imgui::Ui, summaries: &[Option<PitchSummary>]) {
+    let headers = ["Algorithm", "Median", "Confidence", "Voiced", "Spread"];
+    ui.columns(headers.len() as i32, "pitch_summary", false);
+    for header in headers {
+        ui.text(header);
+        ui.next_column();
+    }
+    ui.separator();
+
+    for (algorithm, summary) in PitchAlgorithm::ALL.iter().zip(summaries) {
+        let cells = match summary {
+            Some(summary) => [
+                format!("{:.2} Hz", summary.median_frequency),
+                format!("{:.0}%", summary.mean_confidence * 100.0),
+                format!("{:.0}%", summary.voiced * 100.0),
+                format!("{:.1} cents", summary.spread_cents),
+            ],
+            None => [
+                String::from("-"),
+                String::from("-"),
+                String::from("0%"),
+                String::from("-"),
+            ],
+        };
+        ui.text(algorithm.name());
+        ui.next_column();
+        for cell in cells {
+            ui.text(cell);
+            ui.next_column();
+        }
+    }
+    ui.columns(1, "pitch_summary", false);
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use crate::analysis::live::LiveAnalysis;
+use crate::recorder::{Recorder, RecorderError, TriggerMode};
+use crate::settings::RecorderSettings;
+
+const TRIGGERS: [TriggerMode; 3] = [TriggerMode::Manual, TriggerMode::Level, TriggerMode::Onset];
+const TRIGGER_NAMES: [&str; 3] = ["Manual", "Level", "Strike"];
+const TAKES_SHOWN: usize = 10;
+const RECORDING_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+const ARMED_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// Recording of takes to WAV, by hand or triggered by the input
+pub struct RecorderPanel {
+    recorder: Recorder,
+    /// Contents of the directory field
+    directory: String,
+    error: Option<String>,
+}
+
+impl RecorderPanel {
+    pub fn new(settings: &RecorderSettings) -> Self {
+        RecorderPanel {
+            recorder: Recorder::new(settings),
+            directory: settings
+                .directory()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default(),
+            error: None,
+        }
+    }
+
+    pub fn settings(&self) -> &RecorderSettings {
+        self.recorder.settings()
+    }
+
+    /// Pass the newest input to the recorder. Called once per UI frame, whether the panel is
+    /// shown or not.
+    pub fn update(&mut self, analysis: &LiveAnalysis) {
+        let result = self.recorder.process(
+            analysis.latest_input(),
+            analysis.sample_rate(),
+            analysis.latest_strikes(),
+            analysis.input_position(),
+        );
+        if let Err(e) = result {
+            self.error = Some(e.to_string());
+            // a take that failed to write isn't continued
+            let _ = self.recorder.stop();
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui) {
+        let mut settings = self.recorder.settings().clone();
+
+        let mut trigger = TRIGGERS
+            .iter()
+            .position(|&t| t == settings.trigger)
+            .unwrap_or(0);
+        ui.combo_simple_string("Trigger", &mut trigger, &TRIGGER_NAMES);
+        settings.trigger = TRIGGERS[trigger];
+        if settings.trigger == TriggerMode::Level {
+            ui.slider_config("Trigger Level", -80.0, 0.0)
+                .display_format("%.0f dBFS")
+                .build(&mut settings.trigger_level_db);
+        }
+        ui.slider_config("Pre-roll", 0.0, 5.0)
+            .display_format("%.2f s")
+            .build(&mut settings.pre_roll_seconds);
+        if settings.trigger != TriggerMode::Manual {
+            ui.slider_config("Stop Level", -100.0, -20.0)
+                .display_format("%.0f dBFS")
+                .build(&mut settings.stop_level_db);
+            ui.slider_config("Stop After", 0.5, 30.0)
+                .display_format("%.1f s")
+                .build(&mut settings.stop_hold_seconds);
+        }
+        if ui.input_text("Directory", &mut self.directory).build() {
+            let directory = self.directory.trim();
+            settings.directory = (!directory.is_empty()).then(|| PathBuf::from(directory));
+        }
+        if &settings != self.recorder.settings() {
+            self.recorder.set_settings(settings);
+        }
+
+        ui.separator();
+        if self.recorder.is_recording() {
+            if ui.button("Stop") {
+                self.report(|recorder| recorder.stop());
+            }
+        } else if ui.button("Record") {
+            self.error = None;
+            self.report(|recorder| recorder.start());
+        }
+        if self.recorder.settings().trigger != TriggerMode::Manual {
+            ui.same_line();
+            let mut armed = self.recorder.is_armed();
+            if ui.checkbox("Armed", &mut armed) {
+                self.recorder.set_armed(armed);
+            }
+        }
+
+        if let Some(seconds) = self.recorder.take_seconds() {
+            ui.text_colored(RECORDING_COLOR, format!("Recording {:.1} s", seconds));
+        } else if self.recorder.is_armed() {
+            ui.text_colored(ARMED_COLOR, "Waiting for a trigger");
+        } else {
+            ui.text("Stopped");
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+
+        ui.separator();
+        ui.text("Takes");
+        for take in self.recorder.takes().iter().rev().take(TAKES_SHOWN) {
+            ui.text(take.display().to_string());
+        }
+    }
+
+    fn report(&mut self, action: impl FnOnce(&mut Recorder) -> Result<(), RecorderError>) {
+        if let Err(e) = action(&mut self.recorder) {
+            self.error = Some(e.to_string());
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use crate::audio_engine::convolution::{
+    partition_size, ImpulseResponse, MAX_IR_SECONDS, MAX_PRE_DELAY_MS,
+};
+use crate::audio_engine::io_manager::IOManager;
+use crate::settings::ReverbSettings;
+
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// Convolution reverb controls, to audition a bowl in a measured space
+pub struct ReverbPanel {
+    /// Contents of the impulse response field
+    path: String,
+    error: Option<String>,
+}
+
+impl ReverbPanel {
+    pub fn new(settings: &ReverbSettings) -> Self {
+        ReverbPanel {
+            path: settings
+                .impulse_response
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            error: None,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, io_manager: &IOManager) {
+        let convolution = io_manager.convolution();
+
+        ui.input_text("Impulse Response", &mut self.path).build();
+        if ui.button("Load") {
+            let path = PathBuf::from(self.path.trim());
+            match ImpulseResponse::load(&path) {
+                Ok(impulse_response) => {
+                    io_manager.set_impulse_response(Some(impulse_response));
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(format!("{}: {}", path.display(), e)),
+            }
+        }
+        ui.same_line();
+        if ui.button("Unload") {
+            io_manager.set_impulse_response(None);
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+
+        match convolution.impulse_response() {
+            Some(impulse_response) => {
+                let name = impulse_response
+                    .path()
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let seconds = impulse_response.seconds();
+                if seconds > MAX_IR_SECONDS {
+                    ui.text(format!(
+                        "{} ({:.1} s, cut to {:.0} s)",
+                        name, seconds, MAX_IR_SECONDS
+                    ));
+                } else {
+                    ui.text(format!("{} ({:.1} s)", name, seconds));
+                }
+            }
+            None => ui.text_disabled("No impulse response loaded"),
+        }
+
+        let mut enabled = convolution.enabled();
+        if ui.checkbox("Reverb", &mut enabled) {
+            convolution.set_enabled(enabled);
+        }
+
+        let mut mix = convolution.mix() * 100.0;
+        if ui
+            .slider_config("Dry/Wet", 0.0, 100.0)
+            .display_format("%.0f%%")
+            .build(&mut mix)
+        {
+            convolution.set_mix(mix / 100.0);
+        }
+
+        let mut pre_delay_ms = convolution.pre_delay_ms();
+        if ui
+            .slider_config("Pre-delay", 0.0, MAX_PRE_DELAY_MS)
+            .display_format("%.0f ms")
+            .build(&mut pre_delay_ms)
+        {
+            convolution.set_pre_delay_ms(pre_delay_ms);
+        }
+        if let Some(sample_rate) = io_manager.get_output_sample_rate() {
+            // the wet signal can't come sooner than one partition
+            let latency = partition_size(sample_rate) as f32 / sample_rate as f32;
+            ui.text_disabled(format!(
+                "Reverb is at least {:.0} ms late",
+                latency * 1000.0
+            ));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::f32::consts::PI;
+
+use crate::analysis::decay::analyze_modes;
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::nodal::NodalPattern;
+use crate::user_interface::spectrum::{GRID, GRID_LABEL, PLOT_BACKGROUND, SPECTRUM_LINE};
+
+const SHAPE_SIZE: f32 = 200.0;
+const SHAPE_MARGIN: f32 = 16.0;
+/// Points the rim is drawn with
+const RIM_POINTS: usize = 240;
+const ANTINODE_MARKER: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const NODE_MARKER: [f32; 4] = [1.0, 0.3, 0.6, 1.0];
+/// Amplitude assumed for the quieter half of a doublet when only its beating is known
+const ASSUMED_BALANCE: f32 = 0.5;
+const MAX_EXAGGERATION: f32 = 0.3;
+const MAX_VIBRATION_HZ: f32 = 4.0;
+const MAX_BEAT_SLOWDOWN: f32 = 20.0;
+
+/// Rim motion of one partial: a doublet of cos(n t) shapes a quarter wavelength apart, which
+/// beat against each other when the casting has split their frequencies
+struct ModeShape {
+    frequency: f32,
+    order: u32,
+    /// Antinode of the louder half of the doublet, in degrees
+    antinode_deg: f32,
+    /// Amplitude of the quieter half relative to the louder
+    balance: f32,
+    beat_hz: Option<f32>,
+    /// Orientation and balance come from a nodal map rather than being assumed
+    measured: bool,
+}
+
+impl ModeShape {
+    fn from_pattern(pattern: &NodalPattern) -> Option<Self> {
+        let fit = pattern.fit?;
+        Some(ModeShape {
+            frequency: pattern.partial.frequency,
+            order: fit.order,
+            antinode_deg: fit.antinode_deg,
+            balance: fit.node_depth,
+            beat_hz: pattern.beat_hz,
+            measured: true,
+        })
+    }
+
+    /// Radial and tangential displacement of the rim at an angle, relative to the peak
+    /// radial displacement. `vibration` and `beat` are the phases of the oscillation and of
+    /// the beating in radians.
+    fn displacement(&self, angle: f32, vibration: f32, beat: f32) -> (f32, f32) {
+        let n = self.order as f32;
+        let t = n * (angle - self.antinode_deg.to_radians());
+        let louder = vibration.cos();
+        let quieter = self.balance * (vibration + beat).cos();
+        // an inextensional ring mode moves the rim sideways by 1/n of its radial motion,
+        // a quarter wavelength along
+        let radial = t.cos() * louder + t.sin() * quieter;
+        let tangential = (-t.sin() * louder + t.cos() * quieter) / n;
+        (radial, tangential)
+    }
+}
+
+/// Rim deformation of each mode, animated in slow motion
+pub struct ModeShapesPanel {
+    shapes: Vec<ModeShape>,
+    playing: bool,
+    /// Animation time in seconds
+    time: f32,
+    /// Displayed oscillations per second; the true frequencies are far too fast to see
+    vibration_hz: f32,
+    /// Factor the beating is slowed down by
+    beat_slowdown: f32,
+    /// Peak displacement as a fraction of the rim radius
+    exaggeration: f32,
+}
+
+impl ModeShapesPanel {
+    pub fn new() -> Self {
+        ModeShapesPanel {
+            shapes: Vec::new(),
+            playing: true,
+            time: 0.0,
+            vibration_hz: 1.0,
+            beat_slowdown: 4.0,
+            exaggeration: 0.12,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis, patterns: &[NodalPattern]) {
+        if ui.button("Use Live Partials") {
+            self.shapes = live_shapes(analysis);
+        }
+        ui.same_line();
+        ui.disabled(patterns.is_empty(), || {
+            if ui.button("Use Nodal Map") {
+                self.shapes = patterns
+                    .iter()
+                    .filter_map(ModeShape::from_pattern)
+                    .collect();
+            }
+        });
+
+        ui.checkbox("Play", &mut self.playing);
+        ui.same_line();
+        if ui.button("Restart") {
+            self.time = 0.0;
+        }
+        ui.slider_config("Vibration", 0.1, MAX_VIBRATION_HZ)
+            .display_format("%.1f per second")
+            .build(&mut self.vibration_hz);
+        ui.slider_config("Beat Slow-down", 1.0, MAX_BEAT_SLOWDOWN)
+            .display_format("%.0fx")
+            .flags(imgui::SliderFlags::LOGARITHMIC)
+            .build(&mut self.beat_slowdown);
+        ui.slider_config("Exaggeration", 0.01, MAX_EXAGGERATION)
+            .display_format("%.2f")
+            .build(&mut self.exaggeration);
+        if self.playing {
+            self.time += ui.io().delta_time;
+        }
+
+        ui.separator();
+        if self.shapes.is_empty() {
+            ui.text("No modes: take them from the live partials or from a nodal map");
+            return;
+        }
+        if !self.shapes.iter().all(|shape| shape.measured) {
+            ui.text_disabled(
+                "Orientations are arbitrary and doublet balance assumed without a nodal map",
+            );
+        }
+
+        let per_row =
+            ((ui.content_region_avail()[0] / (SHAPE_SIZE + SHAPE_MARGIN)) as usize).max(1);
+        for (i, shape) in self.shapes.iter().enumerate() {
+            if i % per_row != 0 {
+                ui.same_line();
+            }
+            let _id = ui.push_id_usize(i);
+            ui.group(|| {
+                self.draw_shape(ui, shape);
+                ui.text(format!("{:.1} Hz, n = {}", shape.frequency, shape.order));
+                match shape.beat_hz {
+                    Some(beat_hz) => ui.text_disabled(format!("Beats at {:.2} Hz", beat_hz)),
+                    None => ui.text_disabled("No beating"),
+                }
+            });
+        }
+    }
+
+    fn draw_shape(&self, ui: &imgui::Ui, shape: &ModeShape) {
+        let [x, y] = ui.cursor_screen_pos();
+        let radius = (SHAPE_SIZE / 2.0 - SHAPE_MARGIN) / (1.0 + MAX_EXAGGERATION);
+        let centre = [x + SHAPE_SIZE / 2.0, y + SHAPE_SIZE / 2.0];
+        // 0 degrees at the top, clockwise, as in the nodal map
+        let point = |angle: f32, r: f32| {
+            let (sin, cos) = angle.sin_cos();
+            [centre[0] + r * radius * sin, centre[1] - r * radius * cos]
+        };
+
+        let vibration = 2.0 * PI * self.vibration_hz * self.time;
+        // without a measured beat the halves keep a quarter period apart, which fills in
+        // the nodes as much as the measured node depth
+        let beat = match shape.beat_hz {
+            Some(beat_hz) => 2.0 * PI * beat_hz * self.time / self.beat_slowdown,
+            None => PI / 2.0,
+        };
+
+        let draw_list = ui.get_window_draw_list();
+        draw_list
+            .add_rect([x, y], [x + SHAPE_SIZE, y + SHAPE_SIZE], PLOT_BACKGROUND)
+            .filled(true)
+            .build();
+        draw_list.add_circle(centre, radius, GRID).build();
+        draw_list.add_text([centre[0] - 4.0, y + 1.0], GRID_LABEL, "0");
+
+        // nodes and antinodes of the louder half
+        let spacing = PI / shape.order as f32;
+        let antinode = shape.antinode_deg.to_radians();
+        for k in 0..2 * shape.order {
+            let angle = antinode + k as f32 * spacing;
+            draw_list
+                .add_circle(point(angle, 1.0), 3.0, ANTINODE_MARKER)
+                .filled(true)
+                .build();
+            draw_list
+                .add_circle(point(angle + spacing / 2.0, 1.0), 3.0, NODE_MARKER)
+                .filled(true)
+                .build();
+        }
+
+        let rim: Vec<[f32; 2]> = (0..=RIM_POINTS)
+            .map(|k| {
+                let angle = k as f32 * 2.0 * PI / RIM_POINTS as f32;
+                let (radial, tangential) = shape.displacement(angle, vibration, beat);
+                point(
+                    angle + tangential * self.exaggeration,
+                    1.0 + radial * self.exaggeration,
+                )
+            })
+            .collect();
+        draw_list
+            .add_polyline(rim, SPECTRUM_LINE)
+            .thickness(2.0)
+            .build();
+
+        ui.dummy([SHAPE_SIZE, SHAPE_SIZE]);
+    }
+}
+
+/// Shapes of the current live partials, by their nearest ring mode, with beating measured
+/// over the input history
+fn live_shapes(analysis: &LiveAnalysis) -> Vec<ModeShape> {
+    let Some(partials) = analysis.partials() else {
+        return Vec::new();
+    };
+
+    let modes = analyze_modes(analysis.history(), analysis.sample_rate(), partials);
+    partials
+        .partials
+        .iter()
+        .zip(&modes)
+        .map(|(partial, mode)| ModeShape {
+            frequency: partial.frequency,
+            order: partial.ring_mode,
+            antinode_deg: 0.0,
+            balance: if mode.beat_hz.is_some() {
+                ASSUMED_BALANCE
+            } else {
+                0.0
+            },
+            beat_hz: mode.beat_hz,
+            measured: false,
+        })
+        .collect()
+}
@@ -0,0 +1,280 @@
+use std::path::PathBuf;
+
+use crate::analysis::recording::{load_wav, save_wav};
+use crate::analysis::sms::{analyze_sms, synthesize_sms, SmsAnalysis, SmsModifiers, NOISE_OFF_DB};
+use crate::audio_engine::io_manager::IOManager;
+use crate::audio_engine::player::Clip;
+use crate::user_interface::spectrum::{GRID, GRID_LABEL, PLOT_BACKGROUND, SPECTRUM_LINE};
+
+const PLOT_HEIGHT: f32 = 240.0;
+/// Frequency range of the track plot
+const MIN_PLOT_FREQUENCY: f32 = 50.0;
+const MAX_PLOT_FREQUENCY: f32 = 10000.0;
+/// Tracks this far below the loudest are drawn faintest
+const PLOT_RANGE_DB: f32 = 60.0;
+const FREQUENCY_GRID: [f32; 6] = [100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0];
+const MAX_FREQUENCY_SCALE: f32 = 2.0;
+const MAX_DECAY_SCALE: f32 = 4.0;
+const MAX_NOISE_GAIN_DB: f32 = 20.0;
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// The analyzed recording
+struct Source {
+    path: PathBuf,
+    samples: Vec<f32>,
+    analysis: SmsAnalysis,
+}
+
+/// What was last handed to the player
+#[derive(Clone, Copy, PartialEq)]
+enum Playing {
+    Original,
+    Resynthesis,
+}
+
+/// A recording split into partials and noise, resynthesized with their frequencies, decays
+/// and level changed
+pub struct SmsPanel {
+    path: String,
+    source: Option<Source>,
+    modifiers: SmsModifiers,
+    /// Resynthesis with the modifiers it was made with
+    resynthesis: Option<(Vec<f32>, SmsModifiers)>,
+    clip: Option<Playing>,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+impl SmsPanel {
+    pub fn new() -> Self {
+        SmsPanel {
+            path: String::new(),
+            source: None,
+            modifiers: SmsModifiers::default(),
+            resynthesis: None,
+            clip: None,
+            message: None,
+            error: None,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, io_manager: &IOManager) {
+        ui.input_text("WAV File", &mut self.path).build();
+        ui.same_line();
+        if ui.button("Analyze") {
+            let path = PathBuf::from(self.path.trim());
+            match load_wav(&path) {
+                Ok((samples, sample_rate)) => {
+                    let analysis = analyze_sms(&samples, sample_rate);
+                    self.source = Some(Source {
+                        path,
+                        samples,
+                        analysis,
+                    });
+                    self.resynthesis = None;
+                    self.error = None;
+                    self.message = None;
+                    if self.clip.take().is_some() {
+                        io_manager.set_clip(None);
+                    }
+                }
+                Err(e) => self.error = Some(format!("{}: {}", path.display(), e)),
+            }
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+        let Some(Source { analysis, .. }) = &self.source else {
+            ui.text("Analyze a recording to split it into partials and noise");
+            return;
+        };
+
+        ui.text(format!(
+            "{} partial tracks over {:.1} s",
+            analysis.tracks.len(),
+            analysis.duration()
+        ));
+        draw_tracks(ui, analysis);
+
+        ui.separator();
+        ui.slider_config("Frequency", 1.0 / MAX_FREQUENCY_SCALE, MAX_FREQUENCY_SCALE)
+            .display_format("x%.3f")
+            .flags(imgui::SliderFlags::LOGARITHMIC)
+            .build(&mut self.modifiers.frequency_scale);
+        ui.slider_config("Decay", 1.0 / MAX_DECAY_SCALE, MAX_DECAY_SCALE)
+            .display_format("x%.2f")
+            .flags(imgui::SliderFlags::LOGARITHMIC)
+            .build(&mut self.modifiers.decay_scale);
+        let format = if self.modifiers.noise_gain_db <= NOISE_OFF_DB {
+            "Off"
+        } else {
+            "%+.1f dB"
+        };
+        ui.slider_config("Noise", NOISE_OFF_DB, MAX_NOISE_GAIN_DB)
+            .display_format(format)
+            .build(&mut self.modifiers.noise_gain_db);
+        if ui.button("Reset") {
+            self.modifiers = SmsModifiers::default();
+        }
+
+        ui.separator();
+        self.build_playback(ui, io_manager);
+    }
+
+    fn build_playback(&mut self, ui: &imgui::Ui, io_manager: &IOManager) {
+        let player = io_manager.player();
+        if ui.button("Play Resynthesis") {
+            self.resynthesize();
+            if let (Some((samples, _)), Some(source)) = (&self.resynthesis, &self.source) {
+                let sample_rate = source.analysis.sample_rate;
+                io_manager.set_clip(Some(Clip::new(samples.clone(), sample_rate)));
+                self.clip = Some(Playing::Resynthesis);
+                player.play();
+            }
+        }
+        ui.same_line();
+        if ui.button("Play Original") {
+            if let Some(source) = &self.source {
+                let sample_rate = source.analysis.sample_rate;
+                io_manager.set_clip(Some(Clip::new(source.samples.clone(), sample_rate)));
+                self.clip = Some(Playing::Original);
+                player.play();
+            }
+        }
+        ui.same_line();
+        if ui.button("Stop") {
+            player.stop();
+        }
+        ui.same_line();
+        if ui.button("Save Resynthesis") {
+            self.resynthesize();
+            match self.save() {
+                Some(Ok(path)) => {
+                    self.message = Some(format!("Wrote {}", path.display()));
+                    self.error = None;
+                }
+                Some(Err(e)) => self.error = Some(e.to_string()),
+                None => {}
+            }
+        }
+
+        let mut gain_db = player.gain_db();
+        if ui
+            .slider_config("Playback Gain", -40.0, 12.0)
+            .display_format("%.1f dB")
+            .build(&mut gain_db)
+        {
+            player.set_gain_db(gain_db);
+        }
+        if player.is_playing() {
+            let label = match self.clip {
+                Some(Playing::Original) => "Original",
+                _ => "Resynthesis",
+            };
+            imgui::ProgressBar::new(player.progress())
+                .overlay_text(label)
+                .build(ui);
+        }
+        if io_manager.get_output_sample_rate().is_none() {
+            ui.text_disabled("Select an output device to listen");
+        }
+        if let Some(message) = &self.message {
+            ui.text(message);
+        }
+    }
+
+    /// Render the resynthesis unless it is up to date with the modifiers
+    fn resynthesize(&mut self) {
+        let Some(source) = &self.source else {
+            return;
+        };
+        if self
+            .resynthesis
+            .as_ref()
+            .is_some_and(|(_, modifiers)| *modifiers == self.modifiers)
+        {
+            return;
+        }
+        let samples = synthesize_sms(&source.analysis, &self.modifiers);
+        self.resynthesis = Some((samples, self.modifiers));
+    }
+
+    /// Written next to the recording. None without a resynthesis.
+    fn save(&self) -> Option<Result<PathBuf, hound::Error>> {
+        let (Some((samples, _)), Some(source)) = (&self.resynthesis, &self.source) else {
+            return None;
+        };
+        let path = &source.path;
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let output_path = path.with_file_name(format!(
+            "{}_sms_f{:.3}_d{:.2}.wav",
+            stem, self.modifiers.frequency_scale, self.modifiers.decay_scale
+        ));
+        let sample_rate = source.analysis.sample_rate;
+        Some(save_wav(&output_path, samples, sample_rate).map(|()| output_path))
+    }
+}
+
+/// Partial tracks over time on a log frequency axis, brighter for louder partials
+fn draw_tracks(ui: &imgui::Ui, analysis: &SmsAnalysis) {
+    let [x, y] = ui.cursor_screen_pos();
+    let width = ui.content_region_avail()[0];
+    let (min, max) = ([x, y], [x + width, y + PLOT_HEIGHT]);
+    let duration = analysis.duration().max(f32::MIN_POSITIVE);
+    let range = (MAX_PLOT_FREQUENCY / MIN_PLOT_FREQUENCY).ln();
+    let to_y = |frequency: f32| {
+        let t = (frequency.max(1.0) / MIN_PLOT_FREQUENCY).ln() / range;
+        max[1] - t * PLOT_HEIGHT
+    };
+    let loudest = analysis
+        .tracks
+        .iter()
+        .flat_map(|track| track.amplitudes.iter())
+        .fold(f32::MIN_POSITIVE, |a, b| a.max(*b));
+
+    let draw_list = ui.get_window_draw_list();
+    draw_list.with_clip_rect_intersect(min, max, || {
+        draw_list
+            .add_rect(min, max, PLOT_BACKGROUND)
+            .filled(true)
+            .build();
+        for frequency in FREQUENCY_GRID {
+            let line_y = to_y(frequency);
+            draw_list
+                .add_line([x, line_y], [max[0], line_y], GRID)
+                .build();
+            let label = if frequency >= 1000.0 {
+                format!("{}k", frequency / 1000.0)
+            } else {
+                format!("{}", frequency)
+            };
+            draw_list.add_text([x + 2.0, line_y - 14.0], GRID_LABEL, label);
+        }
+        draw_list.add_text(
+            [max[0] - 60.0, max[1] - 14.0],
+            GRID_LABEL,
+            format!("{:.1} s", duration),
+        );
+
+        for track in analysis.tracks.iter() {
+            let peak = track.amplitudes.iter().cloned().fold(0.0, f32::max);
+            let level_db = 20.0 * (peak / loudest).log10();
+            let alpha = (1.0 + level_db / PLOT_RANGE_DB).clamp(0.1, 1.0);
+            let points: Vec<[f32; 2]> = track
+                .frequencies
+                .iter()
+                .enumerate()
+                .map(|(j, frequency)| {
+                    let time = analysis.frame_time(track.start + j);
+                    [x + time / duration * width, to_y(*frequency)]
+                })
+                .collect();
+            let [r, g, b, _] = SPECTRUM_LINE;
+            draw_list.add_polyline(points, [r, g, b, alpha]).build();
+        }
+    });
+    ui.dummy([width, PLOT_HEIGHT]);
+}
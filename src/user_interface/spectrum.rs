@@ -0,0 +1,240 @@
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::partials::{ring_ratio, MAX_RING_MODE};
+use crate::analysis::spectrum::Spectrum;
+
+const PLOT_HEIGHT: f32 = 320.0;
+/// Frequencies labelled on the axis
+const FREQUENCY_GRID: [f32; 10] = [
+    50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 15000.0, 20000.0,
+];
+const DB_GRID_STEP: f32 = 20.0;
+
+pub const PLOT_BACKGROUND: [f32; 4] = [0.08, 0.08, 0.08, 1.0];
+pub const GRID: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+pub const GRID_LABEL: [f32; 4] = [0.55, 0.55, 0.55, 1.0];
+pub const SPECTRUM_LINE: [f32; 4] = [0.3, 0.75, 1.0, 1.0];
+const PARTIAL_LINE: [f32; 4] = [1.0, 0.8, 0.2, 0.8];
+const RING_MODEL_LINE: [f32; 4] = [1.0, 0.3, 0.6, 0.6];
+const HARMONIC_LINE: [f32; 4] = [0.5, 0.9, 0.5, 0.35];
+
+/// Maps frequency and level to screen coordinates within a plot rectangle
+#[derive(Clone, Copy)]
+pub struct PlotArea {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    pub log_frequency: bool,
+    pub floor_db: f32,
+}
+
+impl PlotArea {
+    pub fn x(&self, frequency: f32) -> f32 {
+        let t = if self.log_frequency {
+            (frequency.max(1.0) / self.min_frequency).ln()
+                / (self.max_frequency / self.min_frequency).ln()
+        } else {
+            (frequency - self.min_frequency) / (self.max_frequency - self.min_frequency)
+        };
+        self.min[0] + t * (self.max[0] - self.min[0])
+    }
+
+    pub fn frequency(&self, x: f32) -> f32 {
+        let t = (x - self.min[0]) / (self.max[0] - self.min[0]);
+        if self.log_frequency {
+            self.min_frequency * (self.max_frequency / self.min_frequency).powf(t)
+        } else {
+            self.min_frequency + t * (self.max_frequency - self.min_frequency)
+        }
+    }
+
+    pub fn y(&self, db: f32) -> f32 {
+        let t = (db / self.floor_db).clamp(0.0, 1.0);
+        self.min[1] + t * (self.max[1] - self.min[1])
+    }
+
+    pub fn contains_frequency(&self, frequency: f32) -> bool {
+        frequency >= self.min_frequency && frequency <= self.max_frequency
+    }
+
+    /// Background, frequency and level grid
+    pub fn draw_grid(&self, draw_list: &imgui::DrawListMut) {
+        draw_list
+            .add_rect(self.min, self.max, PLOT_BACKGROUND)
+            .filled(true)
+            .build();
+
+        for frequency in FREQUENCY_GRID
+            .iter()
+            .filter(|&&f| self.contains_frequency(f))
+        {
+            let x = self.x(*frequency);
+            draw_list
+                .add_line([x, self.min[1]], [x, self.max[1]], GRID)
+                .build();
+            let label = if *frequency >= 1000.0 {
+                format!("{}k", frequency / 1000.0)
+            } else {
+                format!("{}", frequency)
+            };
+            draw_list.add_text([x + 2.0, self.max[1] - 14.0], GRID_LABEL, label);
+        }
+
+        let mut db = 0.0;
+        while db > self.floor_db {
+            let y = self.y(db);
+            draw_list
+                .add_line([self.min[0], y], [self.max[0], y], GRID)
+                .build();
+            draw_list.add_text([self.min[0] + 2.0, y], GRID_LABEL, format!("{} dB", db));
+            db -= DB_GRID_STEP;
+        }
+    }
+
+    /// Spectrum as a line, taking the highest bin under each pixel column
+    pub fn draw_spectrum(
+        &self,
+        draw_list: &imgui::DrawListMut,
+        spectrum: &Spectrum,
+        color: [f32; 4],
+    ) {
+        let width = (self.max[0] - self.min[0]).max(1.0) as usize;
+        let last_bin = spectrum.magnitudes_db.len().saturating_sub(1);
+
+        let points: Vec<[f32; 2]> = (0..width)
+            .filter_map(|column| {
+                let x = self.min[0] + column as f32;
+                let first = (self.frequency(x) / spectrum.bin_hz).round() as usize;
+                let last = (self.frequency(x + 1.0) / spectrum.bin_hz).round() as usize;
+                let bins = spectrum
+                    .magnitudes_db
+                    .get(first.min(last_bin)..=last.min(last_bin))?;
+                let db = bins.iter().cloned().fold(f32::MIN, f32::max);
+                Some([x, self.y(db)])
+            })
+            .collect();
+
+        draw_list.add_polyline(points, color).build();
+    }
+
+    /// Full height marker with an optional label at the top
+    pub fn draw_marker(
+        &self,
+        draw_list: &imgui::DrawListMut,
+        frequency: f32,
+        color: [f32; 4],
+        label: Option<String>,
+    ) {
+        if !self.contains_frequency(frequency) {
+            return;
+        }
+        let x = self.x(frequency);
+        draw_list
+            .add_line([x, self.min[1]], [x, self.max[1]], color)
+            .build();
+        if let Some(label) = label {
+            draw_list.add_text([x + 2.0, self.min[1] + 2.0], color, label);
+        }
+    }
+}
+
+/// Live spectrum with the partial ratios and model predictions overlaid
+pub struct SpectrumView {
+    log_frequency: bool,
+    max_frequency: f32,
+    floor_db: f32,
+    show_partial_ratios: bool,
+    show_ring_model: bool,
+    show_harmonics: bool,
+}
+
+impl SpectrumView {
+    pub fn new() -> Self {
+        SpectrumView {
+            log_frequency: true,
+            max_frequency: 5000.0,
+            floor_db: -100.0,
+            show_partial_ratios: true,
+            show_ring_model: false,
+            show_harmonics: false,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        ui.checkbox("Log Frequency", &mut self.log_frequency);
+        ui.same_line();
+        ui.checkbox("Partial Ratios", &mut self.show_partial_ratios);
+        ui.same_line();
+        ui.checkbox("Ring Model", &mut self.show_ring_model);
+        ui.same_line();
+        ui.checkbox("Harmonics", &mut self.show_harmonics);
+        ui.slider_config("Max Frequency", 500.0, 20000.0)
+            .display_format("%.0f Hz")
+            .flags(imgui::SliderFlags::LOGARITHMIC)
+            .build(&mut self.max_frequency);
+        ui.slider_config("Floor", -140.0, -40.0)
+            .display_format("%.0f dB")
+            .build(&mut self.floor_db);
+
+        let [x, y] = ui.cursor_screen_pos();
+        let width = ui.content_region_avail()[0];
+        let area = PlotArea {
+            min: [x, y],
+            max: [x + width, y + PLOT_HEIGHT],
+            min_frequency: 20.0,
+            max_frequency: self.max_frequency,
+            log_frequency: self.log_frequency,
+            floor_db: self.floor_db,
+        };
+
+        let draw_list = ui.get_window_draw_list();
+        draw_list.with_clip_rect_intersect(area.min, area.max, || {
+            area.draw_grid(&draw_list);
+            if let Some(spectrum) = analysis.spectrum() {
+                area.draw_spectrum(&draw_list, spectrum, SPECTRUM_LINE);
+            }
+            if let Some(partials) = analysis.partials() {
+                self.draw_overlays(&draw_list, &area, partials);
+            }
+        });
+        ui.dummy([width, PLOT_HEIGHT]);
+    }
+
+    fn draw_overlays(
+        &self,
+        draw_list: &imgui::DrawListMut,
+        area: &PlotArea,
+        partials: &crate::analysis::partials::PartialAnalysis,
+    ) {
+        let f0 = partials.fundamental;
+
+        if self.show_harmonics {
+            let mut harmonic = 1;
+            while f0 * harmonic as f32 <= area.max_frequency {
+                area.draw_marker(draw_list, f0 * harmonic as f32, HARMONIC_LINE, None);
+                harmonic += 1;
+            }
+        }
+        if self.show_ring_model {
+            for mode in 2..=MAX_RING_MODE {
+                let frequency = f0 * ring_ratio(mode) as f32;
+                area.draw_marker(
+                    draw_list,
+                    frequency,
+                    RING_MODEL_LINE,
+                    Some(format!("n={}", mode)),
+                );
+            }
+        }
+        if self.show_partial_ratios {
+            for partial in partials.partials.iter() {
+                area.draw_marker(
+                    draw_list,
+                    partial.frequency,
+                    PARTIAL_LINE,
+                    Some(format!("{:.2}", partial.ratio)),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use crate::analysis::recording::{load_wav, save_wav};
+use crate::audio_engine::phase_vocoder::{
+    semitones_to_ratio, stretch_recording, MAX_PITCH_SEMITONES, MAX_STRETCH, MIN_STRETCH,
+};
+
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// Offline time stretch and pitch shift of a recording, written next to it as a new WAV file
+pub struct StretchPanel {
+    path: String,
+    stretch: f32,
+    semitones: f32,
+    /// Last file written and its length in seconds
+    result: Option<(PathBuf, f32)>,
+    error: Option<String>,
+}
+
+impl StretchPanel {
+    pub fn new() -> Self {
+        StretchPanel {
+            path: String::new(),
+            stretch: 2.0,
+            semitones: 0.0,
+            result: None,
+            error: None,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui) {
+        ui.input_text("WAV File", &mut self.path).build();
+        ui.slider_config("Time Stretch", MIN_STRETCH, MAX_STRETCH)
+            .display_format("x%.2f")
+            .flags(imgui::SliderFlags::LOGARITHMIC)
+            .build(&mut self.stretch);
+        ui.slider_config("Pitch Shift", -MAX_PITCH_SEMITONES, MAX_PITCH_SEMITONES)
+            .display_format("%.2f semitones")
+            .build(&mut self.semitones);
+        ui.text_disabled("Stretch above 1 slows the sound down, e.g. to hear beating");
+
+        if ui.button("Process") {
+            let path = PathBuf::from(self.path.trim());
+            match self.process(&path) {
+                Ok(result) => {
+                    self.result = Some(result);
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(format!("{}: {}", path.display(), e)),
+            }
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+        if let Some((path, seconds)) = &self.result {
+            ui.text(format!("Wrote {} ({:.1} s)", path.display(), seconds));
+        }
+    }
+
+    fn process(&self, path: &Path) -> Result<(PathBuf, f32), hound::Error> {
+        let (samples, sample_rate) = load_wav(path)?;
+        let output = stretch_recording(
+            &samples,
+            sample_rate,
+            self.stretch,
+            semitones_to_ratio(self.semitones),
+        );
+
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let output_path = path.with_file_name(format!(
+            "{}_x{:.2}_{:+.1}st.wav",
+            stem, self.stretch, self.semitones
+        ));
+        save_wav(&output_path, &output, sample_rate)?;
+        Ok((output_path, output.len() as f32 / sample_rate as f32))
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::onset::OnsetFunction;
+use crate::analysis::recording::{analyze_strikes, load_wav, StrikeAnalysis};
+use crate::settings::OnsetSettings;
+
+const FUNCTIONS: [OnsetFunction; 3] = [
+    OnsetFunction::SpectralFlux,
+    OnsetFunction::HighFrequencyContent,
+    OnsetFunction::ComplexDomain,
+];
+const FUNCTION_NAMES: [&str; 3] = ["Spectral Flux", "High Frequency Content", "Complex Domain"];
+/// The strike indicator stays lit this long
+const INDICATOR_SECONDS: f32 = 0.3;
+const LIVE_STRIKES_SHOWN: usize = 8;
+const INDICATOR_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// Live strike detection and the segmentation of recordings into strikes
+pub struct StrikesPanel {
+    settings: OnsetSettings,
+    path: String,
+    /// Sample rate and strikes of the last segmented recording
+    recording: Option<(u32, Vec<StrikeAnalysis>)>,
+    error: Option<String>,
+}
+
+impl StrikesPanel {
+    pub fn new(settings: &OnsetSettings) -> Self {
+        StrikesPanel {
+            settings: settings.clone(),
+            path: String::new(),
+            recording: None,
+            error: None,
+        }
+    }
+
+    pub fn settings(&self) -> &OnsetSettings {
+        &self.settings
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &mut LiveAnalysis) {
+        let mut function = FUNCTIONS
+            .iter()
+            .position(|&f| f == self.settings.function)
+            .unwrap_or(0);
+        let mut changed = ui.combo_simple_string("Onset Function", &mut function, &FUNCTION_NAMES);
+        self.settings.function = FUNCTIONS[function];
+        changed |= ui.slider("Sensitivity", 0.0, 1.0, &mut self.settings.sensitivity);
+        if changed {
+            analysis.set_onset_detection(self.settings.function, self.settings.sensitivity);
+        }
+
+        ui.separator();
+        build_live_strikes(ui, analysis);
+
+        ui.separator();
+        ui.text("Segment a Recording");
+        ui.input_text("WAV File", &mut self.path).build();
+        ui.same_line();
+        if ui.button("Detect Strikes") {
+            let path = PathBuf::from(self.path.trim());
+            match load_wav(&path) {
+                Ok((samples, sample_rate)) => {
+                    let strikes = analyze_strikes(
+                        &samples,
+                        sample_rate,
+                        self.settings.function,
+                        self.settings.sensitivity,
+                    );
+                    self.recording = Some((sample_rate, strikes));
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(format!("{}: {}", path.display(), e)),
+            }
+        }
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+        if let Some((sample_rate, strikes)) = &self.recording {
+            build_strike_table(ui, *sample_rate, strikes);
+        }
+    }
+}
+
+fn build_live_strikes(ui: &imgui::Ui, analysis: &LiveAnalysis) {
+    let sample_rate = analysis.sample_rate();
+    if sample_rate == 0 {
+        ui.text("Start the input to detect strikes");
+        return;
+    }
+
+    let position = analysis.input_position();
+    let age = |sample: u64| position.saturating_sub(sample) as f32 / sample_rate as f32;
+    let last = analysis.strikes().back();
+    if last.is_some_and(|onset| age(onset.sample) < INDICATOR_SECONDS) {
+        ui.text_colored(INDICATOR_COLOR, "STRIKE");
+    } else {
+        ui.text_disabled("STRIKE");
+    }
+
+    for onset in analysis.strikes().iter().rev().take(LIVE_STRIKES_SHOWN) {
+        ui.text(format!(
+            "{:>8.1} s ago   sample {:>10}   strength {:.1}",
+            age(onset.sample),
+            onset.sample,
+            onset.strength
+        ));
+    }
+}
+
+fn build_strike_table(ui: &imgui::Ui, sample_rate: u32, strikes: &[StrikeAnalysis]) {
+    if strikes.is_empty() {
+        ui.text("No strikes found");
+        return;
+    }
+
+    let headers = [
+        "Strike",
+        "Start",
+        "Length",
+        "Fundamental",
+        "T60",
+        "Beat",
+        "Inharmonicity",
+    ];
+    ui.columns(headers.len() as i32, "strike_table", false);
+    for header in headers {
+        ui.text(header);
+        ui.next_column();
+    }
+    ui.separator();
+
+    let format_optional = |value: Option<f32>, unit: &str| match value {
+        Some(value) => format!("{:.2} {}", value, unit),
+        None => String::from("-"),
+    };
+    for (i, strike) in strikes.iter().enumerate() {
+        let fundamental = strike.analysis.modes.first();
+        let partials = strike.analysis.frame.partials.as_ref();
+        let cells = [
+            format!("{}", i + 1),
+            format!("{:.3} s", strike.onset.seconds(sample_rate)),
+            format!("{:.1} s", strike.range.len() as f32 / sample_rate as f32),
+            format_optional(fundamental.map(|m| m.frequency), "Hz"),
+            format_optional(fundamental.and_then(|m| m.t60), "s"),
+            format_optional(fundamental.and_then(|m| m.beat_hz), "Hz"),
+            format_optional(partials.map(|p| p.inharmonicity_cents), "cents"),
+        ];
+        for cell in cells {
+            ui.text(cell);
+            ui.next_column();
+        }
+    }
+    ui.columns(1, "strike_table", false);
+}
@@ -0,0 +1,405 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::recording::{analyze_recording, load_wav};
+use crate::analysis::sweep::{
+    PolynomialFit, SweepQuantity, SweepSession, SweepTake, Track, MAX_FIT_DEGREE,
+};
+use crate::catalog::bowl::Mode;
+use crate::user_interface::catalog::capture_modes;
+use crate::user_interface::spectrum::{GRID, GRID_LABEL, PLOT_BACKGROUND};
+
+const PLOT_HEIGHT: f32 = 280.0;
+/// Space left of the plot for the value labels
+const AXIS_WIDTH: f32 = 70.0;
+const Y_GRID_LINES: usize = 5;
+/// Points each fitted curve is drawn with
+const CURVE_POINTS: usize = 64;
+const FIT_NAMES: [&str; MAX_FIT_DEGREE + 1] = ["None", "Linear", "Quadratic", "Cubic"];
+/// Colors the tracks cycle through
+const TRACK_COLORS: [[f32; 4]; 6] = [
+    [0.3, 0.75, 1.0, 1.0],
+    [1.0, 0.55, 0.2, 1.0],
+    [0.5, 0.9, 0.5, 1.0],
+    [1.0, 0.3, 0.6, 1.0],
+    [1.0, 0.8, 0.2, 1.0],
+    [0.7, 0.5, 1.0, 1.0],
+];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// Takes of a bowl tagged with the value of one physical parameter, and how each partial's
+/// frequency, level and decay follow it
+pub struct SweepPanel {
+    session: SweepSession,
+    /// Session file
+    path: String,
+    /// Parameter value of the next take
+    value: f32,
+    recording_path: String,
+    quantity: SweepQuantity,
+    /// Polynomial degree fitted across the takes, 0 for no fit
+    degree: usize,
+    /// Followed through the takes, recomputed when they change
+    tracks: Vec<Track>,
+    error: Option<String>,
+}
+
+impl SweepPanel {
+    pub fn new() -> Self {
+        SweepPanel {
+            session: SweepSession::default(),
+            path: String::new(),
+            value: 0.0,
+            recording_path: String::new(),
+            quantity: SweepQuantity::Frequency,
+            degree: 1,
+            tracks: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        self.build_session(ui);
+        ui.separator();
+        self.build_takes(ui, analysis);
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+
+        if self.tracks.is_empty() {
+            return;
+        }
+        ui.separator();
+        ui.set_next_item_width(160.0);
+        let mut quantity = SweepQuantity::ALL
+            .iter()
+            .position(|&q| q == self.quantity)
+            .unwrap_or(0);
+        if ui.combo("Plot", &mut quantity, &SweepQuantity::ALL, |q| {
+            Cow::Borrowed(q.name())
+        }) {
+            self.quantity = SweepQuantity::ALL[quantity];
+        }
+        ui.same_line();
+        ui.set_next_item_width(120.0);
+        ui.combo_simple_string("Fit", &mut self.degree, &FIT_NAMES);
+
+        let series: Vec<Vec<(f64, f64)>> = self
+            .tracks
+            .iter()
+            .map(|track| track.series(self.quantity))
+            .collect();
+        let fits: Vec<Option<PolynomialFit>> = series
+            .iter()
+            .map(|points| {
+                (self.degree > 0)
+                    .then(|| PolynomialFit::new(points, self.degree))
+                    .flatten()
+            })
+            .collect();
+        self.draw_plot(ui, &series, &fits);
+        self.build_fit_table(ui, &series, &fits);
+    }
+
+    fn build_session(&mut self, ui: &imgui::Ui) {
+        ui.input_text("Session File", &mut self.path)
+            .hint("sweep.toml")
+            .build();
+        ui.same_line();
+        if ui.button("Load") {
+            let path = PathBuf::from(self.path.trim());
+            match SweepSession::load(&path) {
+                Ok(session) => {
+                    self.session = session;
+                    self.tracks = self.session.tracks();
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+        ui.same_line();
+        if ui.button("Save") {
+            let path = PathBuf::from(self.path.trim());
+            self.error = self.session.save(&path).err().map(|e| e.to_string());
+        }
+        ui.same_line();
+        if ui.button("New") {
+            self.session = SweepSession::default();
+            self.tracks.clear();
+            self.error = None;
+        }
+
+        ui.input_text("Parameter", &mut self.session.parameter)
+            .hint("water level, striking height, mallet")
+            .build();
+        ui.input_text("Unit", &mut self.session.unit)
+            .hint("ml, mm, ...")
+            .build();
+        ui.input_text_multiline("Notes", &mut self.session.notes, [0.0, 40.0])
+            .build();
+    }
+
+    fn build_takes(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        ui.input_float(self.parameter_label(), &mut self.value)
+            .build();
+        ui.text_disabled("Number categories such as the mallet type, e.g. by hardness");
+        if ui.button("Capture from Input") {
+            let modes = capture_modes(analysis);
+            if modes.is_empty() {
+                self.error = Some(String::from("No partials in the input"));
+            } else {
+                self.add_take(None, modes);
+            }
+        }
+        ui.input_text("WAV File", &mut self.recording_path).build();
+        ui.same_line();
+        if ui.button("Add Recording") {
+            let path = PathBuf::from(self.recording_path.trim());
+            match recording_modes(&path) {
+                Ok(modes) => self.add_take(Some(path), modes),
+                Err(e) => self.error = Some(e),
+            }
+        }
+
+        if self.session.takes.is_empty() {
+            ui.text("No takes yet");
+            return;
+        }
+        let headers = ["Value", "Fundamental", "Partials", "Recording", ""];
+        ui.columns(headers.len() as i32, "sweep_takes", false);
+        for header in headers {
+            ui.text(header);
+            ui.next_column();
+        }
+        ui.separator();
+
+        let mut removed = None;
+        for (i, take) in self.session.takes.iter().enumerate() {
+            ui.text(format!("{} {}", take.value, self.session.unit));
+            ui.next_column();
+            match take.modes.first() {
+                Some(mode) => ui.text(format!("{:.2} Hz", mode.frequency)),
+                None => ui.text_disabled("-"),
+            }
+            ui.next_column();
+            ui.text(format!("{}", take.modes.len()));
+            ui.next_column();
+            match &take.recording {
+                Some(path) => ui.text(path.display().to_string()),
+                None => ui.text_disabled("live input"),
+            }
+            ui.next_column();
+            let _id = ui.push_id_usize(i);
+            if ui.small_button("Remove") {
+                removed = Some(i);
+            }
+            ui.next_column();
+        }
+        ui.columns(1, "sweep_takes", false);
+
+        if let Some(i) = removed {
+            self.session.takes.remove(i);
+            self.tracks = self.session.tracks();
+        }
+    }
+
+    fn add_take(&mut self, recording: Option<PathBuf>, modes: Vec<Mode>) {
+        self.session.add_take(SweepTake {
+            value: self.value,
+            recording,
+            modes,
+        });
+        self.tracks = self.session.tracks();
+        self.error = None;
+    }
+
+    fn parameter_label(&self) -> String {
+        let name = if self.session.parameter.is_empty() {
+            "Value"
+        } else {
+            self.session.parameter.as_str()
+        };
+        if self.session.unit.is_empty() {
+            format!("{}##value", name)
+        } else {
+            format!("{} ({})##value", name, self.session.unit)
+        }
+    }
+
+    /// Each track's values against the parameter as points, with its fitted curve
+    fn draw_plot(
+        &self,
+        ui: &imgui::Ui,
+        series: &[Vec<(f64, f64)>],
+        fits: &[Option<PolynomialFit>],
+    ) {
+        let [x, y] = ui.cursor_screen_pos();
+        let width = ui.content_region_avail()[0];
+        let (min, max) = ([x + AXIS_WIDTH, y], [x + width, y + PLOT_HEIGHT]);
+
+        let (mut x_min, mut x_max) = (f64::MAX, f64::MIN);
+        let (mut y_min, mut y_max) = (f64::MAX, f64::MIN);
+        for (px, py) in series.iter().flatten() {
+            x_min = x_min.min(*px);
+            x_max = x_max.max(*px);
+            y_min = y_min.min(*py);
+            y_max = y_max.max(*py);
+        }
+        if x_min > x_max {
+            ui.text_disabled(format!("No {} measured", self.quantity.name()));
+            return;
+        }
+        // a little room around the points, and some span if they are all equal
+        let x_pad = ((x_max - x_min) * 0.05).max(0.5);
+        let y_pad = ((y_max - y_min) * 0.1).max(1e-3 * y_max.abs()).max(0.5);
+        let (x_min, x_max) = (x_min - x_pad, x_max + x_pad);
+        let (y_min, y_max) = (y_min - y_pad, y_max + y_pad);
+        let to_screen = |px: f64, py: f64| {
+            [
+                min[0] + ((px - x_min) / (x_max - x_min)) as f32 * (max[0] - min[0]),
+                max[1] - ((py - y_min) / (y_max - y_min)) as f32 * (max[1] - min[1]),
+            ]
+        };
+
+        let draw_list = ui.get_window_draw_list();
+        draw_list
+            .add_rect(min, max, PLOT_BACKGROUND)
+            .filled(true)
+            .build();
+        for k in 0..=Y_GRID_LINES {
+            let value = y_min + (y_max - y_min) * k as f64 / Y_GRID_LINES as f64;
+            let [_, line_y] = to_screen(x_min, value);
+            draw_list
+                .add_line([min[0], line_y], [max[0], line_y], GRID)
+                .build();
+            draw_list.add_text(
+                [x, line_y - 7.0],
+                GRID_LABEL,
+                format!("{:.1} {}", value, self.quantity.unit()),
+            );
+        }
+        // a vertical line at each take
+        let mut values: Vec<f32> = self.session.takes.iter().map(|take| take.value).collect();
+        values.dedup();
+        for value in values {
+            let [line_x, _] = to_screen(value as f64, y_min);
+            draw_list
+                .add_line([line_x, min[1]], [line_x, max[1]], GRID)
+                .build();
+            draw_list.add_text(
+                [line_x + 2.0, max[1] - 14.0],
+                GRID_LABEL,
+                format!("{}", value),
+            );
+        }
+
+        draw_list.with_clip_rect_intersect(min, max, || {
+            for (i, (points, fit)) in series.iter().zip(fits).enumerate() {
+                let color = TRACK_COLORS[i % TRACK_COLORS.len()];
+                match fit {
+                    Some(fit) => {
+                        let (first, last) = (points[0].0, points[points.len() - 1].0);
+                        let curve: Vec<[f32; 2]> = (0..=CURVE_POINTS)
+                            .map(|k| {
+                                let px = first + (last - first) * k as f64 / CURVE_POINTS as f64;
+                                to_screen(px, fit.evaluate(px))
+                            })
+                            .collect();
+                        draw_list.add_polyline(curve, color).build();
+                    }
+                    None => {
+                        let line: Vec<[f32; 2]> =
+                            points.iter().map(|(px, py)| to_screen(*px, *py)).collect();
+                        draw_list
+                            .add_polyline(line, [color[0], color[1], color[2], 0.4])
+                            .build();
+                    }
+                }
+                for (px, py) in points {
+                    draw_list
+                        .add_circle(to_screen(*px, *py), 3.5, color)
+                        .filled(true)
+                        .build();
+                }
+            }
+        });
+        ui.dummy([width, PLOT_HEIGHT]);
+    }
+
+    fn build_fit_table(
+        &self,
+        ui: &imgui::Ui,
+        series: &[Vec<(f64, f64)>],
+        fits: &[Option<PolynomialFit>],
+    ) {
+        let unit = self.quantity.unit();
+        let per = if self.session.unit.is_empty() {
+            String::from("per unit")
+        } else {
+            format!("per {}", self.session.unit)
+        };
+        let headers = ["Partial", "Takes", "Change", "Mean Slope", "R²"];
+        ui.columns(headers.len() as i32, "sweep_fits", false);
+        for header in headers {
+            ui.text(header);
+            ui.next_column();
+        }
+        ui.separator();
+
+        for (i, ((track, points), fit)) in self.tracks.iter().zip(series).zip(fits).enumerate() {
+            let color = TRACK_COLORS[i % TRACK_COLORS.len()];
+            ui.text_colored(color, format!("{:.1} Hz", track.first().1.frequency));
+            ui.next_column();
+            ui.text(format!("{}", points.len()));
+            ui.next_column();
+
+            // over the takes the value was measured in, along the fit if there is one
+            let change = match (points.first(), points.last()) {
+                (Some(&(first_x, first_y)), Some(&(last_x, last_y))) if points.len() > 1 => {
+                    let (first_y, last_y) = match fit {
+                        Some(fit) => (fit.evaluate(first_x), fit.evaluate(last_x)),
+                        None => (first_y, last_y),
+                    };
+                    Some((last_y - first_y, (last_y - first_y) / (last_x - first_x)))
+                }
+                _ => None,
+            };
+            match change {
+                Some((change, slope)) if slope.is_finite() => {
+                    ui.text(format!("{:+.2} {}", change, unit));
+                    ui.next_column();
+                    ui.text(format!("{:+.3} {} {}", slope, unit, per));
+                    ui.next_column();
+                }
+                _ => {
+                    ui.text_disabled("-");
+                    ui.next_column();
+                    ui.text_disabled("-");
+                    ui.next_column();
+                }
+            }
+            match fit {
+                Some(fit) => ui.text(format!("{:.3}", fit.r_squared)),
+                None => ui.text_disabled("-"),
+            }
+            ui.next_column();
+        }
+        ui.columns(1, "sweep_fits", false);
+    }
+}
+
+/// Modes of a recorded strike
+fn recording_modes(path: &Path) -> Result<Vec<Mode>, String> {
+    let (samples, sample_rate) =
+        load_wav(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let modes: Vec<Mode> = analyze_recording(&samples, sample_rate)
+        .modes
+        .iter()
+        .map(Mode::from)
+        .collect();
+    if modes.is_empty() {
+        return Err(format!("{}: no partials found", path.display()));
+    }
+    Ok(modes)
+}
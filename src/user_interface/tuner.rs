@@ -0,0 +1,285 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::tuning::{ratio_to_cents, Scale, Temperament, Tuning, NOTE_NAMES};
+use crate::settings::TunerSettings;
+
+const REFERENCES: [f32; 2] = [440.0, 432.0];
+const REFERENCE_NAMES: [&str; 3] = ["440 Hz", "432 Hz", "Custom"];
+const TEMPERAMENTS: [Temperament; 3] = [Temperament::Equal, Temperament::Just, Temperament::Scala];
+const TEMPERAMENT_NAMES: [&str; 3] = ["12-TET", "Just Intonation", "Scala File"];
+
+/// Readings of the fundamental over this window are used for the stability indicator
+const STABILITY_WINDOW: Duration = Duration::from_secs(1);
+/// Spread of the readings in cents at which the pitch counts as unstable
+const UNSTABLE_CENTS: f32 = 10.0;
+/// Deviation within which a note is shown as in tune
+const IN_TUNE_CENTS: f32 = 5.0;
+/// Range of the cents needle
+const NEEDLE_RANGE_CENTS: f32 = 50.0;
+const NEEDLE_HEIGHT: f32 = 16.0;
+
+const NEEDLE_BACKGROUND: [f32; 4] = [0.15, 0.15, 0.15, 1.0];
+const NEEDLE_CENTRE: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const NEEDLE_IN_TUNE: [f32; 4] = [0.3, 0.85, 0.35, 1.0];
+const NEEDLE_OFF: [f32; 4] = [0.9, 0.75, 0.1, 1.0];
+
+/// Note and cents readout for the fundamental and strong partials
+pub struct TunerPanel {
+    settings: TunerSettings,
+    tuning: Tuning,
+    /// Show the reference as a custom value even if it matches a preset
+    custom_reference: bool,
+    /// Contents of the Scala file path field
+    scala_path: String,
+    scala_error: Option<String>,
+    /// Recent fundamental readings in cents relative to the reference
+    readings: VecDeque<(Instant, f32)>,
+}
+
+impl TunerPanel {
+    pub fn new(settings: &TunerSettings) -> Self {
+        let mut panel = TunerPanel {
+            settings: settings.clone(),
+            tuning: Tuning::new(
+                settings.reference_a4,
+                settings.root,
+                Scale::equal_temperament(),
+            ),
+            custom_reference: !REFERENCES.contains(&settings.reference_a4),
+            scala_path: settings
+                .scala_file
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            scala_error: None,
+            readings: VecDeque::new(),
+        };
+        panel.update_tuning();
+        panel
+    }
+
+    /// Current tuner configuration, for saving
+    pub fn settings(&self) -> &TunerSettings {
+        &self.settings
+    }
+
+    /// Rebuild the tuning from the settings. A Scala file that can't be loaded falls back to
+    /// equal temperament.
+    fn update_tuning(&mut self) {
+        let scale = match (self.settings.temperament, &self.settings.scala_file) {
+            (Temperament::Equal, _) => Scale::equal_temperament(),
+            (Temperament::Just, _) => Scale::just_intonation(),
+            (Temperament::Scala, Some(path)) => match Scale::load_scala(path) {
+                Ok(scale) => {
+                    self.scala_error = None;
+                    scale
+                }
+                Err(e) => {
+                    self.scala_error = Some(format!("{}: {}", path.display(), e));
+                    Scale::equal_temperament()
+                }
+            },
+            (Temperament::Scala, None) => {
+                self.scala_error = Some(String::from("No Scala file loaded"));
+                Scale::equal_temperament()
+            }
+        };
+        self.tuning = Tuning::new(self.settings.reference_a4, self.settings.root, scale);
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        self.build_config(ui);
+        ui.separator();
+
+        let pitch = analysis.pitch();
+        let now = Instant::now();
+        if let Some(pitch) = pitch {
+            let cents = ratio_to_cents((pitch.frequency / self.settings.reference_a4) as f64);
+            self.readings.push_back((now, cents as f32));
+        }
+        while self
+            .readings
+            .front()
+            .is_some_and(|(time, _)| now.duration_since(*time) > STABILITY_WINDOW)
+        {
+            self.readings.pop_front();
+        }
+
+        match pitch.and_then(|pitch| Some((pitch, self.tuning.nearest_note(pitch.frequency)?))) {
+            Some((pitch, note)) => {
+                ui.set_window_font_scale(2.0);
+                ui.text(format!("{}  {:+.1} cents", note.name, note.cents));
+                ui.set_window_font_scale(1.0);
+                ui.text(format!(
+                    "Fundamental {:.2} Hz, target {:.2} Hz, confidence {:.0}%",
+                    pitch.frequency,
+                    note.target_frequency,
+                    pitch.confidence * 100.0
+                ));
+                build_needle(ui, note.cents);
+            }
+            None => {
+                ui.set_window_font_scale(2.0);
+                ui.text("--");
+                ui.set_window_font_scale(1.0);
+                ui.text("No pitch detected");
+                build_needle(ui, f32::NAN);
+            }
+        }
+        self.build_stability(ui);
+
+        ui.separator();
+        self.build_partials(ui, analysis);
+    }
+
+    fn build_config(&mut self, ui: &imgui::Ui) {
+        let mut changed = false;
+
+        let mut reference_index = REFERENCES
+            .iter()
+            .position(|&r| r == self.settings.reference_a4 && !self.custom_reference)
+            .unwrap_or(REFERENCES.len());
+        if ui.combo_simple_string("Reference A4", &mut reference_index, &REFERENCE_NAMES) {
+            self.custom_reference = reference_index == REFERENCES.len();
+            if let Some(&reference) = REFERENCES.get(reference_index) {
+                self.settings.reference_a4 = reference;
+                changed = true;
+            }
+        }
+        if self.custom_reference {
+            let mut reference = self.settings.reference_a4;
+            if ui
+                .input_float("A4 (Hz)", &mut reference)
+                .step(0.1)
+                .display_format("%.2f")
+                .build()
+            {
+                self.settings.reference_a4 = reference.clamp(100.0, 1000.0);
+                changed = true;
+            }
+        }
+
+        let mut temperament_index = TEMPERAMENTS
+            .iter()
+            .position(|&t| t == self.settings.temperament)
+            .unwrap_or(0);
+        if ui.combo_simple_string("Temperament", &mut temperament_index, &TEMPERAMENT_NAMES) {
+            self.settings.temperament = TEMPERAMENTS[temperament_index];
+            changed = true;
+        }
+
+        let mut root = self.settings.root;
+        if ui.combo_simple_string("Root", &mut root, &NOTE_NAMES) {
+            self.settings.root = root;
+            changed = true;
+        }
+
+        if self.settings.temperament == Temperament::Scala {
+            ui.input_text("Scala File", &mut self.scala_path).build();
+            ui.same_line();
+            if ui.button("Load") {
+                self.settings.scala_file = Some(PathBuf::from(self.scala_path.trim()));
+                changed = true;
+            }
+            match &self.scala_error {
+                Some(error) => ui.text_colored([1.0, 0.4, 0.4, 1.0], error),
+                None => ui.text(format!(
+                    "{} ({} notes)",
+                    self.tuning.scale.description,
+                    self.tuning.scale.degrees.len()
+                )),
+            }
+        }
+
+        if changed {
+            self.update_tuning();
+        }
+    }
+
+    /// Spread of the recent fundamental readings
+    fn build_stability(&self, ui: &imgui::Ui) {
+        if self.readings.len() < 2 {
+            imgui::ProgressBar::new(0.0)
+                .overlay_text("Stability: -")
+                .build(ui);
+            return;
+        }
+
+        let count = self.readings.len() as f32;
+        let mean = self.readings.iter().map(|(_, c)| c).sum::<f32>() / count;
+        let variance = self
+            .readings
+            .iter()
+            .map(|(_, c)| (c - mean) * (c - mean))
+            .sum::<f32>()
+            / count;
+        let spread = variance.sqrt();
+        let stability = (1.0 - spread / UNSTABLE_CENTS).clamp(0.0, 1.0);
+
+        imgui::ProgressBar::new(stability)
+            .overlay_text(format!("Stability: ±{:.1} cents", spread))
+            .build(ui);
+    }
+
+    fn build_partials(&self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        let mut peaks = analysis.peaks().to_vec();
+        peaks.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+
+        ui.columns(4, "partials", false);
+        for header in ["Frequency", "Note", "Cents", "Level"] {
+            ui.text(header);
+            ui.next_column();
+        }
+        ui.separator();
+
+        for peak in peaks {
+            let Some(note) = self.tuning.nearest_note(peak.frequency) else {
+                continue;
+            };
+            ui.text(format!("{:.2} Hz", peak.frequency));
+            ui.next_column();
+            ui.text(&note.name);
+            ui.next_column();
+            ui.text(format!("{:+.1}", note.cents));
+            ui.next_column();
+            ui.text(format!("{:.1} dB", peak.magnitude_db));
+            ui.next_column();
+        }
+        ui.columns(1, "partials", false);
+    }
+}
+
+/// Horizontal deviation gauge from -50 to +50 cents. NaN draws the empty scale.
+fn build_needle(ui: &imgui::Ui, cents: f32) {
+    let draw_list = ui.get_window_draw_list();
+    let width = ui.content_region_avail()[0];
+    let [x, y] = ui.cursor_screen_pos();
+    let bottom = y + NEEDLE_HEIGHT;
+    let centre = x + width / 2.0;
+
+    draw_list
+        .add_rect([x, y], [x + width, bottom], NEEDLE_BACKGROUND)
+        .filled(true)
+        .build();
+    draw_list
+        .add_line([centre, y], [centre, bottom], NEEDLE_CENTRE)
+        .build();
+
+    if !cents.is_nan() {
+        let offset = (cents / NEEDLE_RANGE_CENTS).clamp(-1.0, 1.0) * width / 2.0;
+        let color = if cents.abs() <= IN_TUNE_CENTS {
+            NEEDLE_IN_TUNE
+        } else {
+            NEEDLE_OFF
+        };
+        draw_list
+            .add_line([centre + offset, y], [centre + offset, bottom], color)
+            .thickness(3.0)
+            .build();
+    }
+
+    ui.dummy([width, NEEDLE_HEIGHT]);
+}
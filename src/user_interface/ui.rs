@@ -1,9 +1,35 @@
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::pitch::PitchAlgorithm;
+use crate::audio_engine::denoise::{DenoiseMethod, MAX_REDUCTION_DB, MAX_SMOOTHING};
+use crate::audio_engine::phase_vocoder::MAX_PITCH_SEMITONES;
+use crate::audio_engine::stft::{frame_size, OVERLAP};
+use crate::user_interface::catalog::CatalogPanel;
+use crate::user_interface::compare::ComparisonPanel;
+use crate::user_interface::dynamics::build_dynamics_panel;
+use crate::user_interface::ensemble::EnsemblePanel;
+use crate::user_interface::meters::MeterDisplay;
+use crate::user_interface::nodes::NodesPanel;
+use crate::user_interface::partials::build_partials_panel;
+use crate::user_interface::pitch::PitchPanel;
+use crate::user_interface::recorder::RecorderPanel;
+use crate::user_interface::reverb::ReverbPanel;
 use crate::user_interface::setup;
+use crate::user_interface::shapes::ModeShapesPanel;
+use crate::user_interface::sms::SmsPanel;
+use crate::user_interface::spectrum::SpectrumView;
+use crate::user_interface::stretch::StretchPanel;
+use crate::user_interface::strikes::StrikesPanel;
+use crate::user_interface::sweep::SweepPanel;
+use crate::user_interface::tuner::TunerPanel;
+use crate::user_interface::zoom::ZoomPanel;
 use glow::HasContext;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 type PhysicalSize = glutin::dpi::PhysicalSize<u32>;
 
+/// Settings are written once they have been unchanged for this long
+const SETTINGS_SAVE_DELAY: Duration = Duration::from_millis(500);
+
 pub struct UserInterface {
     event_loop: glutin::event_loop::EventLoop<()>,
     window: glutin::WindowedContext<glutin::PossiblyCurrent>,
@@ -30,7 +56,11 @@ impl UserInterface {
     }
 
     /// Run main event loop
-    pub fn run(self, mut io_manager: crate::audio_engine::io_manager::IOManager) {
+    pub fn run(
+        self,
+        mut io_manager: crate::audio_engine::io_manager::IOManager,
+        mut settings: crate::settings::Settings,
+    ) {
         let UserInterface {
             event_loop,
             window,
@@ -39,7 +69,13 @@ impl UserInterface {
             mut ig_renderer,
         } = self;
 
+        if let Some(layout) = &settings.ui.layout {
+            ig_context.load_ini_settings(layout);
+        }
+
         let mut last_frame = Instant::now();
+        let mut settings_changed: Option<Instant> = None;
+        let mut ui_state = UiState::new(&settings);
         //let mut main_window_size = PhysicalSize::new(WINDOW_W as u32, WINDOW_H as u32);
 
         event_loop.run(move |event, _, control_flow| {
@@ -68,9 +104,11 @@ impl UserInterface {
                     // The renderer assumes you'll be clearing the buffer yourself
                     unsafe { ig_renderer.gl_context().clear(glow::COLOR_BUFFER_BIT) };
 
+                    io_manager.poll_devices();
+
                     let ui = ig_context.frame();
 
-                    build_ui(ui, &mut io_manager);
+                    build_ui(ui, &mut io_manager, &mut ui_state);
 
                     platform.prepare_render(ui, window.window());
                     let draw_data = ig_context.render();
@@ -81,11 +119,39 @@ impl UserInterface {
                         .expect("error rendering imgui");
 
                     window.swap_buffers().unwrap();
+
+                    // save once persistent state stopped changing, e.g. after a slider drag
+                    let mut current_settings = settings.clone();
+                    current_settings.capture(&io_manager);
+                    current_settings.dsp.pitch_algorithm = ui_state.analysis.pitch_algorithm();
+                    current_settings.tuner = ui_state.tuner.settings().clone();
+                    current_settings.onset = ui_state.strikes.settings().clone();
+                    current_settings.recorder = ui_state.recorder.settings().clone();
+                    if ig_context.io().want_save_ini_settings {
+                        let mut layout = String::new();
+                        ig_context.save_ini_settings(&mut layout);
+                        current_settings.ui.layout = Some(layout);
+                    }
+                    if current_settings != settings {
+                        settings = current_settings;
+                        settings_changed = Some(Instant::now());
+                    }
+                    if settings_changed.is_some_and(|t| t.elapsed() >= SETTINGS_SAVE_DELAY) {
+                        settings_changed = None;
+                        if let Err(e) = settings.save() {
+                            eprintln!("Could not save settings: {}", e);
+                        }
+                    }
                 }
                 glutin::event::Event::WindowEvent {
                     event: glutin::event::WindowEvent::CloseRequested,
                     ..
                 } => {
+                    if settings_changed.take().is_some() {
+                        if let Err(e) = settings.save() {
+                            eprintln!("Could not save settings: {}", e);
+                        }
+                    }
                     *control_flow = glutin::event_loop::ControlFlow::Exit;
                 }
                 event => {
@@ -96,10 +162,67 @@ impl UserInterface {
     }
 }
 
+/// State of panels that has to persist between frames
+struct UiState {
+    meters: MeterDisplay,
+    analysis: LiveAnalysis,
+    tuner: TunerPanel,
+    pitch: PitchPanel,
+    spectrum: SpectrumView,
+    zoom: ZoomPanel,
+    catalog: CatalogPanel,
+    comparison: ComparisonPanel,
+    ensemble: EnsemblePanel,
+    strikes: StrikesPanel,
+    nodes: NodesPanel,
+    shapes: ModeShapesPanel,
+    sweep: SweepPanel,
+    recorder: RecorderPanel,
+    reverb: ReverbPanel,
+    stretch: StretchPanel,
+    sms: SmsPanel,
+}
+
+impl UiState {
+    fn new(settings: &crate::settings::Settings) -> Self {
+        let mut analysis = LiveAnalysis::new();
+        analysis.set_onset_detection(settings.onset.function, settings.onset.sensitivity);
+        analysis.set_pitch_algorithm(settings.dsp.pitch_algorithm);
+
+        UiState {
+            meters: MeterDisplay::new(),
+            analysis,
+            tuner: TunerPanel::new(&settings.tuner),
+            pitch: PitchPanel::new(),
+            spectrum: SpectrumView::new(),
+            zoom: ZoomPanel::new(),
+            catalog: CatalogPanel::new(),
+            comparison: ComparisonPanel::new(),
+            ensemble: EnsemblePanel::new(),
+            strikes: StrikesPanel::new(&settings.onset),
+            nodes: NodesPanel::new(),
+            shapes: ModeShapesPanel::new(),
+            sweep: SweepPanel::new(),
+            recorder: RecorderPanel::new(&settings.recorder),
+            reverb: ReverbPanel::new(&settings.dsp.reverb),
+            stretch: StretchPanel::new(),
+            sms: SmsPanel::new(),
+        }
+    }
+}
+
 /// Configure all components and subwindows in imgui instance
-fn build_ui(ui: &mut imgui::Ui, io_manager: &mut crate::audio_engine::io_manager::IOManager) {
+fn build_ui(
+    ui: &mut imgui::Ui,
+    io_manager: &mut crate::audio_engine::io_manager::IOManager,
+    ui_state: &mut UiState,
+) {
     //let size = main_window_size.to_logical::<f32>(1.0);
 
+    ui_state.analysis.update(io_manager.analysis_tap());
+    ui_state.recorder.update(&ui_state.analysis);
+    ui_state.zoom.update(&ui_state.analysis);
+
     ui.window("main")
         .size([1000.0, 800.0], imgui::Condition::FirstUseEver)
         .flags(
@@ -119,14 +242,14 @@ fn build_ui(ui: &mut imgui::Ui, io_manager: &mut crate::audio_engine::io_manager
             ui.child_window("settings")
                 .size(settings_window_size)
                 .build(|| {
-                    build_settings_menu(&ui, io_manager);
+                    build_settings_menu(&ui, io_manager, ui_state);
                 });
 
             ui.next_column();
             ui.set_current_column_width(setup::WINDOW_W * (4.0 / 5.0));
             let app_window_size = [setup::WINDOW_W * (4.0 / 5.0), setup::WINDOW_H];
             ui.child_window("app").size(app_window_size).build(|| {
-                build_app_window(&ui, io_manager);
+                build_app_window(&ui, io_manager, ui_state);
             });
         });
 }
@@ -135,8 +258,21 @@ fn build_ui(ui: &mut imgui::Ui, io_manager: &mut crate::audio_engine::io_manager
 fn build_settings_menu(
     ui: &&mut imgui::Ui,
     io_manager: &mut crate::audio_engine::io_manager::IOManager,
+    ui_state: &mut UiState,
 ) {
     if imgui::CollapsingHeader::new("Devices").build(ui) {
+        let hosts = io_manager.get_host_names();
+
+        let mut current_host_index = io_manager.get_current_host_index();
+
+        if ui.combo("Host", &mut current_host_index, &hosts, |item| {
+            std::borrow::Cow::Borrowed(item)
+        }) {
+            if let Err(e) = io_manager.set_host(current_host_index) {
+                eprintln!("Could not switch host: {}", e);
+            }
+        };
+
         let output_devices = io_manager.get_output_devices_names();
 
         let mut current_out_device_index = io_manager.get_current_out_device_index();
@@ -163,12 +299,22 @@ fn build_settings_menu(
             io_manager.enable_input_device(current_in_device_index);
         };
 
+        ui_state.meters.build(ui, io_manager.meters());
+
+        if ui.button("Refresh Devices") {
+            io_manager.refresh_devices();
+        }
+
         let sample_rates = [
             5512, 8000, 11025, 16000, 22050, 32000, 44100, 48000, 64000, 88200, 96000, 176400,
             192000,
         ];
+        let options = io_manager.get_stream_options();
+        let mut sample_rate_index = sample_rates
+            .iter()
+            .position(|&rate| rate == options.sample_rate)
+            .unwrap_or(6); // 44100
         let sample_rates = sample_rates.map(|rate| rate.to_string());
-        let mut sample_rate_index: usize = 6; // 44100
 
         if ui.combo(
             "Sample Rate",
@@ -178,10 +324,133 @@ fn build_settings_menu(
         ) {
             io_manager.set_sample_rate(sample_rates[sample_rate_index].parse::<u32>().unwrap());
         };
+
+        let buffer_sizes = [64, 128, 256, 512, 1024, 2048, 4096];
+        let mut buffer_size_index = buffer_sizes
+            .iter()
+            .position(|&size| size == options.buffer_size)
+            .unwrap_or(3); // 512
+        let buffer_sizes = buffer_sizes.map(|size| size.to_string());
+
+        if ui.combo(
+            "Buffer Size",
+            &mut buffer_size_index,
+            &buffer_sizes,
+            |item| std::borrow::Cow::Borrowed(item.as_str()),
+        ) {
+            io_manager.set_buffer_size(buffer_sizes[buffer_size_index].parse::<u32>().unwrap());
+        };
+
+        let input_channels = ["1", "2"];
+        let mut input_channel = options.input_channel;
+
+        if ui.combo(
+            "Input Channel",
+            &mut input_channel,
+            &input_channels,
+            |item| std::borrow::Cow::Borrowed(item),
+        ) {
+            io_manager.set_input_channel(input_channel);
+        };
+
+        // devices that don't support the selected rate run at their own rate and are resampled
+        let format_rate = |rate: Option<u32>| match rate {
+            Some(rate) => format!("{} Hz", rate),
+            None => String::from("-"),
+        };
+        ui.text(format!(
+            "In: {}  Out: {}",
+            format_rate(io_manager.get_input_sample_rate()),
+            format_rate(io_manager.get_output_sample_rate())
+        ));
+    }
+
+    if imgui::CollapsingHeader::new("Monitoring").build(ui) {
+        let monitor = io_manager.monitor();
+
+        let mut input_gain = monitor.input_gain_db();
+        if ui
+            .slider_config("Input Gain", -60.0, 24.0)
+            .display_format("%.1f dB")
+            .build(&mut input_gain)
+        {
+            monitor.set_input_gain_db(input_gain);
+        }
+
+        let mut output_gain = monitor.output_gain_db();
+        if ui
+            .slider_config("Output Gain", -60.0, 12.0)
+            .display_format("%.1f dB")
+            .build(&mut output_gain)
+        {
+            monitor.set_output_gain_db(output_gain);
+        }
+
+        // 0% hears the input as captured, 100% the processed signal
+        let mut mix = monitor.mix() * 100.0;
+        if ui
+            .slider_config("Dry/Processed", 0.0, 100.0)
+            .display_format("%.0f%%")
+            .build(&mut mix)
+        {
+            monitor.set_mix(mix / 100.0);
+        }
+
+        let mut mute = monitor.mute();
+        if ui.checkbox("Mute", &mut mute) {
+            monitor.set_mute(mute);
+        }
+
+        let mut limiter = monitor.limiter();
+        if ui.checkbox("Safety Limiter", &mut limiter) {
+            monitor.set_limiter(limiter);
+        }
     }
 
     if imgui::CollapsingHeader::new("DSP").build(ui) {
-        ui.text("test");
+        let mut frequency_domain_processing = io_manager.get_frequency_domain_processing();
+        if ui.checkbox("Frequency Domain", &mut frequency_domain_processing) {
+            io_manager.set_frequency_domain_processing(frequency_domain_processing);
+        }
+        if frequency_domain_processing {
+            let mut semitones = io_manager.pitch_shift().semitones();
+            if ui
+                .slider_config("Pitch Shift", -MAX_PITCH_SEMITONES, MAX_PITCH_SEMITONES)
+                .display_format("%.2f semitones")
+                .build(&mut semitones)
+            {
+                io_manager.pitch_shift().set_semitones(semitones);
+            }
+            if let Some(sample_rate) = io_manager.get_input_sample_rate() {
+                // a frame for each of the noise reduction and pitch shift stages in use
+                let denoise = io_manager.denoise();
+                let stages = [denoise.enabled() || denoise.is_learning(), semitones != 0.0]
+                    .into_iter()
+                    .filter(|active| *active)
+                    .count();
+                let latency = stages as f32 * frame_size(sample_rate) as f32 / sample_rate as f32;
+                ui.text_disabled(format!("Adds {:.0} ms latency", latency * 1000.0));
+            }
+            ui.separator();
+            build_noise_reduction(ui, io_manager);
+        }
+
+        let algorithms = PitchAlgorithm::ALL;
+        let mut algorithm = algorithms
+            .iter()
+            .position(|&a| a == ui_state.analysis.pitch_algorithm())
+            .unwrap_or(0);
+        if ui.combo("Pitch Algorithm", &mut algorithm, &algorithms, |a| {
+            std::borrow::Cow::Borrowed(a.name())
+        }) {
+            ui_state.analysis.set_pitch_algorithm(algorithms[algorithm]);
+        }
+    }
+    if imgui::CollapsingHeader::new("Dynamics").build(ui) {
+        build_dynamics_panel(ui, io_manager);
+    }
+    if imgui::CollapsingHeader::new("Reverb").build(ui) {
+        ui_state.reverb.build(ui, io_manager);
     }
     if imgui::CollapsingHeader::new("App Style").build(ui) {
         ui.text("test");
@@ -189,11 +458,123 @@ fn build_settings_menu(
     if imgui::CollapsingHeader::new("Recording").build(ui) {
         ui.text("test");
     }
+    if imgui::CollapsingHeader::new("Performance").build(ui) {
+        build_performance_panel(ui, io_manager);
+    }
+}
+
+/// Noise reduction controls and learning of the noise profile
+fn build_noise_reduction(ui: &imgui::Ui, io_manager: &crate::audio_engine::io_manager::IOManager) {
+    let denoise = io_manager.denoise();
+
+    let mut enabled = denoise.enabled();
+    if ui.checkbox("Noise Reduction", &mut enabled) {
+        denoise.set_enabled(enabled);
+    }
+
+    let methods = DenoiseMethod::ALL;
+    let mut method = methods
+        .iter()
+        .position(|&m| m == denoise.method())
+        .unwrap_or(0);
+    if ui.combo("Method", &mut method, &methods, |m| {
+        std::borrow::Cow::Borrowed(m.name())
+    }) {
+        denoise.set_method(methods[method]);
+    }
+
+    let mut reduction_db = denoise.reduction_db();
+    if ui
+        .slider_config("Reduction", 0.0, MAX_REDUCTION_DB)
+        .display_format("%.0f dB")
+        .build(&mut reduction_db)
+    {
+        denoise.set_reduction_db(reduction_db);
+    }
+
+    // higher values leave less musical noise but blur the strike
+    let mut smoothing = denoise.smoothing();
+    if ui
+        .slider_config("Smoothing", 0.0, MAX_SMOOTHING)
+        .display_format("%.2f")
+        .build(&mut smoothing)
+    {
+        denoise.set_smoothing(smoothing);
+    }
+
+    if denoise.is_learning() {
+        if ui.button("Stop Learning") {
+            denoise.stop_learning();
+        }
+    } else if ui.button("Learn Noise") {
+        denoise.start_learning();
+    }
+    ui.same_line();
+    if ui.button("Clear Profile") {
+        denoise.clear_profile();
+    }
+
+    let frames = denoise.profile_frames();
+    match io_manager.get_input_sample_rate() {
+        Some(sample_rate) if frames > 0 => {
+            let hop = frame_size(sample_rate) / OVERLAP;
+            let seconds = frames as f32 * hop as f32 / sample_rate as f32;
+            let state = if denoise.is_learning() {
+                "Learning"
+            } else {
+                "Profile"
+            };
+            ui.text(format!("{}: {} frames ({:.1} s)", state, frames, seconds));
+        }
+        _ if denoise.is_learning() => ui.text("Learning: waiting for input"),
+        _ => ui.text_disabled("No noise profile, learn one while only noise is playing"),
+    }
+}
+
+/// Dropout counters, ring buffer fill level and callback timing
+fn build_performance_panel(
+    ui: &imgui::Ui,
+    io_manager: &crate::audio_engine::io_manager::IOManager,
+) {
+    let stats = io_manager.get_stats();
+
+    ui.text(format!("Underruns: {}", stats.underruns));
+    ui.text(format!("Overruns: {}", stats.overruns));
+    ui.text(format!(
+        "Late callbacks: {}/{} in, {}/{} out",
+        stats.input.late_callbacks,
+        stats.input.callbacks,
+        stats.output.late_callbacks,
+        stats.output.callbacks
+    ));
+
+    imgui::ProgressBar::new(stats.buffer_fill_ratio())
+        .overlay_text(format!("Buffer {:.0}%", stats.buffer_fill_ratio() * 100.0))
+        .build(ui);
+    imgui::ProgressBar::new(stats.input.load.min(1.0))
+        .overlay_text(format!("Input CPU {:.0}%", stats.input.load * 100.0))
+        .build(ui);
+    imgui::ProgressBar::new(stats.output.load.min(1.0))
+        .overlay_text(format!("Output CPU {:.0}%", stats.output.load * 100.0))
+        .build(ui);
+
+    // callback duration as a share of the callback period, in 10% steps; last bin is late
+    let histogram = stats.input.histogram.map(|count| count as f32);
+    ui.plot_histogram("##callback_durations", &histogram)
+        .overlay_text("Input callback duration")
+        .scale_min(0.0)
+        .graph_size([0.0, 60.0])
+        .build();
+
+    if ui.button("Reset Stats") {
+        io_manager.reset_stats();
+    }
 }
 
 fn build_app_window(
     ui: &&mut imgui::Ui,
     io_manager: &mut crate::audio_engine::io_manager::IOManager,
+    ui_state: &mut UiState,
 ) {
     if ui.button("start_output") {
         io_manager.play_output();
@@ -207,4 +588,53 @@ fn build_app_window(
     if ui.button("stop_input") {
         io_manager.pause_input();
     }
+    if let Some(_tab_bar) = ui.tab_bar("analysis_views") {
+        if let Some(_tab) = ui.tab_item("Tuner") {
+            ui_state.tuner.build(ui, &ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Pitch") {
+            ui_state.pitch.build(ui, &ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Spectrum") {
+            ui_state.spectrum.build(ui, &ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Zoom") {
+            ui_state.zoom.build(ui, &ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Partials") {
+            build_partials_panel(ui, &ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Strikes") {
+            ui_state.strikes.build(ui, &mut ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Nodes") {
+            ui_state.nodes.build(ui);
+        }
+        if let Some(_tab) = ui.tab_item("Mode Shapes") {
+            ui_state
+                .shapes
+                .build(ui, &ui_state.analysis, ui_state.nodes.patterns());
+        }
+        if let Some(_tab) = ui.tab_item("Sweep") {
+            ui_state.sweep.build(ui, &ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Recorder") {
+            ui_state.recorder.build(ui);
+        }
+        if let Some(_tab) = ui.tab_item("Stretch") {
+            ui_state.stretch.build(ui);
+        }
+        if let Some(_tab) = ui.tab_item("SMS") {
+            ui_state.sms.build(ui, io_manager);
+        }
+        if let Some(_tab) = ui.tab_item("Catalog") {
+            ui_state.catalog.build(ui, &ui_state.analysis);
+        }
+        if let Some(_tab) = ui.tab_item("Compare") {
+            ui_state.comparison.build(ui, ui_state.catalog.catalog());
+        }
+        if let Some(_tab) = ui.tab_item("Ensemble") {
+            ui_state.ensemble.build(ui, ui_state.catalog.catalog());
+        }
+    }
 }
@@ -0,0 +1,284 @@
+use std::path::PathBuf;
+
+use crate::analysis::live::LiveAnalysis;
+use crate::analysis::recording::load_wav;
+use crate::analysis::zoom::{zoom_recording, ZoomAnalyzer, ZoomSpectrum};
+use crate::user_interface::spectrum::{PlotArea, GRID, GRID_LABEL, SPECTRUM_LINE};
+
+const PLOT_HEIGHT: f32 = 320.0;
+/// Labelled frequencies across the plot
+const FREQUENCY_TICKS: usize = 5;
+/// The live spectrum is recomputed after this much more has been captured
+const REFRESH_SECONDS: f32 = 0.25;
+const PEAKS_SHOWN: usize = 4;
+const PEAK_LINE: [f32; 4] = [1.0, 0.8, 0.2, 0.8];
+const CAPTURING_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// High resolution spectrum of a narrow band around one partial, from a long live capture or
+/// a recording, for measuring doublet splits far below the bin spacing of the main spectrum
+pub struct ZoomPanel {
+    centre: f32,
+    span: f32,
+    floor_db: f32,
+    /// Running live capture
+    capture: Option<ZoomAnalyzer>,
+    /// Capture length the spectrum was last computed at
+    refreshed_seconds: f32,
+    spectrum: Option<ZoomSpectrum>,
+    path: String,
+    error: Option<String>,
+}
+
+impl ZoomPanel {
+    pub fn new() -> Self {
+        ZoomPanel {
+            centre: 440.0,
+            span: 10.0,
+            floor_db: -100.0,
+            capture: None,
+            refreshed_seconds: 0.0,
+            spectrum: None,
+            path: String::new(),
+            error: None,
+        }
+    }
+
+    /// Pass the newest input to a running capture. Called once per UI frame, whether the panel
+    /// is shown or not.
+    pub fn update(&mut self, analysis: &LiveAnalysis) {
+        let Some(capture) = &mut self.capture else {
+            return;
+        };
+        if capture.sample_rate() != analysis.sample_rate() {
+            self.capture = None;
+            self.error = Some(String::from("Capture stopped, the sample rate changed"));
+            return;
+        }
+        capture.process(analysis.latest_input());
+        if capture.seconds() - self.refreshed_seconds >= REFRESH_SECONDS || capture.is_full() {
+            self.spectrum = capture.spectrum();
+            self.refreshed_seconds = capture.seconds();
+        }
+        if capture.is_full() {
+            self.capture = None;
+        }
+    }
+
+    pub fn build(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        self.build_band(ui, analysis);
+        ui.separator();
+        self.build_sources(ui, analysis);
+        if let Some(error) = &self.error {
+            ui.text_colored(ERROR_COLOR, error);
+        }
+        ui.separator();
+
+        ui.slider_config("Floor", -140.0, -20.0)
+            .display_format("%.0f dB")
+            .build(&mut self.floor_db);
+        match &self.spectrum {
+            Some(spectrum) => {
+                self.build_plot(ui, spectrum);
+                build_peaks(ui, spectrum);
+            }
+            None => ui.text("Capture or load a recording to zoom in on the band"),
+        }
+    }
+
+    fn build_band(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        let partials = analysis
+            .partials()
+            .map(|p| p.partials.as_slice())
+            .unwrap_or_default();
+        if !partials.is_empty() {
+            let mut chosen = partials
+                .iter()
+                .position(|p| (p.frequency - self.centre).abs() < 0.5 * self.span)
+                .unwrap_or(0);
+            if ui.combo("Partial", &mut chosen, partials, |partial| {
+                std::borrow::Cow::Owned(format!(
+                    "{:.2} Hz (x{:.2})",
+                    partial.frequency, partial.ratio
+                ))
+            }) {
+                self.centre = partials[chosen].frequency;
+            }
+        }
+
+        let mut centre = self.centre;
+        if ui
+            .input_float("Centre (Hz)", &mut centre)
+            .step(0.1)
+            .display_format("%.3f")
+            .build()
+        {
+            self.centre = centre.clamp(1.0, 20000.0);
+        }
+        ui.slider_config("Span", 0.5, 100.0)
+            .display_format("%.2f Hz")
+            .flags(imgui::SliderFlags::LOGARITHMIC)
+            .build(&mut self.span);
+    }
+
+    fn build_sources(&mut self, ui: &imgui::Ui, analysis: &LiveAnalysis) {
+        match &self.capture {
+            Some(capture) => {
+                if ui.button("Stop Capture") {
+                    self.spectrum = capture.spectrum();
+                    self.capture = None;
+                }
+            }
+            None => {
+                if ui.button("Start Capture") {
+                    if analysis.sample_rate() == 0 {
+                        self.error = Some(String::from("Start the input to capture"));
+                    } else {
+                        self.capture = Some(ZoomAnalyzer::new(
+                            self.centre,
+                            self.span,
+                            analysis.sample_rate(),
+                        ));
+                        self.refreshed_seconds = 0.0;
+                        self.spectrum = None;
+                        self.error = None;
+                    }
+                }
+            }
+        }
+        if let Some(capture) = &self.capture {
+            ui.same_line();
+            ui.text_colored(
+                CAPTURING_COLOR,
+                format!(
+                    "Capturing {:.2} +/- {:.2} Hz: {:.1} s",
+                    capture.centre(),
+                    0.5 * capture.span(),
+                    capture.seconds()
+                ),
+            );
+        }
+
+        ui.input_text("WAV File", &mut self.path).build();
+        ui.same_line();
+        if ui.button("Zoom Recording") {
+            let path = PathBuf::from(self.path.trim());
+            match load_wav(&path) {
+                Ok((samples, sample_rate)) => {
+                    self.capture = None;
+                    self.spectrum = zoom_recording(&samples, sample_rate, self.centre, self.span);
+                    self.error = self
+                        .spectrum
+                        .is_none()
+                        .then(|| String::from("Recording too short for the span"));
+                }
+                Err(e) => self.error = Some(format!("{}: {}", path.display(), e)),
+            }
+        }
+    }
+
+    fn build_plot(&self, ui: &imgui::Ui, spectrum: &ZoomSpectrum) {
+        let [x, y] = ui.cursor_screen_pos();
+        let width = ui.content_region_avail()[0];
+        let area = PlotArea {
+            min: [x, y],
+            max: [x + width, y + PLOT_HEIGHT],
+            min_frequency: spectrum.start_frequency,
+            max_frequency: spectrum.end_frequency(),
+            log_frequency: false,
+            floor_db: self.floor_db,
+        };
+
+        let draw_list = ui.get_window_draw_list();
+        draw_list.with_clip_rect_intersect(area.min, area.max, || {
+            area.draw_grid(&draw_list);
+            for tick in 0..=FREQUENCY_TICKS {
+                let frequency = area.min_frequency
+                    + (area.max_frequency - area.min_frequency) * tick as f32
+                        / FREQUENCY_TICKS as f32;
+                let x = area.x(frequency);
+                draw_list
+                    .add_line([x, area.min[1]], [x, area.max[1]], GRID)
+                    .build();
+                draw_list.add_text(
+                    [x + 2.0, area.max[1] - 14.0],
+                    GRID_LABEL,
+                    format!("{:.3}", frequency),
+                );
+            }
+
+            // highest bin under each pixel column
+            let points: Vec<[f32; 2]> = (0..width.max(1.0) as usize)
+                .filter_map(|column| {
+                    let x = area.min[0] + column as f32;
+                    let bin = |x: f32| {
+                        ((area.frequency(x) - spectrum.start_frequency) / spectrum.bin_hz).round()
+                            as usize
+                    };
+                    let last = spectrum.magnitudes_db.len().checked_sub(1)?;
+                    let bins = spectrum
+                        .magnitudes_db
+                        .get(bin(x).min(last)..=bin(x + 1.0).min(last))?;
+                    let db = bins.iter().cloned().fold(f32::MIN, f32::max);
+                    Some([x, area.y(db)])
+                })
+                .collect();
+            draw_list.add_polyline(points, SPECTRUM_LINE).build();
+
+            for peak in spectrum.peaks().iter().take(PEAKS_SHOWN) {
+                area.draw_marker(
+                    &draw_list,
+                    peak.frequency,
+                    PEAK_LINE,
+                    Some(format!("{:.4}", peak.frequency)),
+                );
+            }
+        });
+        ui.dummy([width, PLOT_HEIGHT]);
+    }
+}
+
+fn build_peaks(ui: &imgui::Ui, spectrum: &ZoomSpectrum) {
+    ui.text(format!(
+        "Resolution {:.4} Hz, bin spacing {:.5} Hz",
+        spectrum.resolution_hz, spectrum.bin_hz
+    ));
+
+    let peaks = spectrum.peaks();
+    if let [first, second, ..] = peaks.as_slice() {
+        let split = (second.frequency - first.frequency).abs();
+        let resolved = if split >= spectrum.resolution_hz {
+            ""
+        } else {
+            ", below the resolution"
+        };
+        ui.text(format!(
+            "Doublet split {:.4} Hz, beat period {:.1} s{}",
+            split,
+            1.0 / split.max(f32::EPSILON),
+            resolved
+        ));
+    }
+
+    let headers = ["Peak", "Frequency", "Level", "Offset"];
+    ui.columns(headers.len() as i32, "zoom_peaks", false);
+    for header in headers {
+        ui.text(header);
+        ui.next_column();
+    }
+    ui.separator();
+    let strongest = peaks.first().map_or(0.0, |p| p.frequency);
+    for (i, peak) in peaks.iter().take(PEAKS_SHOWN).enumerate() {
+        let cells = [
+            format!("{}", i + 1),
+            format!("{:.4} Hz", peak.frequency),
+            format!("{:.1} dB", peak.magnitude_db),
+            format!("{:+.4} Hz", peak.frequency - strongest),
+        ];
+        for cell in cells {
+            ui.text(cell);
+            ui.next_column();
+        }
+    }
+    ui.columns(1, "zoom_peaks", false);
+}